@@ -0,0 +1,99 @@
+//! Measure the per-frame cost of each synthesis kernel with each instruction set.
+
+#![feature(test)]
+
+extern crate imbe;
+extern crate test;
+
+use imbe::consts::{MAX_HARMONICS, SAMPLES_PER_FRAME};
+use imbe::simd::{self, Isa, Oscillators};
+use test::{black_box, Bencher};
+
+/// Synthesize a frame of voiced samples from the previous and current frames' harmonics.
+fn oscillators(b: &mut Bencher, isa: Isa) {
+    if !isa.supported() {
+        return;
+    }
+
+    b.iter(|| {
+        let mut prev = Oscillators::new(isa);
+        let mut cur = Oscillators::new(isa);
+
+        for l in 0..MAX_HARMONICS {
+            prev.push(100.0, l as f32, 0.05 * l as f32);
+            cur.push(100.0, -(l as f32), 0.06 * l as f32);
+        }
+
+        for _ in 0..SAMPLES_PER_FRAME {
+            black_box(prev.next() + cur.next());
+        }
+    });
+}
+
+/// Evaluate the previous and current frames' IDFTs at each sample of a frame.
+fn idft(b: &mut Bencher, isa: Isa) {
+    if !isa.supported() {
+        return;
+    }
+
+    let re = (0..128).map(|k| (k as f32).sin()).collect::<Vec<f32>>();
+    let im = (0..128).map(|k| (k as f32).cos()).collect::<Vec<f32>>();
+
+    b.iter(|| {
+        for n in 0..2 * SAMPLES_PER_FRAME {
+            black_box(simd::idft(isa, &re, &im, 0.01 * n as f32));
+        }
+    });
+}
+
+/// Combine a frame of unvoiced samples.
+fn overlap_add(b: &mut Bencher, isa: Isa) {
+    if !isa.supported() {
+        return;
+    }
+
+    let sig = (0..SAMPLES_PER_FRAME).map(|n| (n as f32).sin()).collect::<Vec<f32>>();
+    let win = (0..SAMPLES_PER_FRAME).map(|n| n as f32 / 160.0).collect::<Vec<f32>>();
+    let mut out = vec![0.0; SAMPLES_PER_FRAME];
+
+    b.iter(|| {
+        simd::overlap_add(isa, &sig, &sig, &win, &win, &mut out);
+        black_box(&out);
+    });
+}
+
+#[bench]
+fn bench_oscillators_scalar(b: &mut Bencher) { oscillators(b, Isa::Scalar) }
+
+#[bench]
+fn bench_oscillators_sse2(b: &mut Bencher) { oscillators(b, Isa::Sse2) }
+
+#[bench]
+fn bench_oscillators_avx2(b: &mut Bencher) { oscillators(b, Isa::Avx2) }
+
+#[bench]
+fn bench_oscillators_neon(b: &mut Bencher) { oscillators(b, Isa::Neon) }
+
+#[bench]
+fn bench_idft_scalar(b: &mut Bencher) { idft(b, Isa::Scalar) }
+
+#[bench]
+fn bench_idft_sse2(b: &mut Bencher) { idft(b, Isa::Sse2) }
+
+#[bench]
+fn bench_idft_avx2(b: &mut Bencher) { idft(b, Isa::Avx2) }
+
+#[bench]
+fn bench_idft_neon(b: &mut Bencher) { idft(b, Isa::Neon) }
+
+#[bench]
+fn bench_overlap_add_scalar(b: &mut Bencher) { overlap_add(b, Isa::Scalar) }
+
+#[bench]
+fn bench_overlap_add_sse2(b: &mut Bencher) { overlap_add(b, Isa::Sse2) }
+
+#[bench]
+fn bench_overlap_add_avx2(b: &mut Bencher) { overlap_add(b, Isa::Avx2) }
+
+#[bench]
+fn bench_overlap_add_neon(b: &mut Bencher) { overlap_add(b, Isa::Neon) }
@@ -0,0 +1,290 @@
+//! Measure each stage of decoding individually and end to end over a fixed frame corpus.
+
+#![feature(test)]
+
+extern crate imbe;
+extern crate rand;
+extern crate test;
+
+use imbe::coefs::Coefficients;
+use imbe::consts::SAMPLES_PER_FRAME;
+use imbe::descramble::{descramble, Bootstrap, QuantizedAmplitudes, VoiceDecisions};
+use imbe::enhance::{self, EnhancedSpectrals, EnhanceErrors, FrameEnergy};
+use imbe::frame::{ReceivedFrame, TEST_CHUNKS};
+use imbe::gain::Gains;
+use imbe::noise::{NoiseKind, NoiseSource};
+use imbe::params::BaseParams;
+use imbe::prev::PrevFrame;
+use imbe::spectral::Spectrals;
+use imbe::timing::Timing;
+use imbe::unvoiced::{Unvoiced, UnvoicedDft};
+use imbe::voiced::{Phase, PhaseBase, Voiced};
+use imbe::ImbeDecoder;
+use rand::XorShiftRng;
+use test::{black_box, Bencher};
+
+/// Frames with a range of pitches and voicing, from mostly voiced to mostly unvoiced.
+const CORPUS: [[u32; 8]; 6] = [
+    TEST_CHUNKS,
+    [
+        0b101111010010,
+        0b110011001100,
+        0b111000111000,
+        0b111111111111,
+        0b11111111111,
+        0b10101111010,
+        0b01110111011,
+        0b00001000,
+    ],
+    [
+        0b000000111111,
+        0b111111111111,
+        0b111111111111,
+        0b111111111111,
+        0b11111111111,
+        0b11111111111,
+        0b11111111111,
+        0b00001000,
+    ],
+    [
+        0b011010100101,
+        0b100110011010,
+        0b010101101001,
+        0b001100110011,
+        0b00000000000,
+        0b01011010010,
+        0b10010110100,
+        0b01101001,
+    ],
+    [
+        0b100101011100,
+        0b011011001010,
+        0b101001110001,
+        0b110100101110,
+        0b01010101010,
+        0b00111100001,
+        0b11000011110,
+        0b00110011,
+    ],
+    [
+        0b010011110000,
+        0b001110001110,
+        0b110001100011,
+        0b011100011100,
+        0b10011001100,
+        0b11100001111,
+        0b00011110000,
+        0b01010101,
+    ],
+];
+
+/// Intermediate results of decoding a corpus frame.
+struct Stages {
+    chunks: [u32; 8],
+    params: BaseParams,
+    amps: QuantizedAmplitudes,
+    voice: VoiceDecisions,
+    gain_idx: usize,
+    gains: Gains,
+    coefs: Coefficients,
+    spectrals: Spectrals,
+    /// Saved parameters of the previous frame.
+    prev: PrevFrame,
+    /// Saved parameters of this frame.
+    cur: PrevFrame,
+}
+
+/// Decode the given frame following the given previous frame, saving each stage's
+/// results.
+fn decode(chunks: [u32; 8], prev: PrevFrame, rng: &mut XorShiftRng) -> Stages {
+    let params = match Bootstrap::new(&chunks) {
+        Bootstrap::Period(p) => BaseParams::new(p),
+        _ => panic!("invalid benchmark frame"),
+    };
+    let (amps, voice, gain_idx) = descramble(&chunks, &params);
+    let gains = Gains::new(gain_idx, &amps, &params);
+    let coefs = Coefficients::new(&gains, &amps, &params);
+    let spectrals = Spectrals::new(&coefs, &params, &prev);
+    let energy = FrameEnergy::new(&spectrals, &prev.energy, &params);
+    let enhanced = EnhancedSpectrals::new(&spectrals, &energy, &params);
+    let udft = UnvoicedDft::new(&params, &voice, &enhanced, rng);
+    let base = PhaseBase::new(&params, &prev);
+    let phase = Phase::new(&base, &params, &prev, &voice, rng);
+
+    let cur = PrevFrame {
+        params: params,
+        model: params,
+        spectrals: spectrals.clone(),
+        enhanced: enhanced,
+        voice: voice,
+        energy: energy,
+        unvoiced: udft,
+        phase_base: base,
+        phase: phase,
+        .. PrevFrame::default()
+    };
+
+    Stages {
+        chunks: chunks,
+        params: params,
+        amps: amps,
+        voice: voice,
+        gain_idx: gain_idx,
+        gains: gains,
+        coefs: coefs,
+        spectrals: spectrals,
+        prev: prev,
+        cur: cur,
+    }
+}
+
+/// Decode every frame in the corpus following the frame before it.
+fn corpus() -> Vec<Stages> {
+    let mut rng = XorShiftRng::new_unseeded();
+
+    (0..CORPUS.len()).map(|i| {
+        let before = CORPUS[(i + CORPUS.len() - 1) % CORPUS.len()];
+        let prev = decode(before, PrevFrame::default(), &mut rng).cur;
+
+        decode(CORPUS[i], prev, &mut rng)
+    }).collect()
+}
+
+#[bench]
+fn bench_descramble(b: &mut Bencher) {
+    let frames = corpus();
+
+    b.iter(|| {
+        for f in frames.iter() {
+            black_box(descramble(&f.chunks, &f.params));
+        }
+    });
+}
+
+#[bench]
+fn bench_gains(b: &mut Bencher) {
+    let frames = corpus();
+
+    b.iter(|| {
+        for f in frames.iter() {
+            black_box(Gains::new(f.gain_idx, &f.amps, &f.params));
+        }
+    });
+}
+
+#[bench]
+fn bench_coefficients(b: &mut Bencher) {
+    let frames = corpus();
+
+    b.iter(|| {
+        for f in frames.iter() {
+            black_box(Coefficients::new(&f.gains, &f.amps, &f.params));
+        }
+    });
+}
+
+#[bench]
+fn bench_spectrals(b: &mut Bencher) {
+    let frames = corpus();
+
+    b.iter(|| {
+        for f in frames.iter() {
+            black_box(Spectrals::new(&f.coefs, &f.params, &f.prev));
+        }
+    });
+}
+
+#[bench]
+fn bench_enhance(b: &mut Bencher) {
+    let frames = corpus();
+    let errors = EnhanceErrors::new(&[0; 7], 0.0);
+
+    b.iter(|| {
+        for f in frames.iter() {
+            let energy = FrameEnergy::new(&f.spectrals, &f.prev.energy, &f.params);
+            let mut enhanced = EnhancedSpectrals::new(&f.spectrals, &energy, &f.params);
+            let mut voice = f.voice;
+            let thresh = enhance::amp_thresh(&errors, f.prev.amp_thresh);
+
+            enhance::smooth(&mut enhanced, &mut voice, &errors, &energy, thresh);
+            black_box(&enhanced);
+        }
+    });
+}
+
+#[bench]
+fn bench_unvoiced_dft(b: &mut Bencher) {
+    let frames = corpus();
+    let mut rng = XorShiftRng::new_unseeded();
+
+    b.iter(|| {
+        for f in frames.iter() {
+            black_box(UnvoicedDft::new(&f.params, &f.voice, &f.cur.enhanced, &mut rng));
+        }
+    });
+}
+
+/// Compute the unvoiced DFT of each frame with the given noise source.
+fn unvoiced_dft(b: &mut Bencher, kind: NoiseKind) {
+    let frames = corpus();
+    let mut noise = NoiseSource::new(kind);
+    let mut rng = XorShiftRng::new_unseeded();
+
+    b.iter(|| {
+        for f in frames.iter() {
+            black_box(UnvoicedDft::with_noise(&f.params, &f.voice, &f.cur.enhanced,
+                                              &mut noise, &mut rng));
+        }
+    });
+}
+
+#[bench]
+fn bench_unvoiced_dft_table(b: &mut Bencher) { unvoiced_dft(b, NoiseKind::Table) }
+
+#[bench]
+fn bench_unvoiced_dft_uniform(b: &mut Bencher) { unvoiced_dft(b, NoiseKind::Uniform) }
+
+#[bench]
+fn bench_unvoiced_dft_standard(b: &mut Bencher) { unvoiced_dft(b, NoiseKind::Standard) }
+
+#[bench]
+fn bench_unvoiced(b: &mut Bencher) {
+    let frames = corpus();
+    let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+    b.iter(|| {
+        for f in frames.iter() {
+            let u = Unvoiced::new(&f.cur.unvoiced, &f.prev.unvoiced);
+            u.add_to(0, &mut buf);
+            black_box(&buf);
+        }
+    });
+}
+
+#[bench]
+fn bench_voiced(b: &mut Bencher) {
+    let frames = corpus();
+    let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+    b.iter(|| {
+        for f in frames.iter() {
+            let v = Voiced::with_timing(&f.params, &f.prev, &f.cur.phase, &f.cur.enhanced,
+                                        &f.voice, Timing::native(), false);
+            v.fill(0, &mut buf);
+            black_box(&buf);
+        }
+    });
+}
+
+#[bench]
+fn bench_decode(b: &mut Bencher) {
+    let mut dec = ImbeDecoder::new();
+    let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+    b.iter(|| {
+        for &chunks in CORPUS.iter() {
+            dec.decode(ReceivedFrame::new(chunks, [0; 7]), &mut buf);
+            black_box(&buf);
+        }
+    });
+}
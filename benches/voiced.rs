@@ -0,0 +1,98 @@
+//! Compare per-sample and oscillator bank voiced synthesis.
+
+#![feature(test)]
+
+extern crate imbe;
+extern crate rand;
+extern crate test;
+
+use imbe::coefs::Coefficients;
+use imbe::consts::SAMPLES_PER_FRAME;
+use imbe::descramble::{descramble, Bootstrap};
+use imbe::enhance::{EnhancedSpectrals, FrameEnergy};
+use imbe::gain::Gains;
+use imbe::params::BaseParams;
+use imbe::prev::PrevFrame;
+use imbe::spectral::Spectrals;
+use imbe::timing::Timing;
+use imbe::voiced::{Phase, PhaseBase, Voiced};
+use rand::XorShiftRng;
+use test::{black_box, Bencher};
+
+/// Frame with 52 harmonics, most of them voiced.
+const CHUNKS: [u32; 8] = [
+    0b101111010010,
+    0b110011001100,
+    0b111000111000,
+    0b111111111111,
+    0b11111111111,
+    0b10101111010,
+    0b01110111011,
+    0b00001000,
+];
+
+/// Decode the benchmark frame following the given previous frame.
+fn decode(prev: &PrevFrame, rng: &mut XorShiftRng) -> PrevFrame {
+    let params = match Bootstrap::new(&CHUNKS) {
+        Bootstrap::Period(p) => BaseParams::new(p),
+        _ => panic!("invalid benchmark frame"),
+    };
+    let (amps, voice, gain_idx) = descramble(&CHUNKS, &params);
+    let gains = Gains::new(gain_idx, &amps, &params);
+    let coefs = Coefficients::new(&gains, &amps, &params);
+    let spectrals = Spectrals::new(&coefs, &params, prev);
+    let energy = FrameEnergy::new(&spectrals, &prev.energy, &params);
+    let enhanced = EnhancedSpectrals::new(&spectrals, &energy, &params);
+    let base = PhaseBase::new(&params, prev);
+    let phase = Phase::new(&base, &params, prev, &voice, rng);
+
+    PrevFrame {
+        params: params.clone(),
+        model: params,
+        spectrals: spectrals,
+        enhanced: enhanced,
+        voice: voice,
+        energy: energy,
+        phase_base: base,
+        phase: phase,
+        .. PrevFrame::default()
+    }
+}
+
+/// Decode two consecutive frames so both overlapping frames contribute harmonics.
+fn frames() -> (PrevFrame, PrevFrame) {
+    let mut rng = XorShiftRng::new_unseeded();
+    let prev = decode(&PrevFrame::default(), &mut rng);
+    let cur = decode(&prev, &mut rng);
+
+    (prev, cur)
+}
+
+#[bench]
+fn bench_get(b: &mut Bencher) {
+    let (prev, cur) = frames();
+    let v = Voiced::with_timing(&cur.params, &prev, &cur.phase, &cur.enhanced, &cur.voice,
+                                Timing::native(), false);
+    let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+    b.iter(|| {
+        for (n, x) in buf.iter_mut().enumerate() {
+            *x = v.get(n);
+        }
+
+        black_box(&buf);
+    });
+}
+
+#[bench]
+fn bench_fill(b: &mut Bencher) {
+    let (prev, cur) = frames();
+    let v = Voiced::with_timing(&cur.params, &prev, &cur.phase, &cur.enhanced, &cur.voice,
+                                Timing::native(), false);
+    let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+    b.iter(|| {
+        v.fill(0, &mut buf);
+        black_box(&buf);
+    });
+}
@@ -0,0 +1,70 @@
+//! Measure how often the decoder repeats and mutes frames over a range of simulated
+//! channel conditions.
+//!
+//! ```text
+//! cargo run --release --example ber -- [random|burst|fading] [frames]
+//! ```
+
+extern crate imbe;
+
+use std::env;
+use std::process;
+
+use imbe::channel::{Channel, GilbertElliott, Impairment, Rayleigh};
+use imbe::frame::TEST_CHUNKS;
+use imbe::rng::XorShift;
+use imbe::{FrameStatus, ImbeDecoder};
+
+/// Build the sweep of impairments for the given channel model.
+fn sweep(model: &str) -> Option<Vec<Impairment>> {
+    let bers = [0.001, 0.005, 0.01, 0.02, 0.03, 0.05, 0.08, 0.1, 0.15, 0.2];
+
+    Some(match model {
+        "random" => bers.iter().map(|&p| Impairment::Random(p)).collect(),
+        "burst" => bers.iter().map(|&p| {
+            Impairment::Burst(GilbertElliott::new(50.0, p / 0.5, 0.5))
+        }).collect(),
+        "fading" => (0..10).map(|i| {
+            Impairment::Fading(Rayleigh { snr: 30.0 - 3.0 * i as f32, doppler: 20.0 })
+        }).collect(),
+        _ => return None,
+    })
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    let model = args.get(1).map(|s| &s[..]).unwrap_or("random");
+
+    let frames = match args.get(2) {
+        Some(n) => n.parse().expect("invalid frame count"),
+        None => 1000,
+    };
+
+    let sweep = match sweep(model) {
+        Some(s) => s,
+        None => {
+            println!("usage: ber [random|burst|fading] [frames]");
+            process::exit(1);
+        },
+    };
+
+    println!("{:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+             "ber", "measured", "normal", "repeat", "mute", "invalid");
+
+    for imp in sweep {
+        let mut ch = Channel::with_rng(imp, XorShift::default());
+        let mut dec = ImbeDecoder::with_rng(Default::default(), XorShift::default());
+        let mut buf = [0.0; imbe::consts::SAMPLES_PER_FRAME];
+
+        for _ in 0..frames {
+            dec.decode(ch.transmit(&TEST_CHUNKS), &mut buf);
+        }
+
+        let frac = |s| dec.stats().count(s) as f32 / frames as f32;
+
+        println!("{:>8.4} {:>8.4} {:>8.3} {:>8.3} {:>8.3} {:>8.3}",
+                 imp.mean_ber(), ch.ber(), frac(FrameStatus::Normal),
+                 frac(FrameStatus::Repeat), frac(FrameStatus::Mute),
+                 frac(FrameStatus::Invalid));
+    }
+}
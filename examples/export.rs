@@ -0,0 +1,97 @@
+//! Decode a vector file and export each frame's parameter tracks or a spectrogram of the
+//! synthesized audio for analysis.
+//!
+//! ```text
+//! cargo run --example export -- csv tests/vectors/synthetic.vec tracks.csv
+//! cargo run --example export -- npy tests/vectors/synthetic.vec /path/to/dir
+//! cargo run --example export -- text tests/vectors/synthetic.vec [range-db]
+//! cargo run --example export -- svg tests/vectors/synthetic.vec out.svg [range-db]
+//! ```
+//!
+//! NPY export writes `params.npy`, `voicing.npy`, `spectrals.npy`, `enhanced.npy`,
+//! `energy.npy`, and `spectrogram.npy` to the given directory.
+
+extern crate imbe;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::process;
+
+use imbe::conformance;
+use imbe::export::{Array, Recorder};
+use imbe::ReceivedFrame;
+
+/// Supported export modes.
+const MODES: [&'static str; 4] = ["csv", "npy", "text", "svg"];
+
+/// Default dynamic range of spectrograms, in dB.
+const RANGE: f32 = 60.0;
+
+fn usage() -> ! {
+    println!("usage: export csv <vectors> <out.csv>");
+    println!("       export npy <vectors> <dir>");
+    println!("       export text <vectors> [range-db]");
+    println!("       export svg <vectors> <out.svg> [range-db]");
+    process::exit(1);
+}
+
+/// Decode every frame of the given vector file.
+fn record(path: &str) -> Recorder {
+    let file = File::open(path).expect("unable to open vectors");
+    let frames = conformance::read(BufReader::new(file)).unwrap_or_else(|e| {
+        println!("{}: {}", path, e);
+        process::exit(1);
+    });
+
+    let mut dec = conformance::decoder();
+    let mut rec = Recorder::new();
+
+    for f in frames {
+        rec.record(&mut dec, ReceivedFrame::new(f.chunks, f.errors));
+    }
+
+    rec
+}
+
+/// Parse the optional dynamic range argument.
+fn range(arg: Option<&String>) -> f32 {
+    arg.map(|r| r.parse().expect("invalid range")).unwrap_or(RANGE)
+}
+
+/// Create a buffered output file at the given path.
+fn create<P: AsRef<Path>>(path: P) -> BufWriter<File> {
+    BufWriter::new(File::create(path).expect("unable to create output"))
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+
+    let (mode, input) = match (args.get(1), args.get(2)) {
+        (Some(m), Some(i)) if MODES.contains(&&m[..]) => (&m[..], i),
+        _ => usage(),
+    };
+
+    let rec = record(input);
+
+    match (mode, args.get(3)) {
+        ("csv", Some(out)) => rec.write_csv(&mut create(out)).unwrap(),
+        ("npy", Some(dir)) => {
+            fs::create_dir_all(dir).expect("unable to create directory");
+
+            for &array in Array::all().iter() {
+                let path = Path::new(dir).join(format!("{}.npy", array.name()));
+                rec.write_npy(array, &mut create(path)).unwrap();
+            }
+        },
+        ("text", r) => {
+            let stdout = io::stdout();
+            rec.spectrogram().write_text(&mut stdout.lock(), range(r)).unwrap();
+        },
+        ("svg", Some(out)) => {
+            rec.spectrogram().write_svg(&mut create(out), range(args.get(4))).unwrap();
+        },
+        _ => usage(),
+    }
+}
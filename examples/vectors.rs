@@ -0,0 +1,172 @@
+//! Generate the bundled synthetic conformance vectors, or check the decoder against a
+//! directory of reference vectors.
+//!
+//! ```text
+//! cargo run --example vectors -- generate tests/vectors
+//! cargo run --example vectors -- check /path/to/vectors [pcm-tolerance]
+//! ```
+
+extern crate imbe;
+
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process;
+
+use imbe::conformance::{self, Tolerance};
+use imbe::frame::TEST_CHUNKS;
+
+/// Frames with a range of pitches and voicing, from mostly voiced to mostly unvoiced.
+const FRAMES: [[u32; 8]; 6] = [
+    TEST_CHUNKS,
+    [
+        0b101111010010,
+        0b110011001100,
+        0b111000111000,
+        0b111111111111,
+        0b11111111111,
+        0b10101111010,
+        0b01110111011,
+        0b00001000,
+    ],
+    [
+        0b000000111111,
+        0b111111111111,
+        0b111111111111,
+        0b111111111111,
+        0b11111111111,
+        0b11111111111,
+        0b11111111111,
+        0b00001000,
+    ],
+    [
+        0b011010100101,
+        0b100110011010,
+        0b010101101001,
+        0b001100110011,
+        0b00000000000,
+        0b01011010010,
+        0b10010110100,
+        0b01101001,
+    ],
+    [
+        0b100101011100,
+        0b011011001010,
+        0b101001110001,
+        0b110100101110,
+        0b01010101010,
+        0b00111100001,
+        0b11000011110,
+        0b00110011,
+    ],
+    [
+        0b010011110000,
+        0b001110001110,
+        0b110001100011,
+        0b011100011100,
+        0b10011001100,
+        0b11100001111,
+        0b00011110000,
+        0b01010101,
+    ],
+];
+
+/// Frame with b<sub>0</sub> = 216, which signals silence.
+const SILENCE: [u32; 8] = [0b110110000000, 0, 0, 0, 0, 0, 0, 0];
+
+/// Frame with the invalid b<sub>0</sub> = 208.
+const INVALID: [u32; 8] = [0b110100000000, 0, 0, 0, 0, 0, 0, 0];
+
+/// Build the synthetic stream as pairs of chunks and error counts, covering pitch
+/// changes, corrected errors, repeats, silence, and invalid frames.
+fn stream() -> Vec<([u32; 8], [usize; 7])> {
+    let mut s = vec![];
+
+    for &f in FRAMES.iter() {
+        s.push((f, [0; 7]));
+        s.push((f, [0; 7]));
+    }
+
+    s.push((FRAMES[0], [1, 2, 0, 1, 1, 0, 0]));
+    s.push((FRAMES[1], [3, 3, 3, 3, 1, 0, 0]));
+    s.push((FRAMES[1], [0; 7]));
+    s.push((SILENCE, [0; 7]));
+    s.push((FRAMES[2], [0; 7]));
+    s.push((INVALID, [0; 7]));
+    s.push((FRAMES[3], [0, 0, 0, 0, 1, 1, 1]));
+    s.push((FRAMES[4], [0; 7]));
+
+    s
+}
+
+/// Write the synthetic vectors into the given directory.
+fn generate(dir: &Path) {
+    let path = dir.join("synthetic").with_extension(conformance::EXTENSION);
+    let mut out = BufWriter::new(File::create(&path).expect("unable to create vectors"));
+    let mut dec = conformance::decoder();
+
+    writeln!(out, "# Synthetic vectors generated by `cargo run --example vectors`.")
+        .expect("unable to write vectors");
+
+    for (chunks, errors) in stream() {
+        conformance::record(&mut dec, chunks, errors).write(&mut out)
+            .expect("unable to write vectors");
+    }
+
+    println!("wrote {}", path.display());
+}
+
+/// Check the vectors in the given directory, returning whether they all match.
+fn check(dir: &Path, tol: Tolerance) -> bool {
+    let reports = match conformance::check_dir(dir, &tol) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("error: {}", e);
+            return false;
+        },
+    };
+
+    let mut pass = true;
+
+    for r in reports {
+        match r.divergence {
+            Some(d) => {
+                println!("{}: {}", r.path.display(), d);
+                pass = false;
+            },
+            None => println!("{}: {} frames match", r.path.display(), r.frames),
+        }
+    }
+
+    pass
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+
+    let ok = match (args.get(1).map(|s| &s[..]), args.get(2)) {
+        (Some("generate"), Some(dir)) => {
+            generate(Path::new(dir));
+            true
+        },
+        (Some("check"), Some(dir)) => {
+            let mut tol = Tolerance::default();
+
+            if let Some(pcm) = args.get(3) {
+                tol.pcm = pcm.parse().expect("invalid pcm tolerance");
+            }
+
+            check(Path::new(dir), tol)
+        },
+        _ => {
+            println!("usage: vectors generate <dir>");
+            println!("       vectors check <dir> [pcm-tolerance]");
+            false
+        },
+    };
+
+    if !ok {
+        process::exit(1);
+    }
+}
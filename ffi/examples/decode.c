@@ -0,0 +1,82 @@
+/*
+ * Decode a short stream of frames through the C interface, printing the status and
+ * peak amplitude of each.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "imbe.h"
+
+/* Voiced frame given as prioritized chunks. */
+static const uint32_t CHUNKS[8] = {
+    0x212, 0xccc, 0xe38, 0xfff, 0x535, 0x17a, 0x3bb, 0x08,
+};
+
+/* Same frame after error correction coding and interleaving. */
+static const uint8_t CODED[IMBE_CODED_BYTES] = {
+    0x22, 0x0c, 0x6a, 0x56, 0x60, 0x7e, 0x76, 0x5c, 0xeb,
+    0x05, 0x10, 0x7f, 0x66, 0x9b, 0xbd, 0x0e, 0xc0, 0x35,
+};
+
+/* Silence frame, with b0 = 216. */
+static const uint32_t SILENCE[8] = {0xd80, 0, 0, 0, 0, 0, 0, 0};
+
+static int peak(const int16_t *samples) {
+    int max = 0;
+
+    for (int i = 0; i < IMBE_SAMPLES_PER_FRAME; i++) {
+        int x = abs(samples[i]);
+
+        if (x > max) {
+            max = x;
+        }
+    }
+
+    return max;
+}
+
+int main(void) {
+    static const uint8_t NO_ERRORS[7] = {0};
+    static const uint8_t ERRORS[7] = {3, 3, 3, 3, 1, 0, 0};
+
+    imbe_decoder *dec = imbe_decoder_new();
+    int16_t samples[IMBE_SAMPLES_PER_FRAME];
+    uint8_t coded[IMBE_CODED_BYTES];
+    int status;
+
+    if (!dec) {
+        return 1;
+    }
+
+    for (int i = 0; i < 4; i++) {
+        status = imbe_decode_chunks(dec, CHUNKS, NO_ERRORS, samples);
+        printf("chunks: status %d peak %d\n", status, peak(samples));
+    }
+
+    status = imbe_decode_chunks(dec, CHUNKS, ERRORS, samples);
+    printf("errors: status %d\n", status);
+
+    /* Flip bits of c0 and c4 in the first dibits, which error correction repairs. */
+    for (int i = 0; i < IMBE_CODED_BYTES; i++) {
+        coded[i] = CODED[i];
+    }
+
+    coded[0] ^= 0x88;
+
+    status = imbe_decode_frame(dec, coded, samples);
+    printf("coded: status %d peak %d\n", status, peak(samples));
+
+    status = imbe_decode_chunks(dec, SILENCE, NO_ERRORS, samples);
+    printf("silence: status %d peak %d\n", status, peak(samples));
+
+    printf("frames: %zu normal: %zu\n", imbe_decoder_count(dec, IMBE_ERROR),
+           imbe_decoder_count(dec, IMBE_NORMAL));
+
+    imbe_decoder_reset(dec);
+    printf("reset: %zu\n", imbe_decoder_count(dec, IMBE_ERROR));
+
+    imbe_decoder_free(dec);
+
+    return 0;
+}
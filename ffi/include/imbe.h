@@ -0,0 +1,87 @@
+/*
+ * C interface to the IMBE voice decoder.
+ *
+ * Each decoder outputs IMBE_SAMPLES_PER_FRAME signed 16-bit samples per frame at
+ * 8kHz. Decoders aren't thread safe, but separate decoders can be used from separate
+ * threads.
+ */
+
+#ifndef IMBE_H
+#define IMBE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Number of output samples per frame. */
+#define IMBE_SAMPLES_PER_FRAME 160
+
+/* Number of bytes in a 144-bit coded frame. */
+#define IMBE_CODED_BYTES 18
+
+/* Describes how the decoder handled a frame. */
+enum imbe_status {
+    /* Arguments were invalid or decoding failed, which resets the decoder. */
+    IMBE_ERROR = -1,
+    /* Frame was decoded and synthesized normally. */
+    IMBE_NORMAL = 0,
+    /* Frame had too many errors, so the previous frame was repeated. */
+    IMBE_REPEAT = 1,
+    /* Error rate was too high, so the frame was muted. */
+    IMBE_MUTE = 2,
+    /* Frame contained a silence b0 value. */
+    IMBE_SILENCE = 3,
+    /* Frame contained an invalid b0 value, so the previous frame was repeated. */
+    IMBE_INVALID = 4,
+};
+
+/* Opaque decoder state. */
+typedef struct imbe_decoder imbe_decoder;
+
+/*
+ * Create a new decoder, which must be released with imbe_decoder_free, returning NULL
+ * if creation fails.
+ */
+imbe_decoder *imbe_decoder_new(void);
+
+/* Release the given decoder, which may be NULL. */
+void imbe_decoder_free(imbe_decoder *dec);
+
+/* Reset the given decoder to its initial state, clearing its statistics. */
+void imbe_decoder_reset(imbe_decoder *dec);
+
+/*
+ * Decode a frame given as the prioritized chunks u0, ..., u7 and the number of errors
+ * corrected in each of u0, ..., u6 into IMBE_SAMPLES_PER_FRAME samples, returning the
+ * frame status.
+ */
+int imbe_decode_chunks(imbe_decoder *dec, const uint32_t chunks[8],
+                       const uint8_t errors[7],
+                       int16_t samples[IMBE_SAMPLES_PER_FRAME]);
+
+/*
+ * Decode a 144-bit error-coded frame, given as received over the air with the
+ * interleaved code vectors c0, ..., c7 packed MSB first, into IMBE_SAMPLES_PER_FRAME
+ * samples, returning the frame status.
+ */
+int imbe_decode_frame(imbe_decoder *dec, const uint8_t frame[IMBE_CODED_BYTES],
+                      int16_t samples[IMBE_SAMPLES_PER_FRAME]);
+
+/*
+ * Retrieve the number of frames decoded with the given status since the decoder was
+ * created or reset, the total number of frames if the status is IMBE_ERROR, or 0 if the
+ * status is unknown.
+ */
+size_t imbe_decoder_count(const imbe_decoder *dec, int status);
+
+/* Compute the call-quality score, between 0 (unusable) and 1 (error free). */
+float imbe_decoder_quality(const imbe_decoder *dec);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,263 @@
+//! C interface to the IMBE decoder.
+//!
+//! This crate builds a shared and static library exposing `ImbeDecoder` through the
+//! functions declared in `include/imbe.h`. Each decoder outputs 160 signed 16-bit
+//! samples per frame at 8kHz.
+//!
+//! Functions never unwind into the caller: invalid arguments and internal panics are
+//! reported as `IMBE_ERROR`, and a decoder that panics is reset to its initial state.
+
+extern crate imbe;
+
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use imbe::consts::SAMPLES_PER_FRAME;
+use imbe::fec::{self, CODED_BYTES};
+use imbe::frame::ReceivedFrame;
+use imbe::{FrameStatus, ImbeDecoder};
+
+/// Returned when a function is called with invalid arguments or fails internally.
+pub const IMBE_ERROR: c_int = -1;
+
+/// Opaque decoder handle.
+pub struct Decoder(ImbeDecoder);
+
+/// Map the given frame status to its C enum value.
+fn status_code(status: FrameStatus) -> c_int {
+    match status {
+        FrameStatus::Normal => 0,
+        FrameStatus::Repeat => 1,
+        FrameStatus::Mute => 2,
+        FrameStatus::Silence => 3,
+        FrameStatus::Invalid => 4,
+    }
+}
+
+/// Map the given C enum value to a frame status.
+fn code_status(code: c_int) -> Option<FrameStatus> {
+    match code {
+        0 => Some(FrameStatus::Normal),
+        1 => Some(FrameStatus::Repeat),
+        2 => Some(FrameStatus::Mute),
+        3 => Some(FrameStatus::Silence),
+        4 => Some(FrameStatus::Invalid),
+        _ => None,
+    }
+}
+
+/// Decode the given frame into the given sample buffer, catching any panic.
+unsafe fn decode(dec: *mut Decoder, frame: ReceivedFrame, samples: *mut i16) -> c_int {
+    let out = slice::from_raw_parts_mut(samples, SAMPLES_PER_FRAME);
+
+    catch(dec, |dec| dec.decode_i16(frame, out))
+}
+
+/// Run the given decoding function on the given decoder, resetting the decoder if it
+/// panics, since a panic can leave its state partially updated.
+unsafe fn catch<F: FnOnce(&mut ImbeDecoder) -> FrameStatus>(dec: *mut Decoder, f: F)
+    -> c_int
+{
+    let dec = &mut (*dec).0;
+
+    match panic::catch_unwind(AssertUnwindSafe(|| f(dec))) {
+        Ok(status) => status_code(status),
+        Err(_) => {
+            *dec = ImbeDecoder::new();
+            IMBE_ERROR
+        },
+    }
+}
+
+/// Create a new decoder, which must be released with `imbe_decoder_free`, returning
+/// null if creation fails.
+#[no_mangle]
+pub extern "C" fn imbe_decoder_new() -> *mut Decoder {
+    match panic::catch_unwind(|| Box::new(Decoder(ImbeDecoder::new()))) {
+        Ok(dec) => Box::into_raw(dec),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Release the given decoder, which may be null.
+#[no_mangle]
+pub unsafe extern "C" fn imbe_decoder_free(dec: *mut Decoder) {
+    if !dec.is_null() {
+        drop(Box::from_raw(dec));
+    }
+}
+
+/// Reset the given decoder to its initial state, clearing its statistics.
+#[no_mangle]
+pub unsafe extern "C" fn imbe_decoder_reset(dec: *mut Decoder) {
+    if !dec.is_null() {
+        (*dec).0 = ImbeDecoder::new();
+    }
+}
+
+/// Decode a frame given as the chunks u<sub>0</sub>, ..., u<sub>7</sub> and error
+/// counts ϵ<sub>0</sub>, ..., ϵ<sub>6</sub> into 160 samples, returning the frame
+/// status.
+#[no_mangle]
+pub unsafe extern "C" fn imbe_decode_chunks(dec: *mut Decoder, chunks: *const u32,
+                                            errors: *const u8, samples: *mut i16)
+    -> c_int
+{
+    if dec.is_null() || chunks.is_null() || errors.is_null() || samples.is_null() {
+        return IMBE_ERROR;
+    }
+
+    let mut c = [0; 8];
+    let mut e = [0; 7];
+
+    c.copy_from_slice(slice::from_raw_parts(chunks, 8));
+
+    for (dst, &src) in e.iter_mut().zip(slice::from_raw_parts(errors, 7)) {
+        *dst = src as usize;
+    }
+
+    match ReceivedFrame::try_new(c, e) {
+        Some(f) => decode(dec, f, samples),
+        None => IMBE_ERROR,
+    }
+}
+
+/// Decode a 144-bit error-coded frame, given as 18 bytes received over the air with the
+/// interleaved code vectors c<sub>0</sub>, ..., c<sub>7</sub> packed MSB first, into 160
+/// samples, returning the frame status.
+#[no_mangle]
+pub unsafe extern "C" fn imbe_decode_frame(dec: *mut Decoder, frame: *const u8,
+                                           samples: *mut i16)
+    -> c_int
+{
+    if dec.is_null() || frame.is_null() || samples.is_null() {
+        return IMBE_ERROR;
+    }
+
+    let mut f = [0; CODED_BYTES];
+    f.copy_from_slice(slice::from_raw_parts(frame, CODED_BYTES));
+
+    decode(dec, fec::decode(&fec::deinterleave(&f)), samples)
+}
+
+/// Retrieve the number of frames decoded with the given status since the decoder was
+/// created or reset, the total number of frames if the status is `IMBE_ERROR`, or 0 if
+/// the status is unknown.
+#[no_mangle]
+pub unsafe extern "C" fn imbe_decoder_count(dec: *const Decoder, status: c_int) -> usize {
+    if dec.is_null() {
+        return 0;
+    }
+
+    let stats = (*dec).0.stats();
+
+    if status == IMBE_ERROR {
+        return stats.frames();
+    }
+
+    code_status(status).map_or(0, |s| stats.count(s))
+}
+
+/// Compute the call-quality score of the given decoder, between 0 (unusable) and 1
+/// (error free).
+#[no_mangle]
+pub unsafe extern "C" fn imbe_decoder_quality(dec: *const Decoder) -> f32 {
+    if dec.is_null() {
+        return 0.0;
+    }
+
+    (*dec).0.stats().quality()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use imbe::frame::TEST_CHUNKS;
+
+    #[test]
+    fn test_decode() {
+        let mut out = [0i16; SAMPLES_PER_FRAME];
+
+        unsafe {
+            let dec = imbe_decoder_new();
+
+            let s = imbe_decode_chunks(dec, TEST_CHUNKS.as_ptr(), [0; 7].as_ptr(),
+                                       out.as_mut_ptr());
+            assert_eq!(s, 0);
+
+            let air = fec::interleave(&fec::encode(&TEST_CHUNKS));
+            let s = imbe_decode_frame(dec, air.as_ptr(), out.as_mut_ptr());
+            assert_eq!(s, 0);
+            assert!(out.iter().any(|&x| x != 0));
+
+            let errors = [3, 3, 3, 3, 1, 0, 0];
+            let s = imbe_decode_chunks(dec, TEST_CHUNKS.as_ptr(), errors.as_ptr(),
+                                       out.as_mut_ptr());
+            assert_eq!(s, 1);
+
+            // Chunk with too many bits.
+            let mut bad = TEST_CHUNKS;
+            bad[7] = 0x80;
+            let s = imbe_decode_chunks(dec, bad.as_ptr(), [0; 7].as_ptr(),
+                                       out.as_mut_ptr());
+            assert_eq!(s, IMBE_ERROR);
+
+            assert_eq!(imbe_decoder_count(dec, 0), 2);
+            assert_eq!(imbe_decoder_count(dec, 1), 1);
+            assert_eq!(imbe_decoder_count(dec, IMBE_ERROR), 3);
+            assert_eq!(imbe_decoder_count(dec, 5), 0);
+            assert_eq!(imbe_decoder_count(dec, -2), 0);
+
+            imbe_decoder_reset(dec);
+            assert_eq!(imbe_decoder_count(dec, IMBE_ERROR), 0);
+            assert_eq!(imbe_decoder_quality(dec), 1.0);
+
+            assert_eq!(imbe_decode_frame(ptr::null_mut(), ptr::null(), ptr::null_mut()),
+                       IMBE_ERROR);
+
+            imbe_decoder_free(dec);
+            imbe_decoder_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_burst() {
+        let mut out = [0i16; SAMPLES_PER_FRAME];
+        let mut air = fec::interleave(&fec::encode(&TEST_CHUNKS));
+
+        // A burst over the first 4 dibits is spread over 6 code vectors and corrected.
+        air[0] ^= 0xff;
+
+        unsafe {
+            let dec = imbe_decoder_new();
+
+            let s = imbe_decode_frame(dec, air.as_ptr(), out.as_mut_ptr());
+            assert_eq!(s, 0);
+
+            imbe_decoder_free(dec);
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        let mut out = [0i16; SAMPLES_PER_FRAME];
+
+        unsafe {
+            let dec = imbe_decoder_new();
+
+            let s = imbe_decode_chunks(dec, TEST_CHUNKS.as_ptr(), [0; 7].as_ptr(),
+                                       out.as_mut_ptr());
+            assert_eq!(s, 0);
+            assert_eq!(imbe_decoder_count(dec, IMBE_ERROR), 1);
+
+            // Recover from a panic partway through a frame with a fresh decoder.
+            assert_eq!(catch(dec, |_| panic!("decode")), IMBE_ERROR);
+            assert_eq!(imbe_decoder_count(dec, IMBE_ERROR), 0);
+
+            imbe_decoder_free(dec);
+        }
+    }
+}
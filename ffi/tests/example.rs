@@ -0,0 +1,59 @@
+//! Build and run the example C program against the static library.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// System libraries the static library depends on, for each supported target.
+#[cfg(target_os = "linux")]
+const LIBS: &[&str] = &["-lpthread", "-ldl", "-lm"];
+#[cfg(target_os = "macos")]
+const LIBS: &[&str] = &["-lSystem"];
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn test_example() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    // Test executables are built into the same directory as the libraries.
+    let exe = env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    let out = deps.join("decode-example");
+
+    let status = Command::new(env::var("CC").unwrap_or("cc".to_string()))
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I").arg(root.join("include"))
+        .arg(root.join("examples/decode.c"))
+        .arg(deps.join("libimbe_ffi.a"))
+        .args(LIBS)
+        .arg("-o").arg(&out)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+
+    let output = Command::new(&out).output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines.len(), 9);
+
+    for line in &lines[..4] {
+        assert!(line.starts_with("chunks: status 0 peak "));
+    }
+
+    assert_eq!(lines[4], "errors: status 1");
+    assert!(lines[5].starts_with("coded: status 0 peak "));
+    assert_eq!(lines[6], "silence: status 3 peak 0");
+    assert_eq!(lines[7], "frames: 7 normal: 5");
+    assert_eq!(lines[8], "reset: 0");
+
+    // Frames after the first should be audible.
+    let peak = |line: &str| line.rsplit(' ').next().unwrap().parse::<i32>().unwrap();
+    assert!(peak(lines[3]) > 100);
+    assert!(peak(lines[5]) > 100);
+}
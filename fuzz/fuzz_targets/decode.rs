@@ -0,0 +1,9 @@
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate imbe_fuzz;
+
+fuzz_target!(|data: &[u8]| {
+    imbe_fuzz::decode(data);
+});
@@ -0,0 +1,204 @@
+//! Entry points for fuzzing frame parsing, descrambling, and full decoding, from both
+//! coded frames and raw chunks with arbitrary error counts.
+//!
+//! Each function takes arbitrary bytes and must never panic. They're shared by the
+//! libFuzzer targets in `fuzz_targets` and the offline smoke tests.
+
+extern crate imbe;
+
+use imbe::coefs::Coefficients;
+use imbe::consts::SAMPLES_PER_FRAME;
+use imbe::descramble::{descramble, Bootstrap};
+use imbe::fec::{self, CodedFrame, CODED_BYTES};
+use imbe::fixed::FixedDecoder;
+use imbe::frame::{self, Chunks, ReceivedFrame};
+use imbe::gain::Gains;
+use imbe::noise::NoiseKind;
+use imbe::params::BaseParams;
+use imbe::resample::MAX_SAMPLES_PER_FRAME;
+use imbe::rng::XorShift;
+use imbe::{DecoderConfig, ImbeDecoder, SampleRate, Synthesis, VoiceTransform};
+
+/// Number of bits in each chunk u<sub>i</sub>.
+const CHUNK_BITS: [usize; 8] = [12, 12, 12, 12, 11, 11, 11, 7];
+
+/// Read the given number of bits starting at the given bit position, MSB first.
+fn get_bits(data: &[u8], pos: usize, len: usize) -> u32 {
+    (pos..pos + len).fold(0, |w, b| w << 1 | (data[b / 8] >> (7 - b % 8) & 1) as u32)
+}
+
+/// Unpack 88 bits of the given bytes into chunks of the correct widths.
+fn unpack(data: &[u8; 11]) -> Chunks {
+    let mut chunks = [0; 8];
+    let mut pos = 0;
+
+    for (c, &bits) in chunks.iter_mut().zip(CHUNK_BITS.iter()) {
+        *c = get_bits(data, pos, bits);
+        pos += bits;
+    }
+
+    chunks
+}
+
+/// Parse raw chunks and coded frames.
+///
+/// The first 32 bytes are taken as unchecked little-endian chunks, which, if they fit
+/// their widths, must decode to finite samples. Every 18-byte piece is then error
+/// decoded, which must always produce a valid frame that encodes back to a codeword
+/// with the same chunks.
+pub fn chunks(data: &[u8]) {
+    if data.len() >= 32 {
+        let mut chunks = [0; 8];
+
+        for (c, b) in chunks.iter_mut().zip(data.chunks(4)) {
+            *c = b.iter().rev().fold(0, |w, &b| w << 8 | b as u32);
+        }
+
+        if let Some(frame) = ReceivedFrame::try_new(chunks, [0; 7]) {
+            let mut dec = ImbeDecoder::with_rng(DecoderConfig::default(),
+                                                XorShift::default());
+            let mut out = [0.0f32; SAMPLES_PER_FRAME];
+
+            dec.decode_into(frame, &mut out);
+            assert!(out.iter().all(|x| x.is_finite()));
+        }
+    }
+
+    for piece in data.chunks(CODED_BYTES).filter(|p| p.len() == CODED_BYTES) {
+        let mut coded: CodedFrame = [0; CODED_BYTES];
+        coded.copy_from_slice(piece);
+
+        let frame = fec::decode(&coded);
+        assert!(frame::valid_chunks(&frame.chunks));
+
+        let again = fec::decode(&fec::encode(&frame.chunks));
+        assert_eq!(again.chunks, frame.chunks);
+        assert_eq!(again.errors, [0; 7]);
+    }
+}
+
+/// Descramble every 11-byte piece as packed chunks and reconstruct the spectral
+/// amplitude model.
+pub fn descramble_chunks(data: &[u8]) {
+    for piece in data.chunks(11).filter(|p| p.len() == 11) {
+        let mut packed = [0; 11];
+        packed.copy_from_slice(piece);
+
+        let chunks = unpack(&packed);
+
+        let params = match Bootstrap::new(&chunks) {
+            Bootstrap::Period(p) => BaseParams::new(p),
+            _ => continue,
+        };
+
+        let (amps, voice, gain_idx) = descramble(&chunks, &params);
+        assert!(gain_idx < 64);
+        assert!(voice.unvoiced_count() <= params.harmonics);
+
+        let gains = Gains::new(gain_idx, &amps, &params);
+        let coefs = Coefficients::new(&gains, &amps, &params);
+
+        for l in 1..params.harmonics as usize + 1 {
+            assert!(coefs.get(l).is_finite());
+        }
+    }
+}
+
+/// Create a decoder configuration from the given option bits.
+fn config(opts: u8) -> DecoderConfig {
+    DecoderConfig {
+        rate: match opts & 0b11 {
+            0 => SampleRate::Hz8000,
+            1 => SampleRate::Hz16000,
+            2 => SampleRate::Hz44100,
+            _ => SampleRate::Hz48000,
+        },
+        synthesis: if opts >> 2 & 1 == 0 {
+            Synthesis::Resample
+        } else {
+            Synthesis::Direct
+        },
+        extend_bandwidth: opts >> 3 & 1 == 1,
+        noise: match opts >> 4 & 0b11 {
+            0 => NoiseKind::Polar,
+            1 => NoiseKind::Table,
+            2 => NoiseKind::Uniform,
+            _ => NoiseKind::Standard,
+        },
+        postfilter: if opts >> 6 & 1 == 1 { 1.0 } else { 0.0 },
+        transform: if opts >> 7 == 1 {
+            VoiceTransform::new(1.5, 0.8)
+        } else {
+            VoiceTransform::default()
+        },
+        .. DecoderConfig::default()
+    }
+}
+
+/// Decode a stream of frames with the floating-point and fixed-point decoders.
+///
+/// The first byte selects the floating-point decoder's output options, and each
+/// following piece of the given size is parsed into a frame by the given function.
+fn decode_pieces<F: Fn(&[u8]) -> Option<ReceivedFrame>>(data: &[u8], size: usize,
+                                                       parse: F)
+{
+    let (opts, frames) = match data.split_first() {
+        Some((&o, f)) => (o, f),
+        None => return,
+    };
+
+    let mut dec = ImbeDecoder::with_rng(config(opts), XorShift::default());
+    let mut fixed = FixedDecoder::with_rng(XorShift::default());
+
+    let mut out = [0.0f32; MAX_SAMPLES_PER_FRAME];
+    let out = &mut out[..dec.samples_per_frame()];
+    let mut fixed_out = [0; SAMPLES_PER_FRAME];
+
+    for piece in frames.chunks(size).filter(|p| p.len() == size) {
+        let frame = match parse(piece) {
+            Some(f) => f,
+            None => continue,
+        };
+
+        fixed.decode(ReceivedFrame::new(frame.chunks, frame.errors), &mut fixed_out);
+
+        dec.decode_into(frame, out);
+        assert!(out.iter().all(|x| x.is_finite()));
+    }
+}
+
+/// Decode a stream of coded frames with the floating-point and fixed-point decoders.
+///
+/// The first byte selects the floating-point decoder's output options, and each
+/// following 18-byte piece is error decoded into a frame.
+pub fn decode(data: &[u8]) {
+    decode_pieces(data, CODED_BYTES, |piece| {
+        let mut coded: CodedFrame = [0; CODED_BYTES];
+        coded.copy_from_slice(piece);
+
+        Some(fec::decode(&coded))
+    });
+}
+
+/// Decode a stream of raw frames with the floating-point and fixed-point decoders.
+///
+/// The first byte selects the floating-point decoder's output options, and each
+/// following 39-byte piece is taken as 32 bytes of little-endian chunks and 7 error
+/// counts ϵ<sub>0</sub>, ..., ϵ<sub>6</sub> of 0 to 255, bypassing error correction.
+/// Frames accepted by `ReceivedFrame::try_new` must decode to finite samples.
+pub fn decode_raw(data: &[u8]) {
+    decode_pieces(data, 39, |piece| {
+        let mut chunks = [0; 8];
+        let mut errors = [0; 7];
+
+        for (c, b) in chunks.iter_mut().zip(piece[..32].chunks(4)) {
+            *c = b.iter().rev().fold(0, |w, &b| w << 8 | b as u32);
+        }
+
+        for (e, &b) in errors.iter_mut().zip(piece[32..].iter()) {
+            *e = b as usize;
+        }
+
+        ReceivedFrame::try_new(chunks, errors)
+    });
+}
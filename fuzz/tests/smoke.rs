@@ -0,0 +1,82 @@
+//! Run the fuzz entry points over random inputs without libFuzzer.
+
+extern crate imbe;
+extern crate imbe_fuzz;
+
+use imbe::fec;
+use imbe::frame::TEST_CHUNKS;
+use imbe::rng::{Random, XorShift};
+
+/// Generate random inputs of varying lengths up to the given maximum.
+fn inputs(seed: u32, max: usize) -> Vec<Vec<u8>> {
+    let mut r = XorShift::new([seed, 1, 2, 3]);
+
+    (0..200).map(|_| {
+        let len = r.next_u32() as usize % max;
+        (0..len).map(|_| r.next_u32() as u8).collect()
+    }).collect()
+}
+
+#[test]
+fn test_chunks() {
+    for data in inputs(1, 200) {
+        imbe_fuzz::chunks(&data);
+    }
+
+    // Chunks that fit their widths, then chunks that overflow them.
+    let mut data = vec![];
+
+    for &c in TEST_CHUNKS.iter() {
+        data.extend_from_slice(&[c as u8, (c >> 8) as u8, 0, 0]);
+    }
+
+    imbe_fuzz::chunks(&data);
+    imbe_fuzz::chunks(&[0xff; 32]);
+}
+
+#[test]
+fn test_descramble() {
+    for data in inputs(2, 400) {
+        imbe_fuzz::descramble_chunks(&data);
+    }
+}
+
+#[test]
+fn test_decode() {
+    for data in inputs(3, 200) {
+        imbe_fuzz::decode(&data);
+    }
+
+    // Valid frames under every decoder configuration.
+    for opts in 0..256 {
+        let mut data = vec![opts as u8];
+
+        for _ in 0..3 {
+            data.extend_from_slice(&fec::encode(&TEST_CHUNKS));
+        }
+
+        imbe_fuzz::decode(&data);
+    }
+}
+
+#[test]
+fn test_decode_raw() {
+    for data in inputs(4, 400) {
+        imbe_fuzz::decode_raw(&data);
+    }
+
+    // Valid chunks with every error count, including ones no code could report.
+    for errors in 0..256 {
+        let mut data = vec![errors as u8];
+
+        for _ in 0..3 {
+            for &c in TEST_CHUNKS.iter() {
+                data.extend_from_slice(&[c as u8, (c >> 8) as u8, 0, 0]);
+            }
+
+            data.extend_from_slice(&[errors as u8; 7]);
+        }
+
+        imbe_fuzz::decode_raw(&data);
+    }
+}
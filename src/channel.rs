@@ -0,0 +1,381 @@
+//! Simulated channel impairments of coded voice frames.
+//!
+//! A `Channel` flips bits of clean 144-bit coded frames, interleaved as sent over the
+//! air, according to one of several error models, then deinterleaves and error
+//! correction decodes the result, so the received frames carry the same corrected error
+//! counts ϵ<sub>i</sub> a real receiver would see. This allows repeat and mute behavior
+//! to be measured offline against bit error rate:
+//!
+//! - `Impairment::Random` flips each bit independently with a fixed probability.
+//! - `Impairment::Burst` follows a Gilbert–Elliott channel, which alternates between a
+//!   good and a bad state with different error probabilities, producing error bursts.
+//! - `Impairment::Fading` models coherent BPSK over a Rayleigh-faded channel, where
+//!   errors cluster in the fades of a slowly varying channel gain.
+//!
+//! Channel state carries over from frame to frame, so bursts and fades can span frame
+//! boundaries.
+
+use std::f32::consts::{LN_10, PI};
+
+use fec::{self, CodedFrame, CODED_BYTES};
+use frame::{Chunks, ReceivedFrame};
+#[cfg(not(feature = "std"))]
+use math::Float;
+use rng::{Random, XorShift};
+
+/// Number of bits in a coded frame.
+pub const CODED_BITS: usize = CODED_BYTES * 8;
+
+/// Rate of coded bits on the channel, in bits per second.
+pub const BIT_RATE: f32 = CODED_BITS as f32 / 0.02;
+
+/// Two-state Markov model of bursty bit errors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GilbertElliott {
+    /// Probability of moving from the good to the bad state before each bit.
+    pub good_to_bad: f32,
+    /// Probability of moving from the bad to the good state before each bit.
+    pub bad_to_good: f32,
+    /// Bit error probability in the good state.
+    pub good_ber: f32,
+    /// Bit error probability in the bad state.
+    pub bad_ber: f32,
+}
+
+impl GilbertElliott {
+    /// Create a new `GilbertElliott` with the given mean burst length, in bits, and
+    /// fraction of time spent in the bad state, where bits are received without error in
+    /// the good state and with the given error probability in the bad state.
+    pub fn new(burst: f32, bad: f32, bad_ber: f32) -> GilbertElliott {
+        let bad_to_good = 1.0 / burst;
+
+        GilbertElliott {
+            good_to_bad: bad_to_good * bad / (1.0 - bad),
+            bad_to_good: bad_to_good,
+            good_ber: 0.0,
+            bad_ber: bad_ber,
+        }
+    }
+
+    /// Compute the long-run fraction of bits spent in the bad state.
+    pub fn bad_fraction(&self) -> f32 {
+        self.good_to_bad / (self.good_to_bad + self.bad_to_good)
+    }
+
+    /// Compute the long-run bit error rate.
+    pub fn mean_ber(&self) -> f32 {
+        let bad = self.bad_fraction();
+        (1.0 - bad) * self.good_ber + bad * self.bad_ber
+    }
+}
+
+/// Coherent BPSK over a flat Rayleigh-faded channel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rayleigh {
+    /// Mean received energy per bit to noise density ratio, E<sub>b</sub> /
+    /// N<sub>0</sub>, in dB.
+    pub snr: f32,
+    /// Maximum Doppler frequency f<sub>D</sub> of the fading, in Hz.
+    pub doppler: f32,
+}
+
+impl Rayleigh {
+    /// Compute the long-run bit error rate, ½(1 - √(γ / (1 + γ))) for mean SNR γ.
+    pub fn mean_ber(&self) -> f32 {
+        let snr = db_to_linear(self.snr);
+        0.5 * (1.0 - (snr / (1.0 + snr)).sqrt())
+    }
+
+    /// Compute the correlation of the channel gain between consecutive bits.
+    ///
+    /// The gain is modeled as a first-order Gauss–Markov process, which approximates
+    /// the Jakes spectrum with correlation e<sup>-2πf<sub>D</sub>T</sup> over a bit
+    /// period T.
+    fn correlation(&self) -> f32 {
+        (-2.0 * PI * self.doppler / BIT_RATE).exp()
+    }
+}
+
+/// Model of the bit errors introduced by the channel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Impairment {
+    /// Independent bit errors with the given probability.
+    Random(f32),
+    /// Burst errors from a Gilbert–Elliott channel.
+    Burst(GilbertElliott),
+    /// Errors from coherent detection over a Rayleigh fading channel.
+    Fading(Rayleigh),
+}
+
+impl Impairment {
+    /// Compute the long-run bit error rate of the impairment.
+    pub fn mean_ber(&self) -> f32 {
+        match *self {
+            Impairment::Random(p) => p,
+            Impairment::Burst(ref g) => g.mean_ber(),
+            Impairment::Fading(ref r) => r.mean_ber(),
+        }
+    }
+}
+
+/// Applies an impairment to a stream of coded frames, using the given type of random
+/// number generator.
+pub struct Channel<R: Random = XorShift> {
+    /// Error model.
+    impairment: Impairment,
+    /// Whether a Gilbert–Elliott channel is in the bad state.
+    bad: bool,
+    /// Complex fading gain (real, imaginary), with unit mean power.
+    gain: (f32, f32),
+    /// Total number of bits sent.
+    bits: u64,
+    /// Total number of bits flipped.
+    errors: u64,
+    /// Drives state transitions, fading, and errors.
+    rng: R,
+}
+
+#[cfg(feature = "std")]
+impl Channel {
+    /// Create a new `Channel` with the given impairment, seeded from the system.
+    pub fn new(impairment: Impairment) -> Channel {
+        Channel::with_rng(impairment, XorShift::seeded())
+    }
+}
+
+impl<R: Random> Channel<R> {
+    /// Create a new `Channel` with the given impairment and random number generator.
+    pub fn with_rng(impairment: Impairment, mut rng: R) -> Channel<R> {
+        let sigma = 0.5f32.sqrt();
+        let gain = (rng.normal(sigma), rng.normal(sigma));
+
+        Channel {
+            impairment: impairment,
+            bad: false,
+            gain: gain,
+            bits: 0,
+            errors: 0,
+            rng: rng,
+        }
+    }
+
+    /// Retrieve the impairment applied by the channel.
+    pub fn impairment(&self) -> &Impairment { &self.impairment }
+
+    /// Retrieve the total number of bits sent over the channel.
+    pub fn bits(&self) -> u64 { self.bits }
+
+    /// Retrieve the total number of bits flipped by the channel.
+    pub fn bit_errors(&self) -> u64 { self.errors }
+
+    /// Compute the measured bit error rate over all bits sent.
+    pub fn ber(&self) -> f32 {
+        if self.bits == 0 {
+            0.0
+        } else {
+            self.errors as f32 / self.bits as f32
+        }
+    }
+
+    /// Flip bits of the given interleaved coded frame in place, returning the number of
+    /// bits flipped.
+    pub fn corrupt(&mut self, frame: &mut CodedFrame) -> usize {
+        let mut flipped = 0;
+
+        for b in 0..CODED_BITS {
+            if self.next_error() {
+                frame[b / 8] ^= 0x80 >> (b % 8);
+                flipped += 1;
+            }
+        }
+
+        self.bits += CODED_BITS as u64;
+        self.errors += flipped as u64;
+
+        flipped
+    }
+
+    /// Encode and interleave the given chunks, send them over the channel, and
+    /// deinterleave and error correction decode the received frame.
+    pub fn transmit(&mut self, chunks: &Chunks) -> ReceivedFrame {
+        let mut air = fec::interleave(&fec::encode(chunks));
+        self.corrupt(&mut air);
+
+        fec::decode(&fec::deinterleave(&air))
+    }
+
+    /// Advance the channel by one bit, returning whether that bit is in error.
+    fn next_error(&mut self) -> bool {
+        match self.impairment {
+            Impairment::Random(p) => self.rng.uniform(0.0, 1.0) < p,
+            Impairment::Burst(g) => {
+                let flip = if self.bad { g.bad_to_good } else { g.good_to_bad };
+
+                if self.rng.uniform(0.0, 1.0) < flip {
+                    self.bad = !self.bad;
+                }
+
+                let p = if self.bad { g.bad_ber } else { g.good_ber };
+                self.rng.uniform(0.0, 1.0) < p
+            },
+            Impairment::Fading(r) => {
+                let rho = r.correlation();
+                let sigma = (0.5 * (1.0 - rho * rho)).sqrt();
+                let (re, im) = self.gain;
+
+                self.gain = (
+                    rho * re + self.rng.normal(sigma),
+                    rho * im + self.rng.normal(sigma),
+                );
+
+                // Detect a +1 symbol with the current amplitude and unit-variance noise,
+                // which errs with probability Q(√(2|h|²γ)).
+                let (re, im) = self.gain;
+                let amp = (2.0 * (re * re + im * im) * db_to_linear(r.snr)).sqrt();
+
+                amp + self.rng.normal(1.0) < 0.0
+            },
+        }
+    }
+}
+
+/// Convert the given power ratio in dB to a linear ratio.
+fn db_to_linear(db: f32) -> f32 {
+    (db / 10.0 * LN_10).exp()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use decode::ImbeDecoder;
+    use frame::TEST_CHUNKS;
+    use stats::FrameStatus;
+
+    /// Send the given number of frames over a channel with the given impairment,
+    /// returning the channel.
+    fn run(imp: Impairment, frames: usize) -> Channel {
+        let mut ch = Channel::with_rng(imp, XorShift::new([1, 2, 3, 4]));
+
+        for _ in 0..frames {
+            ch.transmit(&TEST_CHUNKS);
+        }
+
+        ch
+    }
+
+    #[test]
+    fn test_clean() {
+        let mut ch = Channel::with_rng(Impairment::Random(0.0), XorShift::default());
+
+        for _ in 0..10 {
+            let f = ch.transmit(&TEST_CHUNKS);
+
+            assert_eq!(f.chunks, TEST_CHUNKS);
+            assert_eq!(f.errors, [0; 7]);
+        }
+
+        assert_eq!(ch.bits(), 1440);
+        assert_eq!(ch.bit_errors(), 0);
+        assert_eq!(ch.ber(), 0.0);
+    }
+
+    #[test]
+    fn test_random() {
+        let ch = run(Impairment::Random(0.02), 2000);
+
+        assert_eq!(ch.bits(), 2000 * CODED_BITS as u64);
+        assert!((ch.ber() - 0.02).abs() < 0.002);
+
+        // Every bit is flipped.
+        let mut ch = Channel::with_rng(Impairment::Random(1.0), XorShift::default());
+        let mut frame = [0; CODED_BYTES];
+
+        assert_eq!(ch.corrupt(&mut frame), CODED_BITS);
+        assert_eq!(frame, [0xff; CODED_BYTES]);
+    }
+
+    #[test]
+    fn test_errors() {
+        // Corrected errors are reported in the received frame.
+        let mut ch = Channel::with_rng(Impairment::Random(0.01), XorShift::default());
+        let mut total = 0;
+
+        for _ in 0..500 {
+            let f = ch.transmit(&TEST_CHUNKS);
+            total += f.errors.iter().fold(0, |s, &e| s + e);
+        }
+
+        assert!(total > 0);
+        assert!(total as u64 <= ch.bit_errors());
+    }
+
+    #[test]
+    fn test_burst() {
+        let g = GilbertElliott::new(20.0, 0.1, 0.5);
+
+        assert!((g.bad_fraction() - 0.1).abs() < 1e-6);
+        assert!((g.mean_ber() - 0.05).abs() < 1e-6);
+        assert!((g.bad_to_good - 0.05).abs() < 1e-6);
+
+        let ch = run(Impairment::Burst(g), 5000);
+        assert!((ch.ber() - 0.05).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_burst_clusters() {
+        // Bursts at the same mean BER leave more frames untouched than random errors.
+        let clean = |imp| {
+            let mut ch = Channel::with_rng(imp, XorShift::default());
+
+            (0..2000).filter(|_| {
+                let mut frame = [0; CODED_BYTES];
+                ch.corrupt(&mut frame) == 0
+            }).count()
+        };
+
+        let burst = clean(Impairment::Burst(GilbertElliott::new(100.0, 0.02, 0.5)));
+        let random = clean(Impairment::Random(0.01));
+
+        assert!(burst > 2 * random);
+    }
+
+    #[test]
+    fn test_fading() {
+        let r = Rayleigh { snr: 10.0, doppler: 100.0 };
+
+        // Compare with ½(1 - √(10 / 11)).
+        assert!((r.mean_ber() - 0.023269).abs() < 1e-5);
+
+        let ch = run(Impairment::Fading(r), 5000);
+        assert!((ch.ber() - r.mean_ber()).abs() < 0.004);
+
+        // Error rate falls roughly inversely with mean SNR.
+        let r = Rayleigh { snr: 20.0, doppler: 100.0 };
+        assert!((r.mean_ber() - 0.002481).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut dec = ImbeDecoder::new();
+        let mut buf = [0.0; ::consts::SAMPLES_PER_FRAME];
+        let mut ch = Channel::with_rng(Impairment::Random(0.001), XorShift::default());
+
+        for _ in 0..100 {
+            dec.decode(ch.transmit(&TEST_CHUNKS), &mut buf);
+        }
+
+        assert!(dec.stats().count(FrameStatus::Normal) > 90);
+
+        // A heavily impaired channel causes repeats, and muting once enough decoded
+        // frames raise the error rate.
+        let mut dec = ImbeDecoder::new();
+        let mut ch = Channel::with_rng(Impairment::Random(0.15), XorShift::default());
+
+        for _ in 0..500 {
+            dec.decode(ch.transmit(&TEST_CHUNKS), &mut buf);
+        }
+
+        assert!(dec.stats().count(FrameStatus::Repeat) > 0);
+        assert!(dec.stats().count(FrameStatus::Mute) > 0);
+    }
+}
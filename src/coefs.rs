@@ -1,5 +1,6 @@
 //! Higher order DCT coefficients.
 
+use std;
 use std::f32::consts::PI;
 
 use arrayvec::ArrayVec;
@@ -8,6 +9,8 @@ use allocs::allocs;
 use consts::{MIN_HARMONICS, MAX_HARMONICS};
 use descramble::QuantizedAmplitudes;
 use gain::Gains;
+#[cfg(not(feature = "std"))]
+use math::Float;
 use params::BaseParams;
 
 /// Higher order DCT coefficients vector T<sub>l</sub>, 1 ≤ l ≤ L.
@@ -42,6 +45,13 @@ impl Coefficients {
     pub fn get(&self, l: usize) -> f32 { self.0[l - 1] }
 }
 
+impl std::iter::FromIterator<f32> for Coefficients {
+    /// Collect coefficients T<sub>1</sub>, ..., T<sub>L</sub> into a `Coefficients`.
+    fn from_iter<I: IntoIterator<Item = f32>>(iter: I) -> Coefficients {
+        Coefficients(iter.into_iter().collect())
+    }
+}
+
 /// Block of coeffients C<sub>i,k</sub>, 1 ≤ i ≤ 6 and 1 ≤ k ≤ J<sub>i</sub>.
 struct CoefBlock(ArrayVec<[f32; 10]>);
 
@@ -103,7 +113,7 @@ impl CoefBlock {
 /// Each AMPS_USED[l] gives J<sub>1</sub> - 1,..,,J<sub>6</sub> - 1 for harmonics
 /// parameter l = L - 9. Each J<sub>i</sub> - 1 represents the number of quantized
 /// amplitudes used in coefficient block i.
-static AMPS_USED: [[usize; 6]; 48] = [
+pub static AMPS_USED: [[usize; 6]; 48] = [
     [0, 0, 0, 1, 1, 1],
     [0, 0, 1, 1, 1, 1],
     [0, 1, 1, 1, 1, 1],
@@ -156,7 +166,7 @@ static AMPS_USED: [[usize; 6]; 48] = [
 
 /// Each DCT_STEP_SIZE[b] is the "uniform quantizer step size" [p31] for the bit
 /// allocation b = B<sub>m</sub> - 1.
-const DCT_STEP_SIZE: [f32; 10] = [
+pub const DCT_STEP_SIZE: [f32; 10] = [
     1.2,
     0.85,
     0.65,
@@ -171,7 +181,7 @@ const DCT_STEP_SIZE: [f32; 10] = [
 
 /// Each DCT_STD_DEV[j] is the DCT standard deviation [p32] for the coefficient
 /// C<sub>i,j+2</sub>.
-const DCT_STD_DEV: [f32; 9] = [
+pub const DCT_STD_DEV: [f32; 9] = [
     0.307,
     0.241,
     0.207,
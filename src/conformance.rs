@@ -0,0 +1,583 @@
+//! Check the decoder against reference vectors stage by stage.
+//!
+//! A vector file describes a stream of frames, each with its received chunks and error
+//! counts and any of the expected intermediate values b<sub>0</sub>, ...,
+//! b<sub>L+1</sub>, received v<sub>l</sub>, M<sub>l</sub>, and enhanced
+//! M̄<sub>l</sub> (after adaptive smoothing), along with the 160 decoded 8kHz samples.
+//! Frames are decoded in order by a single decoder, and each stage is compared in
+//! decoding order, so the first reported divergence is the earliest point where the
+//! decoder departs from the reference.
+//!
+//! Files are line oriented, with blank lines and lines beginning with `#` ignored:
+//!
+//! ```text
+//! frame
+//! chunks 530 3276 3640 4095 1333 378 955 8
+//! errors 0 0 0 0 0 0 0
+//! b 32 41 21 6 34 27 25 15 36 53 23 13 14 7 7 6 4 2
+//! v 1 1 1 0 0 0 1 1 1 0 0 0 0 0 0 1
+//! m 0.530677 0.35350072 0.91738766 ...
+//! mbar 0.46407437 0.3091346 0.4158741 ...
+//! pcm 0 0 0 ...
+//! ```
+//!
+//! Every line but `chunks` is optional, and missing stages aren't compared. Voice
+//! decisions and intermediate amplitudes are only defined for frames that pass through
+//! normal decoding, so they should be omitted for silence, invalid, repeated, and
+//! muted frames.
+//!
+//! The decoder uses the standard's noise sequence for unvoiced synthesis but random
+//! voiced phases, so decoded samples can only match a reference produced by this crate
+//! exactly. Samples from other implementations should be compared with a looser
+//! `Tolerance::pcm`.
+
+use std;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use consts::SAMPLES_PER_FRAME;
+use decode::{DecoderConfig, ImbeDecoder};
+use descramble::{descramble, Bootstrap};
+use frame::{valid_chunks, Chunks, Errors, ReceivedFrame};
+use noise::NoiseKind;
+use params::BaseParams;
+use rng::{Random, XorShift};
+use scan::ScanSep;
+use stats::FrameStatus;
+
+/// Extension of vector files in a vector directory.
+pub const EXTENSION: &'static str = "vec";
+
+/// Decoding stage compared against a reference.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Quantized parameters b<sub>0</sub>, ..., b<sub>L+1</sub>.
+    Bits,
+    /// Received voiced/unvoiced decisions v<sub>l</sub>.
+    Voicing,
+    /// Spectral amplitudes M<sub>l</sub>.
+    Spectrals,
+    /// Enhanced spectral amplitudes M̄<sub>l</sub>.
+    Enhanced,
+    /// Decoded 8kHz samples.
+    Pcm,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Stage::Bits => "b",
+            Stage::Voicing => "v",
+            Stage::Spectrals => "m",
+            Stage::Enhanced => "mbar",
+            Stage::Pcm => "pcm",
+        })
+    }
+}
+
+/// Expected or decoded values of a single frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameVector {
+    /// Received chunks u<sub>0</sub>, ..., u<sub>7</sub>.
+    pub chunks: Chunks,
+    /// Error counts ϵ<sub>0</sub>, ..., ϵ<sub>6</sub>.
+    pub errors: Errors,
+    /// Quantized parameters b<sub>0</sub>, ..., b<sub>L+1</sub>.
+    pub bits: Option<Vec<u32>>,
+    /// Received voiced/unvoiced decisions v<sub>1</sub>, ..., v<sub>L</sub>.
+    pub voice: Option<Vec<bool>>,
+    /// Spectral amplitudes M<sub>1</sub>, ..., M<sub>L</sub>.
+    pub spectrals: Option<Vec<f32>>,
+    /// Enhanced spectral amplitudes M̄<sub>1</sub>, ..., M̄<sub>L</sub>.
+    pub enhanced: Option<Vec<f32>>,
+    /// Decoded samples.
+    pub pcm: Option<Vec<i16>>,
+}
+
+impl FrameVector {
+    /// Create a new `FrameVector` with the given received values and no expected
+    /// values.
+    pub fn new(chunks: Chunks, errors: Errors) -> FrameVector {
+        FrameVector {
+            chunks: chunks,
+            errors: errors,
+            bits: None,
+            voice: None,
+            spectrals: None,
+            enhanced: None,
+            pcm: None,
+        }
+    }
+
+    /// Write the vector in the file format to the given stream.
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "frame")?;
+        write_line(w, "chunks", self.chunks.iter())?;
+        write_line(w, "errors", self.errors.iter())?;
+
+        if let Some(ref b) = self.bits {
+            write_line(w, "b", b.iter())?;
+        }
+
+        if let Some(ref v) = self.voice {
+            write_line(w, "v", v.iter().map(|&v| v as u8))?;
+        }
+
+        if let Some(ref m) = self.spectrals {
+            write_line(w, "m", m.iter())?;
+        }
+
+        if let Some(ref m) = self.enhanced {
+            write_line(w, "mbar", m.iter())?;
+        }
+
+        if let Some(ref p) = self.pcm {
+            write_line(w, "pcm", p.iter())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Write a line with the given key followed by the given values.
+fn write_line<W: Write, T: fmt::Display, I: Iterator<Item = T>>(w: &mut W, key: &str,
+                                                                 vals: I)
+    -> io::Result<()>
+{
+    w.write_all(key.as_bytes())?;
+
+    for v in vals {
+        write!(w, " {}", v)?;
+    }
+
+    writeln!(w, "")
+}
+
+/// Error encountered while reading vectors.
+#[derive(Debug)]
+pub enum Error {
+    /// Vectors couldn't be read.
+    Io(io::Error),
+    /// The given 1-based line is malformed.
+    Parse(usize),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error { Error::Io(e) }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::Parse(line) => write!(f, "malformed vector on line {}", line),
+        }
+    }
+}
+
+/// Parse the values following the key on the given line.
+fn parse_vals<T: FromStr>(vals: &[&str], line: usize) -> Result<Vec<T>, Error> {
+    vals.iter().map(|v| v.parse().map_err(|_| Error::Parse(line))).collect()
+}
+
+/// Read frame vectors in the file format from the given stream.
+pub fn read<R: BufRead>(r: R) -> Result<Vec<FrameVector>, Error> {
+    let mut frames: Vec<FrameVector> = vec![];
+
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        let num = i + 1;
+        let words = line.split_whitespace().collect::<Vec<_>>();
+
+        let (key, vals) = match words.split_first() {
+            Some((k, _)) if k.starts_with('#') => continue,
+            Some((k, v)) => (*k, v),
+            None => continue,
+        };
+
+        if key == "frame" {
+            frames.push(FrameVector::new([0; 8], [0; 7]));
+            continue;
+        }
+
+        let frame = match frames.last_mut() {
+            Some(f) => f,
+            None => return Err(Error::Parse(num)),
+        };
+
+        match key {
+            "chunks" => {
+                let c = parse_vals::<u32>(vals, num)?;
+
+                if c.len() != 8 {
+                    return Err(Error::Parse(num));
+                }
+
+                frame.chunks.copy_from_slice(&c);
+
+                if !valid_chunks(&frame.chunks) {
+                    return Err(Error::Parse(num));
+                }
+            },
+            "errors" => {
+                let e = parse_vals::<usize>(vals, num)?;
+
+                if e.len() != 7 {
+                    return Err(Error::Parse(num));
+                }
+
+                frame.errors.copy_from_slice(&e);
+            },
+            "b" => frame.bits = Some(parse_vals(vals, num)?),
+            "v" => {
+                let v = parse_vals::<u8>(vals, num)?;
+
+                if v.iter().any(|&v| v > 1) {
+                    return Err(Error::Parse(num));
+                }
+
+                frame.voice = Some(v.into_iter().map(|v| v == 1).collect());
+            },
+            "m" => frame.spectrals = Some(parse_vals(vals, num)?),
+            "mbar" => frame.enhanced = Some(parse_vals(vals, num)?),
+            "pcm" => frame.pcm = Some(parse_vals(vals, num)?),
+            _ => return Err(Error::Parse(num)),
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Create a decoder in the configuration used to record and check vectors.
+pub fn decoder() -> ImbeDecoder<XorShift> {
+    let config = DecoderConfig {
+        noise: NoiseKind::Standard,
+        .. DecoderConfig::default()
+    };
+
+    ImbeDecoder::with_rng(config, XorShift::default())
+}
+
+/// Decode the given frame with the given decoder, recording the values of every stage.
+pub fn record<R: Random>(dec: &mut ImbeDecoder<R>, chunks: Chunks, errors: Errors)
+    -> FrameVector
+{
+    let mut pcm = [0; SAMPLES_PER_FRAME];
+    let status = dec.decode_i16(ReceivedFrame::new(chunks, errors), &mut pcm);
+
+    let mut vec = FrameVector::new(chunks, errors);
+    vec.pcm = Some(pcm.to_vec());
+
+    let period = match Bootstrap::new(&chunks) {
+        Bootstrap::Period(p) => p,
+        _ => return vec,
+    };
+
+    let params = BaseParams::new(period);
+    let (amps, voice, gain_idx) = descramble(&chunks, &params);
+
+    let mut bits = vec![period as u32, ScanSep::new(&chunks, &params).voiced,
+                        gain_idx as u32];
+    bits.extend((3...params.harmonics as usize + 1).map(|m| amps.get(m)));
+    vec.bits = Some(bits);
+
+    if status != FrameStatus::Normal {
+        return vec;
+    }
+
+    let prev = dec.prev();
+
+    vec.voice = Some((1...params.harmonics as usize).map(|l| voice.is_voiced(l))
+                                                   .collect());
+    vec.spectrals = Some(prev.spectrals.to_vec());
+    vec.enhanced = Some(prev.enhanced.to_vec());
+
+    vec
+}
+
+/// Allowed deviation from reference values.
+#[derive(Copy, Clone, Debug)]
+pub struct Tolerance {
+    /// Maximum relative error of spectral amplitudes.
+    pub amplitude: f32,
+    /// Maximum absolute error of decoded samples.
+    pub pcm: i16,
+}
+
+impl Default for Tolerance {
+    /// Create a new `Tolerance` that allows for differences in floating-point rounding.
+    fn default() -> Tolerance {
+        Tolerance {
+            amplitude: 1.0e-4,
+            pcm: 2,
+        }
+    }
+}
+
+/// First point where decoding departs from the reference.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Divergence {
+    /// Index of the frame within its vector file.
+    pub frame: usize,
+    /// Stage that departs from the reference.
+    pub stage: Stage,
+    /// Index of the first differing value within the stage, or the expected number of
+    /// values if the counts differ.
+    pub index: usize,
+    /// Expected value, if any.
+    pub expected: Option<f32>,
+    /// Decoded value, if any.
+    pub actual: Option<f32>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |x: Option<f32>| match x {
+            Some(x) => format!("{}", x),
+            None => "nothing".to_string(),
+        };
+
+        write!(f, "frame {} diverges at {}[{}]: expected {}, decoded {}", self.frame,
+               self.stage, self.index, show(self.expected), show(self.actual))
+    }
+}
+
+/// Compare the given expected and decoded values of a stage, returning the index and
+/// values of the first mismatch.
+fn compare<T: Copy + Into<f64>, F: Fn(T, T) -> bool>(expected: &Option<Vec<T>>,
+                                                     actual: &Option<Vec<T>>, eq: F)
+    -> Option<(usize, Option<f32>, Option<f32>)>
+{
+    let exp = match *expected {
+        Some(ref e) => e,
+        None => return None,
+    };
+
+    let act = match *actual {
+        Some(ref a) => &a[..],
+        None => &[],
+    };
+
+    let val = |x: Option<&T>| x.map(|&x| x.into() as f32);
+
+    for i in 0..std::cmp::max(exp.len(), act.len()) {
+        match (exp.get(i), act.get(i)) {
+            (Some(&e), Some(&a)) if eq(e, a) => {},
+            (e, a) => return Some((i, val(e), val(a))),
+        }
+    }
+
+    None
+}
+
+/// Decode the given frames in order with a fresh decoder, returning the first point
+/// where decoding departs from the expected values.
+pub fn check(frames: &[FrameVector], tol: &Tolerance) -> Option<Divergence> {
+    let mut dec = decoder();
+
+    let amp = |e: f32, a: f32| (a - e).abs() <= tol.amplitude * e.abs();
+    let pcm = |e: i16, a: i16| (a as i32 - e as i32).abs() <= tol.pcm as i32;
+
+    for (i, exp) in frames.iter().enumerate() {
+        let act = record(&mut dec, exp.chunks, exp.errors);
+
+        let diff = compare(&exp.bits, &act.bits, |e, a| e == a)
+            .map(|d| (Stage::Bits, d))
+            .or_else(|| compare(&exp.voice.as_ref().map(|v| to_u8(v)),
+                                &act.voice.as_ref().map(|v| to_u8(v)), |e, a| e == a)
+                .map(|d| (Stage::Voicing, d)))
+            .or_else(|| compare(&exp.spectrals, &act.spectrals, &amp)
+                .map(|d| (Stage::Spectrals, d)))
+            .or_else(|| compare(&exp.enhanced, &act.enhanced, &amp)
+                .map(|d| (Stage::Enhanced, d)))
+            .or_else(|| compare(&exp.pcm, &act.pcm, &pcm)
+                .map(|d| (Stage::Pcm, d)));
+
+        if let Some((stage, (index, expected, actual))) = diff {
+            return Some(Divergence {
+                frame: i,
+                stage: stage,
+                index: index,
+                expected: expected,
+                actual: actual,
+            });
+        }
+    }
+
+    None
+}
+
+/// Convert the given voice decisions to comparable values.
+fn to_u8(v: &[bool]) -> Vec<u8> {
+    v.iter().map(|&v| v as u8).collect()
+}
+
+/// Result of checking a single vector file.
+#[derive(Debug)]
+pub struct FileReport {
+    /// Path of the vector file.
+    pub path: PathBuf,
+    /// Number of frames in the file.
+    pub frames: usize,
+    /// First divergence from the reference, if any.
+    pub divergence: Option<Divergence>,
+}
+
+/// Check every vector file in the given directory, in order of file name, with a fresh
+/// decoder for each file.
+pub fn check_dir<P: AsRef<Path>>(dir: P, tol: &Tolerance)
+    -> Result<Vec<FileReport>, Error>
+{
+    let mut paths = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.extension().map_or(false, |e| e == EXTENSION) {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    paths.into_iter().map(|path| {
+        let frames = read(BufReader::new(File::open(&path)?))?;
+        let divergence = check(&frames, tol);
+
+        Ok(FileReport {
+            path: path,
+            frames: frames.len(),
+            divergence: divergence,
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    use frame::TEST_CHUNKS;
+
+    /// Record a short stream including silence and a repeated frame.
+    fn stream() -> Vec<FrameVector> {
+        let mut dec = decoder();
+        let silence = [0b110110000000, 0, 0, 0, 0, 0, 0, 0];
+
+        vec![
+            record(&mut dec, TEST_CHUNKS, [0; 7]),
+            record(&mut dec, TEST_CHUNKS, [0; 7]),
+            record(&mut dec, silence, [0; 7]),
+            record(&mut dec, TEST_CHUNKS, [3, 3, 3, 3, 1, 0, 0]),
+            record(&mut dec, TEST_CHUNKS, [0; 7]),
+        ]
+    }
+
+    #[test]
+    fn test_record() {
+        let s = stream();
+
+        let b = s[0].bits.as_ref().unwrap();
+        assert_eq!(b.len(), 18);
+        assert_eq!(b[0], 32);
+        assert_eq!(b[2], 0b010101);
+        assert_eq!(b[3], 0b000110);
+        assert_eq!(b[17], 0b10);
+
+        let v = s[0].voice.as_ref().unwrap();
+        assert_eq!(&v[..4], &[true, true, true, false]);
+        assert_eq!(s[0].spectrals.as_ref().unwrap().len(), 16);
+        assert_eq!(s[0].enhanced.as_ref().unwrap().len(), 16);
+
+        assert!(s[2].bits.is_none());
+        assert!(s[2].voice.is_none());
+        assert_eq!(s[2].pcm, Some(vec![0; 160]));
+
+        assert!(s[3].bits.is_some());
+        assert!(s[3].spectrals.is_none());
+        assert!(s[3].pcm.as_ref().unwrap().iter().any(|&x| x != 0));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let s = stream();
+        let mut buf = vec![];
+
+        for f in s.iter() {
+            f.write(&mut buf).unwrap();
+        }
+
+        assert_eq!(read(Cursor::new(&buf[..])).unwrap(), s);
+        assert_eq!(check(&s, &Tolerance::default()), None);
+    }
+
+    #[test]
+    fn test_divergence() {
+        let tol = Tolerance::default();
+        let s = stream();
+
+        let mut d = s.clone();
+        d[1].spectrals.as_mut().unwrap()[5] *= 1.01;
+        d[4].bits.as_mut().unwrap()[3] ^= 1;
+
+        assert_eq!(check(&d, &tol), Some(Divergence {
+            frame: 1,
+            stage: Stage::Spectrals,
+            index: 5,
+            expected: Some(d[1].spectrals.as_ref().unwrap()[5]),
+            actual: Some(s[1].spectrals.as_ref().unwrap()[5]),
+        }));
+
+        // Earlier stages are reported first within a frame.
+        let mut d = s.clone();
+        d[0].pcm.as_mut().unwrap()[0] += 10;
+        d[0].voice.as_mut().unwrap().pop();
+
+        let div = check(&d, &tol).unwrap();
+        assert_eq!((div.frame, div.stage, div.index), (0, Stage::Voicing, 15));
+        assert_eq!(div.expected, None);
+        assert_eq!(div.actual, Some(1.0));
+
+        // Stages absent from the reference aren't compared.
+        let mut d = s.clone();
+        d[3].spectrals = Some(vec![1.0; 16]);
+        assert_eq!(check(&d, &tol).unwrap().stage, Stage::Spectrals);
+        d[3].spectrals = None;
+        d[4].pcm = None;
+        d[4].enhanced = None;
+        assert_eq!(check(&d, &tol), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "# comment\n\nframe\nchunks 1 2 3 4 5 6 7 8\nv 1 0 1\nm 1.5 2\n";
+        let f = read(Cursor::new(text)).unwrap();
+
+        assert_eq!(f.len(), 1);
+        assert_eq!(f[0].chunks, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(f[0].errors, [0; 7]);
+        assert_eq!(f[0].voice, Some(vec![true, false, true]));
+        assert_eq!(f[0].spectrals, Some(vec![1.5, 2.0]));
+        assert_eq!(f[0].pcm, None);
+
+        let bad = [
+            "chunks 1 2 3 4 5 6 7 8\n",
+            "frame\nchunks 1 2 3\n",
+            "frame\nchunks 4096 0 0 0 0 0 0 0\n",
+            "frame\nerrors 0 0\n",
+            "frame\nv 2\n",
+            "frame\nm x\n",
+            "frame\n\nbogus 1\n",
+        ];
+
+        for (i, &b) in bad.iter().enumerate() {
+            match read(Cursor::new(b)) {
+                Err(Error::Parse(line)) => assert_eq!(line, b.lines().count(), "{}", i),
+                _ => panic!("accepted malformed vector {}", i),
+            }
+        }
+    }
+}
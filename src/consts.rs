@@ -9,6 +9,9 @@ pub const SAMPLES_PER_FRAME: usize = 160;
 pub const MIN_HARMONICS: usize = 9;
 /// Number of harmonics L when the fundamental frequency ω<sub>0</sub> is minimum.
 pub const MAX_HARMONICS: usize = 56;
+/// Number of harmonics of the minimum fundamental frequency ω<sub>0</sub> below 8kHz,
+/// which bounds the harmonics synthesized when extending bandwidth.
+pub const MAX_EXT_HARMONICS: usize = 123;
 /// Number of discrete values that exist for the harmonics parameter.
 pub const NUM_HARMONICS: usize = MAX_HARMONICS - MIN_HARMONICS + 1;
 
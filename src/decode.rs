@@ -1,10 +1,7 @@
 //! Decode IMBE frames into an audio signal.
 
-use std::sync::Arc;
-
-use collect_slice::CollectSlice;
+#[cfg(feature = "threads")]
 use crossbeam;
-use rand;
 
 use coefs::Coefficients;
 use consts::SAMPLES_PER_FRAME;
@@ -12,96 +9,387 @@ use descramble::{descramble, Bootstrap};
 use enhance::{self, EnhancedSpectrals, FrameEnergy, EnhanceErrors};
 use frame::{AudioBuf, ReceivedFrame};
 use gain::Gains;
+#[cfg(not(feature = "std"))]
+use math::Float;
+use noise::{NoiseKind, NoiseSource};
 use params::BaseParams;
+use postfilter;
 use prev::PrevFrame;
+#[cfg(feature = "profile")]
+use profile::{Profile, Stage};
+use resample::{self, Resampler, SampleRate, MAX_SAMPLES_PER_FRAME};
+use rng::{Random, XorShift};
+use sample::{self, Sample};
 use spectral::Spectrals;
+use stats::{DecodeStats, FrameStatus};
+use timing::{Timing, MAX_STRETCH_SAMPLES};
+use transform::VoiceTransform;
 use unvoiced::{UnvoicedDft, Unvoiced};
 use voiced::{Phase, PhaseBase, Voiced};
 
+/// Evaluate the given expression as the given decoding stage, recording its running time
+/// in the decoder's profile.
+#[cfg(feature = "profile")]
+macro_rules! stage {
+    ($dec:expr, $stage:ident, $e:expr) => {{
+        let start = ::std::time::Instant::now();
+        let ret = $e;
+        $dec.profile.record(Stage::$stage, start.elapsed());
+        ret
+    }}
+}
+
+/// Evaluate the given expression as the given decoding stage.
+#[cfg(not(feature = "profile"))]
+macro_rules! stage {
+    ($dec:expr, $stage:ident, $e:expr) => { $e }
+}
+
 /// Number of threads to spin up per frame.
+#[cfg(feature = "threads")]
 const THREADS: usize = 4;
-/// Number of samples to process in each thread.
-const SAMPLES_PER_THREAD: usize = SAMPLES_PER_FRAME / THREADS;
 
-/// Decodes a stream of IMBE frames.
-pub struct ImbeDecoder {
+/// Method used to produce output at rates other than the native rate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Synthesis {
+    /// Synthesize at the native rate and resample to the output rate.
+    Resample,
+    /// Synthesize directly at the output rate by evaluating the voiced and unvoiced
+    /// signals at fractional native times.
+    Direct,
+}
+
+/// Options that control how decoded audio is output.
+#[derive(Copy, Clone)]
+pub struct DecoderConfig {
+    /// Linear gain applied to samples before they're converted to an output format.
+    pub gain: f32,
+    /// Sample rate of converted output.
+    pub rate: SampleRate,
+    /// How output at the configured rate is produced.
+    pub synthesis: Synthesis,
+    /// Whether direct synthesis adds voiced harmonics above the native 4kHz bandwidth,
+    /// up to the lesser of 8kHz and the output Nyquist frequency.
+    pub extend_bandwidth: bool,
+    /// Strength of the perceptual post-filter applied to spectral amplitudes, where 0
+    /// disables it and 1 is the nominal full effect.
+    pub postfilter: f32,
+    /// Pitch and formant transform applied to model parameters before synthesis.
+    pub transform: VoiceTransform,
+    /// Source of the noise spectrum used for unvoiced synthesis.
+    pub noise: NoiseKind,
+}
+
+impl Default for DecoderConfig {
+    /// Create a new `DecoderConfig` that outputs samples at the standard's scale.
+    fn default() -> DecoderConfig {
+        DecoderConfig {
+            gain: 1.0,
+            rate: SampleRate::Hz8000,
+            synthesis: Synthesis::Resample,
+            extend_bandwidth: false,
+            postfilter: 0.0,
+            transform: VoiceTransform::default(),
+            noise: NoiseKind::default(),
+        }
+    }
+}
+
+/// Decodes a stream of IMBE frames, using the given type of random number generator for
+/// synthesis.
+pub struct ImbeDecoder<R: Random = XorShift> {
+    /// Output options.
+    config: DecoderConfig,
     /// Tracks saved parameters across frames.
     prev: PrevFrame,
+    /// Converts decoded frames to the output sample rate.
+    resampler: Resampler,
+    /// Whether the previous output frame passed through the resampler, so its filter
+    /// history continues into the next.
+    resampled: bool,
+    /// Running statistics over all decoded frames.
+    stats: DecodeStats,
+    /// Generates noise spectra for unvoiced synthesis.
+    noise: NoiseSource,
+    /// Time spent in each decoding stage.
+    #[cfg(feature = "profile")]
+    profile: Profile,
+    /// Generates random phases and noise spectra.
+    rng: R,
 }
 
+#[cfg(feature = "std")]
 impl ImbeDecoder {
-    /// Create a new `ImbeDecoder` in the default state.
+    /// Create a new `ImbeDecoder` in the default state, seeded from the system.
     pub fn new() -> ImbeDecoder {
+        Self::with_config(DecoderConfig::default())
+    }
+
+    /// Create a new `ImbeDecoder` in the default state with the given output options,
+    /// seeded from the system.
+    pub fn with_config(config: DecoderConfig) -> ImbeDecoder {
+        ImbeDecoder::with_rng(config, XorShift::seeded())
+    }
+}
+
+impl<R: Random> ImbeDecoder<R> {
+    /// Create a new `ImbeDecoder` in the default state with the given output options and
+    /// random number generator.
+    pub fn with_rng(config: DecoderConfig, rng: R) -> ImbeDecoder<R> {
         ImbeDecoder {
+            config: config,
             prev: PrevFrame::default(),
+            resampler: Resampler::new(config.rate),
+            resampled: false,
+            stats: DecodeStats::new(),
+            noise: NoiseSource::new(config.noise),
+            #[cfg(feature = "profile")]
+            profile: Profile::new(),
+            rng: rng,
         }
     }
 
-    /// Decode the given frame into the given audio sample buffer.
-    pub fn decode(&mut self, frame: ReceivedFrame, buf: &mut AudioBuf) {
+    /// Retrieve the current output options.
+    pub fn config(&self) -> &DecoderConfig { &self.config }
+
+    /// Modify the output options, which take effect at the next decoded frame.
+    pub fn config_mut(&mut self) -> &mut DecoderConfig { &mut self.config }
+
+    /// Retrieve the statistics accumulated over all decoded frames.
+    pub fn stats(&self) -> &DecodeStats { &self.stats }
+
+    /// Retrieve the parameters saved from the most recently decoded frame.
+    pub fn prev(&self) -> &PrevFrame { &self.prev }
+
+    /// Clear the accumulated statistics without affecting the decoder state.
+    pub fn reset_stats(&mut self) {
+        self.stats = DecodeStats::new();
+    }
+
+    /// Retrieve the time spent in each decoding stage over all decoded frames.
+    #[cfg(feature = "profile")]
+    pub fn profile(&self) -> &Profile { &self.profile }
+
+    /// Clear the accumulated stage timings without affecting the decoder state.
+    #[cfg(feature = "profile")]
+    pub fn reset_profile(&mut self) {
+        self.profile = Profile::new();
+    }
+
+    /// Decode the given frame into the given audio sample buffer, returning how the frame
+    /// was handled.
+    ///
+    /// Samples are left at the standard's scale, without the configured gain applied.
+    pub fn decode(&mut self, frame: ReceivedFrame, buf: &mut AudioBuf) -> FrameStatus {
+        self.decode_timed(frame, Timing::native(), false, &mut buf[..])
+    }
+
+    /// Decode the given frame into the given buffer of samples synthesized with the given
+    /// timing, optionally extending the bandwidth of each frame.
+    fn decode_timed(&mut self, frame: ReceivedFrame, timing: Timing, extend: bool,
+                    buf: &mut [f32])
+        -> FrameStatus
+    {
+        if self.noise.kind() != self.config.noise {
+            self.noise = NoiseSource::new(self.config.noise);
+        }
+
+        let (status, forced, rate) = self.decode_frame(&frame, timing, extend, buf);
+        self.stats.record(status, &frame.errors, rate, forced);
+
+        status
+    }
+
+    /// Retrieve the number of output samples produced for each frame by `decode_into`
+    /// at the configured sample rate.
+    pub fn samples_per_frame(&self) -> usize { self.config.rate.samples_per_frame() }
+
+    /// Decode the given frame into the given buffer of output samples, applying the
+    /// configured gain and saturating samples that fall outside the output range.
+    ///
+    /// The buffer must hold exactly `samples_per_frame()` samples. Output is produced at
+    /// the configured sample rate, so the k'th sample of the f'th decoded frame always
+    /// has timestamp `f * 20ms + k / rate`, offset by the constant `delay()`.
+    pub fn decode_into<S: Sample>(&mut self, frame: ReceivedFrame, out: &mut [S])
+        -> FrameStatus
+    {
+        assert_eq!(out.len(), self.samples_per_frame());
+
+        let mut conv = [0.0; MAX_SAMPLES_PER_FRAME];
+        let conv = &mut conv[..out.len()];
+
+        let status = match self.config.synthesis {
+            Synthesis::Resample => {
+                let mut buf = [0.0; SAMPLES_PER_FRAME];
+                let status = self.decode(frame, &mut buf);
+
+                // Start the filter over if the rate changed or the previous frame was
+                // synthesized some other way.
+                if self.resampler.rate() != self.config.rate {
+                    self.resampler = Resampler::new(self.config.rate);
+                } else if !self.resampled {
+                    self.resampler.reset();
+                }
+
+                self.resampler.process(&buf, conv);
+                self.resampled = true;
+
+                status
+            },
+            Synthesis::Direct => {
+                let timing = Timing::new(self.config.rate);
+                let extend = self.config.extend_bandwidth;
+                self.resampled = false;
+
+                self.decode_timed(frame, timing, extend, conv)
+            },
+        };
+
+        sample::convert(conv, self.config.gain, out);
+
+        status
+    }
+
+    /// Decode the given frame into the given buffer of output samples, stretching the
+    /// frame in time to fill the buffer without changing its pitch.
+    ///
+    /// The buffer length sets the frame length at the configured sample rate and must
+    /// correspond to between `MIN_PERIOD` and `MAX_PERIOD` native samples, so playback
+    /// speed can vary from 0.5x to 2x frame by frame (see `TimeStretch`). Samples are
+    /// always synthesized directly at the output rate.
+    pub fn decode_stretched<S: Sample>(&mut self, frame: ReceivedFrame, out: &mut [S])
+        -> FrameStatus
+    {
+        let timing = Timing::stretched(self.config.rate, out.len());
+        let extend = self.config.extend_bandwidth;
+
+        let mut conv = [0.0; MAX_STRETCH_SAMPLES];
+        let conv = &mut conv[..out.len()];
+        self.resampled = false;
+
+        let status = self.decode_timed(frame, timing, extend, conv);
+        sample::convert(conv, self.config.gain, out);
+
+        status
+    }
+
+    /// Decode the given frame into the given buffer of signed 16-bit samples, which must
+    /// hold exactly `samples_per_frame()` samples.
+    pub fn decode_i16(&mut self, frame: ReceivedFrame, out: &mut [i16]) -> FrameStatus {
+        self.decode_into(frame, out)
+    }
+
+    /// Retrieve the delay of `decode_into` output relative to the decoded signal, in
+    /// output samples.
+    pub fn delay(&self) -> f32 {
+        match self.config.synthesis {
+            Synthesis::Resample => resample::delay(self.config.rate),
+            Synthesis::Direct => 0.0,
+        }
+    }
+
+    /// Decode the given frame into the given audio buffer, returning the frame status,
+    /// whether adaptive smoothing forced any harmonics to voiced, and the frame's
+    /// ϵ<sub>R</sub>.
+    ///
+    /// Frames with an invalid or silence b<sub>0</sub> aren't error checked, so they
+    /// carry the previous ϵ<sub>R</sub>.
+    fn decode_frame(&mut self, frame: &ReceivedFrame, timing: Timing, extend: bool,
+                    buf: &mut [f32])
+        -> (FrameStatus, bool, f32)
+    {
         let period = match Bootstrap::new(&frame.chunks) {
             Bootstrap::Period(p) => p,
             Bootstrap::Invalid => {
                 // Repeat previous frame on invalid period [p46].
-                self.repeat(buf);
-                return;
+                self.repeat(timing, extend, buf);
+                return (FrameStatus::Invalid, false, self.prev.err_rate);
             },
             Bootstrap::Silence => {
                 self.silence(buf);
-                return;
+                return (FrameStatus::Silence, false, self.prev.err_rate);
             },
         };
 
         let errors = EnhanceErrors::new(&frame.errors, self.prev.err_rate);
 
         if enhance::should_repeat(&errors) {
-            self.repeat(buf);
-            return;
+            self.repeat(timing, extend, buf);
+            return (FrameStatus::Repeat, false, errors.rate);
         }
 
         if enhance::should_mute(&errors) {
             self.silence(buf);
-            return;
+            return (FrameStatus::Mute, false, errors.rate);
         }
 
         let params = BaseParams::new(period);
-        let (amps, mut voice, gain_idx) = descramble(&frame.chunks, &params);
-        let gains = Gains::new(gain_idx, &amps, &params);
-        let coefs = Coefficients::new(&gains, &amps, &params);
-        let spectrals = Spectrals::new(&coefs, &params, &self.prev);
-        let energy = FrameEnergy::new(&spectrals, &self.prev.energy, &params);
-
-        let mut enhanced = EnhancedSpectrals::new(&spectrals, &energy, &params);
-        let amp_thresh = enhance::amp_thresh(&errors, self.prev.amp_thresh);
-        enhance::smooth(&mut enhanced, &mut voice, &errors, &energy, amp_thresh);
-
-        let udft = UnvoicedDft::new(&params, &voice, &enhanced, rand::weak_rng());
-        let vbase = PhaseBase::new(&params, &self.prev);
-        let vphase = Phase::new(&vbase, &params, &self.prev, &voice, rand::weak_rng());
-
-        crossbeam::scope(|scope| {
-            let unvoiced = Arc::new(Unvoiced::new(&udft, &self.prev.unvoiced));
-            let voiced = Arc::new(Voiced::new(&params, &self.prev, &vphase, &enhanced, &voice));
-
-            for (i, chunk) in buf.chunks_mut(SAMPLES_PER_THREAD).enumerate() {
-                let u = unvoiced.clone();
-                let v = voiced.clone();
-
-                let start = i * SAMPLES_PER_THREAD;
-                let stop = start + SAMPLES_PER_THREAD;
-
-                // Compute Eq 142 for this chunk.
-                scope.spawn(move || {
-                    (start..stop)
-                        .map(|n| u.get(n) + v.get(n))
-                        .collect_slice_checked(&mut chunk[..]);
-                });
-            }
+        let (amps, mut voice, gain_idx) =
+            stage!(self, Descramble, descramble(&frame.chunks, &params));
+        let gains = stage!(self, Gains, Gains::new(gain_idx, &amps, &params));
+        let coefs = stage!(self, Coefficients, Coefficients::new(&gains, &amps, &params));
+        let spectrals = stage!(self, Spectrals,
+                               Spectrals::new(&coefs, &params, &self.prev));
+
+        let (energy, enhanced, amp_thresh, forced) = stage!(self, Enhance, {
+            let energy = FrameEnergy::new(&spectrals, &self.prev.energy, &params);
+
+            let mut enhanced = EnhancedSpectrals::new(&spectrals, &energy, &params);
+            let amp_thresh = enhance::amp_thresh(&errors, self.prev.amp_thresh);
+
+            let unvoiced = voice.unvoiced_count();
+            enhance::smooth(&mut enhanced, &mut voice, &errors, &energy, amp_thresh);
+            let forced = voice.unvoiced_count() < unvoiced;
+
+            postfilter::postfilter(&mut enhanced, &params, self.config.postfilter,
+                                  amp_thresh);
+
+            (energy, enhanced, amp_thresh, forced)
         });
 
+        // Numeric state that's no longer finite would persist through prediction into
+        // every following frame, so discard it and start over.
+        if !finite(&spectrals) || !finite(&enhanced) || !energy.tracking.is_finite() ||
+            !amp_thresh.is_finite()
+        {
+            return self.recover(errors.rate, buf);
+        }
+
+        // Transform parameters for synthesis, keeping the decoded parameters for
+        // prediction in the next frame.
+        let model = params;
+        let (params, enhanced, voice) = match self.config.transform {
+            t if t.is_identity() => (params, enhanced, voice),
+            t => {
+                let p = t.params(&model);
+                (p, t.enhanced(&enhanced, &model, &p), t.voice(&voice, &model, &p))
+            },
+        };
+
+        let udft = stage!(self, UnvoicedDft,
+                          UnvoicedDft::with_noise(&params, &voice, &enhanced,
+                                                  &mut self.noise, &mut self.rng));
+        let (vbase, vphase) = stage!(self, Phase, {
+            let vbase = PhaseBase::with_period(&params, &self.prev, timing.period());
+            let vphase = Phase::new(&vbase, &params, &self.prev, &voice, &mut self.rng);
+
+            (vbase, vphase)
+        });
+
+        stage!(self, Synthesis, synthesize(
+            &Unvoiced::with_timing(&udft, &self.prev.unvoiced, timing, &mut self.rng),
+            &Voiced::with_timing(&params, &self.prev, &vphase, &enhanced, &voice, timing,
+                                 extend),
+            buf));
+
+        if !finite(buf) {
+            return self.recover(errors.rate, buf);
+        }
+
         // Save current parameters.
         self.prev = PrevFrame {
             params: params,
+            model: model,
             spectrals: spectrals,
             enhanced: enhanced,
             voice: voice,
@@ -112,42 +400,540 @@ impl ImbeDecoder {
             phase_base: vbase,
             phase: vphase,
         };
+
+        (FrameStatus::Normal, forced, errors.rate)
+    }
+
+    /// Reset the saved parameters to their initial state and mute the current frame with
+    /// the given ϵ<sub>R</sub>, recovering from numeric state that has become non-finite.
+    fn recover(&mut self, rate: f32, buf: &mut [f32]) -> (FrameStatus, bool, f32) {
+        self.prev = PrevFrame::default();
+        self.silence(buf);
+
+        (FrameStatus::Mute, false, rate)
     }
 
     /// Fill the given audio buffer with silence.
-    fn silence(&self, buf: &mut AudioBuf) {
-        (0..SAMPLES_PER_FRAME).map(|_| 0.0).collect_slice_checked(&mut buf[..]);
+    fn silence(&self, buf: &mut [f32]) {
+        for x in buf.iter_mut() {
+            *x = 0.0;
+        }
     }
 
     /// Repeat the previous frame into the given audio buffer.
-    fn repeat(&self, buf: &mut AudioBuf) {
+    fn repeat(&mut self, timing: Timing, extend: bool, buf: &mut [f32]) {
         // Apply Eqs 99 through 104.
         let params = self.prev.params.clone();
         let voice = self.prev.voice.clone();
         let enhanced = self.prev.enhanced.clone();
 
-        let udft = UnvoicedDft::new(&params, &voice, &enhanced, rand::weak_rng());
-        let vbase = PhaseBase::new(&params, &self.prev);
-        let vphase = Phase::new(&vbase, &params, &self.prev, &voice, rand::weak_rng());
-
-        let unvoiced = Unvoiced::new(&udft, &self.prev.unvoiced);
-        let voiced = Voiced::new(&params, &self.prev, &vphase, &enhanced, &voice);
+        let udft = stage!(self, UnvoicedDft,
+                          UnvoicedDft::with_noise(&params, &voice, &enhanced,
+                                                  &mut self.noise, &mut self.rng));
+        let vphase = stage!(self, Phase, {
+            let vbase = PhaseBase::with_period(&params, &self.prev, timing.period());
+            Phase::new(&vbase, &params, &self.prev, &voice, &mut self.rng)
+        });
 
         // Repeat frame using previous parameters [p47].
-        (0..SAMPLES_PER_FRAME)
-            .map(|n| unvoiced.get(n) + voiced.get(n))
-            .collect_slice_checked(&mut buf[..]);
+        stage!(self, Synthesis, synthesize(
+            &Unvoiced::with_timing(&udft, &self.prev.unvoiced, timing, &mut self.rng),
+            &Voiced::with_timing(&params, &self.prev, &vphase, &enhanced, &voice, timing,
+                                 extend),
+            buf));
     }
 }
 
+/// Check whether every value in the given slice is finite.
+fn finite(xs: &[f32]) -> bool {
+    xs.iter().all(|x| x.is_finite())
+}
+
+/// Compute Eq 142 for each sample in the given buffer, splitting the work over threads.
+#[cfg(feature = "threads")]
+fn synthesize(unvoiced: &Unvoiced, voiced: &Voiced, buf: &mut [f32]) {
+    // Split samples as evenly as possible over threads.
+    let per_thread = (buf.len() + THREADS - 1) / THREADS;
+
+    crossbeam::scope(|scope| {
+        for (i, chunk) in buf.chunks_mut(per_thread).enumerate() {
+            let start = i * per_thread;
+
+            scope.spawn(move || {
+                voiced.fill(start, chunk);
+                unvoiced.add_to(start, chunk);
+            });
+        }
+    });
+}
+
+/// Compute Eq 142 for each sample in the given buffer.
+#[cfg(not(feature = "threads"))]
+fn synthesize(unvoiced: &Unvoiced, voiced: &Voiced, buf: &mut [f32]) {
+    voiced.fill(0, buf);
+    unvoiced.add_to(0, buf);
+}
+
 #[cfg(test)]
 mod test {
-    use super::THREADS;
+    use super::*;
     use consts::SAMPLES_PER_FRAME;
+    use descramble::random_chunks;
+    use frame::TEST_CHUNKS;
+    use rand::{Rng, XorShiftRng};
+    use timing::TimeStretch;
 
     #[test]
+    #[cfg(feature = "threads")]
     fn verify_threads() {
         // Verify samples are split cleanly over threads.
         assert!(SAMPLES_PER_FRAME % THREADS == 0);
     }
+
+    #[test]
+    fn test_stats() {
+        let mut dec = ImbeDecoder::new();
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+        let s = dec.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut buf);
+        assert_eq!(s, FrameStatus::Normal);
+
+        let frame = ReceivedFrame::new(TEST_CHUNKS, [3, 3, 3, 3, 1, 0, 0]);
+        let s = dec.decode(frame, &mut buf);
+        assert_eq!(s, FrameStatus::Repeat);
+
+        // The repeated frame's own ϵ_R is recorded.
+        assert!((dec.stats().err_rate() - 0.000365 * 13.0).abs() < 1e-6);
+
+        // b_0 = 216.
+        let s = dec.decode(ReceivedFrame::new([0b110110000000, 0, 0, 0, 0, 0, 0, 0], [0; 7]),
+                           &mut buf);
+        assert_eq!(s, FrameStatus::Silence);
+        assert!(buf.iter().all(|&x| x == 0.0));
+
+        // b_0 = 252.
+        let s = dec.decode(ReceivedFrame::new([0b111111000000, 0, 0, 0, 0, 0, 0, 0], [0; 7]),
+                           &mut buf);
+        assert_eq!(s, FrameStatus::Invalid);
+
+        assert_eq!(dec.stats().frames(), 4);
+        assert_eq!(dec.stats().count(FrameStatus::Normal), 1);
+        assert_eq!(dec.stats().count(FrameStatus::Repeat), 1);
+        assert_eq!(dec.stats().count(FrameStatus::Silence), 1);
+        assert_eq!(dec.stats().count(FrameStatus::Invalid), 1);
+        assert_eq!(dec.stats().error_hist()[0], 3);
+        assert_eq!(dec.stats().error_hist()[13], 1);
+
+        dec.reset_stats();
+        assert_eq!(dec.stats().frames(), 0);
+    }
+
+    #[test]
+    fn test_mute_rate() {
+        let mut dec = ImbeDecoder::new();
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+        // Steady errors without repeats raise ϵ_R until the frame is muted.
+        let errors = [1, 3, 3, 3, 1, 1, 1];
+        let mut prev = 0.0;
+
+        for i in 0..50 {
+            let s = dec.decode(ReceivedFrame::new(TEST_CHUNKS, errors), &mut buf);
+            assert_eq!(s == FrameStatus::Mute, i == 49);
+
+            let rate = dec.stats().err_rate();
+            assert!((rate - (0.95 * prev + 0.000365 * 13.0)).abs() < 1e-6);
+
+            prev = rate;
+        }
+
+        // The muted frame is recorded with the ϵ_R that muted it.
+        assert!(dec.stats().err_rate() > enhance::MUTE_RATE);
+        assert_eq!(dec.stats().count(FrameStatus::Mute), 1);
+    }
+
+    #[test]
+    fn test_all_periods() {
+        let mut rng = XorShiftRng::new_unseeded();
+        let mut dec = ImbeDecoder::with_rng(DecoderConfig::default(),
+                                            XorShift::default());
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+        for period in 0...207 {
+            for _ in 0..4 {
+                let chunks = random_chunks(period, &mut rng);
+                let s = dec.decode(ReceivedFrame::new(chunks, [0; 7]), &mut buf);
+
+                assert_eq!(s, FrameStatus::Normal);
+                assert!(buf.iter().all(|x| x.is_finite()));
+            }
+        }
+
+        // Jump between random periods.
+        for _ in 0..500 {
+            let chunks = random_chunks(rng.gen_range(0, 208), &mut rng);
+            dec.decode(ReceivedFrame::new(chunks, [0; 7]), &mut buf);
+
+            assert!(buf.iter().all(|x| x.is_finite()));
+        }
+    }
+
+    #[test]
+    fn test_error_overflow() {
+        let mut dec = ImbeDecoder::new();
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+        // Corrupt error counts must not overflow the total.
+        let frame = ReceivedFrame::new(TEST_CHUNKS, [::std::usize::MAX; 7]);
+        let s = dec.decode(frame, &mut buf);
+        assert_eq!(s, FrameStatus::Repeat);
+        assert_eq!(*dec.stats().error_hist().last().unwrap(), 1);
+
+        let s = dec.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut buf);
+        assert_eq!(s, FrameStatus::Normal);
+        assert!(buf.iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn test_recover() {
+        // Gains in this frame drive the predicted amplitudes up by orders of magnitude.
+        let loud = [1215, 1775, 3657, 1328, 724, 1576, 663, 7];
+
+        let mut dec = ImbeDecoder::new();
+        let mut fresh = ImbeDecoder::new();
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+        // Repeat it with the most errors that are still corrected, until the error rate
+        // mutes the stream.
+        for i in 0..60 {
+            let s = dec.decode(ReceivedFrame::new(loud, [1, 3, 3, 3, 1, 1, 1]), &mut buf);
+            assert_eq!(s, if i < 49 { FrameStatus::Normal } else { FrameStatus::Mute });
+            assert!(buf.iter().all(|x| x.is_finite()));
+
+            assert!(dec.prev().spectrals.iter().all(|x| x.is_finite()));
+            assert!(dec.prev().enhanced.iter().all(|x| x.is_finite()));
+            assert!(dec.prev().energy.tracking.is_finite());
+            assert!(dec.prev().amp_thresh.is_finite());
+        }
+
+        assert!(dec.prev().spectrals.iter().any(|&m| m > 8388608.0));
+
+        // Clean frames then decode normally and converge on the same parameters as from
+        // a fresh state.
+        for _ in 0..20 {
+            let s = dec.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut buf);
+            assert_eq!(s, FrameStatus::Normal);
+            assert!(buf.iter().all(|x| x.is_finite()));
+
+            fresh.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut buf);
+        }
+
+        for (&a, &b) in dec.prev().spectrals.iter().zip(fresh.prev().spectrals.iter()) {
+            assert!((a / b - 1.0).abs() < 1e-5);
+        }
+
+        for (&a, &b) in dec.prev().enhanced.iter().zip(fresh.prev().enhanced.iter()) {
+            assert!((a / b - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "profile")]
+    fn test_profile() {
+        use profile::Stage;
+
+        let mut dec = ImbeDecoder::new();
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+        for _ in 0..3 {
+            dec.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut buf);
+        }
+
+        dec.decode(ReceivedFrame::new(TEST_CHUNKS, [3, 3, 3, 3, 1, 0, 0]), &mut buf);
+
+        for &s in Stage::all().iter() {
+            let calls = match s {
+                Stage::UnvoicedDft | Stage::Phase | Stage::Synthesis => 4,
+                _ => 3,
+            };
+
+            assert_eq!(dec.profile().calls(s), calls);
+        }
+
+        let zero = ::std::time::Duration::new(0, 0);
+        assert!(dec.profile().elapsed(Stage::Synthesis) > zero);
+
+        dec.reset_profile();
+        assert_eq!(dec.profile().calls(Stage::Synthesis), 0);
+    }
+
+    #[test]
+    fn test_decode_i16() {
+        // Fully voiced frame with maximum gain, which makes the voiced signal saturate
+        // at 2τ_M.
+        let chunks = [
+            0b000000111111,
+            0b111111111111,
+            0b111111111111,
+            0b111111111111,
+            0b11111111111,
+            0b11111111111,
+            0b11111111111,
+            0b00001000,
+        ];
+
+        let mut ref_dec = ImbeDecoder::new();
+        let mut dec = ImbeDecoder::new();
+        let mut half_dec = ImbeDecoder::with_config(DecoderConfig {
+            gain: 0.5,
+            .. DecoderConfig::default()
+        });
+
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+        let mut out = [0; SAMPLES_PER_FRAME];
+        let mut half = [0; SAMPLES_PER_FRAME];
+
+        for _ in 0..4 {
+            ref_dec.decode(ReceivedFrame::new(chunks, [0; 7]), &mut buf);
+            dec.decode_i16(ReceivedFrame::new(chunks, [0; 7]), &mut out[..]);
+            half_dec.decode_i16(ReceivedFrame::new(chunks, [0; 7]), &mut half[..]);
+
+            let peak = buf.iter().fold(0.0f32, |m, &x| m.max(x.abs()));
+            assert!(peak > 32767.0);
+            assert!(peak <= 2.0 * 20480.0 * 1.001);
+
+            for ((&x, &y), &z) in buf.iter().zip(out.iter()).zip(half.iter()) {
+                assert_eq!(y as f32, x.round().max(-32768.0).min(32767.0));
+                assert_eq!(z as f32, (0.5 * x).round());
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_i16_unvoiced() {
+        use quantize::quantize;
+
+        // Unvoiced frame with the fewest harmonics and its energy in the lowest bands,
+        // which makes the noise reach well beyond the voiced range.
+        let amps = [1e5, 1e5, 1e5, 1e5, 1.0, 1.0, 1.0, 1.0, 1.0];
+
+        // Seed every decoder so they draw the same noise.
+        let mut ref_dec = ImbeDecoder::with_rng(DecoderConfig::default(),
+                                                XorShift::default());
+        let mut dec = ImbeDecoder::with_rng(DecoderConfig::default(),
+                                            XorShift::default());
+        let mut half_dec = ImbeDecoder::with_rng(DecoderConfig {
+            gain: 0.5,
+            .. DecoderConfig::default()
+        }, XorShift::default());
+
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+        let mut out = [0; SAMPLES_PER_FRAME];
+        let mut half = [0; SAMPLES_PER_FRAME];
+
+        for i in 0..8 {
+            let chunks = quantize(0, 0, &amps, ref_dec.prev());
+
+            ref_dec.decode(ReceivedFrame::new(chunks, [0; 7]), &mut buf);
+            dec.decode_i16(ReceivedFrame::new(chunks, [0; 7]), &mut out[..]);
+            half_dec.decode_i16(ReceivedFrame::new(chunks, [0; 7]), &mut half[..]);
+
+            assert_eq!(ref_dec.prev().voice.unvoiced_count(), 9);
+
+            // Skip the frames where the amplitudes ramp up from the initial state.
+            if i >= 4 {
+                let peak = buf.iter().fold(0.0f32, |m, &x| m.max(x.abs()));
+                assert!(peak > 2.0 * 20480.0);
+                assert!(peak < 16.0 * 20480.0);
+            }
+
+            for ((&x, &y), &z) in buf.iter().zip(out.iter()).zip(half.iter()) {
+                assert_eq!(y as f32, x.round().max(-32768.0).min(32767.0));
+                assert_eq!(z as f32, (0.5 * x).round().max(-32768.0).min(32767.0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_rate() {
+        let mut dec = ImbeDecoder::new();
+        assert_eq!(dec.samples_per_frame(), 160);
+        assert_eq!(dec.delay(), 0.0);
+
+        dec.config_mut().rate = SampleRate::Hz48000;
+        assert_eq!(dec.samples_per_frame(), 960);
+        assert_eq!(dec.delay(), 192.0);
+
+        let mut out = [0i16; 960];
+        let s = dec.decode_i16(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut out[..]);
+        assert_eq!(s, FrameStatus::Normal);
+
+        // Initial samples are within the filter delay.
+        assert!(out[..100].iter().all(|&x| x == 0));
+        assert!(out[192..].iter().any(|&x| x != 0));
+
+        dec.config_mut().rate = SampleRate::Hz44100;
+        let mut out = [0.0f32; 882];
+        dec.decode_into(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut out[..]);
+    }
+
+    #[test]
+    fn test_decode_direct() {
+        // Seed both decoders so they draw the same noise and voiced phases.
+        let mut native = ImbeDecoder::with_rng(DecoderConfig::default(),
+                                               XorShift::default());
+        let mut direct = ImbeDecoder::with_rng(DecoderConfig {
+            rate: SampleRate::Hz48000,
+            synthesis: Synthesis::Direct,
+            .. DecoderConfig::default()
+        }, XorShift::default());
+
+        assert_eq!(direct.samples_per_frame(), 960);
+        assert_eq!(direct.delay(), 0.0);
+
+        let mut a = [0.0f32; 160];
+        let mut b = [0.0f32; 960];
+
+        for _ in 0..10 {
+            native.decode_into(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut a[..]);
+            let frame = ReceivedFrame::new(TEST_CHUNKS, [0; 7]);
+            let s = direct.decode_into(frame, &mut b[..]);
+            assert_eq!(s, FrameStatus::Normal);
+
+            assert!(b.iter().all(|x| x.is_finite()));
+            assert!(b.iter().any(|&x| x != 0.0));
+
+            // Every 6th sample at 48kHz falls on a native sample.
+            for (k, &x) in a.iter().enumerate() {
+                assert!((b[6 * k] - x).abs() < 0.01);
+            }
+        }
+
+        direct.config_mut().extend_bandwidth = true;
+        direct.decode_into(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut b[..]);
+        assert!(b.iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn test_switch_synthesis() {
+        // Seed both decoders so they draw the same noise and voiced phases.
+        let mut native = ImbeDecoder::with_rng(DecoderConfig::default(),
+                                               XorShift::default());
+        let mut dec = ImbeDecoder::with_rng(DecoderConfig {
+            rate: SampleRate::Hz48000,
+            .. DecoderConfig::default()
+        }, XorShift::default());
+
+        let mut a = [0.0; SAMPLES_PER_FRAME];
+        let mut b = [0.0f32; 960];
+
+        for &synthesis in [Synthesis::Resample, Synthesis::Direct].iter() {
+            dec.config_mut().synthesis = synthesis;
+
+            for _ in 0..3 {
+                native.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut a);
+                dec.decode_into(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut b[..]);
+            }
+        }
+
+        // Switching back resamples from a clear history rather than the history left by
+        // the last resampled frame.
+        dec.config_mut().synthesis = Synthesis::Resample;
+
+        native.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut a);
+        dec.decode_into(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut b[..]);
+
+        let mut expected = [0.0; 960];
+        Resampler::new(SampleRate::Hz48000).process(&a, &mut expected[..]);
+
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert!((x - y).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_decode_postfilter() {
+        let mut dec = ImbeDecoder::with_config(DecoderConfig {
+            postfilter: 1.0,
+            .. DecoderConfig::default()
+        });
+
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+        for _ in 0..3 {
+            let s = dec.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut buf);
+            assert_eq!(s, FrameStatus::Normal);
+            assert!(buf.iter().all(|x| x.is_finite()));
+        }
+
+        assert!(buf.iter().any(|&x| x != 0.0));
+    }
+
+    #[test]
+    fn test_decode_transform() {
+        let mut dec = ImbeDecoder::with_config(DecoderConfig {
+            transform: VoiceTransform::new(1.5, 1.2),
+            .. DecoderConfig::default()
+        });
+
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+
+        for _ in 0..3 {
+            let s = dec.decode(ReceivedFrame::new(TEST_CHUNKS, [0; 7]), &mut buf);
+            assert_eq!(s, FrameStatus::Normal);
+            assert!(buf.iter().all(|x| x.is_finite()));
+        }
+
+        assert!(buf.iter().any(|&x| x != 0.0));
+
+        // Verify synthesis uses the transformed grid while prediction uses the decoded
+        // one.
+        assert_eq!(dec.prev.model.harmonics, 16);
+        assert_eq!(dec.prev.params.harmonics, 11);
+        assert_eq!(dec.prev.enhanced.len(), 11);
+        assert_eq!(dec.prev.spectrals.len(), 16);
+    }
+
+    #[test]
+    fn test_decode_stretched() {
+        let mut dec = ImbeDecoder::new();
+        let mut out = Vec::new();
+        let mut bounds = Vec::new();
+
+        for &len in [160, 80, 320, 107, 213, 80, 320].iter() {
+            let mut buf = [0.0f32; MAX_STRETCH_SAMPLES];
+
+            let s = dec.decode_stretched(ReceivedFrame::new(TEST_CHUNKS, [0; 7]),
+                                         &mut buf[..len]);
+            assert_eq!(s, FrameStatus::Normal);
+
+            out.extend_from_slice(&buf[..len]);
+            bounds.push(out.len());
+        }
+
+        assert_eq!(out.len(), 1280);
+        assert!(out.iter().all(|x| x.is_finite()));
+
+        // Verify frames join without discontinuities: the step across each boundary
+        // should be comparable to the steps around it.
+        let diffs = out.windows(2).map(|w| (w[1] - w[0]).abs()).collect::<Vec<_>>();
+
+        for &b in bounds[..bounds.len() - 1].iter() {
+            let near = diffs[b - 5..b - 1].iter().chain(diffs[b..b + 4].iter())
+                .fold(0.0f32, |m, &d| m.max(d));
+
+            assert!(diffs[b - 1] < 2.0 * near);
+        }
+
+        let mut stretch = TimeStretch::new(1.5, SampleRate::Hz16000);
+        let mut buf = [0i16; MAX_STRETCH_SAMPLES];
+
+        dec.config_mut().rate = SampleRate::Hz16000;
+
+        for _ in 0..3 {
+            let len = stretch.next();
+            dec.decode_stretched(ReceivedFrame::new(TEST_CHUNKS, [0; 7]),
+                                 &mut buf[..len]);
+        }
+    }
 }
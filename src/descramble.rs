@@ -56,6 +56,22 @@ impl Bootstrap {
     }
 }
 
+/// Generate random chunks that encode the given period b<sub>0</sub>.
+#[cfg(test)]
+pub fn random_chunks<R: ::rand::Rng>(period: u8, rng: &mut R) -> Chunks {
+    let mut chunks = [0; 8];
+
+    for (c, &bits) in chunks.iter_mut().zip([12, 12, 12, 12, 11, 11, 11, 7].iter()) {
+        *c = rng.next_u32() & ((1 << bits) - 1);
+    }
+
+    // Place the 6 MSBs of b_0 in u_0 and the 2 LSBs in u_7 [p39].
+    chunks[0] = chunks[0] & 0b111111 | (period as u32 >> 2) << 6;
+    chunks[7] = chunks[7] & !0b110 | (period as u32 & 0b11) << 1;
+
+    chunks
+}
+
 /// Compute the 8-bit quantized period b<sub>0</sub> from the given u<sub>0</sub>, ...,
 /// s<sub>7</sub>.
 fn period(chunks: &Chunks) -> u8 {
@@ -92,7 +108,9 @@ impl QuantizedAmplitudes {
                     continue;
                 }
 
-                // Shift the next scanned bit onto the LSB.
+                // Shift the next scanned bit onto the LSB. For every L, the allocations
+                // sum to exactly the number of scanned bits, as verified over all
+                // periods by `test_scan_len` in `scan`, so no input runs the scan short.
                 amps[i] <<= 1;
                 amps[i] |= scan.next().unwrap();
             }
@@ -195,6 +213,31 @@ fn gen_harmonics_bitmap(voiced: u32, params: &BaseParams) -> u64 {
 mod tests {
     use super::*;
     use params::BaseParams;
+    use rand::XorShiftRng;
+
+    #[test]
+    fn test_random_chunks() {
+        let mut rng = XorShiftRng::new_unseeded();
+
+        for period in 0...207 {
+            for _ in 0..20 {
+                let chunks = random_chunks(period, &mut rng);
+                assert_eq!(Bootstrap::new(&chunks).unwrap_period(), period);
+
+                let p = BaseParams::new(period);
+                let (amps, voice, gain_idx) = descramble(&chunks, &p);
+                let (bits, _) = allocs(p.harmonics);
+
+                assert!(gain_idx < 64);
+                assert!(voice.unvoiced_count() <= p.harmonics);
+
+                // Each b_m fits in its B_m bits.
+                for m in 3...p.harmonics as usize + 1 {
+                    assert_eq!(amps.get(m) >> bits[m - 3], 0);
+                }
+            }
+        }
+    }
 
     #[test]
     #[should_panic]
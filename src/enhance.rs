@@ -9,6 +9,8 @@ use map_in_place::MapInPlace;
 use consts::MAX_HARMONICS;
 use frame::Errors;
 use descramble::VoiceDecisions;
+#[cfg(not(feature = "std"))]
+use math::Float;
 use params::BaseParams;
 use spectral::Spectrals;
 
@@ -28,7 +30,7 @@ impl EnhanceErrors {
     /// Create a new `EnhanceErrors` from the errors corrected in the current frame,
     /// ϵ<sub>i</sub>, and the previous frame's ϵ<sub>R</sub> value.
     pub fn new(errors: &Errors, prev_rate: f32) -> EnhanceErrors {
-        let total = errors.iter().fold(0, |s, &e| s + e);
+        let total = errors.iter().fold(0, |s: usize, &e| s.saturating_add(e));
 
         EnhanceErrors {
             total: total,
@@ -41,6 +43,7 @@ impl EnhanceErrors {
 }
 
 /// Energy-related parameters for a voice frame.
+#[derive(Copy, Clone)]
 pub struct FrameEnergy {
     /// Spectral amplitude energy, R<sub>M0</sub>.
     pub energy: f32,
@@ -66,11 +69,18 @@ impl FrameEnergy {
             .map(|(l, &m)| m.powi(2) * (params.fundamental * (l + 1) as f32).cos())
             .fold(0.0, |s, x| s + x);
 
+        // Compute energy tracking EWMA according to Eq 111.
+        let tracking = (0.95 * prev.tracking + 0.05 * energy).max(10000.0);
+
         FrameEnergy {
             energy: energy,
             scaled: scaled,
-            // Compute energy tracking EWMA according to Eq 111.
-            tracking: (0.95 * prev.tracking + 0.05 * energy).max(10000.0),
+            // Restart tracking from the initial value if it has diverged.
+            tracking: if tracking.is_finite() {
+                tracking
+            } else {
+                FrameEnergy::default().tracking
+            },
         }
     }
 }
@@ -109,8 +119,9 @@ impl EnhancedSpectrals {
         let mut enhanced = spectrals.iter().enumerate().map(|(l, &m)| {
             let l = l + 1;
 
-            // Handle fast-path case in Eq 108.
-            if 8 * l as u32 <= params.harmonics {
+            // Handle fast-path case in Eq 108, and skip weighting when the energies
+            // are degenerate and the weight would be undefined.
+            if 8 * l as u32 <= params.harmonics || !(denom > 0.0 && denom.is_finite()) {
                 return m;
             }
 
@@ -126,10 +137,14 @@ impl EnhancedSpectrals {
             m * weight.max(0.5).min(1.2)
         }).collect::<ArrayVec<[f32; MAX_HARMONICS]>>();
 
-        // Compute root ratio of energies according to Eq 109.
-        let scale = (
+        // Compute root ratio of energies according to Eq 109, leaving amplitudes
+        // unscaled if either energy is degenerate.
+        let scale = match (
             fen.energy / enhanced.iter().fold(0.0, |s, &m| s + m.powi(2))
-        ).sqrt();
+        ).sqrt() {
+            s if s.is_finite() => s,
+            _ => 1.0,
+        };
 
         // Perform second scaling pass according to Eq 110.
         enhanced.map_in_place(|&m| m * scale);
@@ -158,6 +173,13 @@ impl std::ops::DerefMut for EnhancedSpectrals {
     fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 }
 }
 
+impl std::iter::FromIterator<f32> for EnhancedSpectrals {
+    /// Collect amplitudes M<sub>1</sub>, ..., M<sub>L</sub> into an `EnhancedSpectrals`.
+    fn from_iter<I: IntoIterator<Item = f32>>(iter: I) -> EnhancedSpectrals {
+        EnhancedSpectrals(iter.into_iter().collect())
+    }
+}
+
 impl Default for EnhancedSpectrals {
     /// Create a new `EnhancedSpectrals` with default initial values.
     fn default() -> EnhancedSpectrals {
@@ -199,10 +221,21 @@ pub fn smooth(enhanced: &mut EnhancedSpectrals, voiced: &mut VoiceDecisions,
         }
     }
 
+    limit(enhanced, amp_thresh);
+}
+
+/// Scale the given enhanced spectral amplitudes M<sub>l</sub> so their sum doesn't exceed
+/// the given spectral amplitude threshold τ<sub>M</sub>.
+pub fn limit(enhanced: &mut EnhancedSpectrals, amp_thresh: f32) {
     // Compute amplitude sum in Eq 114.
     let amp = enhanced.iter().fold(0.0, |s, &m| s + m);
-    // Compute scale factor in Eq 116.
-    let scale = (amp_thresh / amp).min(1.0);
+    // Compute scale factor in Eq 116, guarding against a zero sum and against a
+    // threshold that has gone negative or diverged.
+    let scale = if amp > 0.0 && amp_thresh.is_finite() {
+        (amp_thresh / amp).min(1.0).max(0.0)
+    } else {
+        1.0
+    };
 
     // Scale each enhanced M_l [p50].
     enhanced.map_in_place(|&m| m * scale);
@@ -215,11 +248,14 @@ pub fn should_repeat(errors: &EnhanceErrors) -> bool {
     errors.golay_init >= 2 && errors.total as f32 >= 10.0 + 40.0 * errors.rate
 }
 
+/// Error rate ϵ<sub>R</sub> above which frames are muted [p47].
+pub const MUTE_RATE: f32 = 0.0875;
+
 /// Check if the current frame should be discarded and replaced with silence/comfort noise
 /// based on the given error characteristics of the current frame.
 pub fn should_mute(errors: &EnhanceErrors) -> bool {
     // Check the condition on [p47].
-    errors.rate > 0.0875
+    errors.rate > MUTE_RATE
 }
 
 #[cfg(test)]
@@ -442,4 +478,44 @@ mod tests {
         assert_eq!(amps.get(30), 0.0);
         assert_eq!(amps.get(56), 0.0);
     }
+
+    #[test]
+    fn test_degenerate() {
+        let p = BaseParams::new(32);
+        let s: Spectrals = (0..p.harmonics).map(|_| 0.0).collect();
+        let fe = FrameEnergy::new(&s, &FrameEnergy::default(), &p);
+        let mut e = EnhancedSpectrals::new(&s, &fe, &p);
+
+        assert!(e.iter().all(|&m| m == 0.0));
+
+        let mut voice = VoiceDecisions::default();
+        let err = EnhanceErrors::new(&[0; 7], 0.0);
+        smooth(&mut e, &mut voice, &err, &fe, 20480.0);
+
+        assert!(e.iter().all(|&m| m == 0.0));
+
+        // Diverged tracking restarts from its initial value.
+        let prev = FrameEnergy {
+            energy: 0.0,
+            scaled: 0.0,
+            tracking: ::std::f32::INFINITY,
+        };
+
+        let fe = FrameEnergy::new(&s, &prev, &p);
+        assert_eq!(fe.tracking, FrameEnergy::default().tracking);
+
+        // Error counts beyond what the codes can correct make Eq 115 negative, and
+        // negative or undefined thresholds leave amplitudes non-negative and finite.
+        let bad = EnhanceErrors::new(&[0, 0, 0, 0, 0, 0, 100], 0.0);
+        let thresh = amp_thresh(&bad, 0.0);
+        assert!(thresh < 0.0);
+
+        let mut e: EnhancedSpectrals = (0..p.harmonics).map(|_| 1.0).collect();
+        smooth(&mut e, &mut voice, &err, &fe, thresh);
+        assert!(e.iter().all(|&m| m == 0.0));
+
+        let mut e: EnhancedSpectrals = (0..p.harmonics).map(|_| 1.0).collect();
+        smooth(&mut e, &mut voice, &err, &fe, ::std::f32::NAN);
+        assert!(e.iter().all(|&m| m == 1.0));
+    }
 }
@@ -0,0 +1,629 @@
+//! Export per-frame model parameters and spectrograms for analysis.
+//!
+//! A `Recorder` decodes frames while saving the model parameters each was synthesized
+//! from, along with the synthesized audio:
+//!
+//! - the period b<sub>0</sub>, fundamental frequency ω<sub>0</sub>, harmonics L, and
+//!   bands K, from `BaseParams`,
+//! - the band voiced/unvoiced bitmap b<sub>1</sub> behind the `VoiceDecisions`,
+//! - the spectral amplitudes M<sub>l</sub> and enhanced amplitudes M̄<sub>l</sub>,
+//! - the energy R<sub>M0</sub>, scaled energy R<sub>M1</sub>, and energy tracker
+//!   S<sub>E</sub>, from `FrameEnergy`.
+//!
+//! Repeated and invalid frames take the parameters of the frame they repeat, and muted
+//! and silence frames have no parameters.
+//!
+//! The tracks can be written as a single CSV table with one row per frame, or as
+//! separate NPY arrays of 32-bit floats for loading with `numpy.load`, where missing
+//! values are NaN. Per-harmonic and per-band values are padded to the most harmonics
+//! and bands any frame can have.
+//!
+//! A `Spectrogram` of the synthesized audio has a column for each 20ms frame, with
+//! power from 0Hz to 4kHz, and can be rendered as text or SVG.
+
+use std::f32::consts::{LN_10, PI};
+use std::io::{self, Write};
+
+use consts::{MAX_HARMONICS, SAMPLE_RATE, SAMPLES_PER_FRAME};
+use decode::ImbeDecoder;
+use descramble::Bootstrap;
+use enhance::{EnhancedSpectrals, FrameEnergy};
+use frame::ReceivedFrame;
+use params::BaseParams;
+use quality::{DFT_HALF, DFT_SIZE, Dft};
+use rng::Random;
+use scan::ScanSep;
+use spectral::Spectrals;
+use stats::FrameStatus;
+
+/// Maximum number of voiced/unvoiced bands K in a frame.
+pub const MAX_BANDS: usize = 12;
+
+/// Number of characters across each line of a text spectrogram.
+pub const TEXT_COLUMNS: usize = 64;
+
+/// Characters of increasing power used in text spectrograms.
+const SHADES: &'static [u8] = b" .:-=+*#%@";
+
+/// Width and height of each cell in an SVG spectrogram, in pixels.
+const SVG_CELL: usize = 2;
+
+/// Model parameters a frame was synthesized from.
+#[derive(Clone)]
+pub struct FrameModel {
+    /// Period b<sub>0</sub>.
+    pub period: u8,
+    /// Received fundamental frequency, harmonics, and bands, before any voice
+    /// transform.
+    pub params: BaseParams,
+    /// Band voiced/unvoiced bitmap b<sub>1</sub>, with band 1 in the MSB of K bits.
+    pub voiced: u32,
+    /// Spectral amplitudes M<sub>l</sub>.
+    pub spectrals: Spectrals,
+    /// Enhanced spectral amplitudes M̄<sub>l</sub>, over the harmonics used for
+    /// synthesis after any voice transform.
+    pub enhanced: EnhancedSpectrals,
+    /// Frame energy values.
+    pub energy: FrameEnergy,
+}
+
+impl FrameModel {
+    /// Compute the fundamental frequency in Hz.
+    pub fn fundamental_hz(&self) -> f32 {
+        self.params.fundamental * SAMPLE_RATE as f32 / (2.0 * PI)
+    }
+
+    /// Check if the given band k, 1 ≤ k ≤ K, is voiced.
+    pub fn is_band_voiced(&self, k: usize) -> bool {
+        let bands = self.params.bands as usize;
+        k >= 1 && k <= bands && self.voiced >> (bands - k) & 1 == 1
+    }
+}
+
+/// Parameter track of a single decoded frame.
+#[derive(Clone)]
+pub struct FrameTrack {
+    /// How the frame was handled.
+    pub status: FrameStatus,
+    /// Model parameters the frame was synthesized from, if any.
+    pub model: Option<FrameModel>,
+}
+
+/// Array of per-frame values that can be exported in NPY format.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Array {
+    /// Status code (0 normal, 1 repeat, 2 mute, 3 silence, 4 invalid), b<sub>0</sub>,
+    /// ω<sub>0</sub>, fundamental frequency in Hz, L, and K, with shape (frames, 6).
+    Params,
+    /// 1 for voiced and 0 for unvoiced bands, with shape (frames, 12).
+    Voicing,
+    /// Spectral amplitudes M<sub>l</sub>, with shape (frames, 56).
+    Spectrals,
+    /// Enhanced spectral amplitudes M̄<sub>l</sub>, with shape (frames, 56).
+    Enhanced,
+    /// R<sub>M0</sub>, R<sub>M1</sub>, and S<sub>E</sub>, with shape (frames, 3).
+    Energy,
+    /// Spectrogram power in dB, with shape (frames, 129).
+    Spectrogram,
+}
+
+impl Array {
+    /// All arrays, in export order.
+    pub fn all() -> [Array; 6] {
+        [Array::Params, Array::Voicing, Array::Spectrals, Array::Enhanced, Array::Energy,
+         Array::Spectrogram]
+    }
+
+    /// Retrieve the base file name of the array.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Array::Params => "params",
+            Array::Voicing => "voicing",
+            Array::Spectrals => "spectrals",
+            Array::Enhanced => "enhanced",
+            Array::Energy => "energy",
+            Array::Spectrogram => "spectrogram",
+        }
+    }
+
+    /// Retrieve the number of values in each row of the array.
+    pub fn columns(&self) -> usize {
+        match *self {
+            Array::Params => 6,
+            Array::Voicing => MAX_BANDS,
+            Array::Spectrals | Array::Enhanced => MAX_HARMONICS,
+            Array::Energy => 3,
+            Array::Spectrogram => DFT_HALF,
+        }
+    }
+}
+
+/// Decodes frames while recording their parameter tracks and synthesized audio.
+pub struct Recorder {
+    /// Track of each decoded frame.
+    tracks: Vec<FrameTrack>,
+    /// Synthesized samples of all frames.
+    pcm: Vec<f32>,
+    /// Model of the most recent normally decoded frame.
+    last: Option<FrameModel>,
+}
+
+impl Recorder {
+    /// Create a new `Recorder` with no frames.
+    pub fn new() -> Recorder {
+        Recorder {
+            tracks: vec![],
+            pcm: vec![],
+            last: None,
+        }
+    }
+
+    /// Retrieve the track of each recorded frame.
+    pub fn tracks(&self) -> &[FrameTrack] { &self.tracks }
+
+    /// Retrieve the synthesized samples of all recorded frames, at the standard's scale.
+    pub fn pcm(&self) -> &[f32] { &self.pcm }
+
+    /// Decode the given frame with the given decoder, recording its track and audio.
+    pub fn record<R: Random>(&mut self, dec: &mut ImbeDecoder<R>, frame: ReceivedFrame)
+        -> FrameStatus
+    {
+        let chunks = frame.chunks;
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+        let status = dec.decode(frame, &mut buf);
+
+        self.pcm.extend_from_slice(&buf);
+
+        let model = match status {
+            FrameStatus::Normal => {
+                let prev = dec.prev();
+                let period = match Bootstrap::new(&chunks) {
+                    Bootstrap::Period(p) => p,
+                    _ => unreachable!(),
+                };
+                let params = BaseParams::new(period);
+
+                self.last = Some(FrameModel {
+                    period: period,
+                    params: prev.model,
+                    voiced: ScanSep::new(&chunks, &params).voiced,
+                    spectrals: prev.spectrals.clone(),
+                    enhanced: prev.enhanced.clone(),
+                    energy: prev.energy,
+                });
+
+                self.last.clone()
+            },
+            FrameStatus::Repeat | FrameStatus::Invalid => self.last.clone(),
+            FrameStatus::Mute | FrameStatus::Silence => None,
+        };
+
+        self.tracks.push(FrameTrack {
+            status: status,
+            model: model,
+        });
+
+        status
+    }
+
+    /// Compute the spectrogram of the synthesized audio.
+    pub fn spectrogram(&self) -> Spectrogram { Spectrogram::new(&self.pcm) }
+
+    /// Write the tracks as a CSV table with a header row and one row per frame.
+    ///
+    /// Columns are the frame index, status, b<sub>0</sub>, ω<sub>0</sub>, fundamental
+    /// frequency in Hz, L, K, b<sub>1</sub>, R<sub>M0</sub>, R<sub>M1</sub>,
+    /// S<sub>E</sub>, voicing of bands 1 to 12, M<sub>l</sub> for 1 ≤ l ≤ 56, and
+    /// M̄<sub>l</sub> for 1 ≤ l ≤ 56. Missing values are left empty.
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "frame,status,b0,w0,hz,harmonics,bands,b1,energy,scaled,tracking")?;
+
+        for k in 1...MAX_BANDS {
+            write!(w, ",v{}", k)?;
+        }
+
+        for l in 1...MAX_HARMONICS {
+            write!(w, ",m{}", l)?;
+        }
+
+        for l in 1...MAX_HARMONICS {
+            write!(w, ",mbar{}", l)?;
+        }
+
+        writeln!(w, "")?;
+
+        for (i, track) in self.tracks.iter().enumerate() {
+            write!(w, "{},{}", i, status_name(track.status))?;
+
+            let m = match track.model {
+                Some(ref m) => m,
+                None => {
+                    let empty = 9 + MAX_BANDS + 2 * MAX_HARMONICS;
+                    writeln!(w, "{}", ",".repeat(empty))?;
+                    continue;
+                },
+            };
+
+            write!(w, ",{},{},{},{},{},{},{},{},{}", m.period, m.params.fundamental,
+                   m.fundamental_hz(), m.params.harmonics, m.params.bands, m.voiced,
+                   m.energy.energy, m.energy.scaled, m.energy.tracking)?;
+
+            for k in 1...MAX_BANDS {
+                if k <= m.params.bands as usize {
+                    write!(w, ",{}", m.is_band_voiced(k) as u8)?;
+                } else {
+                    write!(w, ",")?;
+                }
+            }
+
+            write_padded(w, &m.spectrals)?;
+            write_padded(w, &m.enhanced)?;
+
+            writeln!(w, "")?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the given array of the tracks in NPY format.
+    pub fn write_npy<W: Write>(&self, array: Array, w: &mut W) -> io::Result<()> {
+        let cols = array.columns();
+        let nan = ::std::f32::NAN;
+
+        if array == Array::Spectrogram {
+            let spec = self.spectrogram();
+            let vals = (0..spec.segments()).flat_map(|s| spec.column(s).iter().cloned());
+
+            return write_npy(w, spec.segments(), cols, vals);
+        }
+
+        let mut vals = Vec::with_capacity(self.tracks.len() * cols);
+
+        for track in &self.tracks {
+            let start = vals.len();
+
+            if array == Array::Params {
+                vals.push(status_code(track.status));
+            }
+
+            if let Some(ref m) = track.model {
+                match array {
+                    Array::Params => vals.extend_from_slice(&[
+                        m.period as f32,
+                        m.params.fundamental,
+                        m.fundamental_hz(),
+                        m.params.harmonics as f32,
+                        m.params.bands as f32,
+                    ]),
+                    Array::Voicing => vals.extend((1...m.params.bands as usize).map(|k| {
+                        if m.is_band_voiced(k) { 1.0 } else { 0.0 }
+                    })),
+                    Array::Spectrals => vals.extend_from_slice(&m.spectrals),
+                    Array::Enhanced => vals.extend_from_slice(&m.enhanced),
+                    Array::Energy => vals.extend_from_slice(&[
+                        m.energy.energy,
+                        m.energy.scaled,
+                        m.energy.tracking,
+                    ]),
+                    Array::Spectrogram => unreachable!(),
+                }
+            }
+
+            let pad = start + cols - vals.len();
+            vals.extend((0..pad).map(|_| nan));
+        }
+
+        write_npy(w, self.tracks.len(), cols, vals.into_iter())
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Recorder { Recorder::new() }
+}
+
+/// Get the CSV name of the given status.
+fn status_name(status: FrameStatus) -> &'static str {
+    match status {
+        FrameStatus::Normal => "normal",
+        FrameStatus::Repeat => "repeat",
+        FrameStatus::Mute => "mute",
+        FrameStatus::Silence => "silence",
+        FrameStatus::Invalid => "invalid",
+    }
+}
+
+/// Get the NPY code of the given status.
+fn status_code(status: FrameStatus) -> f32 {
+    match status {
+        FrameStatus::Normal => 0.0,
+        FrameStatus::Repeat => 1.0,
+        FrameStatus::Mute => 2.0,
+        FrameStatus::Silence => 3.0,
+        FrameStatus::Invalid => 4.0,
+    }
+}
+
+/// Write the given per-harmonic values as CSV cells, padded with empty cells to the
+/// maximum number of harmonics.
+fn write_padded<W: Write>(w: &mut W, vals: &[f32]) -> io::Result<()> {
+    for v in vals {
+        write!(w, ",{}", v)?;
+    }
+
+    write!(w, "{}", ",".repeat(MAX_HARMONICS - vals.len()))
+}
+
+/// Write the given values as a little-endian 32-bit float NPY array with the given
+/// number of rows and columns in row-major order.
+pub fn write_npy<W: Write, I: Iterator<Item = f32>>(w: &mut W, rows: usize, cols: usize,
+                                                     vals: I)
+    -> io::Result<()>
+{
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, \
+                              'shape': ({}, {}), }}", rows, cols);
+
+    // Pad the magic string, version, length, and header to a multiple of 64 bytes,
+    // ending with a newline.
+    let len = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - len % 64) % 64));
+    header.push('\n');
+
+    w.write_all(b"\x93NUMPY\x01\x00")?;
+    w.write_all(&[header.len() as u8, (header.len() >> 8) as u8])?;
+    w.write_all(header.as_bytes())?;
+
+    for v in vals {
+        let bits = v.to_bits();
+        w.write_all(&[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8,
+                      (bits >> 24) as u8])?;
+    }
+
+    Ok(())
+}
+
+/// Power spectra of consecutive frames of audio.
+pub struct Spectrogram {
+    /// Power of each DFT point, 0 ≤ k ≤ N / 2, in dB, for each frame.
+    power: Vec<[f32; DFT_HALF]>,
+}
+
+impl Spectrogram {
+    /// Compute the spectrogram of the given samples, with a Hann windowed 256-point DFT
+    /// over each frame of 160 samples. Any trailing partial frame is ignored.
+    pub fn new(pcm: &[f32]) -> Spectrogram {
+        let dft = Dft::new();
+
+        Spectrogram {
+            power: pcm.chunks(SAMPLES_PER_FRAME)
+                .filter(|seg| seg.len() == SAMPLES_PER_FRAME)
+                .map(|seg| {
+                    let mut p = dft.power(seg);
+
+                    for x in p.iter_mut() {
+                        // Compute 10 log10(x) = 10 ln(x) / ln(10).
+                        *x = 10.0 * x.max(::std::f32::MIN_POSITIVE).ln() / LN_10;
+                    }
+
+                    p
+                })
+                .collect(),
+        }
+    }
+
+    /// Retrieve the number of frames.
+    pub fn segments(&self) -> usize { self.power.len() }
+
+    /// Retrieve the power in dB of each DFT point of the given frame.
+    pub fn column(&self, seg: usize) -> &[f32; DFT_HALF] { &self.power[seg] }
+
+    /// Compute the frequency in Hz of the given DFT point.
+    pub fn bin_freq(k: usize) -> f32 { (k * SAMPLE_RATE) as f32 / DFT_SIZE as f32 }
+
+    /// Find the DFT point with the most power in the given frame.
+    pub fn peak(&self, seg: usize) -> usize {
+        let col = &self.power[seg];
+        (0..DFT_HALF).fold(0, |best, k| if col[k] > col[best] { k } else { best })
+    }
+
+    /// Find the maximum power in dB over all frames.
+    fn max(&self) -> f32 {
+        self.power.iter().flat_map(|c| c.iter())
+            .fold(::std::f32::NEG_INFINITY, |m, &x| m.max(x))
+    }
+
+    /// Scale the given power to 0 at the given floor and 1 at the given peak.
+    fn level(db: f32, peak: f32, range: f32) -> f32 {
+        ((db - peak + range) / range).max(0.0).min(1.0)
+    }
+
+    /// Write the spectrogram as text, with a line for each frame starting with its time
+    /// in ms, and frequency increasing to the right.
+    ///
+    /// Each character shades the peak power of its DFT points over the given dynamic
+    /// range, in dB, below the overall peak.
+    pub fn write_text<W: Write>(&self, w: &mut W, range: f32) -> io::Result<()> {
+        let peak = self.max();
+        let per = (DFT_HALF - 1) / TEXT_COLUMNS;
+
+        writeln!(w, "{:>8} |0Hz{:>width$}|", "ms",
+                 format!("{}Hz", SAMPLE_RATE / 2), width = TEXT_COLUMNS - 3)?;
+
+        for (s, col) in self.power.iter().enumerate() {
+            let line = (0..TEXT_COLUMNS).map(|c| {
+                let db = col[c * per..(c + 1) * per].iter()
+                    .fold(::std::f32::NEG_INFINITY, |m, &x| m.max(x));
+                let idx = Self::level(db, peak, range) * (SHADES.len() - 1) as f32;
+
+                SHADES[idx.round() as usize] as char
+            }).collect::<String>();
+
+            writeln!(w, "{:>8} |{}|", s * SAMPLES_PER_FRAME * 1000 / SAMPLE_RATE, line)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the spectrogram as an SVG image, with time increasing to the right and
+    /// frequency increasing upward.
+    ///
+    /// Each cell is shaded from black to white over the given dynamic range, in dB,
+    /// below the overall peak.
+    pub fn write_svg<W: Write>(&self, w: &mut W, range: f32) -> io::Result<()> {
+        let peak = self.max();
+        let width = self.power.len() * SVG_CELL;
+        let height = DFT_HALF * SVG_CELL;
+
+        writeln!(w, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" \
+                     height=\"{}\" shape-rendering=\"crispEdges\">", width, height)?;
+        writeln!(w, "<rect width=\"{}\" height=\"{}\" fill=\"black\"/>", width, height)?;
+
+        for (s, col) in self.power.iter().enumerate() {
+            for (k, &db) in col.iter().enumerate() {
+                let shade = (Self::level(db, peak, range) * 255.0).round() as u8;
+
+                if shade == 0 {
+                    continue;
+                }
+
+                writeln!(w, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                             fill=\"rgb({},{},{})\"/>",
+                         s * SVG_CELL, (DFT_HALF - 1 - k) * SVG_CELL, SVG_CELL, SVG_CELL,
+                         shade, shade, shade)?;
+            }
+        }
+
+        writeln!(w, "</svg>")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rng::XorShift;
+    use tone::{Tone, ToneGenerator};
+
+    /// Record the given number of frames of a 1kHz tone followed by a silence frame.
+    fn record(frames: usize) -> Recorder {
+        let mut dec = ImbeDecoder::with_rng(Default::default(), XorShift::default());
+        let mut rec = Recorder::new();
+        let gen = ToneGenerator::new(Tone::Single(1000.0), -10.0).unwrap();
+
+        for chunks in gen.take(frames) {
+            rec.record(&mut dec, ReceivedFrame::new(chunks, [0; 7]));
+        }
+
+        // Silence frame.
+        rec.record(&mut dec, ReceivedFrame::new([0b110110000000, 0, 0, 0, 0, 0, 0, 0],
+                                                [0; 7]));
+        // Invalid frame.
+        rec.record(&mut dec, ReceivedFrame::new([0b110100000000, 0, 0, 0, 0, 0, 0, 0],
+                                                [0; 7]));
+
+        rec
+    }
+
+    #[test]
+    fn test_record() {
+        let rec = record(4);
+        let tracks = rec.tracks();
+
+        assert_eq!(tracks.len(), 6);
+        assert_eq!(rec.pcm().len(), 6 * SAMPLES_PER_FRAME);
+
+        for t in &tracks[..4] {
+            assert_eq!(t.status, FrameStatus::Normal);
+
+            let m = t.model.as_ref().unwrap();
+            assert_eq!(m.spectrals.len(), m.params.harmonics as usize);
+            assert!((1...m.params.bands as usize).all(|k| m.is_band_voiced(k)));
+            assert!(!m.is_band_voiced(0));
+            assert!(!m.is_band_voiced(m.params.bands as usize + 1));
+        }
+
+        assert_eq!(tracks[4].status, FrameStatus::Silence);
+        assert!(tracks[4].model.is_none());
+
+        // The invalid frame repeats the last normal frame.
+        assert_eq!(tracks[5].status, FrameStatus::Invalid);
+        assert_eq!(tracks[5].model.as_ref().unwrap().period,
+                   tracks[3].model.as_ref().unwrap().period);
+    }
+
+    #[test]
+    fn test_csv() {
+        let rec = record(2);
+        let mut out = vec![];
+        rec.write_csv(&mut out).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        let cols = 11 + MAX_BANDS + 2 * MAX_HARMONICS;
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("frame,status,b0,w0,hz,"));
+        assert!(lines[1].starts_with("0,normal,"));
+        assert!(lines[3].starts_with("2,silence,"));
+        assert!(lines[4].starts_with("3,invalid,"));
+
+        for line in &lines {
+            assert_eq!(line.split(',').count(), cols);
+        }
+    }
+
+    #[test]
+    fn test_npy() {
+        let rec = record(3);
+
+        for &array in Array::all().iter() {
+            let mut out = vec![];
+            rec.write_npy(array, &mut out).unwrap();
+
+            assert_eq!(&out[..8], b"\x93NUMPY\x01\x00");
+
+            let len = out[8] as usize | (out[9] as usize) << 8;
+            assert_eq!((10 + len) % 64, 0);
+            assert_eq!(out[10 + len - 1], b'\n');
+
+            let header = ::std::str::from_utf8(&out[10..10 + len]).unwrap();
+            assert!(header.contains(&format!("'shape': (5, {})", array.columns())));
+            assert_eq!(out.len(), 10 + len + 4 * 5 * array.columns());
+        }
+
+        let mut out = vec![];
+        write_npy(&mut out, 1, 2, [1.0, -2.5].iter().cloned()).unwrap();
+        assert_eq!(&out[128..], &[0, 0, 0x80, 0x3f, 0, 0, 0x20, 0xc0]);
+    }
+
+    #[test]
+    fn test_spectrogram() {
+        let rec = record(6);
+        let spec = rec.spectrogram();
+
+        assert_eq!(spec.segments(), 8);
+
+        // The tone is at the peak once the decoder settles.
+        for s in 2..6 {
+            let f = Spectrogram::bin_freq(spec.peak(s));
+            assert!((f - 1000.0).abs() <= 40.0);
+        }
+
+        let mut text = vec![];
+        spec.write_text(&mut text, 60.0).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 9);
+        assert!(lines[1].starts_with("       0 |"));
+        assert!(lines[3].contains('@'));
+        // The silence frame is blank.
+        assert_eq!(lines[7].trim_matches(|c| c == '|' || c == ' '), "120");
+
+        let mut svg = vec![];
+        spec.write_svg(&mut svg, 60.0).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("fill=\"rgb(255,255,255)\""));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
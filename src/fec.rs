@@ -0,0 +1,413 @@
+//! Error correction decoding of 144-bit coded voice frames.
+//!
+//! Each frame carries the prioritized chunks u<sub>0</sub>, ..., u<sub>7</sub> as code
+//! vectors c<sub>0</sub>, ..., c<sub>7</sub>: the first 4 chunks are protected by a
+//! (23, 12) Golay code, the next 3 by a (15, 11) Hamming code, and the final 7 bits are
+//! sent uncoded. Vectors c<sub>1</sub>, ..., c<sub>6</sub> are additionally modulated
+//! by a pseudo-random sequence seeded from u<sub>0</sub>, so a corrupted u<sub>0</sub>
+//! scrambles the rest of the frame and shows up as a burst of corrected errors.
+//!
+//! Coded frames are handled in deinterleaved form, with c<sub>0</sub>, ..., c<sub>7</sub>
+//! packed in order, most-significant bit first. Over the air, bits are reordered by the
+//! interleave table of TIA-102.BABA, starting with a dibit symbol that carries the MSBs
+//! of c<sub>0</sub> and c<sub>1</sub>, so that consecutive symbols carry bits of
+//! different code vectors. `interleave` and `deinterleave` convert to and from this
+//! order.
+
+use frame::{Chunks, Errors, ReceivedFrame};
+
+/// Number of bytes in a coded frame.
+pub const CODED_BYTES: usize = 18;
+
+/// Coded frame of 144 bits, packed MSB first, either deinterleaved as c<sub>0</sub>,
+/// ..., c<sub>7</sub> or interleaved as sent over the air.
+pub type CodedFrame = [u8; CODED_BYTES];
+
+/// Generator polynomial g(x) = x<sup>11</sup> + x<sup>10</sup> + x<sup>6</sup> +
+/// x<sup>5</sup> + x<sup>4</sup> + x<sup>2</sup> + 1 of the Golay code.
+const GOLAY_GEN: u32 = 0b110001110101;
+
+/// Generator polynomial g(x) = x<sup>4</sup> + x + 1 of the Hamming code.
+const HAMMING_GEN: u32 = 0b10011;
+
+/// Number of bits in each code vector c<sub>i</sub>.
+const CODE_BITS: [usize; 8] = [23, 23, 23, 23, 15, 15, 15, 7];
+
+/// Position in the interleaved frame of each bit of the deinterleaved frame, from the
+/// interleave table of TIA-102.BABA.
+///
+/// The first dibit carries the MSBs of c<sub>0</sub> and c<sub>1</sub>, and every
+/// run of 12 transmitted bits carries at most 2 bits of any one code vector, so a
+/// burst of errors is spread over several code vectors.
+const INTERLEAVE: [usize; CODED_BYTES * 8] = [
+    0, 7, 12, 19, 24, 31, 36, 43, 48, 55, 60, 67,
+    72, 79, 84, 91, 96, 103, 108, 115, 120, 127, 132, 1,
+    13, 6, 25, 18, 37, 30, 49, 42, 61, 54, 73, 66,
+    85, 78, 97, 90, 109, 102, 121, 114, 133, 126, 139, 138,
+    2, 9, 14, 21, 26, 33, 38, 45, 50, 57, 62, 69,
+    74, 81, 86, 93, 98, 105, 110, 117, 122, 129, 134, 141,
+    3, 8, 15, 20, 27, 32, 39, 44, 51, 56, 63, 68,
+    75, 80, 87, 92, 99, 104, 111, 116, 123, 128, 135, 140,
+    4, 11, 16, 23, 28, 35, 40, 47, 52, 59, 64, 71,
+    76, 83, 88, 95, 100, 107, 112, 119, 124, 131, 136, 143,
+    5, 10, 17, 22, 29, 34, 41, 46, 53, 58, 65, 70,
+    77, 82, 89, 94, 101, 106, 113, 118, 125, 130, 137, 142,
+];
+
+/// Compute the remainder of the given n-bit word divided by the given degree-k
+/// generator polynomial.
+fn remainder(word: u32, gen: u32, n: usize, k: usize) -> u32 {
+    (k..n).rev().fold(word, |r, i| {
+        if r >> i & 1 == 1 { r ^ gen << (i - k) } else { r }
+    })
+}
+
+/// Encode the given 12-bit data word into a systematic Golay codeword.
+pub fn golay_encode(data: u32) -> u32 {
+    let word = data << 11;
+    word | remainder(word, GOLAY_GEN, 23, 11)
+}
+
+/// Decode the given 23-bit Golay codeword, returning the 12-bit data word and the
+/// number of corrected bit errors.
+///
+/// The code is perfect, so every word is within 3 bit errors of exactly one codeword.
+pub fn golay_decode(word: u32) -> (u32, usize) {
+    let err = golay_error(remainder(word, GOLAY_GEN, 23, 11));
+    ((word ^ err) >> 11, err.count_ones() as usize)
+}
+
+/// Find the error pattern of weight at most 3 with the given Golay syndrome.
+fn golay_error(syn: u32) -> u32 {
+    // Errors confined to the parity bits are their own syndrome.
+    if syn.count_ones() <= 3 {
+        return syn;
+    }
+
+    // Syndrome of an error in each data bit.
+    let mut data_syn = [0; 12];
+
+    for (i, s) in data_syn.iter_mut().enumerate() {
+        *s = remainder(1 << (i + 11), GOLAY_GEN, 23, 11);
+    }
+
+    // Try patterns with fewer data bit errors first, leaving the rest of the syndrome
+    // to parity bit errors.
+    for i in 0..12 {
+        let s = syn ^ data_syn[i];
+
+        if s.count_ones() <= 2 {
+            return s | 1 << (i + 11);
+        }
+
+        for j in i + 1..12 {
+            let s = s ^ data_syn[j];
+
+            if s.count_ones() <= 1 {
+                return s | 1 << (i + 11) | 1 << (j + 11);
+            }
+
+            for k in j + 1..12 {
+                if s == data_syn[k] {
+                    return 1 << (i + 11) | 1 << (j + 11) | 1 << (k + 11);
+                }
+            }
+        }
+    }
+
+    unreachable!()
+}
+
+/// Encode the given 11-bit data word into a systematic Hamming codeword.
+pub fn hamming_encode(data: u32) -> u32 {
+    let word = data << 4;
+    word | remainder(word, HAMMING_GEN, 15, 4)
+}
+
+/// Decode the given 15-bit Hamming codeword, returning the 11-bit data word and the
+/// number of corrected bit errors.
+///
+/// The code is perfect, so every word is within 1 bit error of exactly one codeword.
+pub fn hamming_decode(word: u32) -> (u32, usize) {
+    let syn = remainder(word, HAMMING_GEN, 15, 4);
+
+    if syn == 0 {
+        return (word >> 4, 0);
+    }
+
+    let bit = (0..15).find(|&i| remainder(1 << i, HAMMING_GEN, 15, 4) == syn).unwrap();
+
+    ((word ^ 1 << bit) >> 4, 1)
+}
+
+/// Generate the modulation vectors m̂<sub>0</sub>, ..., m̂<sub>7</sub> seeded from the
+/// given u<sub>0</sub>.
+///
+/// The sequence p<sub>r</sub>(0) = 16u<sub>0</sub>, p<sub>r</sub>(n) =
+/// 173p<sub>r</sub>(n - 1) + 13849 mod 65536 supplies one bit per code bit of
+/// c<sub>1</sub>, ..., c<sub>6</sub>, taken from the MSB of each term.
+fn modulation(u0: u32) -> [u32; 8] {
+    let mut m = [0; 8];
+    let mut p = 16 * u0;
+
+    for i in 1...6 {
+        for _ in 0..CODE_BITS[i] {
+            p = (173 * p + 13849) % 65536;
+            m[i] = m[i] << 1 | p >> 15;
+        }
+    }
+
+    m
+}
+
+/// Encode the given chunks into a coded frame.
+pub fn encode(chunks: &Chunks) -> CodedFrame {
+    let m = modulation(chunks[0]);
+    let mut frame = [0; CODED_BYTES];
+    let mut pos = 0;
+
+    for i in 0..8 {
+        let c = match i {
+            0...3 => golay_encode(chunks[i]),
+            4...6 => hamming_encode(chunks[i]),
+            _ => chunks[i],
+        };
+
+        xor_bits(&mut frame, pos, CODE_BITS[i], c ^ m[i]);
+        pos += CODE_BITS[i];
+    }
+
+    frame
+}
+
+/// Decode the given coded frame into chunks and corrected error counts.
+pub fn decode(frame: &CodedFrame) -> ReceivedFrame {
+    let mut chunks: Chunks = [0; 8];
+    let mut errors: Errors = [0; 7];
+    let mut pos = 0;
+
+    // Demodulation depends on u_0, so it must be decoded first.
+    let mut m = [0; 8];
+
+    for i in 0..8 {
+        let c = get_bits(frame, pos, CODE_BITS[i]) ^ m[i];
+        pos += CODE_BITS[i];
+
+        let (u, e) = match i {
+            0...3 => golay_decode(c),
+            4...6 => hamming_decode(c),
+            _ => (c, 0),
+        };
+
+        if i == 0 {
+            m = modulation(u);
+        }
+
+        chunks[i] = u;
+
+        if i < 7 {
+            errors[i] = e;
+        }
+    }
+
+    ReceivedFrame::new(chunks, errors)
+}
+
+/// Interleave the given deinterleaved coded frame into the bit order sent over the air.
+pub fn interleave(frame: &CodedFrame) -> CodedFrame {
+    let mut air = [0; CODED_BYTES];
+
+    for (b, &pos) in INTERLEAVE.iter().enumerate() {
+        xor_bits(&mut air, pos, 1, get_bits(frame, b, 1));
+    }
+
+    air
+}
+
+/// Deinterleave the given coded frame received over the air into code vectors
+/// c<sub>0</sub>, ..., c<sub>7</sub>.
+pub fn deinterleave(air: &CodedFrame) -> CodedFrame {
+    let mut frame = [0; CODED_BYTES];
+
+    for (b, &pos) in INTERLEAVE.iter().enumerate() {
+        xor_bits(&mut frame, b, 1, get_bits(air, pos, 1));
+    }
+
+    frame
+}
+
+/// Read the given number of bits starting at the given bit position, MSB first.
+fn get_bits(frame: &CodedFrame, pos: usize, len: usize) -> u32 {
+    (pos..pos + len).fold(0, |w, b| w << 1 | (frame[b / 8] >> (7 - b % 8) & 1) as u32)
+}
+
+/// Toggle frame bits starting at the given bit position with the low bits of the given
+/// word, MSB first.
+fn xor_bits(frame: &mut CodedFrame, pos: usize, len: usize, word: u32) {
+    for (i, b) in (pos..pos + len).enumerate() {
+        frame[b / 8] ^= ((word >> (len - 1 - i) & 1) as u8) << (7 - b % 8);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use frame::TEST_CHUNKS;
+    use rng::{Random, XorShift};
+
+    /// Generate random chunks with the correct number of bits in each.
+    fn chunks(r: &mut XorShift) -> Chunks {
+        let mut chunks = [0; 8];
+
+        for (c, &bits) in chunks.iter_mut().zip([12, 12, 12, 12, 11, 11, 11, 7].iter()) {
+            *c = r.next_u32() & ((1 << bits) - 1);
+        }
+
+        chunks
+    }
+
+    /// Generate a random word of the given number of bits with the given weight.
+    fn errors(r: &mut XorShift, bits: usize, weight: usize) -> u32 {
+        let mut e = 0u32;
+
+        while e.count_ones() < weight as u32 {
+            e |= 1 << (r.next_u32() as usize % bits);
+        }
+
+        e
+    }
+
+    #[test]
+    fn test_golay() {
+        let mut r = XorShift::new([1, 2, 3, 4]);
+
+        for data in 0..1 << 12 {
+            let word = golay_encode(data);
+            assert_eq!(word >> 11, data);
+            assert_eq!(golay_decode(word), (data, 0));
+
+            let w = r.next_u32() as usize % 4;
+            assert_eq!(golay_decode(word ^ errors(&mut r, 23, w)), (data, w));
+        }
+    }
+
+    #[test]
+    fn test_hamming() {
+        for data in 0..1 << 11 {
+            let word = hamming_encode(data);
+            assert_eq!(word >> 4, data);
+            assert_eq!(hamming_decode(word), (data, 0));
+
+            for b in 0..15 {
+                assert_eq!(hamming_decode(word ^ 1 << b), (data, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_modulation() {
+        let m = modulation(0);
+
+        assert_eq!(m[0], 0);
+        assert_eq!(m[7], 0);
+
+        for i in 1...6 {
+            assert_eq!(m[i] >> CODE_BITS[i], 0);
+        }
+
+        // p_r(1) = 13849 and p_r(2) = 173 * 13849 + 13849 mod 65536 = 38378.
+        assert_eq!(m[1] >> 21, 0b01);
+        assert!(modulation(1) != m);
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut r = XorShift::new([5, 6, 7, 8]);
+
+        for _ in 0..500 {
+            let chunks = chunks(&mut r);
+            let mut frame = encode(&chunks);
+
+            let decoded = decode(&frame);
+            assert_eq!(decoded.chunks, chunks);
+            assert_eq!(decoded.errors, [0; 7]);
+
+            // Flip correctable numbers of bits in each code vector.
+            let mut pos = 0;
+            let mut expected = [0; 7];
+
+            for i in 0..7 {
+                let w = r.next_u32() as usize % if i < 4 { 4 } else { 2 };
+                let e = errors(&mut r, CODE_BITS[i], w);
+
+                xor_bits(&mut frame, pos, CODE_BITS[i], e);
+
+                expected[i] = w;
+                pos += CODE_BITS[i];
+            }
+
+            let decoded = decode(&frame);
+            assert_eq!(decoded.chunks, chunks);
+            assert_eq!(decoded.errors, expected);
+        }
+    }
+
+    #[test]
+    fn test_interleave() {
+        let mut seen = [false; CODED_BYTES * 8];
+
+        for &pos in INTERLEAVE.iter() {
+            assert!(!seen[pos]);
+            seen[pos] = true;
+        }
+
+        // The first dibit carries the MSBs of c_0 and c_1, and the third carries c_4.
+        assert_eq!(INTERLEAVE[0], 0);
+        assert_eq!(INTERLEAVE[23], 1);
+        assert_eq!(INTERLEAVE[23 * 4 + 4], 4);
+
+        let mut msb = [0; CODED_BYTES];
+        xor_bits(&mut msb, 23, 1, 1);
+        assert_eq!(interleave(&msb)[..2], [0b01000000, 0][..]);
+
+        let mut r = XorShift::new([9, 10, 11, 12]);
+
+        for _ in 0..100 {
+            let frame = encode(&chunks(&mut r));
+            let air = interleave(&frame);
+
+            assert_eq!(deinterleave(&air), frame);
+        }
+    }
+
+    #[test]
+    fn test_interleave_vector() {
+        let air = [
+            0x22, 0x0c, 0x6a, 0x56, 0x60, 0x7e, 0x76, 0x5c, 0xeb,
+            0x05, 0x10, 0x7f, 0x66, 0x9b, 0xbd, 0x0e, 0xc0, 0x35,
+        ];
+
+        assert_eq!(interleave(&encode(&TEST_CHUNKS)), air);
+        assert_eq!(decode(&deinterleave(&air)).chunks, TEST_CHUNKS);
+    }
+
+    #[test]
+    fn test_burst() {
+        let chunks = chunks(&mut XorShift::new([13, 14, 15, 16]));
+        let frame = encode(&chunks);
+
+        // An 8-bit burst over the air lands in 6 different code vectors.
+        let mut air = interleave(&frame);
+        xor_bits(&mut air, 0, 8, 0xff);
+
+        let decoded = decode(&deinterleave(&air));
+        assert_eq!(decoded.chunks, chunks);
+        assert_eq!(decoded.errors, [2, 2, 1, 1, 1, 1, 0]);
+
+        // The same burst without interleaving exceeds what c_0 can correct.
+        let mut burst = frame;
+        xor_bits(&mut burst, 0, 8, 0xff);
+
+        assert!(decode(&burst).chunks != chunks);
+    }
+}
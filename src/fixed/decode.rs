@@ -0,0 +1,331 @@
+//! Decode IMBE frames into 16-bit samples with fixed-point arithmetic.
+
+use consts::SAMPLES_PER_FRAME;
+use descramble::{descramble, Bootstrap};
+use fixed::model::{self, FixedEnergy, FixedErrors, FixedParams, LogSpectrals};
+use fixed::synth::SynthFrame;
+use frame::ReceivedFrame;
+use params::BaseParams;
+use rng::{Random, XorShift};
+use stats::FrameStatus;
+
+/// Parameters saved from the previous frame.
+struct FixedPrev {
+    /// Synthesis state.
+    frame: SynthFrame,
+    /// Spectral amplitudes used for prediction.
+    spectrals: LogSpectrals,
+    /// Error rate ϵ<sub>R</sub> in Q28.
+    err_rate: u32,
+    /// Energy tracker S<sub>E</sub> in Q8.
+    tracking: i64,
+    /// Spectral amplitude threshold τ<sub>M</sub>.
+    amp_thresh: i32,
+}
+
+impl Default for FixedPrev {
+    /// Create a new `FixedPrev` suitable for decoding the first frame in a stream.
+    fn default() -> FixedPrev {
+        FixedPrev {
+            frame: SynthFrame::default(),
+            spectrals: LogSpectrals::default(),
+            err_rate: 0,
+            tracking: FixedEnergy::default().tracking,
+            amp_thresh: 0,
+        }
+    }
+}
+
+/// Decodes a stream of IMBE frames using only integer arithmetic in the per-frame and
+/// per-sample computations.
+pub struct FixedDecoder<R: Random = XorShift> {
+    /// Tracks saved parameters across frames.
+    prev: FixedPrev,
+    /// Generates random phases and noise spectra.
+    rng: R,
+}
+
+#[cfg(feature = "std")]
+impl FixedDecoder {
+    /// Create a new `FixedDecoder` in the default state, seeded from the system.
+    pub fn new() -> FixedDecoder {
+        FixedDecoder::with_rng(XorShift::seeded())
+    }
+}
+
+impl<R: Random> FixedDecoder<R> {
+    /// Create a new `FixedDecoder` in the default state with the given random number
+    /// generator.
+    pub fn with_rng(rng: R) -> FixedDecoder<R> {
+        FixedDecoder {
+            prev: FixedPrev::default(),
+            rng: rng,
+        }
+    }
+
+    /// Decode the given frame into the given buffer of samples at the native 8kHz rate
+    /// and the standard's scale, returning how the frame was handled.
+    pub fn decode(&mut self, frame: ReceivedFrame, out: &mut [i16; SAMPLES_PER_FRAME])
+        -> FrameStatus
+    {
+        let period = match Bootstrap::new(&frame.chunks) {
+            Bootstrap::Period(p) => p,
+            Bootstrap::Invalid => {
+                // Repeat previous frame on invalid period [p46].
+                self.repeat(out);
+                return FrameStatus::Invalid;
+            },
+            Bootstrap::Silence => {
+                silence(out);
+                return FrameStatus::Silence;
+            },
+        };
+
+        let errors = FixedErrors::new(&frame.errors, self.prev.err_rate);
+
+        if errors.should_repeat() {
+            self.repeat(out);
+            return FrameStatus::Repeat;
+        }
+
+        if errors.should_mute() {
+            silence(out);
+            return FrameStatus::Mute;
+        }
+
+        let params = FixedParams::new(period);
+        let base = BaseParams::new(period);
+        let (amps, mut voice, gain_idx) = descramble(&frame.chunks, &base);
+        let gains = model::gains(gain_idx, &amps, &params);
+        let coefs = model::coefficients(&gains, &amps, &params);
+        let spectrals = LogSpectrals::new(&coefs, &params, self.prev.frame.params.harmonics,
+                                          &self.prev.spectrals);
+        let energy = FixedEnergy::new(&spectrals, self.prev.tracking, &params);
+
+        let enhanced = model::enhance(&spectrals, &energy, &params);
+        let amp_thresh = model::amp_thresh(&errors, self.prev.amp_thresh);
+        let amps = model::smooth(&enhanced, &mut voice, &errors, &energy, amp_thresh);
+
+        let cur = SynthFrame::new(params, voice, amps, &self.prev.frame, &mut self.rng);
+        cur.synthesize(&self.prev.frame, out);
+
+        // Save current parameters.
+        self.prev = FixedPrev {
+            frame: cur,
+            spectrals: spectrals,
+            err_rate: errors.rate,
+            tracking: energy.tracking,
+            amp_thresh: amp_thresh,
+        };
+
+        FrameStatus::Normal
+    }
+
+    /// Repeat the previous frame into the given buffer.
+    fn repeat(&mut self, out: &mut [i16; SAMPLES_PER_FRAME]) {
+        let prev = &self.prev.frame;
+
+        // Apply Eqs 99 through 104.
+        let cur = SynthFrame::new(prev.params, prev.voice, prev.amps.clone(), prev,
+                                  &mut self.rng);
+
+        // Repeat frame using previous parameters [p47].
+        cur.synthesize(prev, out);
+    }
+}
+
+/// Fill the given buffer with silence.
+fn silence(out: &mut [i16; SAMPLES_PER_FRAME]) {
+    for x in out.iter_mut() {
+        *x = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use decode::ImbeDecoder;
+    use frame::TEST_CHUNKS;
+    use rng::XorShift;
+
+    /// Generate a corpus of random frames with every band either voiced or unvoiced.
+    fn corpus(count: usize, voiced: bool) -> Vec<[u32; 8]> {
+        let mut r = XorShift::new([5, 6, 7, 8]);
+
+        (0..count).map(|_| {
+            let mut chunks = [0; 8];
+
+            for (i, c) in chunks.iter_mut().enumerate() {
+                let bits = match i { 0...3 => 12, 4...6 => 11, _ => 7 };
+                *c = r.next_u32() & ((1 << bits) - 1);
+            }
+
+            // Keep b_0 in the valid range.
+            chunks[0] &= 0b101111111111;
+
+            // Set the first 12 bits of u_4 and u_5, which hold b_1 [p39].
+            if voiced {
+                chunks[4] = 0b11111111111;
+                chunks[5] |= 0b10000000000;
+            } else {
+                chunks[4] = 0;
+                chunks[5] &= 0b01111111111;
+            }
+
+            chunks
+        }).collect()
+    }
+
+    /// Check if every harmonic in the given frame is voiced.
+    fn all_voiced(chunks: &[u32; 8]) -> bool {
+        let params = BaseParams::new(Bootstrap::new(chunks).unwrap_period());
+        descramble(chunks, &params).1.unvoiced_count() == 0
+    }
+
+    #[test]
+    fn test_voiced() {
+        let mut float = ImbeDecoder::with_rng(Default::default(), XorShift::default());
+        let mut fixed = FixedDecoder::with_rng(XorShift::default());
+
+        let mut a = [0; SAMPLES_PER_FRAME];
+        let mut b = [0; SAMPLES_PER_FRAME];
+
+        let mut prev_voiced = false;
+        let mut dev = 0;
+        let mut peak = 0;
+
+        // Float phases lose precision as they accumulate, so only compare a short
+        // stream.
+        for chunks in corpus(100, true) {
+            let s = float.decode_i16(ReceivedFrame::new(chunks, [0; 7]), &mut a);
+            let t = fixed.decode(ReceivedFrame::new(chunks, [0; 7]), &mut b);
+            assert_eq!(s, t);
+
+            // Frames with more than 36 harmonics can still have unvoiced harmonics, and
+            // synthesis is only deterministic when both overlapping frames are fully
+            // voiced.
+            let voiced = all_voiced(&chunks);
+
+            if voiced && prev_voiced {
+                for (&x, &y) in a.iter().zip(b.iter()) {
+                    dev = std::cmp::max(dev, (x as i32 - y as i32).abs());
+                    peak = std::cmp::max(peak, (x as i32).abs());
+                }
+            }
+
+            prev_voiced = voiced;
+        }
+
+        assert!(peak > 10000);
+        assert!(dev <= 16);
+    }
+
+    #[test]
+    fn test_unvoiced() {
+        let mut float = ImbeDecoder::with_rng(Default::default(), XorShift::default());
+        let mut fixed = FixedDecoder::with_rng(XorShift::default());
+
+        let mut a = [0; SAMPLES_PER_FRAME];
+        let mut b = [0; SAMPLES_PER_FRAME];
+
+        let power = |x: &[i16]| x.iter().fold(0.0, |s, &x| s + (x as f64).powi(2));
+        let mut sum = 0.0;
+        let mut count = 0;
+
+        for chunks in corpus(500, false) {
+            float.decode_i16(ReceivedFrame::new(chunks, [0; 7]), &mut a);
+            fixed.decode(ReceivedFrame::new(chunks, [0; 7]), &mut b);
+
+            let (pa, pb) = (power(&a), power(&b));
+
+            if pa > 0.0 && pb > 0.0 {
+                sum += (pb / pa).log2();
+                count += 1;
+            }
+        }
+
+        // Noise differs between decoders, so compare the average power ratio of each
+        // frame.
+        assert!(count > 400);
+        assert!((sum / count as f64).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_status() {
+        let mut float = ImbeDecoder::with_rng(Default::default(), XorShift::default());
+        let mut fixed = FixedDecoder::with_rng(XorShift::default());
+
+        let mut a = [0; SAMPLES_PER_FRAME];
+        let mut b = [0; SAMPLES_PER_FRAME];
+
+        let chunks = corpus(1, true)[0];
+        let silence = [0b110110000000, 0, 0, 0, 0, 0, 0, 0];
+        let invalid = [0b111111000000, 0, 0, 0, 0, 0, 0, 0];
+
+        let mut frames = vec![
+            (chunks, [0; 7], FrameStatus::Normal),
+            (chunks, [3, 3, 3, 3, 1, 0, 0], FrameStatus::Repeat),
+            (silence, [0; 7], FrameStatus::Silence),
+            (invalid, [0; 7], FrameStatus::Invalid),
+        ];
+
+        // Build up the error rate until frames are muted.
+        for _ in 0..60 {
+            frames.push((chunks, [1, 3, 3, 3, 1, 1, 1], FrameStatus::Normal));
+        }
+
+        frames.push((chunks, [1, 3, 3, 3, 1, 1, 1], FrameStatus::Mute));
+
+        let statuses = frames.iter().map(|&(chunks, errors, _)| {
+            let s = float.decode_i16(ReceivedFrame::new(chunks, errors), &mut a);
+            let t = fixed.decode(ReceivedFrame::new(chunks, errors), &mut b);
+            assert_eq!(s, t);
+            t
+        }).collect::<Vec<_>>();
+
+        for (s, &(_, _, expected)) in statuses.iter().zip(frames.iter()).take(4) {
+            assert_eq!(*s, expected);
+        }
+
+        assert_eq!(*statuses.last().unwrap(), FrameStatus::Mute);
+        assert!(b.iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn test_loud() {
+        let mut float = ImbeDecoder::with_rng(Default::default(), XorShift::default());
+        let mut fixed = FixedDecoder::with_rng(XorShift::default());
+
+        let mut a = [0; SAMPLES_PER_FRAME];
+        let mut b = [0; SAMPLES_PER_FRAME];
+
+        // Gains in this frame drive the amplitudes toward their upper bound, and the
+        // steady errors raise τ_M each frame until the stream is muted.
+        let loud = [1215, 1775, 3657, 1328, 724, 1576, 663, 7];
+
+        let mut frames = (0..60).map(|_| (loud, [1, 3, 3, 3, 1, 1, 1]))
+            .collect::<Vec<_>>();
+        frames.extend((0..20).map(|_| (TEST_CHUNKS, [0; 7])));
+
+        let power = |x: &[i16]| x.iter().fold(0.0, |s, &x| s + (x as f64).powi(2));
+        let mut sum = 0.0;
+        let mut count = 0;
+
+        for &(chunks, errors) in frames.iter() {
+            let s = float.decode_i16(ReceivedFrame::new(chunks, errors), &mut a);
+            let t = fixed.decode(ReceivedFrame::new(chunks, errors), &mut b);
+            assert_eq!(s, t);
+
+            let (pa, pb) = (power(&a), power(&b));
+
+            if pa > 0.0 && pb > 0.0 {
+                sum += (pb / pa).log2();
+                count += 1;
+            }
+        }
+
+        assert_eq!(count, 69);
+        assert!((sum / count as f64).abs() < 0.05);
+    }
+}
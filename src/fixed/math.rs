@@ -0,0 +1,172 @@
+//! Table-driven trigonometric, logarithm, and exponential functions.
+//!
+//! Phases are represented as `u32` fractions of a full turn, so 2<sup>32</sup>
+//! corresponds to 2π and phase accumulation wraps around naturally. Trigonometric
+//! results are in Q15, and logarithms are base 2 in Q16.
+
+use std;
+
+use fixed::tables::{COS, LOG2, EXP2};
+
+/// Number of fractional bits in logarithms.
+pub const LOG_BITS: u32 = 16;
+
+/// Logarithm used for an input of zero, which is below that of any representable value
+/// but leaves headroom for sums of several logarithms.
+pub const LOG_ZERO: i32 = -(128 << LOG_BITS);
+
+/// Linearly interpolate between the given values with the given 16-bit fraction.
+fn interp(a: i64, b: i64, frac: u32) -> i64 {
+    a + ((b - a) * frac as i64 >> 16)
+}
+
+/// Compute cos(θ) in Q15 for the given phase θ in turns.
+pub fn cos(phase: u32) -> i32 {
+    // Split the phase into quadrant, table index, and interpolation fraction.
+    let idx = (phase >> 22 & 0xff) as usize;
+    let frac = phase >> 6 & 0xffff;
+
+    // Evaluate the quarter wave forwards for cos and backwards for sin.
+    let fwd = || interp(COS[idx] as i64, COS[idx + 1] as i64, frac) as i32;
+    let rev = || interp(COS[256 - idx] as i64, COS[255 - idx] as i64, frac) as i32;
+
+    match phase >> 30 {
+        0 => fwd(),
+        1 => -rev(),
+        2 => -fwd(),
+        _ => rev(),
+    }
+}
+
+/// Compute sin(θ) in Q15 for the given phase θ in turns.
+pub fn sin(phase: u32) -> i32 {
+    cos(phase.wrapping_sub(1 << 30))
+}
+
+/// Compute log<sub>2</sub>(x) in Q16 for the given x with the given number of fractional
+/// bits.
+pub fn log2(x: u64, frac_bits: u32) -> i32 {
+    if x == 0 {
+        return LOG_ZERO;
+    }
+
+    // Position of the MSB gives the integer part.
+    let exp = 63 - x.leading_zeros();
+    // Normalize so the MSB is at bit 63, leaving the mantissa in the bits below it.
+    let norm = x << (63 - exp);
+
+    let idx = (norm >> 55 & 0xff) as usize;
+    let frac = (norm >> 39 & 0xffff) as u32;
+
+    ((exp as i32 - frac_bits as i32) << LOG_BITS) +
+        interp(LOG2[idx] as i64, LOG2[idx + 1] as i64, frac) as i32
+}
+
+/// Compute 2<sup>x</sup> for the given x in Q16, returning a value with the given number
+/// of fractional bits and saturating on overflow.
+pub fn exp2(x: i32, frac_bits: u32) -> i64 {
+    let int = x >> LOG_BITS;
+    let idx = (x >> 8 & 0xff) as usize;
+    let frac = (x as u32 & 0xff) << 8;
+
+    // Compute 2^fract(x) in Q30.
+    let mant = interp(EXP2[idx] as i64, EXP2[idx + 1] as i64, frac);
+    let shift = int + frac_bits as i32 - 30;
+
+    if shift >= 32 {
+        std::i64::MAX
+    } else if shift >= 0 {
+        mant << shift
+    } else if shift > -63 {
+        // Round to nearest.
+        (mant + (1 << (-shift - 1))) >> -shift
+    } else {
+        0
+    }
+}
+
+/// Compute the integer square root floor(√x).
+pub fn isqrt(x: u64) -> u64 {
+    let mut rem = x;
+    let mut root = 0;
+    let mut bit = 1 << 62;
+
+    while bit > x {
+        bit >>= 2;
+    }
+
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+
+        bit >>= 2;
+    }
+
+    root
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_trig() {
+        for i in 0..4096u64 {
+            let phase = (i << 20 | i * 7919 & 0xfffff) as u32;
+            let x = phase as f64 / 4294967296.0 * 2.0 * std::f64::consts::PI;
+
+            assert!((cos(phase) as f64 / 32768.0 - x.cos()).abs() < 1e-4);
+            assert!((sin(phase) as f64 / 32768.0 - x.sin()).abs() < 1e-4);
+        }
+
+        assert_eq!(cos(0), 32768);
+        assert_eq!(cos(1 << 31), -32768);
+        assert_eq!(sin(1 << 30), 32768);
+        assert_eq!(cos(1 << 30), 0);
+    }
+
+    #[test]
+    fn test_log2() {
+        assert_eq!(log2(1, 0), 0);
+        assert_eq!(log2(1 << 20, 16), 4 << 16);
+        assert_eq!(log2(0, 0), LOG_ZERO);
+
+        for &x in &[3u64, 17, 1000, 123456789, 1 << 40, 987654321987] {
+            let expected = (x as f64 / 256.0).log2();
+            assert!((log2(x, 8) as f64 / 65536.0 - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_exp2() {
+        assert_eq!(exp2(0, 12), 1 << 12);
+        assert_eq!(exp2(3 << 16, 0), 8);
+        assert_eq!(exp2(-(40 << 16), 0), 0);
+        assert_eq!(exp2(40 << 16, 30), std::i64::MAX);
+
+        for &x in &[-10.3f64, -1.5, 0.001, 0.5, 3.75, 16.9] {
+            let got = exp2((x * 65536.0) as i32, 40) as f64 / 1099511627776.0;
+            assert!((got / x.exp2() - 1.0).abs() < 1e-4);
+        }
+
+        // Verify round trip through log2.
+        for &x in &[5u64, 1234, 99999, 1 << 33] {
+            let y = exp2(log2(x, 0), 0) as f64;
+            assert!((y / x as f64 - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_isqrt() {
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(1), 1);
+        assert_eq!(isqrt(15), 3);
+        assert_eq!(isqrt(16), 4);
+        assert_eq!(isqrt(1 << 62), 1 << 31);
+        assert_eq!(isqrt(std::u64::MAX), 4294967295);
+    }
+}
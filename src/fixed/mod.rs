@@ -0,0 +1,22 @@
+//! Fixed-point decoding for targets without a floating-point unit.
+//!
+//! This decoder follows the same steps as `ImbeDecoder` but computes the spectral
+//! amplitude model, enhancement, and synthesis with integer arithmetic, using
+//! table-driven trigonometric, logarithm, and exponential functions. Phases are
+//! fractions of a turn in `u32`, logarithms are base 2 in Q16, and linear spectral
+//! amplitudes are in Q12.
+//!
+//! Descrambling the frame bits still goes through `BaseParams`, which does a handful of
+//! floating-point operations once per frame, but nothing in the per-harmonic or
+//! per-sample paths does. Output is always at the native 8kHz rate and the standard's
+//! 16-bit scale, so resampling, time stretching, voice transforms, and post-filtering
+//! aren't available.
+
+pub mod math;
+pub mod model;
+pub mod synth;
+pub mod tables;
+
+mod decode;
+
+pub use self::decode::FixedDecoder;
@@ -0,0 +1,576 @@
+//! Fixed-point decoding of frame parameters, from quantized values through spectral
+//! amplitude enhancement and adaptive smoothing.
+//!
+//! Spectral amplitudes are predicted and enhanced in the log domain, as
+//! log<sub>2</sub> M<sub>l</sub> in Q16, so the powers and roots of Eqs 107 through 116
+//! become additions and shifts. Frame energies are in Q8 and error rates in Q28.
+
+use std;
+use std::cmp::{max, min};
+
+use arrayvec::ArrayVec;
+
+use allocs::allocs;
+use coefs::AMPS_USED;
+use consts::{MIN_HARMONICS, MAX_HARMONICS};
+use descramble::{QuantizedAmplitudes, VoiceDecisions};
+use fixed::math::{self, LOG_BITS};
+use fixed::tables::{GAIN, STEPS, DCT_STEP_SIZE, DCT_STD_DEV};
+use frame::Errors;
+
+/// Number of fractional bits in frame energies.
+pub const ENERGY_BITS: u32 = 8;
+/// Number of fractional bits in error rates.
+pub const RATE_BITS: u32 = 28;
+/// Number of fractional bits in linear spectral amplitudes.
+pub const AMP_BITS: u32 = 12;
+
+/// log<sub>2</sub>(2π) in Q16.
+const LOG_TWO_PI: i32 = 173768;
+/// log<sub>2</sub>(0.96π) in Q16, from Eq 107.
+const LOG_ENHANCE: i32 = 104373;
+/// log<sub>2</sub>(1.2) in Q16, the upper weight limit in Eq 108.
+const LOG_MAX_WEIGHT: i32 = 17238;
+/// log<sub>2</sub>(45.255) in Q16, from Eq 112.
+const LOG_THRESH_CLEAN: i32 = 360448;
+/// log<sub>2</sub>(1.414) in Q16, from Eq 112.
+const LOG_THRESH_NOISY: i32 = 32754;
+/// 277.26 log<sub>2</sub>(e) in Q16, converting the exponential of Eq 112 to base 2.
+const RATE_EXP: i64 = 26214507;
+
+/// Basic parameters of the current frame in integer form.
+#[derive(Copy, Clone)]
+pub struct FixedParams {
+    /// Fundamental frequency ω<sub>0</sub> as the phase step per sample, in turns.
+    pub step: u32,
+    /// Number of harmonics L.
+    pub harmonics: u32,
+    /// Number of frequency bands K.
+    pub bands: u32,
+}
+
+impl FixedParams {
+    /// Create a new `FixedParams` from the given period b<sub>0</sub>.
+    pub fn new(period: u8) -> FixedParams {
+        let denom = 2 * period as u64 + 79;
+        // Compute Eq 46 as ω_0 / 2π = 4 / (2b_0 + 79).
+        let step = ((1 << 34) + denom / 2) / denom;
+        // Compute Eq 47, where floor(π / ω_0 + 0.25) = floor((2b_0 + 81) / 8).
+        let h = 9254 * ((2 * period as u32 + 81) / 8) / 10000;
+
+        FixedParams {
+            step: step as u32,
+            harmonics: h,
+            // Compute Eq 48.
+            bands: min((h + 2) / 3, 12),
+        }
+    }
+}
+
+impl Default for FixedParams {
+    /// Create a new `FixedParams` with initial default values.
+    fn default() -> FixedParams {
+        // Taken from [p64], where ω_0 = 0.02985π.
+        FixedParams {
+            step: 64102387,
+            harmonics: 30,
+            bands: 10,
+        }
+    }
+}
+
+/// Values derived from error correction decoding.
+pub struct FixedErrors {
+    /// Total number of errors corrected in the current frame, ϵ<sub>T</sub>.
+    pub total: usize,
+    /// Error rate tracking term, ϵ<sub>R</sub>, in Q28.
+    pub rate: u32,
+    /// Errors corrected in the first Golay-coded chunk, ϵ<sub>0</sub>.
+    pub golay_init: usize,
+    /// Errors corrected in the first Hamming-coded chunk, ϵ<sub>4</sub>.
+    pub hamming_init: usize,
+}
+
+impl FixedErrors {
+    /// Create a new `FixedErrors` from the errors corrected in the current frame and the
+    /// previous frame's ϵ<sub>R</sub> value.
+    pub fn new(errors: &Errors, prev_rate: u32) -> FixedErrors {
+        let total = errors.iter().fold(0, |s: usize, &e| s.saturating_add(e));
+
+        // Compute Eq 96.
+        let rate = prev_rate as u64 * 19 / 20 +
+            (min(total, 1 << 20) as u64 * 365 << RATE_BITS) / 1000000;
+
+        FixedErrors {
+            total: total,
+            rate: min(rate, std::u32::MAX as u64) as u32,
+            golay_init: errors[0],
+            hamming_init: errors[4],
+        }
+    }
+
+    /// Check if ϵ<sub>R</sub> is at most the given rate in parts per 10000.
+    fn rate_within(&self, rate: u64) -> bool {
+        self.rate as u64 * 10000 <= rate << RATE_BITS
+    }
+
+    /// Check whether the current frame should be repeated, according to Eqs 97 and 98.
+    pub fn should_repeat(&self) -> bool {
+        self.golay_init >= 2 &&
+            (min(self.total, 1 << 20) as u64) << RATE_BITS >=
+                (10 << RATE_BITS) + 40 * self.rate as u64
+    }
+
+    /// Check whether the current frame should be muted [p47].
+    pub fn should_mute(&self) -> bool {
+        !self.rate_within(875)
+    }
+}
+
+/// Compute the gain vector G<sub>m</sub> and return its inverse DCT R<sub>i</sub>, 1 ≤ i
+/// ≤ 6, in Q16.
+pub fn gains(gain_idx: usize, amps: &QuantizedAmplitudes, params: &FixedParams)
+    -> [i32; 6]
+{
+    let mut gains = [0; 6];
+
+    let (alloc, _) = allocs(params.harmonics);
+    let steps = &STEPS[params.harmonics as usize - MIN_HARMONICS];
+
+    gains[0] = GAIN[gain_idx] as i64;
+
+    // Compute G_2, ..., G_6.
+    for m in 3...7 {
+        gains[m - 2] = quantized(steps[m - 3] as i64, amps.get(m), alloc[m - 3], 24);
+    }
+
+    let mut idct = [0; 6];
+
+    // Compute R_i with phases (m - 1)(i - 0.5) / 12 turns.
+    for (i, r) in idct.iter_mut().enumerate() {
+        *r = (gains[0] + 2 * (2...6).map(|m| {
+            gains[m - 1] * math::cos(turns((m - 1) * (2 * i + 1), 24)) as i64 >> 15
+        }).fold(0, |s, x| s + x)) as i32;
+    }
+
+    idct
+}
+
+/// Compute the higher order DCT coefficients T<sub>l</sub>, 1 ≤ l ≤ L, in Q16 from the
+/// given gain IDCT values R<sub>i</sub> and quantized amplitudes.
+pub fn coefficients(idct: &[i32; 6], amps: &QuantizedAmplitudes, params: &FixedParams)
+    -> ArrayVec<[i32; MAX_HARMONICS]>
+{
+    let mut coefs = ArrayVec::new();
+
+    let (alloc, _) = allocs(params.harmonics);
+    let blocks = &AMPS_USED[params.harmonics as usize - MIN_HARMONICS];
+
+    // First quantized amplitude in the current block [p34].
+    let mut cur = 8;
+
+    for block in 1...6 {
+        let mut c = ArrayVec::<[i64; 10]>::new();
+
+        // C_i,1 = R_i.
+        c.push(idct[block - 1] as i64);
+
+        // Generate C_i,2, ..., C_i,Ji.
+        c.extend((cur..cur + blocks[block - 1]).enumerate().map(|(k, m)| {
+            let bits = alloc[m - 3];
+
+            if bits == 0 {
+                0
+            } else {
+                let step = DCT_STEP_SIZE[bits as usize - 1] as i64 *
+                    DCT_STD_DEV[k] as i64;
+
+                quantized(step, amps.get(m), bits, 32)
+            }
+        }));
+
+        let len = c.len();
+
+        // Compute c_i,j with phases (k - 1)(j - 0.5) / 2J_i turns.
+        coefs.extend((1...len).map(|j| {
+            (c[0] + 2 * (2...len).map(|k| {
+                c[k - 1] * math::cos(turns((k - 1) * (2 * j - 1), 4 * len)) as i64 >> 15
+            }).fold(0, |s, x| s + x)) as i32
+        }));
+
+        cur += len - 1;
+    }
+
+    coefs
+}
+
+/// Compute the dequantized value Δ(b - 2<sup>B-1</sup> + 0.5) in Q16 from the given step
+/// size Δ with the given number of fractional bits, quantized value b, and bit allocation
+/// B.
+fn quantized(step: i64, b: u32, bits: u8, step_bits: u32) -> i64 {
+    if bits == 0 {
+        return 0;
+    }
+
+    let shift = step_bits - LOG_BITS + 1;
+    let x = step * (2 * b as i64 - (1 << bits) + 1);
+
+    (x + (1 << (shift - 1))) >> shift
+}
+
+/// Compute the phase of the given fraction of a full turn.
+fn turns(numer: usize, denom: usize) -> u32 {
+    (((numer as u64) << 32) / denom as u64) as u32
+}
+
+/// Spectral amplitudes stored as log<sub>2</sub> M<sub>l</sub>, 1 ≤ l ≤ L, in Q16.
+#[derive(Clone)]
+pub struct LogSpectrals(ArrayVec<[i32; MAX_HARMONICS]>);
+
+impl LogSpectrals {
+    /// Create a new `LogSpectrals` from the given DCT coefficients T<sub>l</sub> and the
+    /// current parameters, predicting from the given previous amplitudes with the given
+    /// number of harmonics.
+    pub fn new(coefs: &[i32], params: &FixedParams, prev_harmonics: u32,
+               prev: &LogSpectrals)
+        -> LogSpectrals
+    {
+        // Compute (k_l, δ_l) for the given harmonic l [p35], with δ_l in Q16.
+        let indexes = |l: u32| {
+            let k = prev_harmonics * l;
+            ((k / params.harmonics) as usize,
+             ((k % params.harmonics) << 16) as i64 / params.harmonics as i64)
+        };
+
+        // Compute the interpolated previous amplitude.
+        let interp = |l: u32| {
+            let (k, dec) = indexes(l);
+            ((1 << 16) - dec) * prev.get(k) as i64 + dec * prev.get(k + 1) as i64 >> 16
+        };
+
+        // Compute prediction coefficient ρ [p27] in Q16.
+        let pred = max(min((3 * params.harmonics as i64 - 5) * 65536 / 100, 45875), 26214);
+
+        // Compute the sum term.
+        let sum = (1...params.harmonics).map(&interp).fold(0, |s, x| s + x) /
+            params.harmonics as i64;
+
+        // Compute log_2 M_l for each harmonic l.
+        LogSpectrals((1...params.harmonics).map(|l| {
+            coefs[l as usize - 1] + (pred * (interp(l) - sum) >> 16) as i32
+        }).collect())
+    }
+
+    /// Retrieve log<sub>2</sub> M<sub>l</sub> for the given l.
+    pub fn get(&self, l: usize) -> i32 {
+        if l == 0 {
+            // Handles case of Eq 78.
+            0
+        } else if l > self.0.len() {
+            // Handle case of Eq 79.
+            *self.0.last().unwrap()
+        } else {
+            self.0[l - 1]
+        }
+    }
+}
+
+impl std::ops::Deref for LogSpectrals {
+    type Target = ArrayVec<[i32; MAX_HARMONICS]>;
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
+
+impl Default for LogSpectrals {
+    /// Construct the default set of spectral amplitudes.
+    fn default() -> LogSpectrals {
+        // By default, M_l = 1 [p35].
+        LogSpectrals((0..MAX_HARMONICS).map(|_| 0).collect())
+    }
+}
+
+/// Energy-related parameters for a voice frame, in Q8.
+pub struct FixedEnergy {
+    /// Spectral amplitude energy, R<sub>M0</sub>.
+    pub energy: i64,
+    /// Scaled energy value, R<sub>M1</sub>.
+    pub scaled: i64,
+    /// Moving average energy tracker, S<sub>E</sub>.
+    pub tracking: i64,
+}
+
+impl FixedEnergy {
+    /// Create a new `FixedEnergy` from the given spectral amplitudes, previous
+    /// S<sub>E</sub> value, and current frame parameters.
+    pub fn new(spectrals: &LogSpectrals, prev_tracking: i64, params: &FixedParams)
+        -> FixedEnergy
+    {
+        let init = (0i64, 0i64);
+        let (energy, scaled) = spectrals.iter().enumerate().fold(init, |(e, s), (l, &m)| {
+            let sqr = math::exp2(2 * m, ENERGY_BITS);
+            let cos = math::cos(params.step.wrapping_mul(l as u32 + 1)) as i64;
+
+            // Accumulate Eqs 105 and 106. The squares of the loudest amplitudes approach
+            // the range of i64, so multiply by the cosine in two parts and saturate the
+            // sums.
+            (e.saturating_add(sqr),
+             s.saturating_add((sqr >> 15) * cos + ((sqr & 0x7fff) * cos >> 15)))
+        });
+
+        FixedEnergy {
+            energy: energy,
+            scaled: scaled,
+            // Compute Eq 111, weighting the difference so nothing can overflow.
+            tracking: max(prev_tracking - (prev_tracking - energy) / 20,
+                          10000 << ENERGY_BITS),
+        }
+    }
+}
+
+impl Default for FixedEnergy {
+    /// Create a new `FixedEnergy` with default initial values.
+    fn default() -> FixedEnergy {
+        // The initial S_E is taken from [p64].
+        FixedEnergy {
+            energy: 0,
+            scaled: 0,
+            tracking: 75000 << ENERGY_BITS,
+        }
+    }
+}
+
+/// Compute the enhanced spectral amplitudes as log<sub>2</sub> M<sub>l</sub> in Q16 from
+/// the given spectral amplitudes, frame energies, and parameters.
+pub fn enhance(spectrals: &LogSpectrals, fen: &FixedEnergy, params: &FixedParams)
+    -> ArrayVec<[i32; MAX_HARMONICS]>
+{
+    // Compute r = R_M1 / R_M0 in Q30, first reducing both so the quotient can't overflow.
+    let shift = max(32 - fen.energy.leading_zeros() as i32, 0);
+    let r = ((fen.scaled >> shift) << 30) / max(fen.energy >> shift, 1);
+
+    // Compute 1 - r^2, which is proportional to the denominator of Eq 107.
+    let denom = (1 << 30) - (r * r >> 30);
+
+    // Compute the log of the factors common to each harmonic in Eq 107.
+    let common = LOG_ENHANCE - LOG_TWO_PI - math::log2(params.step as u64, 32) -
+        math::log2(fen.energy as u64, ENERGY_BITS) - math::log2(max(denom, 0) as u64, 30);
+
+    let mut enhanced = spectrals.iter().enumerate().map(|(l, &m)| {
+        let l = l as u32 + 1;
+
+        // Handle fast-path case in Eq 108.
+        if 8 * l <= params.harmonics {
+            return m;
+        }
+
+        let cos = math::cos(params.step.wrapping_mul(l)) as i64;
+        // Compute 1 + r^2 - 2r cos(ω_0 l), proportional to the numerator of Eq 107.
+        let numer = (1 << 30) + (r * r >> 30) - 2 * (r * cos >> 15);
+
+        // Compute Eq 107.
+        let weight = (m >> 1) + (common + math::log2(max(numer, 0) as u64, 30) >> 2);
+
+        // Scale current spectral amplitude according to Eq 108.
+        m + max(min(weight, LOG_MAX_WEIGHT), -(1 << LOG_BITS))
+    }).collect::<ArrayVec<[i32; MAX_HARMONICS]>>();
+
+    let energy = enhanced.iter()
+        .fold(0i64, |s, &m| s.saturating_add(math::exp2(2 * m, ENERGY_BITS)));
+
+    // Compute root ratio of energies according to Eq 109.
+    let scale = (math::log2(fen.energy as u64, ENERGY_BITS) -
+                 math::log2(energy as u64, ENERGY_BITS)) >> 1;
+
+    // Perform second scaling pass according to Eq 110.
+    for m in enhanced.iter_mut() {
+        *m += scale;
+    }
+
+    enhanced
+}
+
+/// Compute the spectral amplitude threshold τ<sub>M</sub> from the given error
+/// characteristics and previous threshold.
+pub fn amp_thresh(errors: &FixedErrors, prev: i32) -> i32 {
+    // Compute Eq 115.
+    if errors.rate_within(50) && errors.total <= 6 {
+        20480
+    } else {
+        let thresh = 6000 - 300 * min(errors.total, 1 << 20) as i64 + prev as i64;
+        max(min(thresh, std::i32::MAX as i64), std::i32::MIN as i64) as i32
+    }
+}
+
+/// Smooth the given enhanced spectral amplitudes and voiced/unvoiced decisions, and
+/// return the resulting linear amplitudes M<sub>l</sub> in Q12.
+pub fn smooth(enhanced: &[i32], voiced: &mut VoiceDecisions, errors: &FixedErrors,
+              fen: &FixedEnergy, amp_thresh: i32)
+    -> ArrayVec<[i32; MAX_HARMONICS]>
+{
+    // Compute Eq 112 in the log domain, with 0.375 log_2 S_E.
+    let thresh = if errors.rate_within(50) && errors.total <= 4 {
+        std::i32::MAX
+    } else {
+        let tracking = 3 * math::log2(fen.tracking as u64, ENERGY_BITS) >> 3;
+
+        if errors.rate_within(125) && errors.hamming_init == 0 {
+            LOG_THRESH_CLEAN + tracking - (errors.rate as i64 * RATE_EXP >> RATE_BITS) as i32
+        } else {
+            LOG_THRESH_NOISY + tracking
+        }
+    };
+
+    // Update voiced/unvoiced decisions according to Eq 113.
+    for (l, &m) in enhanced.iter().enumerate() {
+        if m > thresh {
+            voiced.force_voiced(l + 1);
+        }
+    }
+
+    let mut amps = enhanced.iter()
+        .map(|&m| math::exp2(m, AMP_BITS))
+        .collect::<ArrayVec<[i64; MAX_HARMONICS]>>();
+
+    // Compute amplitude sum in Eq 114.
+    let sum = amps.iter().fold(0i64, |s, &m| s.saturating_add(m));
+    let thresh = (amp_thresh as i64) << AMP_BITS;
+
+    // Scale each amplitude by the factor of Eq 116 when it's less than 1 [p50]. The
+    // factor is computed in Q16 first, since amplitudes and τ_M can each take most of
+    // the range of i64 and their product can't be formed directly.
+    if thresh < sum {
+        let scale = (max(thresh, 0) << 16) / sum;
+
+        for m in amps.iter_mut() {
+            *m = *m * scale >> 16;
+        }
+    }
+
+    amps.iter().map(|&m| min(m, std::i32::MAX as i64) as i32).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coefs::Coefficients;
+    use descramble::{Bootstrap, descramble};
+    use enhance::{self, EnhanceErrors, EnhancedSpectrals, FrameEnergy};
+    use frame::TEST_CHUNKS;
+    use gain::Gains;
+    use params::BaseParams;
+    use prev::PrevFrame;
+    use spectral::Spectrals;
+
+    fn log(x: i32) -> f32 { x as f32 / 65536.0 }
+
+    #[test]
+    fn test_params() {
+        for b in 0...207 {
+            let p = FixedParams::new(b);
+            let q = BaseParams::new(b);
+
+            assert_eq!(p.harmonics, q.harmonics);
+            assert_eq!(p.bands, q.bands);
+
+            let fundamental = p.step as f32 / 4294967296.0 * 2.0 * std::f32::consts::PI;
+            assert!((fundamental - q.fundamental).abs() < 1e-6);
+        }
+
+        let p = FixedParams::default();
+        let q = BaseParams::default();
+        let fundamental = p.step as f32 / 4294967296.0 * 2.0 * std::f32::consts::PI;
+        assert!((fundamental - q.fundamental).abs() < 1e-6);
+        assert_eq!(p.harmonics, q.harmonics);
+        assert_eq!(p.bands, q.bands);
+    }
+
+    #[test]
+    fn test_errors() {
+        for &(errors, prev) in &[([0, 0, 0, 0, 0, 0, 0], 0.0),
+                                 ([1, 2, 3, 4, 5, 6, 7], 0.5),
+                                 ([3, 0, 2, 0, 1, 0, 0], 0.01),
+                                 ([2, 1, 1, 1, 2, 1, 4], 0.004)] {
+            let e = EnhanceErrors::new(&errors, prev);
+            let f = FixedErrors::new(&errors, (prev * (1 << RATE_BITS) as f32) as u32);
+
+            assert_eq!(f.total, e.total);
+            assert!((f.rate as f32 / (1 << RATE_BITS) as f32 - e.rate).abs() < 1e-6);
+            assert_eq!(f.should_repeat(), enhance::should_repeat(&e));
+            assert_eq!(f.should_mute(), enhance::should_mute(&e));
+            assert_eq!(amp_thresh(&f, 1000), enhance::amp_thresh(&e, 1000.0) as i32);
+        }
+    }
+
+    #[test]
+    fn test_model() {
+        let b = Bootstrap::new(&TEST_CHUNKS);
+        let p = BaseParams::new(b.unwrap_period());
+        let q = FixedParams::new(b.unwrap_period());
+        let (amps, voice, gain_idx) = descramble(&TEST_CHUNKS, &p);
+
+        let g = Gains::new(gain_idx, &amps, &p);
+        let r = gains(gain_idx, &amps, &q);
+
+        for i in 1...6 {
+            assert!((log(r[i - 1]) - g.idct(i)).abs() < 1e-3);
+        }
+
+        let c = Coefficients::new(&g, &amps, &p);
+        let t = coefficients(&r, &amps, &q);
+
+        assert_eq!(t.len(), 16);
+
+        for l in 1...16 {
+            assert!((log(t[l - 1]) - c.get(l)).abs() < 1e-3);
+        }
+
+        // Decode two frames to exercise prediction.
+        let mut prev = PrevFrame::default();
+        let mut prev_log = LogSpectrals::default();
+        let mut prev_tracking = FixedEnergy::default().tracking;
+
+        for _ in 0..2 {
+            let s = Spectrals::new(&c, &p, &prev);
+            let fs = LogSpectrals::new(&t, &q, prev.model.harmonics, &prev_log);
+
+            for l in 1...16 {
+                assert!((log(fs.get(l)) - s.get(l).log2()).abs() < 2e-3);
+            }
+
+            let fe = FrameEnergy::new(&s, &prev.energy, &p);
+            let ffe = FixedEnergy::new(&fs, prev_tracking, &q);
+
+            assert!((ffe.energy as f32 / 256.0 / fe.energy - 1.0).abs() < 1e-3);
+            assert!((ffe.scaled as f32 / 256.0 - fe.scaled).abs() < fe.energy * 1e-3);
+            assert!((ffe.tracking as f32 / 256.0 / fe.tracking - 1.0).abs() < 1e-3);
+
+            let e = EnhancedSpectrals::new(&s, &fe, &p);
+            let fen = enhance(&fs, &ffe, &q);
+
+            for l in 1...16 {
+                assert!((log(fen[l - 1]) - e.get(l).log2()).abs() < 2e-3);
+            }
+
+            // Verify smoothing with a noisy channel.
+            let errors = [1, 0, 2, 1, 0, 0, 0];
+            let ee = EnhanceErrors::new(&errors, 0.01);
+            let fee = FixedErrors::new(&errors, (0.01 * (1 << RATE_BITS) as f32) as u32);
+
+            let mut e = e.clone();
+            let mut v = voice.clone();
+            let mut fv = voice.clone();
+
+            enhance::smooth(&mut e, &mut v, &ee, &fe, 100.0);
+            let famps = smooth(&fen, &mut fv, &fee, &ffe, 100);
+
+            for l in 1...16 {
+                assert_eq!(fv.is_voiced(l), v.is_voiced(l));
+                assert!((famps[l - 1] as f32 / 4096.0 - e.get(l)).abs() < e.get(l) * 2e-3);
+            }
+
+            prev.spectrals = s;
+            prev.energy = fe;
+            prev_log = fs;
+            prev_tracking = ffe.tracking;
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! Fixed-point voiced and unvoiced synthesis.
+//!
+//! Harmonic phases are tracked in turns, so the cosines of Eqs 131 and 132 are table
+//! lookups driven by wrapping phase accumulators. The unvoiced IDFT of Eq 125 samples the
+//! same table at exact points, since every phase is a multiple of 1/256 turn.
+
+use std;
+use std::cmp::{max, min};
+
+use arrayvec::ArrayVec;
+
+use consts::{SAMPLES_PER_FRAME, MAX_HARMONICS};
+use descramble::VoiceDecisions;
+use fixed::math;
+use fixed::model::{FixedParams, AMP_BITS};
+use rng::Random;
+
+/// Number of points in the real half of the unvoiced DFT.
+const DFT_HALF: usize = 128;
+
+/// Unvoiced scaling coefficient γ<sub>w</sub> of Eq 121 in Q8.
+const SCALING_COEF: i64 = 37541;
+
+/// Number of fractional bits in unvoiced IDFT values.
+const IDFT_BITS: u32 = 8;
+
+/// Compute the speech synthesis window w<sub>s</sub>(n) [p95] in Q15.
+///
+/// The window rises linearly over 50 samples to a flat top spanning -55 ≤ n ≤ 55.
+pub fn window(n: isize) -> i64 {
+    let n = n.abs();
+
+    if n >= 105 {
+        0
+    } else if n <= 55 {
+        1 << 15
+    } else {
+        (((105 - n) << 15) as i64 + 25) / 50
+    }
+}
+
+/// Harmonic phase terms Ψ<sub>l</sub> and Φ<sub>l</sub>, in turns.
+#[derive(Clone)]
+pub struct Phases {
+    /// Base phase offsets Ψ<sub>l</sub>.
+    base: [u32; MAX_HARMONICS],
+    /// Random phase terms Φ<sub>l</sub>.
+    phase: [u32; MAX_HARMONICS],
+}
+
+impl Phases {
+    /// Create a new `Phases` from the given current and previous frame parameters,
+    /// previous phases, and current voiced/unvoiced decisions.
+    pub fn new<R: Random + ?Sized>(params: &FixedParams, prev_params: &FixedParams,
+                                   prev: &Phases, voice: &VoiceDecisions, noise: &mut R)
+        -> Phases
+    {
+        let mut base = [0; MAX_HARMONICS];
+
+        // Compute common scaling factor (ω_0(-1) + ω_0(0)) N / 2 in Eq 139.
+        let scale = prev_params.step.wrapping_add(params.step)
+            .wrapping_mul(SAMPLES_PER_FRAME as u32 / 2);
+
+        // Compute Eq 139.
+        for (l, b) in base.iter_mut().enumerate() {
+            *b = prev.base[l].wrapping_add(scale.wrapping_mul(l as u32 + 1));
+        }
+
+        let mut phase = base;
+
+        // Compute bounds for modification used in Eq 140.
+        let start = params.harmonics as usize / 4;
+        let stop = max(params.harmonics, prev_params.harmonics) as usize;
+
+        for p in &mut phase[start..stop] {
+            // Draw a uniform offset in [-π, π) from the upper 24 bits.
+            let x = (noise.next_u32() >> 8 << 8).wrapping_sub(1 << 31) as i32;
+
+            // Compute Eq 140.
+            *p = p.wrapping_add((x as i64 * voice.unvoiced_count() as i64 /
+                                 params.harmonics as i64) as u32);
+        }
+
+        Phases {
+            base: base,
+            phase: phase,
+        }
+    }
+}
+
+impl Default for Phases {
+    /// Create a new `Phases` in the default state.
+    fn default() -> Phases {
+        // By default all phase terms are 0 [p64].
+        Phases {
+            base: [0; MAX_HARMONICS],
+            phase: [0; MAX_HARMONICS],
+        }
+    }
+}
+
+/// Unvoiced DFT U<sub>w</sub>(m), 0 ≤ m < 128, as (real, imaginary) pairs.
+#[derive(Clone)]
+pub struct FixedDft([(i32, i32); DFT_HALF]);
+
+impl FixedDft {
+    /// Construct a new `FixedDft` from the given frame parameters, voiced/unvoiced
+    /// decisions, and amplitudes M<sub>l</sub> in Q12.
+    pub fn new<R: Random + ?Sized>(params: &FixedParams, voice: &VoiceDecisions,
+                                   amps: &[i32], noise: &mut R)
+        -> FixedDft
+    {
+        // DFT values default to 0 according to Eqs 119 and 124.
+        let mut dft = [(0, 0); DFT_HALF];
+
+        for (l, &amp) in amps.iter().enumerate() {
+            let l = l + 1;
+
+            if voice.is_voiced(l) {
+                continue;
+            }
+
+            let (lower, upper) = edges(l, params);
+
+            // Populate the current band with approximately Gaussian noise.
+            for x in &mut dft[lower..upper] {
+                *x = (gaussian(noise), gaussian(noise));
+            }
+
+            // Compute energy of current band according to Eq 120.
+            let energy = dft[lower..upper].iter().fold(0, |s, &(re, im)| {
+                s + re as i64 * re as i64 + im as i64 * im as i64
+            });
+
+            // Compute RMS of current band according to Eq 120.
+            let rms = math::isqrt(energy as u64 / (upper - lower) as u64) as i64;
+
+            if rms == 0 {
+                continue;
+            }
+
+            // Compute scale γ_w M_l / RMS for the current band in Q28.
+            let scale = (SCALING_COEF * amp as i64 >> 8 << 16) / rms;
+
+            // Scale the band according to Eq 120.
+            for x in &mut dft[lower..upper] {
+                *x = ((x.0 as i64 * scale >> 28) as i32, (x.1 as i64 * scale >> 28) as i32);
+            }
+        }
+
+        FixedDft(dft)
+    }
+
+    /// Compute the IDFT u<sub>w</sub>(n) at the given point n in Q8.
+    pub fn idft(&self, n: isize) -> i64 {
+        // The IDFT is zero outside the defined range [p59].
+        if n < -(DFT_HALF as isize) || n >= DFT_HALF as isize {
+            return 0;
+        }
+
+        // Compute the phase step 2πn / 256.
+        let step = (n as u32) << 24;
+
+        let sum = self.0.iter().enumerate().fold(0, |s, (m, &(re, im))| {
+            let phase = step.wrapping_mul(m as u32);
+            s + re as i64 * math::cos(phase) as i64 - im as i64 * math::sin(phase) as i64
+        });
+
+        // Scale by 2 / 256 and convert from Q15.
+        sum >> (15 + 7 - IDFT_BITS)
+    }
+}
+
+impl Default for FixedDft {
+    /// Create a new `FixedDft` in the default state.
+    fn default() -> FixedDft {
+        // By default all IDFT values are zero [p64].
+        FixedDft([(0, 0); DFT_HALF])
+    }
+}
+
+/// Draw an approximately Gaussian value as the sum of 4 uniform 16-bit values.
+fn gaussian<R: Random + ?Sized>(noise: &mut R) -> i32 {
+    let a = noise.next_u32();
+    let b = noise.next_u32();
+
+    (a >> 16) as i16 as i32 + a as i16 as i32 + (b >> 16) as i16 as i32 + b as i16 as i32
+}
+
+/// Determine the lower and upper band edges (a<sub>l</sub>, b<sub>l</sub>) for the given
+/// harmonic of the fundamental frequency.
+fn edges(l: usize, params: &FixedParams) -> (usize, usize) {
+    // Compute ceil(256 ω_0 (2l ∓ 1) / 4π).
+    let edge = |k: u64| ((256 * params.step as u64 * k + (1 << 33) - 1) >> 33) as usize;
+
+    (
+        // Compute Eq 122.
+        edge(2 * l as u64 - 1),
+        // Compute Eq 123.
+        edge(2 * l as u64 + 1),
+    )
+}
+
+/// Synthesis state of a decoded frame.
+#[derive(Clone)]
+pub struct SynthFrame {
+    /// Frame parameters.
+    pub params: FixedParams,
+    /// Voiced/unvoiced decisions after smoothing.
+    pub voice: VoiceDecisions,
+    /// Enhanced spectral amplitudes M<sub>l</sub> in Q12.
+    pub amps: ArrayVec<[i32; MAX_HARMONICS]>,
+    /// Harmonic phases.
+    pub phases: Phases,
+    /// Unvoiced spectrum.
+    pub dft: FixedDft,
+}
+
+impl SynthFrame {
+    /// Create a new `SynthFrame` from the given decoded parameters, following the given
+    /// previous frame.
+    pub fn new<R: Random + ?Sized>(params: FixedParams, voice: VoiceDecisions,
+                                   amps: ArrayVec<[i32; MAX_HARMONICS]>,
+                                   prev: &SynthFrame, noise: &mut R)
+        -> SynthFrame
+    {
+        let dft = FixedDft::new(&params, &voice, &amps, noise);
+        let phases = Phases::new(&params, &prev.params, &prev.phases, &voice, noise);
+
+        SynthFrame {
+            params: params,
+            voice: voice,
+            amps: amps,
+            phases: phases,
+            dft: dft,
+        }
+    }
+
+    /// Synthesize the speech signal of Eq 142 for this frame following the given previous
+    /// frame into the given buffer of samples.
+    pub fn synthesize(&self, prev: &SynthFrame, out: &mut [i16; SAMPLES_PER_FRAME]) {
+        let n = SAMPLES_PER_FRAME as u32;
+
+        // Voiced harmonics of the previous frame as (amplitude, phase, step), starting
+        // at n = 0.
+        let mut osc_prev = prev.amps.iter().enumerate()
+            .filter(|&(l, _)| prev.voice.is_voiced(l + 1))
+            .map(|(l, &m)| {
+                let step = prev.params.step.wrapping_mul(l as u32 + 1);
+                (m as i64, prev.phases.phase[l], step)
+            })
+            .collect::<ArrayVec<[(i64, u32, u32); MAX_HARMONICS]>>();
+
+        // Voiced harmonics of the current frame, starting at n - N = -N.
+        let mut osc_cur = self.amps.iter().enumerate()
+            .filter(|&(l, _)| self.voice.is_voiced(l + 1))
+            .map(|(l, &m)| {
+                let step = self.params.step.wrapping_mul(l as u32 + 1);
+                (m as i64, self.phases.phase[l].wrapping_sub(step.wrapping_mul(n)), step)
+            })
+            .collect::<ArrayVec<[(i64, u32, u32); MAX_HARMONICS]>>();
+
+        for (n, x) in out.iter_mut().enumerate() {
+            let n = n as isize;
+            let t = n - SAMPLES_PER_FRAME as isize;
+
+            let w_prev = window(n);
+            let w_cur = window(t);
+
+            // Compute Eq 127 in Q12.
+            let voiced = 2 * (w_prev * oscillate(&mut osc_prev) +
+                              w_cur * oscillate(&mut osc_cur)) >> 15;
+
+            // Compute Eq 126 in Q8. The windows overlap such that the denominator is at
+            // least 0.5.
+            let numer = w_prev * prev.dft.idft(n) + w_cur * self.dft.idft(t);
+            let denom = w_prev * w_prev + w_cur * w_cur >> 15;
+            let unvoiced = numer / denom;
+
+            // Combine according to Eq 142 and round to the nearest integer.
+            let y = (voiced >> (AMP_BITS - IDFT_BITS)) + unvoiced + (1 << (IDFT_BITS - 1))
+                >> IDFT_BITS;
+
+            *x = max(min(y, std::i16::MAX as i64), std::i16::MIN as i64) as i16;
+        }
+    }
+}
+
+/// Sum the given harmonic oscillators at the current sample in Q12 according to Eqs 131
+/// and 132, and advance each to the next sample.
+fn oscillate(osc: &mut ArrayVec<[(i64, u32, u32); MAX_HARMONICS]>) -> i64 {
+    osc.iter_mut().fold(0, |s, o| {
+        let y = o.0 * math::cos(o.1) as i64 >> 15;
+        o.1 = o.1.wrapping_add(o.2);
+        s + y
+    })
+}
+
+impl Default for SynthFrame {
+    /// Create a new `SynthFrame` in the default state.
+    fn default() -> SynthFrame {
+        SynthFrame {
+            params: FixedParams::default(),
+            voice: VoiceDecisions::default(),
+            // By default, all enhanced amplitudes are 0 [p64].
+            amps: ArrayVec::new(),
+            phases: Phases::default(),
+            dft: FixedDft::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use params::BaseParams;
+    use rng::XorShift;
+    use unvoiced;
+    use window;
+
+    #[test]
+    fn test_window() {
+        let w = window::synthesis();
+
+        for n in -110...110 {
+            assert!((window(n) as f32 / 32768.0 - w.get(n)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_edges() {
+        for b in 0...207 {
+            let p = FixedParams::new(b);
+            let q = BaseParams::new(b);
+
+            for l in 1...p.harmonics as usize {
+                assert_eq!(edges(l, &p), unvoiced::edges(l, &q));
+            }
+        }
+    }
+
+    #[test]
+    fn test_gaussian() {
+        let mut r = XorShift::default();
+        let (mut sum, mut sqr) = (0.0, 0.0);
+
+        for _ in 0..10000 {
+            let x = gaussian(&mut r) as f64;
+            sum += x;
+            sqr += x * x;
+        }
+
+        // Each 16-bit uniform value has variance 2^32 / 12.
+        let mean = sum / 10000.0;
+        let var = sqr / 10000.0 - mean * mean;
+
+        assert!(mean.abs() < 1000.0);
+        assert!((var / (4.0 * 4294967296.0 / 12.0) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_dft() {
+        let p = FixedParams::new(104);
+        let q = BaseParams::new(104);
+        let voice = VoiceDecisions::new(0, &q);
+
+        let amps = (1...p.harmonics).map(|l| (l as i32 * 10) << AMP_BITS)
+            .collect::<ArrayVec<[i32; MAX_HARMONICS]>>();
+
+        let dft = FixedDft::new(&p, &voice, &amps, &mut XorShift::default());
+
+        // Verify each band has the power set by Eq 120.
+        for l in 1...p.harmonics as usize {
+            let (lower, upper) = edges(l, &p);
+            let power = dft.0[lower..upper].iter().fold(0.0, |s, &(re, im)| {
+                s + (re as f64).powi(2) + (im as f64).powi(2)
+            }) / (upper - lower) as f64;
+
+            let expected = 146.6432708443356 * l as f64 * 10.0;
+            assert!((power.sqrt() / expected - 1.0).abs() < 1e-3);
+        }
+
+        // Verify the IDFT against a direct evaluation of Eq 125.
+        for &n in &[-128isize, -77, -1, 0, 5, 64, 127] {
+            let expected = dft.0.iter().enumerate().fold(0.0, |s, (m, &(re, im))| {
+                let x = 2.0 * std::f64::consts::PI * m as f64 * n as f64 / 256.0;
+                s + re as f64 * x.cos() - im as f64 * x.sin()
+            }) * 2.0 / 256.0;
+
+            let got = dft.idft(n) as f64 / 256.0;
+            assert!((got - expected).abs() < expected.abs() * 1e-3 + 1.0);
+        }
+
+        assert_eq!(dft.idft(128), 0);
+        assert_eq!(dft.idft(-129), 0);
+
+        // Verify voiced bands are left empty.
+        let voiced = VoiceDecisions::new(0b11111111111, &q);
+        let dft = FixedDft::new(&p, &voiced, &amps, &mut XorShift::default());
+        assert!(dft.0.iter().all(|&x| x == (0, 0)));
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let p = FixedParams::new(104);
+        let q = BaseParams::new(104);
+        let mut r = XorShift::default();
+
+        let amps = (1...p.harmonics).map(|_| 100 << AMP_BITS)
+            .collect::<ArrayVec<[i32; MAX_HARMONICS]>>();
+
+        let prev = SynthFrame::default();
+        let cur = SynthFrame::new(p, VoiceDecisions::new(0b11111111111, &q),
+                                  amps.clone(), &prev, &mut r);
+
+        let mut out = [0; SAMPLES_PER_FRAME];
+        cur.synthesize(&prev, &mut out);
+
+        // Only the current frame contributes, starting at n = N - 105.
+        assert!(out[..56].iter().all(|&x| x == 0));
+        assert!(out[56..].iter().any(|&x| x != 0));
+
+        let next = SynthFrame::new(p, VoiceDecisions::new(0b11111111111, &q),
+                                   amps, &cur, &mut r);
+        next.synthesize(&cur, &mut out);
+
+        // Each harmonic has amplitude 2M_l in Eq 127, so the signal should have about
+        // the power L (2M_l)^2 / 2.
+        let power = out.iter().fold(0.0, |s, &x| s + (x as f64).powi(2)) /
+            SAMPLES_PER_FRAME as f64;
+        let expected = p.harmonics as f64 * 200.0f64.powi(2) / 2.0;
+
+        assert!(power > expected * 0.5 && power < expected * 1.5);
+    }
+}
@@ -0,0 +1,200 @@
+//! Lookup tables for fixed-point decoding.
+//!
+//! The parameter tables are the floating-point tables of the standard converted to the
+//! fixed-point formats noted on each.
+
+/// Quarter wave of the cosine, where COS[i] = cos(πi/512) in Q15.
+pub static COS: [i32; 257] = [
+    32768, 32767, 32766, 32762, 32758, 32753, 32746, 32738,
+    32729, 32718, 32706, 32693, 32679, 32664, 32647, 32629,
+    32610, 32590, 32568, 32546, 32522, 32496, 32470, 32442,
+    32413, 32383, 32352, 32319, 32286, 32251, 32214, 32177,
+    32138, 32099, 32058, 32015, 31972, 31927, 31881, 31834,
+    31786, 31737, 31686, 31634, 31581, 31527, 31471, 31415,
+    31357, 31298, 31238, 31177, 31114, 31050, 30986, 30920,
+    30853, 30784, 30715, 30644, 30572, 30499, 30425, 30350,
+    30274, 30196, 30118, 30038, 29957, 29875, 29792, 29707,
+    29622, 29535, 29448, 29359, 29269, 29178, 29086, 28993,
+    28899, 28803, 28707, 28610, 28511, 28411, 28311, 28209,
+    28106, 28002, 27897, 27791, 27684, 27576, 27467, 27357,
+    27246, 27133, 27020, 26906, 26791, 26674, 26557, 26439,
+    26320, 26199, 26078, 25956, 25833, 25708, 25583, 25457,
+    25330, 25202, 25073, 24943, 24812, 24680, 24548, 24414,
+    24279, 24144, 24008, 23870, 23732, 23593, 23453, 23312,
+    23170, 23028, 22884, 22740, 22595, 22449, 22302, 22154,
+    22006, 21856, 21706, 21555, 21403, 21251, 21097, 20943,
+    20788, 20632, 20475, 20318, 20160, 20001, 19841, 19681,
+    19520, 19358, 19195, 19032, 18868, 18703, 18538, 18372,
+    18205, 18037, 17869, 17700, 17531, 17361, 17190, 17018,
+    16846, 16673, 16500, 16326, 16151, 15976, 15800, 15624,
+    15447, 15269, 15091, 14912, 14733, 14553, 14373, 14192,
+    14010, 13828, 13646, 13463, 13279, 13095, 12910, 12725,
+    12540, 12354, 12167, 11980, 11793, 11605, 11417, 11228,
+    11039, 10850, 10660, 10469, 10279, 10088, 9896, 9704,
+    9512, 9319, 9127, 8933, 8740, 8546, 8351, 8157,
+    7962, 7767, 7571, 7376, 7180, 6983, 6787, 6590,
+    6393, 6195, 5998, 5800, 5602, 5404, 5205, 5007,
+    4808, 4609, 4410, 4211, 4011, 3812, 3612, 3412,
+    3212, 3012, 2811, 2611, 2411, 2210, 2009, 1809,
+    1608, 1407, 1206, 1005, 804, 603, 402, 201,
+    0,
+];
+
+/// Each LOG2[i] = log<sub>2</sub>(1 + i/256) in Q16.
+pub static LOG2: [i32; 257] = [
+    0, 369, 736, 1102, 1466, 1829, 2190, 2551,
+    2909, 3267, 3623, 3978, 4331, 4683, 5034, 5384,
+    5732, 6079, 6425, 6769, 7112, 7454, 7795, 8134,
+    8473, 8810, 9146, 9480, 9814, 10146, 10477, 10807,
+    11136, 11464, 11791, 12116, 12440, 12764, 13086, 13407,
+    13727, 14046, 14363, 14680, 14996, 15310, 15624, 15937,
+    16248, 16559, 16868, 17177, 17484, 17791, 18096, 18401,
+    18704, 19007, 19308, 19609, 19909, 20207, 20505, 20802,
+    21098, 21393, 21687, 21980, 22272, 22564, 22854, 23144,
+    23433, 23720, 24007, 24293, 24579, 24863, 25146, 25429,
+    25711, 25992, 26272, 26551, 26830, 27108, 27384, 27660,
+    27936, 28210, 28484, 28757, 29029, 29300, 29571, 29840,
+    30109, 30378, 30645, 30912, 31178, 31443, 31707, 31971,
+    32234, 32496, 32758, 33019, 33279, 33538, 33797, 34055,
+    34312, 34569, 34825, 35080, 35334, 35588, 35841, 36094,
+    36346, 36597, 36847, 37097, 37346, 37595, 37842, 38090,
+    38336, 38582, 38827, 39072, 39316, 39559, 39802, 40044,
+    40286, 40527, 40767, 41006, 41246, 41484, 41722, 41959,
+    42196, 42432, 42667, 42902, 43137, 43370, 43603, 43836,
+    44068, 44300, 44530, 44761, 44990, 45220, 45448, 45676,
+    45904, 46131, 46357, 46583, 46809, 47034, 47258, 47482,
+    47705, 47928, 48150, 48372, 48593, 48813, 49034, 49253,
+    49472, 49691, 49909, 50127, 50344, 50560, 50776, 50992,
+    51207, 51422, 51636, 51850, 52063, 52276, 52488, 52700,
+    52911, 53122, 53332, 53542, 53751, 53960, 54169, 54377,
+    54584, 54791, 54998, 55204, 55410, 55615, 55820, 56025,
+    56229, 56432, 56635, 56838, 57040, 57242, 57443, 57644,
+    57845, 58045, 58245, 58444, 58643, 58841, 59039, 59237,
+    59434, 59631, 59827, 60023, 60219, 60414, 60609, 60803,
+    60997, 61190, 61384, 61576, 61769, 61961, 62152, 62343,
+    62534, 62725, 62915, 63104, 63294, 63483, 63671, 63859,
+    64047, 64234, 64421, 64608, 64794, 64980, 65166, 65351,
+    65536,
+];
+
+/// Each EXP2[i] = 2<sup>i/256</sup> in Q30.
+pub static EXP2: [u32; 257] = [
+    1073741824, 1076653033, 1079572136, 1082499153, 1085434106, 1088377016,
+    1091327906, 1094286796, 1097253708, 1100228665, 1103211687, 1106202798,
+    1109202018, 1112209370, 1115224875, 1118248556, 1121280436, 1124320536,
+    1127368878, 1130425485, 1133490379, 1136563583, 1139645120, 1142735011,
+    1145833280, 1148939949, 1152055042, 1155178580, 1158310587, 1161451085,
+    1164600099, 1167757650, 1170923762, 1174098458, 1177281762, 1180473697,
+    1183674286, 1186883552, 1190101520, 1193328213, 1196563654, 1199807867,
+    1203060876, 1206322705, 1209593378, 1212872918, 1216161350, 1219458698,
+    1222764986, 1226080238, 1229404479, 1232737732, 1236080024, 1239431376,
+    1242791816, 1246161366, 1249540052, 1252927899, 1256324931, 1259731174,
+    1263146652, 1266571390, 1270005413, 1273448747, 1276901417, 1280363448,
+    1283834865, 1287315695, 1290805962, 1294305692, 1297814910, 1301333643,
+    1304861917, 1308399756, 1311947188, 1315504238, 1319070932, 1322647296,
+    1326233356, 1329829140, 1333434672, 1337049980, 1340675091, 1344310030,
+    1347954824, 1351609500, 1355274085, 1358948606, 1362633090, 1366327563,
+    1370032052, 1373746586, 1377471191, 1381205894, 1384950723, 1388705706,
+    1392470869, 1396246240, 1400031848, 1403827719, 1407633882, 1411450365,
+    1415277195, 1419114401, 1422962010, 1426820052, 1430688553, 1434567544,
+    1438457051, 1442357104, 1446267730, 1450188960, 1454120821, 1458063343,
+    1462016553, 1465980482, 1469955159, 1473940611, 1477936870, 1481943963,
+    1485961921, 1489990772, 1494030547, 1498081275, 1502142985, 1506215708,
+    1510299473, 1514394310, 1518500250, 1522617322, 1526745556, 1530884983,
+    1535035634, 1539197537, 1543370725, 1547555228, 1551751076, 1555958300,
+    1560176931, 1564406999, 1568648537, 1572901575, 1577166143, 1581442275,
+    1585730000, 1590029350, 1594340357, 1598663052, 1602997467, 1607343634,
+    1611701585, 1616071351, 1620452965, 1624846459, 1629251865, 1633669214,
+    1638098541, 1642539877, 1646993254, 1651458706, 1655936265, 1660425963,
+    1664927835, 1669441912, 1673968228, 1678506817, 1683057710, 1687620943,
+    1692196547, 1696784557, 1701385007, 1705997930, 1710623359, 1715261330,
+    1719911875, 1724575029, 1729250827, 1733939301, 1738640488, 1743354420,
+    1748081133, 1752820662, 1757573041, 1762338305, 1767116489, 1771907628,
+    1776711757, 1781528911, 1786359126, 1791202437, 1796058879, 1800928489,
+    1805811301, 1810707353, 1815616678, 1820539314, 1825475297, 1830424663,
+    1835387448, 1840363688, 1845353420, 1850356681, 1855373507, 1860403934,
+    1865448001, 1870505744, 1875577199, 1880662405, 1885761398, 1890874216,
+    1896000896, 1901141476, 1906295993, 1911464486, 1916646992, 1921843549,
+    1927054196, 1932278970, 1937517909, 1942771053, 1948038440, 1953320108,
+    1958616096, 1963926443, 1969251188, 1974590370, 1979944027, 1985312200,
+    1990694927, 1996092249, 2001504204, 2006930832, 2012372174, 2017828268,
+    2023299156, 2028784876, 2034285470, 2039800978, 2045331439, 2050876895,
+    2056437387, 2062012954, 2067603638, 2073209480, 2078830522, 2084466803,
+    2090118366, 2095785251, 2101467502, 2107165158, 2112878262, 2118606857,
+    2124350982, 2130110682, 2135885998, 2141676973, 2147483648,
+];
+
+/// First gain value G<sub>1</sub> for each index b<sub>2</sub> in Q16.
+pub static GAIN: [i32; 64] = [
+    -186267, -176569, -167658, -156162, -145558, -137336, -129817, -120328,
+    -107843, -92908, -82661, -73769, -62797, -51222, -36427, -22739,
+    -9650, 1819, 13861, 25453, 36233, 48315, 61092, 74648,
+    86570, 97218, 108023, 118060, 127319, 138845, 152141, 164131,
+    173927, 182233, 191716, 201614, 211080, 223010, 234953, 248028,
+    259229, 272344, 282723, 291252, 299994, 310349, 321749, 333268,
+    344376, 354674, 364911, 376080, 387922, 398964, 411611, 423638,
+    435666, 447917, 460232, 472631, 489621, 507180, 532471, 569889,
+];
+
+/// Each STEPS[l][i] gives the step size Δ<sub>i+3</sub> for the harmonics parameter l =
+/// L - 9 in Q24.
+pub static STEPS: [[i32; 5]; 48] = [
+    [52009, 67444, 56371, 48654, 44292],
+    [104019, 67444, 112743, 97308, 88584],
+    [208037, 134889, 112743, 194616, 177167],
+    [208037, 269778, 225486, 194616, 177167],
+    [416075, 269778, 225486, 364904, 332189],
+    [416075, 505833, 422786, 364904, 332189],
+    [416075, 505833, 422786, 364904, 620086],
+    [780141, 505833, 422786, 681155, 620086],
+    [780141, 505833, 789200, 681155, 620086],
+    [780141, 944222, 789200, 681155, 620086],
+    [780141, 944222, 789200, 973079, 885837],
+    [780141, 944222, 789200, 973079, 885837],
+    [1456262, 944222, 789200, 973079, 885837],
+    [1456262, 944222, 1127429, 973079, 885837],
+    [1456262, 1348888, 1127429, 973079, 885837],
+    [1456262, 1348888, 1127429, 973079, 885837],
+    [1456262, 1348888, 1127429, 973079, 1439485],
+    [1456262, 1348888, 1127429, 1581253, 1439485],
+    [1456262, 1348888, 1127429, 1581253, 1439485],
+    [2080375, 1348888, 1127429, 1581253, 1439485],
+    [2080375, 1348888, 1127429, 1581253, 1439485],
+    [2080375, 1348888, 1127429, 1581253, 1439485],
+    [2080375, 1348888, 1832072, 1581253, 1439485],
+    [2080375, 1348888, 1832072, 1581253, 1439485],
+    [2080375, 2191943, 1832072, 1581253, 1439485],
+    [2080375, 2191943, 1832072, 1581253, 1439485],
+    [2080375, 2191943, 1832072, 1581253, 1439485],
+    [2080375, 2191943, 1832072, 1581253, 1439485],
+    [2080375, 2191943, 1832072, 1581253, 1882404],
+    [2080375, 2191943, 1832072, 1581253, 1882404],
+    [2080375, 2191943, 1832072, 1581253, 1882404],
+    [2080375, 2191943, 1832072, 1581253, 1882404],
+    [2080375, 2191943, 1832072, 2067792, 1882404],
+    [2080375, 2191943, 1832072, 2067792, 1882404],
+    [2080375, 2191943, 1832072, 2067792, 1882404],
+    [2080375, 2191943, 1832072, 2067792, 1882404],
+    [2080375, 2191943, 1832072, 2067792, 1882404],
+    [3380609, 2191943, 1832072, 2067792, 1882404],
+    [3380609, 2191943, 1832072, 2067792, 1882404],
+    [3380609, 2191943, 1832072, 2067792, 1882404],
+    [3380609, 2191943, 1832072, 2067792, 1882404],
+    [3380609, 2191943, 1832072, 2067792, 1882404],
+    [3380609, 2191943, 1832072, 2067792, 1882404],
+    [3380609, 2191943, 2395786, 2067792, 1882404],
+    [3380609, 2191943, 2395786, 2067792, 1882404],
+    [3380609, 2191943, 2395786, 2067792, 1882404],
+    [3380609, 2191943, 2395786, 2067792, 1882404],
+    [3380609, 2191943, 2395786, 2067792, 1882404],
+];
+
+/// Uniform quantizer step size for each bit allocation b = B<sub>m</sub> - 1 in Q16.
+pub static DCT_STEP_SIZE: [i32; 10] = [
+    78643, 55706, 42598, 26214, 18350, 9830, 5243, 2621, 1311, 655,
+];
+
+/// DCT standard deviation for each coefficient C<sub>i,j+2</sub> in Q16.
+pub static DCT_STD_DEV: [i32; 9] = [
+    20120, 15794, 13566, 12452, 11731, 11338, 10813, 11141, 11141,
+];
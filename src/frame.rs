@@ -9,6 +9,21 @@ pub type Chunks = [u32; 8];
 /// ϵ<sub>6</sub>, corresponding to the chunks u<sub>0</sub>, ..., u<sub>6</sub>.
 pub type Errors = [usize; 7];
 
+/// Chunks of a normally decoded voiced frame with period b<sub>0</sub> = 32 and 16
+/// harmonics, shared by the tests, examples, and benchmarks of this crate and the `ffi`
+/// and `fuzz` crates.
+#[doc(hidden)]
+pub const TEST_CHUNKS: Chunks = [
+    0b001000010010,
+    0b110011001100,
+    0b111000111000,
+    0b111111111111,
+    0b10100110101,
+    0b00101111010,
+    0b01110111011,
+    0b00001000,
+];
+
 /// A received IMBE voice frame.
 pub struct ReceivedFrame {
     /// Prioritized bit vector chunks, u<sub>0</sub>, ..., u<sub>7</sub>.
@@ -20,26 +35,56 @@ pub struct ReceivedFrame {
 impl ReceivedFrame {
     /// Create a new `ReceivedFrame` from the given chunks u<sub>0</sub>, ...,
     /// u<sub>7</sub> and error counts ϵ<sub>0</sub>, ..., ϵ<sub>6</sub>.
+    ///
+    /// Panics if any chunk has more bits than allowed (see `valid_chunks`), so chunks
+    /// from untrusted input should go through `try_new` instead. Error counts may take
+    /// any value.
     pub fn new(chunks: Chunks, errors: Errors) -> ReceivedFrame {
-        // First 4 chunks must have at most 12 bits.
-        for i in 0...3 {
-            assert!(chunks[i] >> 12 == 0);
-        }
-
-        // Next 3 chunks must have at most 11 bits.
-        for i in 4...6 {
-            assert!(chunks[i] >> 11 == 0);
-        }
-
-        // Final chunk must have at most 7 bits.
-        assert!(chunks[7] >> 7 == 0);
+        assert!(valid_chunks(&chunks));
 
         ReceivedFrame {
             chunks: chunks,
             errors: errors,
         }
     }
+
+    /// Create a new `ReceivedFrame` from the given chunks and error counts, returning
+    /// `None` if any chunk has more bits than allowed.
+    pub fn try_new(chunks: Chunks, errors: Errors) -> Option<ReceivedFrame> {
+        if valid_chunks(&chunks) {
+            Some(ReceivedFrame::new(chunks, errors))
+        } else {
+            None
+        }
+    }
+}
+
+/// Check if the given chunks fit their widths: at most 12 bits for the first 4 chunks,
+/// 11 bits for the next 3, and 7 bits for the final chunk.
+pub fn valid_chunks(chunks: &Chunks) -> bool {
+    chunks.iter().enumerate().all(|(i, &c)| {
+        c >> match i { 0...3 => 12, 4...6 => 11, _ => 7 } == 0
+    })
 }
 
 /// Audio samples in a decoded voice frame.
 pub type AudioBuf = [f32; SAMPLES_PER_FRAME];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_try_new() {
+        let chunks = [0xfff, 0xfff, 0xfff, 0xfff, 0x7ff, 0x7ff, 0x7ff, 0x7f];
+        assert!(ReceivedFrame::try_new(chunks, [0; 7]).is_some());
+
+        for i in 0..8 {
+            let mut c = chunks;
+            c[i] += 1;
+
+            assert!(!valid_chunks(&c));
+            assert!(ReceivedFrame::try_new(c, [0; 7]).is_none());
+        }
+    }
+}
@@ -5,6 +5,8 @@ use std::f32::consts::PI;
 use collect_slice::CollectSlice;
 
 use descramble::QuantizedAmplitudes;
+#[cfg(not(feature = "std"))]
+use math::Float;
 use params::BaseParams;
 use allocs::allocs;
 
@@ -55,7 +57,7 @@ impl Gains {
 
 /// Each STEPS[l][i] represents the step size Δ<sub>i+3</sub> = Δ<sub>m</sub>, 3 ≤ m ≤ 7,
 /// for the harmonics parameter l = L - 9.
-static STEPS: [[f32; 5]; 48] = [
+pub static STEPS: [[f32; 5]; 48] = [
     [0.003100, 0.004020, 0.003360, 0.002900, 0.002640],
     [0.006200, 0.004020, 0.006720, 0.005800, 0.005280],
     [0.012400, 0.008040, 0.006720, 0.011600, 0.010560],
@@ -108,7 +110,7 @@ static STEPS: [[f32; 5]; 48] = [
 
 /// Each GAIN[b<sub>2</sub>] represents the first gain value G<sub>1</sub> for the index
 /// b<sub>2</sub>.
-const GAIN: [f32; 64] = [
+pub const GAIN: [f32; 64] = [
     -2.842205,
     -2.694235,
     -2.558260,
@@ -1,30 +1,69 @@
 //! Decode the Improved Multi-Band Excitation (IMBE) digital voice codec.
 
 #![feature(inclusive_range_syntax)]
+#![cfg_attr(feature = "simd", feature(stdsimd, target_feature, cfg_target_feature))]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate core as std;
 
 extern crate arrayvec;
 extern crate collect_slice;
+#[cfg(feature = "threads")]
 extern crate crossbeam;
 extern crate map_in_place;
+#[cfg(feature = "std")]
 extern crate num;
+#[cfg(feature = "std")]
 extern crate iq_osc;
+#[cfg(feature = "std")]
 extern crate rand;
 
 pub mod allocs;
+pub mod channel;
 pub mod coefs;
+#[cfg(feature = "std")]
+pub mod conformance;
 pub mod consts;
 pub mod decode;
 pub mod descramble;
 pub mod enhance;
+#[cfg(feature = "std")]
+pub mod export;
+pub mod fec;
+#[cfg(feature = "fixed")]
+pub mod fixed;
 pub mod frame;
 pub mod gain;
+pub mod math;
+pub mod noise;
 pub mod params;
+pub mod postfilter;
 pub mod prev;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod quality;
+pub mod quantize;
+pub mod resample;
+pub mod rng;
+pub mod sample;
 pub mod scan;
+pub mod simd;
 pub mod spectral;
+pub mod stats;
+pub mod stream;
+pub mod timing;
+pub mod tone;
+pub mod transform;
 pub mod unvoiced;
+pub mod vad;
 pub mod voiced;
 pub mod window;
 
-pub use decode::ImbeDecoder;
+pub use decode::{ImbeDecoder, DecoderConfig, Synthesis};
 pub use frame::ReceivedFrame;
+pub use resample::SampleRate;
+pub use stats::FrameStatus;
+pub use stream::DecodeExt;
+pub use timing::TimeStretch;
+pub use transform::VoiceTransform;
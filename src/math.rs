@@ -0,0 +1,500 @@
+//! Floating-point math for targets without the standard library.
+//!
+//! The standard library provides transcendental and rounding functions on `f32` by
+//! linking to the platform's libm, which isn't available to `no_std` builds. This module
+//! provides portable implementations of the functions used by the decoder through the
+//! `Float` trait, which each module imports only when the `std` feature is disabled. The
+//! same method syntax then resolves to the standard library's inherent methods when it's
+//! available.
+//!
+//! Functions are evaluated in double precision with range reduction and truncated series
+//! in the style of libm, so results are accurate to within a few ULP of single precision.
+
+use std;
+use std::f64::consts::{LN_2, FRAC_PI_2};
+
+/// Floating-point functions provided by the standard library.
+pub trait Float: Sized {
+    fn abs(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn trunc(self) -> Self;
+    fn fract(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn exp2(self) -> Self;
+    fn ln(self) -> Self;
+    fn log2(self) -> Self;
+    fn powf(self, y: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn max(self, y: Self) -> Self;
+    fn min(self, y: Self) -> Self;
+    fn is_nan(self) -> bool;
+    fn is_finite(self) -> bool;
+}
+
+impl Float for f32 {
+    fn abs(self) -> f32 {
+        from_bits(to_bits(self) & 0x7fffffff)
+    }
+
+    fn floor(self) -> f32 {
+        let t = Float::trunc(self);
+
+        if t > self { t - 1.0 } else { t }
+    }
+
+    fn ceil(self) -> f32 {
+        let t = Float::trunc(self);
+
+        if t < self { t + 1.0 } else { t }
+    }
+
+    fn round(self) -> f32 {
+        let t = Float::trunc(self);
+
+        // Round half away from zero.
+        if Float::abs(self - t) >= 0.5 {
+            if self < 0.0 { t - 1.0 } else { t + 1.0 }
+        } else {
+            t
+        }
+    }
+
+    fn trunc(self) -> f32 {
+        // Values this large have no fractional bits, and NaN passes through.
+        if !(Float::abs(self) < 8388608.0) {
+            return self;
+        }
+
+        self as i32 as f32
+    }
+
+    fn fract(self) -> f32 { self - Float::trunc(self) }
+
+    fn sqrt(self) -> f32 {
+        if self < 0.0 {
+            return std::f32::NAN;
+        }
+
+        if self == 0.0 || !(self < std::f32::INFINITY) {
+            return self;
+        }
+
+        let x = self as f64;
+
+        // Start from a guess that halves the exponent, then refine with Newton's method.
+        let mut y = from_bits64((to_bits64(x) >> 1) + (1023 << 51));
+
+        for _ in 0..5 {
+            y = 0.5 * (y + x / y);
+        }
+
+        y as f32
+    }
+
+    fn exp(self) -> f32 { exp2_64(self as f64 / LN_2) as f32 }
+
+    fn exp2(self) -> f32 { exp2_64(self as f64) as f32 }
+
+    fn ln(self) -> f32 { (log2_64(self as f64) * LN_2) as f32 }
+
+    fn log2(self) -> f32 { log2_64(self as f64) as f32 }
+
+    fn powf(self, y: f32) -> f32 {
+        if y == 0.0 || self == 1.0 {
+            return 1.0;
+        }
+
+        if Float::is_nan(self) || Float::is_nan(y) {
+            return std::f32::NAN;
+        }
+
+        if self < 0.0 {
+            // Negative bases only have real powers for integer exponents.
+            if Float::trunc(y) != y {
+                return std::f32::NAN;
+            }
+
+            let mag = exp2_64(y as f64 * log2_64(-self as f64)) as f32;
+
+            return if Float::trunc(y * 0.5) == y * 0.5 { mag } else { -mag };
+        }
+
+        exp2_64(y as f64 * log2_64(self as f64)) as f32
+    }
+
+    fn powi(self, n: i32) -> f32 {
+        let mut base = self;
+        let mut exp = if n < 0 { -(n as i64) } else { n as i64 };
+        let mut acc = 1.0;
+
+        // Compute by repeated squaring.
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc *= base;
+            }
+
+            base *= base;
+            exp >>= 1;
+        }
+
+        if n < 0 { 1.0 / acc } else { acc }
+    }
+
+    fn sin(self) -> f32 {
+        let (quad, r) = reduce(self as f64);
+
+        (match quad {
+            0 => sin_poly(r),
+            1 => cos_poly(r),
+            2 => -sin_poly(r),
+            _ => -cos_poly(r),
+        }) as f32
+    }
+
+    fn cos(self) -> f32 {
+        let (quad, r) = reduce(self as f64);
+
+        (match quad {
+            0 => cos_poly(r),
+            1 => -sin_poly(r),
+            2 => -cos_poly(r),
+            _ => sin_poly(r),
+        }) as f32
+    }
+
+    fn max(self, y: f32) -> f32 {
+        if Float::is_nan(self) || y > self { y } else { self }
+    }
+
+    fn min(self, y: f32) -> f32 {
+        if Float::is_nan(self) || y < self { y } else { self }
+    }
+
+    fn is_nan(self) -> bool { f32::is_nan(self) }
+
+    fn is_finite(self) -> bool {
+        // Infinities and NaNs have every exponent bit set.
+        to_bits(self) & 0x7f800000 != 0x7f800000
+    }
+}
+
+/// Reinterpret the bits of the given `f32` as an integer.
+fn to_bits(x: f32) -> u32 { unsafe { std::mem::transmute(x) } }
+
+/// Reinterpret the given integer as the bits of an `f32`.
+fn from_bits(x: u32) -> f32 { unsafe { std::mem::transmute(x) } }
+
+/// Reinterpret the bits of the given `f64` as an integer.
+fn to_bits64(x: f64) -> u64 { unsafe { std::mem::transmute(x) } }
+
+/// Reinterpret the given integer as the bits of an `f64`.
+fn from_bits64(x: u64) -> f64 { unsafe { std::mem::transmute(x) } }
+
+/// Compute 2<sup>x</sup> in double precision.
+fn exp2_64(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    }
+
+    // Handle results outside the range of single precision.
+    if x > 128.0 {
+        return std::f64::INFINITY;
+    }
+
+    if x < -150.0 {
+        return 0.0;
+    }
+
+    // Split into integer part n and fraction f, 0 ≤ f < 1, so 2^x = 2^n 2^f.
+    let mut n = x as i64;
+
+    if n as f64 > x {
+        n -= 1;
+    }
+
+    let f = (x - n as f64) * LN_2;
+
+    // Compute e^f with its Taylor series, which converges quickly for 0 ≤ f < ln 2.
+    let mut term = 1.0;
+    let mut sum = 1.0;
+
+    for k in 1..16 {
+        term *= f / k as f64;
+        sum += term;
+    }
+
+    sum * from_bits64(((n + 1023) as u64) << 52)
+}
+
+/// Compute log<sub>2</sub>(x) in double precision.
+fn log2_64(x: f64) -> f64 {
+    if x.is_nan() || x < 0.0 {
+        return std::f64::NAN;
+    }
+
+    if x == 0.0 {
+        return std::f64::NEG_INFINITY;
+    }
+
+    if x == std::f64::INFINITY {
+        return x;
+    }
+
+    // Split into exponent e and mantissa m, 1 ≤ m < 2, so log2(x) = e + log2(m).
+    let bits = to_bits64(x);
+    let mut e = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mut m = from_bits64(bits & 0x000fffffffffffff | 0x3ff0000000000000);
+
+    // Center the mantissa around 1 for faster convergence.
+    if m > std::f64::consts::SQRT_2 {
+        m *= 0.5;
+        e += 1;
+    }
+
+    // Compute ln(m) = 2 atanh(s) for s = (m - 1) / (m + 1), where |s| < 0.172.
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+
+    let mut term = s;
+    let mut sum = 0.0;
+
+    for k in 0..10 {
+        sum += term / (2 * k + 1) as f64;
+        term *= s2;
+    }
+
+    e as f64 + 2.0 * sum / LN_2
+}
+
+/// Reduce the given angle x to r = x - kπ/2, |r| ≤ π/4, returning the quadrant k mod 4
+/// and r.
+fn reduce(x: f64) -> (u8, f64) {
+    // Split π/2 into high and low parts so k π/2 can be subtracted without losing
+    // precision for large k.
+    const PIO2_HI: f64 = 1.57079632673412561417e+00;
+    const PIO2_LO: f64 = 6.07710050650619224932e-11;
+
+    let q = x / FRAC_PI_2;
+    let k = if q < 0.0 { (q - 0.5) as i64 } else { (q + 0.5) as i64 };
+
+    let r = (x - k as f64 * PIO2_HI) - k as f64 * PIO2_LO;
+
+    ((k & 3) as u8, r)
+}
+
+/// Compute sin(r) for |r| ≤ π/4 with its Taylor series.
+fn sin_poly(r: f64) -> f64 {
+    let r2 = r * r;
+    let mut term = r;
+    let mut sum = r;
+
+    for k in 1..8 {
+        term *= -r2 / ((2 * k) * (2 * k + 1)) as f64;
+        sum += term;
+    }
+
+    sum
+}
+
+/// Compute cos(r) for |r| ≤ π/4 with its Taylor series.
+fn cos_poly(r: f64) -> f64 {
+    let r2 = r * r;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+
+    for k in 1..8 {
+        term *= -r2 / ((2 * k - 1) * (2 * k)) as f64;
+        sum += term;
+    }
+
+    sum
+}
+
+/// Minimal complex number type used for unvoiced synthesis without the standard library.
+#[cfg(not(feature = "std"))]
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct Complex32 {
+    pub re: f32,
+    pub im: f32,
+}
+
+#[cfg(not(feature = "std"))]
+impl Complex32 {
+    /// Create a new `Complex32` with the given real and imaginary parts.
+    pub fn new(re: f32, im: f32) -> Complex32 {
+        Complex32 {
+            re: re,
+            im: im,
+        }
+    }
+
+    /// Create a new `Complex32` equal to 0.
+    pub fn zero() -> Complex32 { Complex32::new(0.0, 0.0) }
+
+    /// Compute the squared magnitude.
+    pub fn norm_sqr(&self) -> f32 { self.re * self.re + self.im * self.im }
+}
+
+#[cfg(not(feature = "std"))]
+impl std::ops::Mul<Complex32> for f32 {
+    type Output = Complex32;
+
+    fn mul(self, x: Complex32) -> Complex32 { Complex32::new(self * x.re, self * x.im) }
+}
+
+/// Generates samples of a complex sinusoid with the given initial phase and phase step,
+/// without the standard library.
+#[cfg(not(feature = "std"))]
+pub struct IQOsc {
+    /// Current (sin, cos) values.
+    cur: (f64, f64),
+    /// (sin, cos) of the phase step.
+    step: (f64, f64),
+}
+
+#[cfg(not(feature = "std"))]
+impl IQOsc {
+    /// Create a new `IQOsc` starting at the given phase and advancing by the given step
+    /// each sample.
+    pub fn new(phase: f32, step: f32) -> IQOsc {
+        IQOsc {
+            cur: (phase.sin() as f64, phase.cos() as f64),
+            step: (step.sin() as f64, step.cos() as f64),
+        }
+    }
+
+    /// Retrieve the current (sin, cos) values and advance the phase.
+    pub fn next(&mut self) -> (f32, f32) {
+        let (s, c) = self.cur;
+        let (ss, cs) = self.step;
+
+        // Rotate by the phase step using the angle sum identities.
+        self.cur = (s * cs + c * ss, c * cs - s * ss);
+
+        (s as f32, c as f32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// Check the given result against the standard library's with the given relative
+    /// tolerance.
+    fn check(got: f32, expected: f32, tol: f32) {
+        if expected.is_nan() {
+            assert!(got.is_nan());
+        } else if expected.is_infinite() || expected == 0.0 {
+            assert_eq!(got, expected);
+        } else {
+            assert!((got - expected).abs() <= tol * expected.abs().max(1e-30),
+                    "{} != {}", got, expected);
+        }
+    }
+
+    #[test]
+    fn test_rounding() {
+        for &x in [0.0, 0.4, 0.5, 0.6, 1.0, 1.5, 2.5, -0.4, -0.5, -0.6, -1.0, -1.5, -2.5,
+                   123.456, -123.456, 8388607.5, 1e10, -1e10, 0.49999997].iter()
+        {
+            let x: f32 = x;
+
+            assert_eq!(Float::floor(x), x.floor());
+            assert_eq!(Float::ceil(x), x.ceil());
+            assert_eq!(Float::round(x), x.round());
+            assert_eq!(Float::trunc(x), x.trunc());
+            assert_eq!(Float::fract(x), x.fract());
+            assert_eq!(Float::abs(x), x.abs());
+        }
+
+        assert!(Float::floor(std::f32::NAN).is_nan());
+        assert_eq!(Float::floor(std::f32::INFINITY), std::f32::INFINITY);
+
+        for &x in [0.0, -1.5, 1e-40, std::f32::MAX, std::f32::MIN, std::f32::INFINITY,
+                   std::f32::NEG_INFINITY, std::f32::NAN].iter()
+        {
+            let x: f32 = x;
+            assert_eq!(Float::is_finite(x), x.is_finite());
+        }
+    }
+
+    #[test]
+    fn test_exp_log() {
+        for i in -2000..2000 {
+            let x = i as f32 * 0.0617;
+
+            check(Float::exp2(x), x.exp2(), 4e-7);
+            check(Float::exp(x), x.exp(), 4e-7);
+        }
+
+        for i in 1..4000 {
+            let x = i as f32 * 0.731;
+
+            check(Float::log2(x), x.log2(), 4e-7);
+            check(Float::ln(x), x.ln(), 4e-7);
+            check(Float::sqrt(x), x.sqrt(), 2e-7);
+
+            check(Float::log2(1.0 / x), (1.0 / x).log2(), 4e-7);
+            check(Float::sqrt(1.0 / x), (1.0 / x).sqrt(), 2e-7);
+        }
+
+        check(Float::log2(0.0f32), std::f32::NEG_INFINITY, 0.0);
+        check(Float::log2(-1.0f32), std::f32::NAN, 0.0);
+        check(Float::sqrt(-1.0f32), std::f32::NAN, 0.0);
+        check(Float::exp2(200.0f32), std::f32::INFINITY, 0.0);
+        check(Float::exp2(-200.0f32), 0.0, 0.0);
+    }
+
+    #[test]
+    fn test_pow() {
+        for &(x, y) in [(2.0, 0.25), (75000.0, 0.375), (1e-3, 0.5), (3.0, -2.5),
+                        (10.0, 0.0), (0.0, 2.0), (-2.0, 3.0), (-2.0, 2.0),
+                        (-2.0, 0.5)].iter()
+        {
+            let (x, y): (f32, f32) = (x, y);
+            check(Float::powf(x, y), x.powf(y), 1e-6);
+        }
+
+        for n in -8..9 {
+            check(Float::powi(1.7f32, n), 1.7f32.powi(n), 1e-6);
+            check(Float::powi(-0.3f32, n), (-0.3f32).powi(n), 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_trig() {
+        for i in -5000..5000 {
+            let x = i as f32 * 0.0137;
+
+            assert!((Float::sin(x) - x.sin()).abs() < 1e-6);
+            assert!((Float::cos(x) - x.cos()).abs() < 1e-6);
+        }
+
+        // Verify large arguments, as from accumulated phases.
+        for &x in [1000.0, 12345.678, -54321.5, 1e6].iter() {
+            let x: f32 = x;
+
+            assert!((Float::sin(x) - x.sin()).abs() < 1e-6);
+            assert!((Float::cos(x) - x.cos()).abs() < 1e-6);
+        }
+
+        assert!(Float::sin(PI).abs() < 1e-6);
+        assert!((Float::cos(PI) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_minmax() {
+        assert_eq!(Float::max(1.0f32, 2.0), 2.0);
+        assert_eq!(Float::min(1.0f32, 2.0), 1.0);
+        assert_eq!(Float::max(std::f32::NAN, 2.0), 2.0);
+        assert_eq!(Float::min(2.0, std::f32::NAN), 2.0);
+        assert!(Float::is_nan(std::f32::NAN));
+        assert!(!Float::is_nan(1.0f32));
+    }
+}
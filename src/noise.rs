@@ -0,0 +1,433 @@
+//! White noise sources for unvoiced synthesis.
+//!
+//! The unvoiced signal is derived from the DFT U<sub>w</sub>(m) of a windowed white
+//! noise signal [p58]. Since Eq 120 normalizes the power of every unvoiced band, only
+//! the shape of the noise spectrum matters, and its scale is arbitrary. This allows a
+//! range of sources that trade conformance against speed:
+//!
+//! - `Polar` samples each DFT point from a Gaussian distribution with `Random::normal`
+//!   (see the `unvoiced` module for the derivation.) This is the Marsaglia polar method
+//!   for generators that only supply bits, and the ziggurat method of `rand` for every
+//!   `rand::Rng`.
+//! - `TableGaussian` samples Gaussian values from a precomputed quantile table, using a
+//!   single random number per value.
+//! - `UniformSum` approximates Gaussian values with the sum of 4 uniform values.
+//! - `StandardNoise` follows the standard exactly, windowing the example u(n) generator
+//!   on [p58] and computing its DFT directly, which is the slowest.
+
+use std::f32::consts::PI;
+
+#[cfg(feature = "std")]
+use num::complex::Complex32;
+
+#[cfg(not(feature = "std"))]
+use math::{Complex32, Float};
+use rng::Random;
+use window;
+
+/// Number of entries in the Gaussian quantile table.
+const TABLE_SIZE: usize = 1024;
+
+/// Number of samples n, -104 ≤ n ≤ 104, in the windowed u(n) signal.
+const NOISE_SAMPLES: usize = 209;
+
+/// Initial value u(-105) of the example noise generator [p58].
+const NOISE_INIT: u32 = 3147;
+
+/// A source of white noise spectrum.
+pub trait Noise {
+    /// Prepare to generate the noise spectrum of a new frame.
+    fn frame(&mut self) {}
+
+    /// Generate a value with mean 0 and variance 1.
+    fn sample<R: Random + ?Sized>(&mut self, rng: &mut R) -> f32;
+
+    /// Fill the given consecutive points U<sub>w</sub>(m) of the current frame's noise
+    /// spectrum, starting at the given m.
+    ///
+    /// By default, the real and imaginary parts of each point are drawn independently
+    /// from `sample`.
+    fn fill<R: Random + ?Sized>(&mut self, rng: &mut R, _start: usize,
+                                points: &mut [Complex32])
+    {
+        for x in points.iter_mut() {
+            let re = self.sample(rng);
+            let im = self.sample(rng);
+
+            *x = Complex32::new(re, im);
+        }
+    }
+}
+
+/// Samples Gaussian values with `Random::normal`, which defaults to the Marsaglia polar
+/// method.
+///
+/// Spectrum points are drawn with the variance E<sub>w</sub> / 2 derived in the
+/// `unvoiced` module.
+#[derive(Copy, Clone, Default)]
+pub struct Polar;
+
+impl Noise for Polar {
+    fn sample<R: Random + ?Sized>(&mut self, rng: &mut R) -> f32 { rng.normal(1.0) }
+
+    fn fill<R: Random + ?Sized>(&mut self, rng: &mut R, _start: usize,
+                                points: &mut [Complex32])
+    {
+        let sigma = (window::ENERGY_SYNTHESIS / 2.0).sqrt();
+
+        for x in points.iter_mut() {
+            *x = Complex32::new(rng.normal(sigma), rng.normal(sigma));
+        }
+    }
+}
+
+/// Samples Gaussian values from a table of quantiles.
+///
+/// Each value is the quantile at the center of one of 1024 equal-probability intervals,
+/// selected by the upper bits of a random number, so values are limited to about ±3.3σ.
+pub struct TableGaussian([f32; TABLE_SIZE]);
+
+impl TableGaussian {
+    /// Create a new `TableGaussian` by computing the quantile table.
+    pub fn new() -> TableGaussian {
+        let mut table = [0.0; TABLE_SIZE];
+
+        for (i, x) in table.iter_mut().enumerate() {
+            *x = quantile((i as f32 + 0.5) / TABLE_SIZE as f32);
+        }
+
+        // Rescale to unit variance, which truncating the tails slightly reduces.
+        let var = table.iter().fold(0.0, |s, &x| s + x * x) / TABLE_SIZE as f32;
+        let scale = 1.0 / var.sqrt();
+
+        for x in table.iter_mut() {
+            *x *= scale;
+        }
+
+        TableGaussian(table)
+    }
+}
+
+impl Default for TableGaussian {
+    fn default() -> TableGaussian { TableGaussian::new() }
+}
+
+impl Clone for TableGaussian {
+    fn clone(&self) -> TableGaussian { TableGaussian(self.0) }
+}
+
+impl Noise for TableGaussian {
+    fn sample<R: Random + ?Sized>(&mut self, rng: &mut R) -> f32 {
+        self.0[(rng.next_u32() >> 22) as usize]
+    }
+}
+
+/// Approximates Gaussian values with the scaled sum of 4 uniform values, taken as the
+/// 16-bit halves of 2 random numbers.
+#[derive(Copy, Clone, Default)]
+pub struct UniformSum;
+
+impl Noise for UniformSum {
+    fn sample<R: Random + ?Sized>(&mut self, rng: &mut R) -> f32 {
+        let (a, b) = (rng.next_u32(), rng.next_u32());
+
+        // Each half, offset by ½, is uniform over (0, 65536) with variance
+        // 65536^2 / 12, so the sum has mean 131072 and variance 65536^2 / 3.
+        let sum = (a & 0xffff) + (a >> 16) + (b & 0xffff) + (b >> 16) + 2;
+
+        (sum as f32 - 131072.0) / 65536.0 * 3.0f32.sqrt()
+    }
+}
+
+/// Generates noise with the standard's example u(n) generator [p58].
+///
+/// The sequence continues from frame to frame, starting from u(-105) = 3147. Each
+/// frame's spectrum is computed with the DFT of Eq 118 from the windowed samples
+/// w<sub>s</sub>(n) u(n), -104 ≤ n ≤ 104, so the `Random` generator is unused.
+pub struct StandardNoise {
+    /// Most recently generated u(n).
+    state: u32,
+    /// Windowed samples of the current frame.
+    windowed: [f32; NOISE_SAMPLES],
+    /// Values cos(2πk / 256), 0 ≤ k < 256.
+    cos: [f32; 256],
+}
+
+impl StandardNoise {
+    /// Create a new `StandardNoise` at the start of the sequence.
+    pub fn new() -> StandardNoise {
+        let mut cos = [0.0; 256];
+
+        for (k, x) in cos.iter_mut().enumerate() {
+            *x = (2.0 * PI * k as f32 / 256.0).cos();
+        }
+
+        StandardNoise {
+            state: NOISE_INIT,
+            windowed: [0.0; NOISE_SAMPLES],
+            cos: cos,
+        }
+    }
+
+    /// Generate the next u(n).
+    fn next(&mut self) -> u32 {
+        // Compute u(n + 1) = 171 u(n) + 11213 - 53125 ⌊(171 u(n) + 11213) / 53125⌋.
+        self.state = (171 * self.state + 11213) % 53125;
+        self.state
+    }
+}
+
+impl Default for StandardNoise {
+    fn default() -> StandardNoise { StandardNoise::new() }
+}
+
+impl Clone for StandardNoise {
+    fn clone(&self) -> StandardNoise {
+        StandardNoise {
+            state: self.state,
+            windowed: self.windowed,
+            cos: self.cos,
+        }
+    }
+}
+
+impl Noise for StandardNoise {
+    fn frame(&mut self) {
+        let win = window::synthesis();
+
+        for i in 0..NOISE_SAMPLES {
+            let n = i as isize - NOISE_SAMPLES as isize / 2;
+            self.windowed[i] = win.get(n) * self.next() as f32;
+        }
+    }
+
+    fn sample<R: Random + ?Sized>(&mut self, _: &mut R) -> f32 {
+        // Normalize the uniform value to unit variance.
+        (self.next() as f32 - 26562.0) / (53125.0 / 12.0f32.sqrt())
+    }
+
+    fn fill<R: Random + ?Sized>(&mut self, _: &mut R, start: usize,
+                                points: &mut [Complex32])
+    {
+        for (m, x) in (start..).zip(points.iter_mut()) {
+            let (mut re, mut im) = (0.0, 0.0);
+
+            // Compute Eq 118, U_w(m) = Σ w_s(n) u(n) exp(-j 2π mn / 256), with each
+            // angle reduced to an exact table index.
+            for (i, &w) in self.windowed.iter().enumerate() {
+                let n = i as isize - NOISE_SAMPLES as isize / 2;
+                let k = ((m as isize * n) % 256 + 256) as usize % 256;
+
+                // Use sin(θ) = cos(θ - π/2).
+                re += w * self.cos[k];
+                im -= w * self.cos[(k + 192) % 256];
+            }
+
+            *x = Complex32::new(re, im);
+        }
+    }
+}
+
+/// Selects one of the built-in noise sources.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseKind {
+    /// Gaussian values from `Random::normal`.
+    Polar,
+    /// Gaussian values from a quantile table.
+    Table,
+    /// Gaussian approximation from uniform values.
+    Uniform,
+    /// The standard's example u(n) generator.
+    Standard,
+}
+
+impl Default for NoiseKind {
+    /// Use `Random::normal` by default.
+    fn default() -> NoiseKind { NoiseKind::Polar }
+}
+
+/// One of the built-in noise sources, with its state.
+#[derive(Clone)]
+pub enum NoiseSource {
+    /// Source selected by `NoiseKind::Polar`.
+    Polar(Polar),
+    /// Source selected by `NoiseKind::Table`.
+    Table(TableGaussian),
+    /// Source selected by `NoiseKind::Uniform`.
+    Uniform(UniformSum),
+    /// Source selected by `NoiseKind::Standard`.
+    Standard(StandardNoise),
+}
+
+impl NoiseSource {
+    /// Create a new `NoiseSource` of the given kind in its initial state.
+    pub fn new(kind: NoiseKind) -> NoiseSource {
+        match kind {
+            NoiseKind::Polar => NoiseSource::Polar(Polar),
+            NoiseKind::Table => NoiseSource::Table(TableGaussian::new()),
+            NoiseKind::Uniform => NoiseSource::Uniform(UniformSum),
+            NoiseKind::Standard => NoiseSource::Standard(StandardNoise::new()),
+        }
+    }
+
+    /// Retrieve the kind of the source.
+    pub fn kind(&self) -> NoiseKind {
+        match *self {
+            NoiseSource::Polar(_) => NoiseKind::Polar,
+            NoiseSource::Table(_) => NoiseKind::Table,
+            NoiseSource::Uniform(_) => NoiseKind::Uniform,
+            NoiseSource::Standard(_) => NoiseKind::Standard,
+        }
+    }
+}
+
+impl Noise for NoiseSource {
+    fn frame(&mut self) {
+        match *self {
+            NoiseSource::Polar(ref mut n) => n.frame(),
+            NoiseSource::Table(ref mut n) => n.frame(),
+            NoiseSource::Uniform(ref mut n) => n.frame(),
+            NoiseSource::Standard(ref mut n) => n.frame(),
+        }
+    }
+
+    fn sample<R: Random + ?Sized>(&mut self, rng: &mut R) -> f32 {
+        match *self {
+            NoiseSource::Polar(ref mut n) => n.sample(rng),
+            NoiseSource::Table(ref mut n) => n.sample(rng),
+            NoiseSource::Uniform(ref mut n) => n.sample(rng),
+            NoiseSource::Standard(ref mut n) => n.sample(rng),
+        }
+    }
+
+    fn fill<R: Random + ?Sized>(&mut self, rng: &mut R, start: usize,
+                                points: &mut [Complex32])
+    {
+        match *self {
+            NoiseSource::Polar(ref mut n) => n.fill(rng, start, points),
+            NoiseSource::Table(ref mut n) => n.fill(rng, start, points),
+            NoiseSource::Uniform(ref mut n) => n.fill(rng, start, points),
+            NoiseSource::Standard(ref mut n) => n.fill(rng, start, points),
+        }
+    }
+}
+
+/// Approximate the standard Gaussian quantile function at the given probability p,
+/// 0 < p < 1, with absolute error below 4.5×10<sup>-4</sup>.
+fn quantile(p: f32) -> f32 {
+    // Use the rational approximation of Abramowitz and Stegun 26.2.23 on the lower half
+    // and symmetry on the upper half.
+    let q = if p < 0.5 { p } else { 1.0 - p };
+    let t = (-2.0 * q.ln()).sqrt();
+
+    let x = t - (2.515517 + 0.802853 * t + 0.010328 * t * t) /
+        (1.0 + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t);
+
+    if p < 0.5 { -x } else { x }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use rng::XorShift;
+
+    /// Compute the sample mean and variance of the given source.
+    fn moments<N: Noise>(noise: &mut N) -> (f32, f32) {
+        let mut r = XorShift::new([1, 2, 3, 4]);
+        let (mut sum, mut sqr) = (0.0f64, 0.0f64);
+
+        for _ in 0..20000 {
+            let x = noise.sample(&mut r) as f64;
+            assert!(x.is_finite());
+
+            sum += x;
+            sqr += x * x;
+        }
+
+        let mean = sum / 20000.0;
+
+        (mean as f32, (sqr / 20000.0 - mean * mean) as f32)
+    }
+
+    #[test]
+    fn test_moments() {
+        for &kind in [NoiseKind::Polar, NoiseKind::Table, NoiseKind::Uniform,
+                      NoiseKind::Standard].iter()
+        {
+            let mut n = NoiseSource::new(kind);
+            assert_eq!(n.kind(), kind);
+
+            let (mean, var) = moments(&mut n);
+
+            assert!(mean.abs() < 0.05);
+            assert!((var - 1.0).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_quantile() {
+        assert!(quantile(0.5).abs() < 1e-3);
+        assert!((quantile(0.8413447) - 1.0).abs() < 1e-3);
+        assert!((quantile(0.0227501) + 2.0).abs() < 1e-3);
+        assert!((quantile(0.1) + quantile(0.9)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_table() {
+        let t = TableGaussian::new();
+
+        for i in 0..TABLE_SIZE / 2 {
+            assert!((t.0[i] + t.0[TABLE_SIZE - 1 - i]).abs() < 1e-5);
+            assert!(t.0[i] < t.0[i + 1]);
+        }
+    }
+
+    #[test]
+    fn test_standard_sequence() {
+        let mut n = StandardNoise::new();
+
+        // u(-104) = 171 * 3147 + 11213 mod 53125.
+        assert_eq!(n.next(), 18100);
+        assert_eq!(n.next(), (171 * 18100 + 11213) % 53125);
+
+        // Sequence stays in range and continues across frames.
+        let mut n = StandardNoise::new();
+        n.frame();
+        let first = n.windowed;
+        n.frame();
+
+        assert!(first[..] != n.windowed[..]);
+        assert!(n.state < 53125);
+    }
+
+    #[test]
+    fn test_standard_dft() {
+        let mut n = StandardNoise::new();
+        let mut r = XorShift::default();
+        let mut points = [Complex32::new(0.0, 0.0); 40];
+
+        n.frame();
+        n.fill(&mut r, 10, &mut points);
+
+        let win = window::synthesis();
+        let mut u = NOISE_INIT as f64;
+
+        let samples = (-104..105).map(|n| {
+            u = (171.0 * u + 11213.0) % 53125.0;
+            (n, win.get(n) as f64 * u)
+        }).collect::<Vec<_>>();
+
+        for (m, x) in (10..).zip(points.iter()) {
+            let (re, im) = samples.iter().fold((0.0, 0.0), |(re, im), &(n, w)| {
+                let phase = 2.0 * std::f64::consts::PI * (m * n) as f64 / 256.0;
+                (re + w * phase.cos(), im - w * phase.sin())
+            });
+
+            let mag = (re * re + im * im).sqrt();
+
+            assert!((x.re as f64 - re).abs() < 1e-4 * mag + 1.0);
+            assert!((x.im as f64 - im).abs() < 1e-4 * mag + 1.0);
+        }
+    }
+}
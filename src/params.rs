@@ -3,6 +3,9 @@
 use std::cmp::min;
 use std::f32::consts::PI;
 
+#[cfg(not(feature = "std"))]
+use math::Float;
+
 /// Basic parameters of the current frame.
 #[derive(Copy, Clone)]
 pub struct BaseParams {
@@ -27,7 +30,12 @@ impl BaseParams {
     /// Create a new `BaseParams` from the given floating-point period b<sub>0</sub>.
     fn from_float(period: f32) -> BaseParams {
         // Compute Eq 46.
-        let f = 4.0 * PI / (period + 39.5);
+        Self::from_fundamental(4.0 * PI / (period + 39.5))
+    }
+
+    /// Create a new `BaseParams` from the given fundamental frequency ω<sub>0</sub>,
+    /// which must be within the range produced by 0 ≤ b<sub>0</sub> ≤ 207.
+    pub fn from_fundamental(f: f32) -> BaseParams {
         // Compute Eq 47.
         let h = (0.9254 * (PI / f + 0.25).floor()) as u32;
         // Compute Eq 48.
@@ -56,6 +64,7 @@ impl Default for BaseParams {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use consts::{MIN_HARMONICS, MAX_HARMONICS};
 
     #[test]
     fn test_params() {
@@ -77,4 +86,27 @@ mod tests {
         assert_eq!(p.harmonics, 33);
         assert_eq!(p.bands, 11);
     }
+
+    #[test]
+    fn test_all_periods() {
+        let mut prev = BaseParams::new(0);
+
+        for period in 0...207 {
+            let p = BaseParams::new(period);
+            let h = p.harmonics as usize;
+
+            assert!(h >= MIN_HARMONICS && h <= MAX_HARMONICS);
+            assert!(p.bands >= 3 && p.bands <= 12);
+            assert_eq!(p.bands, min((p.harmonics + 2) / 3, 12));
+
+            // Harmonics must stay below the 4kHz Nyquist frequency.
+            assert!(p.fundamental * p.harmonics as f32 <= PI);
+
+            // Longer periods lower the fundamental and add harmonics.
+            assert!(p.fundamental <= prev.fundamental);
+            assert!(p.harmonics >= prev.harmonics);
+
+            prev = p;
+        }
+    }
 }
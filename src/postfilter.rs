@@ -0,0 +1,248 @@
+//! Perceptual post-filtering of enhanced spectral amplitudes.
+//!
+//! This stage isn't part of the standard. It runs after adaptive smoothing and reshapes
+//! the enhanced amplitudes "overbar" M<sub>l</sub> to counter the muffled sound of
+//! speech decoded from noisy channels, in three passes:
+//!
+//! 1. Formant sharpening: the log spectral envelope is estimated by smoothing log
+//!    amplitudes over neighboring harmonics, and its overall tilt is removed with a
+//!    least-squares line. Harmonics where the envelope rises above the line (formants)
+//!    are boosted and those where it falls below (valleys) are attenuated.
+//! 2. Low-frequency boost: harmonics below 500Hz are raised, tapering to unity gain.
+//! 3. Energy normalization: amplitudes are rescaled so the frame has the same energy as
+//!    before filtering, then limited again by the amplitude threshold τ<sub>M</sub> of
+//!    adaptive smoothing, since flattening the spectrum can raise the amplitude sum.
+//!
+//! Each pass is scaled by a strength parameter, where 0 leaves the amplitudes unchanged
+//! and 1 is the nominal full effect.
+
+use std;
+use std::f32::consts::PI;
+
+use map_in_place::MapInPlace;
+
+use consts::MAX_HARMONICS;
+use enhance::{self, EnhancedSpectrals};
+#[cfg(not(feature = "std"))]
+use math::Float;
+use params::BaseParams;
+
+/// Half-width of the envelope smoothing window, in radians per sample (about 250Hz).
+const ENV_WIDTH: f32 = PI / 16.0;
+/// Exponent applied to the envelope deviation at full strength.
+const SHARPEN: f32 = 0.5;
+/// Limit of the sharpening gain, in natural log units, at full strength (about ±6dB).
+const SHARPEN_LIMIT: f32 = 0.7;
+/// Upper frequency of the low-frequency boost, in radians per sample (500Hz).
+const BOOST_FREQ: f32 = PI / 8.0;
+/// Extra gain applied at 0Hz by the low-frequency boost at full strength.
+const BOOST_GAIN: f32 = 0.4;
+
+/// Filter the given enhanced spectral amplitudes M<sub>l</sub> in place with the given
+/// strength, using the fundamental frequency of the given frame parameters and keeping
+/// the amplitude sum within the given spectral amplitude threshold τ<sub>M</sub>.
+pub fn postfilter(enhanced: &mut EnhancedSpectrals, params: &BaseParams, strength: f32,
+                  amp_thresh: f32)
+{
+    if strength <= 0.0 || enhanced.is_empty() {
+        return;
+    }
+
+    let energy = enhanced.iter().fold(0.0, |s, &m| s + m * m);
+
+    if energy <= 0.0 {
+        return;
+    }
+
+    let weights = sharpen_weights(enhanced, params.fundamental, strength);
+
+    for (l, (m, &w)) in enhanced.iter_mut().zip(weights.iter()).enumerate() {
+        let freq = params.fundamental * (l + 1) as f32;
+        *m *= w * boost(freq, strength);
+    }
+
+    // Restore the frame energy.
+    let filtered = enhanced.iter().fold(0.0, |s, &m| s + m * m);
+    let scale = (energy / filtered).sqrt();
+
+    enhanced.map_in_place(|&m| m * scale);
+
+    // Keep within the clamp applied by adaptive smoothing.
+    enhance::limit(enhanced, amp_thresh);
+}
+
+/// Compute the formant sharpening gain of each harmonic from the log spectral envelope
+/// of the given amplitudes.
+fn sharpen_weights(amps: &[f32], fundamental: f32, strength: f32) -> [f32; MAX_HARMONICS] {
+    let mut logs = [0.0; MAX_HARMONICS];
+    let mut env = [0.0; MAX_HARMONICS];
+    let mut weights = [1.0; MAX_HARMONICS];
+
+    let count = amps.len();
+
+    for (log, &m) in logs.iter_mut().zip(amps.iter()) {
+        // Floor amplitudes so silent harmonics don't drag the envelope down.
+        *log = m.max(1e-3).ln();
+    }
+
+    // Number of neighboring harmonics on each side within the smoothing window.
+    let span = (ENV_WIDTH / fundamental) as usize;
+
+    for (l, e) in env.iter_mut().enumerate().take(count) {
+        let start = l.saturating_sub(span);
+        let stop = std::cmp::min(l + span + 1, count);
+
+        *e = logs[start..stop].iter().fold(0.0, |s, &x| s + x) / (stop - start) as f32;
+    }
+
+    let (slope, offset) = fit_line(&env[..count]);
+
+    for (l, w) in weights.iter_mut().enumerate().take(count) {
+        let dev = env[l] - (slope * l as f32 + offset);
+        let limit = SHARPEN_LIMIT * strength;
+
+        *w = (SHARPEN * strength * dev).max(-limit).min(limit).exp();
+    }
+
+    weights
+}
+
+/// Compute the gain of the low-frequency boost at the given frequency.
+fn boost(freq: f32, strength: f32) -> f32 {
+    if freq >= BOOST_FREQ {
+        1.0
+    } else {
+        1.0 + BOOST_GAIN * strength * (1.0 - freq / BOOST_FREQ)
+    }
+}
+
+/// Fit a least-squares line to the given points (i, y<sub>i</sub>), returning the slope
+/// and offset.
+fn fit_line(ys: &[f32]) -> (f32, f32) {
+    let n = ys.len() as f32;
+
+    if ys.len() < 2 {
+        return (0.0, ys.iter().fold(0.0, |s, &y| s + y) / n);
+    }
+
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = ys.iter().fold(0.0, |s, &y| s + y) / n;
+
+    let (cov, var) = ys.iter().enumerate().fold((0.0, 0.0), |(c, v), (i, &y)| {
+        let dx = i as f32 - mean_x;
+        (c + dx * (y - mean_y), v + dx * dx)
+    });
+
+    let slope = cov / var;
+
+    (slope, mean_y - slope * mean_x)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use descramble::{Bootstrap, descramble};
+    use enhance::{EnhancedSpectrals, FrameEnergy};
+    use frame::TEST_CHUNKS;
+    use gain::Gains;
+    use coefs::Coefficients;
+    use params::BaseParams;
+    use prev::PrevFrame;
+    use spectral::Spectrals;
+
+    fn energy(e: &EnhancedSpectrals) -> f32 {
+        e.iter().fold(0.0, |s, &m| s + m * m)
+    }
+
+    fn enhanced() -> (EnhancedSpectrals, BaseParams) {
+        let b = Bootstrap::new(&TEST_CHUNKS);
+        let p = BaseParams::new(b.unwrap_period());
+        let (amps, _, gain_idx) = descramble(&TEST_CHUNKS, &p);
+        let g = Gains::new(gain_idx, &amps, &p);
+        let c = Coefficients::new(&g, &amps, &p);
+        let prev = PrevFrame::default();
+        let s = Spectrals::new(&c, &p, &prev);
+        let fe = FrameEnergy::new(&s, &FrameEnergy::default(), &p);
+
+        (EnhancedSpectrals::new(&s, &fe, &p), p)
+    }
+
+    #[test]
+    fn test_fit_line() {
+        let (slope, offset) = fit_line(&[1.0, 3.0, 5.0, 7.0]);
+        assert!((slope - 2.0).abs() < 1e-6);
+        assert!((offset - 1.0).abs() < 1e-6);
+
+        assert_eq!(fit_line(&[4.0]), (0.0, 4.0));
+    }
+
+    #[test]
+    fn test_boost() {
+        assert!((boost(0.0, 1.0) - 1.4).abs() < 1e-6);
+        assert!((boost(0.0, 0.5) - 1.2).abs() < 1e-6);
+        assert!((boost(BOOST_FREQ / 2.0, 1.0) - 1.2).abs() < 1e-6);
+        assert_eq!(boost(BOOST_FREQ, 1.0), 1.0);
+        assert_eq!(boost(PI, 1.0), 1.0);
+    }
+
+    #[test]
+    fn test_postfilter() {
+        let (orig, p) = enhanced();
+
+        let mut e = orig.clone();
+        postfilter(&mut e, &p, 0.0, 20480.0);
+        assert_eq!(&e[..], &orig[..]);
+
+        let mut e = orig.clone();
+        postfilter(&mut e, &p, 1.0, 20480.0);
+
+        assert_eq!(e.len(), orig.len());
+        assert!(e.iter().all(|m| m.is_finite() && *m >= 0.0));
+        assert!((energy(&e) - energy(&orig)).abs() < energy(&orig) * 1e-4);
+
+        // Peaks of the envelope should stand out further from the valleys.
+        let ratio = |e: &EnhancedSpectrals| e.get(14) / e.get(4);
+        assert!(ratio(&e) > ratio(&orig));
+
+        // Gains should grow with strength.
+        let mut half = orig.clone();
+        postfilter(&mut half, &p, 0.5, 20480.0);
+        assert!(ratio(&half) > ratio(&orig));
+        assert!(ratio(&half) < ratio(&e));
+    }
+
+    #[test]
+    fn test_thresh() {
+        let p = BaseParams::new(32);
+        let ramp = || -> EnhancedSpectrals {
+            (0..p.harmonics).map(|l| (l + 1) as f32).collect()
+        };
+        let sum = |e: &EnhancedSpectrals| e.iter().fold(0.0, |s, &m| s + m);
+
+        let orig = ramp();
+        let thresh = sum(&orig);
+        assert_eq!(thresh, 136.0);
+
+        // Boosting the weak low harmonics flattens the ramp, which raises the sum once
+        // the energy is restored.
+        let mut e = ramp();
+        postfilter(&mut e, &p, 1.0, 20480.0);
+        assert!(sum(&e) > thresh + 1.0);
+
+        // The sum sits right at the clamp before filtering and must stay within it.
+        let mut e = ramp();
+        postfilter(&mut e, &p, 1.0, thresh);
+        assert!(sum(&e) <= thresh * (1.0 + 1e-6));
+        assert!(sum(&e) > thresh * (1.0 - 1e-6));
+        assert!(e.get(1) / e.get(16) > orig.get(1) / orig.get(16));
+    }
+
+    #[test]
+    fn test_silent() {
+        let (_, p) = enhanced();
+        let mut e = EnhancedSpectrals::default();
+
+        postfilter(&mut e, &p, 1.0, 20480.0);
+        assert!(e.is_empty());
+    }
+}
@@ -10,7 +10,10 @@ use voiced::{Phase, PhaseBase};
 /// Various parameters saved from the previous frame, used when constructing the current
 /// frame.
 pub struct PrevFrame {
+    /// Parameters used for synthesis, after any voice transform.
     pub params: BaseParams,
+    /// Parameters as decoded from the frame, used for spectral amplitude prediction.
+    pub model: BaseParams,
     pub spectrals: Spectrals,
     pub enhanced: EnhancedSpectrals,
     pub voice: VoiceDecisions,
@@ -28,6 +31,7 @@ impl Default for PrevFrame {
     fn default() -> PrevFrame {
         PrevFrame {
             params: BaseParams::default(),
+            model: BaseParams::default(),
             spectrals: Spectrals::default(),
             enhanced: EnhancedSpectrals::default(),
             voice: VoiceDecisions::default(),
@@ -0,0 +1,155 @@
+//! Per-stage timing of a running decoder.
+//!
+//! When the `profile` feature is enabled, `ImbeDecoder` measures the wall-clock time
+//! spent in each stage of decoding and accumulates it into a `Profile`, which can be
+//! inspected with `ImbeDecoder::profile`.
+
+use std::time::{Duration, Instant};
+
+/// Number of profiled stages.
+pub const NUM_STAGES: usize = 8;
+
+/// A stage of decoding a frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Descrambling quantized parameters from the frame chunks.
+    Descramble,
+    /// Computing gains (Eqs 66 through 70).
+    Gains,
+    /// Computing DCT coefficients (Eqs 71 through 74).
+    Coefficients,
+    /// Computing log spectral amplitudes (Eqs 75 through 79).
+    Spectrals,
+    /// Enhancing, smoothing, and postfiltering spectral amplitudes (Eqs 105 through
+    /// 116).
+    Enhance,
+    /// Computing the unvoiced noise spectrum (Eqs 117 through 126).
+    UnvoicedDft,
+    /// Computing voiced harmonic phases (Eqs 139 through 141).
+    Phase,
+    /// Synthesizing unvoiced and voiced samples (Eqs 127 through 142).
+    Synthesis,
+}
+
+impl Stage {
+    /// Retrieve every stage in decoding order.
+    pub fn all() -> [Stage; NUM_STAGES] {
+        [
+            Stage::Descramble,
+            Stage::Gains,
+            Stage::Coefficients,
+            Stage::Spectrals,
+            Stage::Enhance,
+            Stage::UnvoicedDft,
+            Stage::Phase,
+            Stage::Synthesis,
+        ]
+    }
+
+    /// Retrieve the index of the stage in `all()`.
+    fn idx(self) -> usize {
+        match self {
+            Stage::Descramble => 0,
+            Stage::Gains => 1,
+            Stage::Coefficients => 2,
+            Stage::Spectrals => 3,
+            Stage::Enhance => 4,
+            Stage::UnvoicedDft => 5,
+            Stage::Phase => 6,
+            Stage::Synthesis => 7,
+        }
+    }
+}
+
+/// Accumulates time spent in each decoding stage.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    /// Total time spent in each stage, indexed by `Stage::idx`.
+    elapsed: [Duration; NUM_STAGES],
+    /// Number of times each stage ran, indexed by `Stage::idx`.
+    calls: [u32; NUM_STAGES],
+}
+
+impl Profile {
+    /// Create a new `Profile` with no time recorded.
+    pub fn new() -> Profile {
+        Profile {
+            elapsed: [Duration::new(0, 0); NUM_STAGES],
+            calls: [0; NUM_STAGES],
+        }
+    }
+
+    /// Run the given closure, recording its running time under the given stage.
+    pub fn time<T, F: FnOnce() -> T>(&mut self, stage: Stage, f: F) -> T {
+        let start = Instant::now();
+        let ret = f();
+        self.record(stage, start.elapsed());
+
+        ret
+    }
+
+    /// Record the given running time under the given stage.
+    pub fn record(&mut self, stage: Stage, elapsed: Duration) {
+        self.elapsed[stage.idx()] += elapsed;
+        self.calls[stage.idx()] += 1;
+    }
+
+    /// Total time spent in the given stage.
+    pub fn elapsed(&self, stage: Stage) -> Duration {
+        self.elapsed[stage.idx()]
+    }
+
+    /// Number of times the given stage ran.
+    pub fn calls(&self, stage: Stage) -> u32 {
+        self.calls[stage.idx()]
+    }
+
+    /// Average time spent in each run of the given stage.
+    pub fn mean(&self, stage: Stage) -> Duration {
+        match self.calls(stage) {
+            0 => Duration::new(0, 0),
+            n => self.elapsed(stage) / n,
+        }
+    }
+
+    /// Total time spent in all stages.
+    pub fn total(&self) -> Duration {
+        self.elapsed.iter().fold(Duration::new(0, 0), |s, &d| s + d)
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Profile { Profile::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_profile() {
+        let mut p = Profile::new();
+
+        assert_eq!(p.total(), Duration::new(0, 0));
+        assert_eq!(p.mean(Stage::Gains), Duration::new(0, 0));
+
+        p.record(Stage::Gains, Duration::new(0, 100));
+        p.record(Stage::Gains, Duration::new(0, 300));
+        p.record(Stage::Synthesis, Duration::new(1, 0));
+
+        assert_eq!(p.calls(Stage::Gains), 2);
+        assert_eq!(p.calls(Stage::Synthesis), 1);
+        assert_eq!(p.calls(Stage::Phase), 0);
+        assert_eq!(p.elapsed(Stage::Gains), Duration::new(0, 400));
+        assert_eq!(p.mean(Stage::Gains), Duration::new(0, 200));
+        assert_eq!(p.total(), Duration::new(1, 400));
+
+        assert_eq!(p.time(Stage::Phase, || 42), 42);
+        assert_eq!(p.calls(Stage::Phase), 1);
+
+        for (i, &s) in Stage::all().iter().enumerate() {
+            assert_eq!(s.idx(), i);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! Objective speech-quality measures.
+//!
+//! These compare a decoded signal against a reference to quantify the effect of decoder
+//! options, such as enhancement, post-filtering, and concealment, without listening:
+//!
+//! - `segmental_snr` averages the signal-to-noise ratio of 20ms segments of PCM, which
+//!   weighs quiet and loud speech more evenly than a single SNR over the whole signal.
+//! - `log_spectral_distortion` averages the RMS difference in dB between the power
+//!   spectra of each segment, which ignores phase and so suits the random phases of
+//!   unvoiced and regenerated voiced synthesis better than SNR does.
+//! - `envelope_distance` compares the vocoder model directly through the RMS difference
+//!   in dB between spectral amplitudes M<sub>l</sub> of corresponding frames.
+//!
+//! Signals of different lengths are compared over their common length, and any
+//! trailing partial segment is ignored.
+
+use std;
+use std::f32::consts::{LN_10, PI};
+
+use consts::SAMPLES_PER_FRAME;
+#[cfg(not(feature = "std"))]
+use math::Float;
+use spectral::Spectrals;
+
+/// Number of samples in each compared segment.
+pub const SEGMENT: usize = SAMPLES_PER_FRAME;
+
+/// Upper limit of each segment's SNR, in dB.
+pub const MAX_SNR: f32 = 35.0;
+
+/// Lower limit of each segment's SNR, in dB.
+pub const MIN_SNR: f32 = -10.0;
+
+/// Dynamic range below each reference segment's spectral peak that's compared, in dB.
+pub const SPECTRAL_RANGE: f32 = 60.0;
+
+/// Number of points in the DFT of each segment.
+pub const DFT_SIZE: usize = 256;
+
+/// Number of DFT points from 0Hz to the Nyquist frequency.
+pub const DFT_HALF: usize = DFT_SIZE / 2 + 1;
+
+/// Convert the given power ratio to dB.
+fn db(ratio: f32) -> f32 {
+    // Compute 10 log10(x) = 10 ln(x) / ln(10).
+    10.0 * ratio.ln() / LN_10
+}
+
+/// Compute the segmental SNR of the given test signal relative to the given reference,
+/// in dB.
+///
+/// The SNR of each segment is limited to [`MIN_SNR`, `MAX_SNR`], so silent or perfectly
+/// matched segments don't dominate the average.
+pub fn segmental_snr(reference: &[f32], test: &[f32]) -> f32 {
+    let count = reference.len().min(test.len()) / SEGMENT;
+
+    if count == 0 {
+        return MAX_SNR;
+    }
+
+    let sum = reference.chunks(SEGMENT).zip(test.chunks(SEGMENT)).take(count)
+        .map(|(r, t)| {
+            let signal = r.iter().fold(0.0, |s, &x| s + x * x);
+            let noise = r.iter().zip(t.iter())
+                .fold(0.0, |s, (&x, &y)| s + (x - y) * (x - y));
+
+            if noise == 0.0 {
+                MAX_SNR
+            } else {
+                db(signal / noise).max(MIN_SNR).min(MAX_SNR)
+            }
+        })
+        .fold(0.0, |s, x| s + x);
+
+    sum / count as f32
+}
+
+/// Compute the log-spectral distortion of the given test signal relative to the given
+/// reference, in dB.
+///
+/// Each segment is Hann windowed and transformed with a 256-point DFT. Power below
+/// `SPECTRAL_RANGE` under the reference segment's peak is raised to that floor in both
+/// spectra, so the measure is independent of signal scale and unaffected by differences
+/// in inaudible detail. Silent reference segments are skipped.
+pub fn log_spectral_distortion(reference: &[f32], test: &[f32]) -> f32 {
+    let count = reference.len().min(test.len()) / SEGMENT;
+    let dft = Dft::new();
+
+    let mut sum = 0.0;
+    let mut used = 0;
+
+    for (r, t) in reference.chunks(SEGMENT).zip(test.chunks(SEGMENT)).take(count) {
+        let pr = dft.power(r);
+        let pt = dft.power(t);
+
+        let peak = pr.iter().fold(0.0f32, |m, &p| m.max(p));
+
+        if peak <= 0.0 {
+            continue;
+        }
+
+        // Compute the power floor, 10^(-range / 10) below the peak.
+        let floor = peak * (-SPECTRAL_RANGE / 10.0 * LN_10).exp();
+
+        let dist = pr.iter().zip(pt.iter()).map(|(&a, &b)| {
+            let d = db(a.max(floor) / b.max(floor));
+            d * d
+        }).fold(0.0, |s, x| s + x) / DFT_HALF as f32;
+
+        sum += dist.sqrt();
+        used += 1;
+    }
+
+    if used == 0 {
+        0.0
+    } else {
+        sum / used as f32
+    }
+}
+
+/// Compute the RMS difference, in dB, between the given spectral amplitudes of a single
+/// frame.
+///
+/// Amplitudes are compared over the harmonics common to both frames, and non-positive
+/// amplitudes are treated as the smallest positive value.
+pub fn frame_envelope_distance(reference: &[f32], test: &[f32]) -> f32 {
+    let count = reference.len().min(test.len());
+
+    if count == 0 {
+        return 0.0;
+    }
+
+    let sum = reference.iter().zip(test.iter()).map(|(&a, &b)| {
+        // Compute 20 log10 of the amplitude ratio.
+        let d = 2.0 * db(a.max(std::f32::MIN_POSITIVE) / b.max(std::f32::MIN_POSITIVE));
+        d * d
+    }).fold(0.0, |s, x| s + x);
+
+    (sum / count as f32).sqrt()
+}
+
+/// Compute the mean envelope distance, in dB, between corresponding frames of the given
+/// spectral amplitude sequences.
+pub fn envelope_distance(reference: &[Spectrals], test: &[Spectrals]) -> f32 {
+    let count = reference.len().min(test.len());
+
+    if count == 0 {
+        return 0.0;
+    }
+
+    reference.iter().zip(test.iter())
+        .map(|(a, b)| frame_envelope_distance(a, b))
+        .fold(0.0, |s, x| s + x) / count as f32
+}
+
+/// Computes power spectra of windowed segments.
+pub struct Dft {
+    /// Values cos(2πk / N), 0 ≤ k < N.
+    cos: [f32; DFT_SIZE],
+    /// Hann window over a segment.
+    window: [f32; SEGMENT],
+}
+
+impl Dft {
+    /// Create a new `Dft` by computing the twiddle and window tables.
+    pub fn new() -> Dft {
+        let mut cos = [0.0; DFT_SIZE];
+        let mut window = [0.0; SEGMENT];
+
+        for (k, x) in cos.iter_mut().enumerate() {
+            *x = (2.0 * PI * k as f32 / DFT_SIZE as f32).cos();
+        }
+
+        for (n, w) in window.iter_mut().enumerate() {
+            *w = 0.5 - 0.5 * (2.0 * PI * (n as f32 + 0.5) / SEGMENT as f32).cos();
+        }
+
+        Dft {
+            cos: cos,
+            window: window,
+        }
+    }
+
+    /// Compute the power |X(k)|<sup>2</sup>, 0 ≤ k ≤ N / 2, of the given segment after
+    /// windowing and zero padding.
+    pub fn power(&self, seg: &[f32]) -> [f32; DFT_HALF] {
+        let mut power = [0.0; DFT_HALF];
+
+        for (k, p) in power.iter_mut().enumerate() {
+            let (re, im) = seg.iter().zip(self.window.iter()).enumerate()
+                .fold((0.0, 0.0), |(re, im), (n, (&x, &w))| {
+                    let idx = n * k % DFT_SIZE;
+                    // Use sin(θ) = cos(θ - π/2).
+                    let sin = self.cos[(idx + DFT_SIZE * 3 / 4) % DFT_SIZE];
+
+                    (re + x * w * self.cos[idx], im - x * w * sin)
+                });
+
+            *p = re * re + im * im;
+        }
+
+        power
+    }
+}
+
+impl Default for Dft {
+    fn default() -> Dft { Dft::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use channel::{Channel, Impairment};
+    use decode::{DecoderConfig, ImbeDecoder};
+    use descramble::random_chunks;
+    use frame::ReceivedFrame;
+    use rand::{Rng, XorShiftRng};
+    use rng::{Random, XorShift};
+
+    /// Generate the given number of samples of white noise.
+    fn noise(len: usize) -> Vec<f32> {
+        let mut r = XorShift::default();
+        (0..len).map(|_| r.normal(1000.0)).collect()
+    }
+
+    /// Decode the given frames with the given options, returning the PCM and spectral
+    /// amplitudes of each frame.
+    fn decode(frames: &[ReceivedFrame], config: DecoderConfig)
+        -> (Vec<f32>, Vec<Spectrals>)
+    {
+        let mut dec = ImbeDecoder::with_rng(config, XorShift::default());
+        let mut pcm = vec![];
+        let mut spectrals = vec![];
+
+        for f in frames {
+            let mut buf = [0.0; SAMPLES_PER_FRAME];
+            dec.decode(ReceivedFrame::new(f.chunks, f.errors), &mut buf);
+
+            pcm.extend_from_slice(&buf);
+            spectrals.push(dec.prev().spectrals.clone());
+        }
+
+        (pcm, spectrals)
+    }
+
+    #[test]
+    fn test_db() {
+        assert!((db(10.0) - 10.0).abs() < 1e-4);
+        assert!((db(0.25) - -6.0206).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_dft() {
+        let dft = Dft::new();
+
+        // The Hann window has a mean square of 3/8.
+        let energy = dft.window.iter().fold(0.0, |s, &w| s + w * w);
+        assert!((energy - 0.375 * SEGMENT as f32).abs() < 1e-3);
+
+        // A tone centered on a bin concentrates power there.
+        let seg = (0..SEGMENT).map(|n| {
+            (2.0 * PI * 32.0 * n as f32 / DFT_SIZE as f32).cos()
+        }).collect::<Vec<_>>();
+
+        let p = dft.power(&seg);
+        let peak = (0..DFT_HALF).max_by_key(|&k| (p[k] * 1e3) as u64).unwrap();
+
+        assert_eq!(peak, 32);
+        assert!(p[64] < p[32] * 1e-4);
+    }
+
+    #[test]
+    fn test_identical() {
+        let x = noise(SEGMENT * 10);
+
+        assert_eq!(segmental_snr(&x, &x), MAX_SNR);
+        assert_eq!(log_spectral_distortion(&x, &x), 0.0);
+
+        // Partial segments are ignored.
+        assert_eq!(segmental_snr(&x[..100], &x[..100]), MAX_SNR);
+        assert_eq!(log_spectral_distortion(&x[..100], &x[..100]), 0.0);
+    }
+
+    #[test]
+    fn test_scaled() {
+        let x = noise(SEGMENT * 10);
+        let y = x.iter().map(|&s| s * 0.5).collect::<Vec<_>>();
+
+        // Noise is half the signal, for 6dB SNR.
+        assert!((segmental_snr(&x, &y) - 6.0206).abs() < 1e-3);
+        // Every bin is 6dB down, except any at the floor.
+        assert!((log_spectral_distortion(&x, &y) - 6.0206).abs() < 0.1);
+
+        let zero = vec![0.0; x.len()];
+
+        assert_eq!(segmental_snr(&x, &zero), 0.0);
+        assert_eq!(segmental_snr(&zero, &x), MIN_SNR);
+        assert_eq!(log_spectral_distortion(&zero, &x), 0.0);
+        assert!(log_spectral_distortion(&x, &zero) > SPECTRAL_RANGE - 10.0);
+    }
+
+    #[test]
+    fn test_envelope() {
+        let a: Spectrals = (1..17).map(|l| l as f32).collect();
+        let b: Spectrals = a.iter().map(|&m| m * 2.0).collect();
+
+        assert_eq!(frame_envelope_distance(&a, &a), 0.0);
+        assert!((frame_envelope_distance(&a, &b) - 6.0206).abs() < 1e-3);
+        assert!((frame_envelope_distance(&a, &b[..8]) - 6.0206).abs() < 1e-3);
+        assert_eq!(frame_envelope_distance(&a, &[]), 0.0);
+
+        let seq = [a.clone(), a.clone()];
+        assert_eq!(envelope_distance(&seq, &seq), 0.0);
+        assert!((envelope_distance(&seq, &[a, b]) - 3.0103).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_corpus() {
+        let mut rng = XorShiftRng::new_unseeded();
+
+        // Follow a slowly varying pitch so frames resemble speech.
+        let chunks = (0..100).map(|i| {
+            random_chunks((i / 4 % 100) as u8 + rng.gen_range(0, 4), &mut rng)
+        }).collect::<Vec<_>>();
+
+        let transmit = |ber| {
+            let mut ch = Channel::with_rng(Impairment::Random(ber), XorShift::default());
+            chunks.iter().map(|c| ch.transmit(c)).collect::<Vec<_>>()
+        };
+
+        let clean = transmit(0.0);
+        let (pcm, spectrals) = decode(&clean, DecoderConfig::default());
+
+        // Decoding is deterministic for a given seed.
+        let (same, _) = decode(&clean, DecoderConfig::default());
+        assert_eq!(segmental_snr(&pcm, &same), MAX_SNR);
+
+        // Quality degrades with the channel error rate.
+        let measure = |ber| {
+            let (p, s) = decode(&transmit(ber), DecoderConfig::default());
+
+            (log_spectral_distortion(&pcm, &p), envelope_distance(&spectrals, &s))
+        };
+
+        let (lsd_low, env_low) = measure(0.01);
+        let (lsd_high, env_high) = measure(0.08);
+
+        assert!(lsd_low < lsd_high);
+        assert!(env_low < env_high);
+
+        // The post-filter changes the spectrum but not the model amplitudes.
+        let (filtered, fs) = decode(&clean, DecoderConfig {
+            postfilter: 1.0,
+            .. DecoderConfig::default()
+        });
+
+        assert!(log_spectral_distortion(&pcm, &filtered) > 0.0);
+        assert_eq!(envelope_distance(&spectrals, &fs), 0.0);
+    }
+}
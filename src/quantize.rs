@@ -0,0 +1,293 @@
+//! Quantization of spectral amplitudes into prioritized chunks.
+//!
+//! This inverts the decoding of spectral amplitudes M<sub>l</sub> from a frame, so
+//! frames can be generated for a desired spectral envelope:
+//!
+//! 1. The log<sub>2</sub> amplitudes, less the prediction from the previous frame, give
+//!    the coefficients T<sub>l</sub>, which are split into 6 blocks of J<sub>i</sub>
+//!    coefficients and transformed by the DCT into C<sub>i,k</sub>.
+//! 2. The first coefficient C<sub>i,1</sub> of each block is transformed by another DCT
+//!    into the gain vector G<sub>m</sub>. G<sub>1</sub> is quantized to the nearest
+//!    gain table entry b<sub>2</sub>, and the remaining gains and higher order
+//!    coefficients are quantized uniformly into b<sub>3</sub>, ..., b<sub>L+1</sub>
+//!    with their bit allocations.
+//! 3. The quantized values are scanned into the prioritized chunks u<sub>0</sub>, ...,
+//!    u<sub>7</sub> along with the period b<sub>0</sub> and voiced/unvoiced bits
+//!    b<sub>1</sub>.
+//!
+//! Prediction depends on the amplitudes the decoder reconstructed for the previous
+//! frame, so a sequence of frames should be quantized against the decoded result of
+//! each (see `dequantize`) rather than against the original amplitudes.
+
+use std::f32::consts::PI;
+
+use arrayvec::ArrayVec;
+
+use allocs::allocs;
+use coefs::{AMPS_USED, DCT_STEP_SIZE, DCT_STD_DEV, Coefficients};
+use consts::{MIN_HARMONICS, MAX_HARMONICS};
+use descramble::descramble;
+use frame::Chunks;
+use gain::{GAIN, STEPS, Gains};
+#[cfg(not(feature = "std"))]
+use math::Float;
+use params::BaseParams;
+use prev::PrevFrame;
+use spectral::{MIN_AMP, Spectrals};
+
+/// Quantize the given spectral amplitudes M<sub>l</sub>, 1 ≤ l ≤ L, into chunks with
+/// the given period b<sub>0</sub> and band voiced/unvoiced bitmap b<sub>1</sub>,
+/// predicting from the given saved parameters of the previous decoded frame.
+///
+/// Panics if the period is out of range or the number of amplitudes doesn't match the
+/// harmonics L of the period.
+pub fn quantize(period: u8, voiced: u32, amps: &[f32], prev: &PrevFrame) -> Chunks {
+    assert!(period <= 207);
+
+    let params = BaseParams::new(period);
+    assert!(amps.len() == params.harmonics as usize);
+
+    // Decode zero coefficients to find the prediction term of each log2 amplitude.
+    let zero = (0..params.harmonics).map(|_| 0.0).collect::<Coefficients>();
+    let pred = Spectrals::new(&zero, &params, prev);
+
+    let coefs = amps.iter().zip(pred.iter()).map(|(&m, &p)| {
+        m.max(MIN_AMP).log2() - p.log2()
+    }).collect::<ArrayVec<[f32; MAX_HARMONICS]>>();
+
+    let (alloc, _) = allocs(params.harmonics);
+    let blocks = &AMPS_USED[params.harmonics as usize - MIN_HARMONICS];
+    let steps = &STEPS[params.harmonics as usize - MIN_HARMONICS];
+
+    // Holds b_3, ..., b_L+1, like `QuantizedAmplitudes`.
+    let mut quant: ArrayVec<[u32; 64]> = (1..params.harmonics).map(|_| 0).collect();
+    let mut firsts = [0.0; 6];
+
+    // Tracks the position of the current block in T_l and the first quantized amplitude
+    // b_m used by its higher order coefficients, which is always b_8 for the first.
+    let mut start = 0;
+    let mut cur = 8;
+
+    for (i, &used) in blocks.iter().enumerate() {
+        let block = &coefs[start..start + used + 1];
+
+        // C_i,1 goes into the gain vector.
+        firsts[i] = dct(block, 1);
+
+        // Quantize C_i,2, ..., C_i,Ji.
+        for k in 2..block.len() + 1 {
+            let m = cur + k - 2;
+            let bits = alloc[m - 3];
+
+            quant[m - 3] = if bits == 0 {
+                0
+            } else {
+                let step = DCT_STEP_SIZE[bits as usize - 1] * DCT_STD_DEV[k - 2];
+                level(dct(block, k), bits, step)
+            };
+        }
+
+        start += block.len();
+        cur += used;
+    }
+
+    // Find the closest G_1 and quantize G_2, ..., G_6 into b_3, ..., b_7.
+    let gain_idx = nearest(dct(&firsts, 1));
+
+    for m in 3...7 {
+        quant[m - 3] = level(dct(&firsts, m - 1), alloc[m - 3], steps[m - 3]);
+    }
+
+    pack(period, voiced, gain_idx, &quant, &params)
+}
+
+/// Decode the spectral amplitudes M<sub>l</sub> of the given chunks, quantized with the
+/// given period, as the decoder would from the given saved parameters of the previous
+/// frame.
+pub fn dequantize(chunks: &Chunks, period: u8, prev: &PrevFrame) -> Spectrals {
+    let params = BaseParams::new(period);
+    let (amps, _, gain_idx) = descramble(chunks, &params);
+    let gains = Gains::new(gain_idx, &amps, &params);
+    let coefs = Coefficients::new(&gains, &amps, &params);
+
+    Spectrals::new(&coefs, &params, prev)
+}
+
+/// Compute the DCT coefficient k, 1 ≤ k ≤ J, of the given block of J values, the
+/// inverse of the block IDCTs used in decoding.
+fn dct(block: &[f32], k: usize) -> f32 {
+    let len = block.len() as f32;
+
+    block.iter().enumerate().map(|(j, &c)| {
+        c * (PI * (k as f32 - 1.0) * (j as f32 + 0.5) / len).cos()
+    }).fold(0.0, |s, x| s + x) / len
+}
+
+/// Quantize the given value to the nearest of the 2<sup>B</sup> uniform levels with the
+/// given bit allocation B and step size.
+fn level(x: f32, bits: u8, step: f32) -> u32 {
+    if bits == 0 {
+        return 0;
+    }
+
+    let offset = (1 << (bits - 1)) as f32 - 0.5;
+    let max = ((1u32 << bits) - 1) as f32;
+
+    (x / step + offset).round().max(0.0).min(max) as u32
+}
+
+/// Find the gain index b<sub>2</sub> whose G<sub>1</sub> is closest to the given gain.
+fn nearest(gain: f32) -> usize {
+    GAIN.iter().enumerate().fold(0, |best, (i, &g)| {
+        if (g - gain).abs() < (GAIN[best] - gain).abs() { i } else { best }
+    })
+}
+
+/// Scan the given quantized values into prioritized chunks, the inverse of
+/// `descramble`.
+fn pack(period: u8, voiced: u32, gain_idx: usize, quant: &[u32], params: &BaseParams)
+    -> Chunks
+{
+    let (alloc, max) = allocs(params.harmonics);
+
+    // Scan bit levels MSB to LSB, over all b_m with bits allocated at each level.
+    let mut scan = (0..max).rev().flat_map(|idx| {
+        quant.iter().zip(alloc.iter())
+            .filter(move |&(_, &bits)| bits > idx)
+            .map(move |(&b, _)| b >> idx & 1)
+    });
+
+    let mut take = |n: u32| scan.by_ref().take(n as usize).fold(0, |w, b| w << 1 | b);
+
+    let period = period as u32;
+    let gain_idx = gain_idx as u32;
+    let bands = params.bands;
+
+    let u0 = take(3);
+    let u1 = take(12);
+    let u2 = take(12);
+    let u3 = take(12);
+    let sep = take(20 - bands);
+    let u6 = take(11);
+    let u7 = take(3);
+
+    // Place the voiced/unvoiced bits, bits 2 and 1 of b_2, and the start of the second
+    // scan in u_4 and u_5 [p39].
+    let parts = (voiced & ((1 << bands) - 1)) << (22 - bands) |
+        (gain_idx >> 1 & 0b11) << (20 - bands) | sep;
+
+    [
+        // Place the 6 MSBs of b_0 and bits 5 through 3 of b_2 in u_0 [p39].
+        (period >> 2) << 6 | gain_idx & 0b111000 | u0,
+        u1,
+        u2,
+        u3,
+        parts >> 11,
+        parts & 0x7ff,
+        u6,
+        // Place bit 0 of b_2 and the 2 LSBs of b_0 in u_7 [p39].
+        u7 << 4 | (gain_idx & 1) << 3 | (period & 0b11) << 1,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use descramble::{Bootstrap, descramble, random_chunks};
+    use frame::valid_chunks;
+    use rand::XorShiftRng;
+    use scan::ScanSep;
+
+    #[test]
+    fn test_pack() {
+        let mut rng = XorShiftRng::new_unseeded();
+
+        for period in 0...207 {
+            let params = BaseParams::new(period);
+
+            for _ in 0..10 {
+                // Bit 0 of u_7 isn't used.
+                let mut chunks = random_chunks(period, &mut rng);
+                chunks[7] &= !1;
+
+                let (amps, _, gain_idx) = descramble(&chunks, &params);
+                let voiced = ScanSep::new(&chunks, &params).voiced;
+
+                let quant = (3..params.harmonics as usize + 2).map(|m| amps.get(m))
+                    .collect::<Vec<_>>();
+
+                let packed = pack(period, voiced, gain_idx, &quant, &params);
+
+                assert!(valid_chunks(&packed));
+                assert_eq!(packed, chunks);
+                assert_eq!(Bootstrap::new(&packed).unwrap_period(), period);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dct() {
+        // The DCT inverts the block IDCT used in decoding.
+        let block = [1.0, -2.0, 0.5, 3.0, 0.25];
+        let coefs = (1...5).map(|k| dct(&block, k)).collect::<Vec<_>>();
+
+        for (j, &c) in block.iter().enumerate() {
+            let x = coefs[0] + 2.0 * (2...5).map(|k| {
+                coefs[k - 1] * (PI * (k as f32 - 1.0) * (j as f32 + 0.5) / 5.0).cos()
+            }).fold(0.0, |s, x| s + x);
+
+            assert!((x - c).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_level() {
+        assert_eq!(level(0.0, 0, 1.0), 0);
+        assert_eq!(level(0.4, 1, 1.0), 1);
+        assert_eq!(level(-0.4, 1, 1.0), 0);
+        assert_eq!(level(0.1, 3, 0.5), 4);
+        assert_eq!(level(100.0, 3, 0.5), 7);
+        assert_eq!(level(-100.0, 3, 0.5), 0);
+
+        assert_eq!(nearest(-10.0), 0);
+        assert_eq!(nearest(0.74), 21);
+        assert_eq!(nearest(10.0), 63);
+    }
+
+    #[test]
+    fn test_quantize() {
+        let mut prev = PrevFrame::default();
+
+        for &period in &[0, 32, 100, 207] {
+            let params = BaseParams::new(period);
+
+            // Smooth envelope falling with frequency.
+            let amps = (1...params.harmonics).map(|l| {
+                100.0 / (1.0 + l as f32 / 4.0)
+            }).collect::<Vec<_>>();
+
+            // Each frame lands near the target, whatever the previous frame.
+            for _ in 0..4 {
+                let chunks = quantize(period, 0b101, &amps, &prev);
+                assert!(valid_chunks(&chunks));
+                assert_eq!(Bootstrap::new(&chunks).unwrap_period(), period);
+
+                let spectrals = dequantize(&chunks, period, &prev);
+                assert_eq!(spectrals.len(), amps.len());
+
+                let errs = spectrals.iter().zip(amps.iter()).map(|(&a, &b)| {
+                    (a.log2() - b.log2()).abs()
+                }).collect::<Vec<_>>();
+
+                let max = errs.iter().fold(0.0, |s: f32, &x| s.max(x));
+                let mean = errs.iter().fold(0.0, |s, &x| s + x) / errs.len() as f32;
+
+                assert!(max < 1.0);
+                assert!(mean < 0.3);
+
+                prev.spectrals = spectrals;
+                prev.model = params;
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Band-limited sample rate conversion of decoded audio.
+//!
+//! Each supported output rate is a rational multiple L/M of the native 8kHz rate, and
+//! every 20ms frame maps to a whole number of output samples. Conversion is performed
+//! with a polyphase windowed-sinc interpolator: output sample k corresponds to input time
+//! kM/L - H, where H is the filter half-length, and is computed from the 2H input samples
+//! surrounding that time.
+//!
+//! Since the output of each frame depends only on its position within the frame, the
+//! k'th output sample of the f'th frame always corresponds to native time 160f + kM/L -
+//! H, so timestamps can be derived exactly from frame counts.
+
+use std::f32::consts::PI;
+
+use consts::{SAMPLE_RATE, SAMPLES_PER_FRAME};
+use frame::AudioBuf;
+#[cfg(not(feature = "std"))]
+use math::Float;
+
+/// Filter half-length H in input samples.
+const HALF_TAPS: usize = 32;
+/// Number of kernel table entries per input sample.
+const TABLE_RES: usize = 128;
+/// Number of entries in the kernel table, including padding for interpolation.
+const TABLE_LEN: usize = HALF_TAPS * TABLE_RES + 2;
+/// Number of input samples kept for filtering, including the current frame.
+const HIST_LEN: usize = 2 * HALF_TAPS + SAMPLES_PER_FRAME;
+
+/// Filter cutoff frequency in Hz, just above the highest IMBE harmonic.
+const CUTOFF: f32 = 3700.0;
+/// Kaiser window shape parameter β, giving about 70dB of stopband attenuation.
+const KAISER_BETA: f32 = 7.0;
+
+/// Maximum number of samples in a frame at any supported output rate.
+pub const MAX_SAMPLES_PER_FRAME: usize = SAMPLES_PER_FRAME * 6;
+
+/// Output sample rates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleRate {
+    /// Native 8kHz rate, without any conversion.
+    Hz8000,
+    /// 16kHz wideband rate.
+    Hz16000,
+    /// 44.1kHz CD audio rate.
+    Hz44100,
+    /// 48kHz rate.
+    Hz48000,
+}
+
+impl SampleRate {
+    /// Retrieve the rate in samples per second.
+    pub fn hz(&self) -> usize {
+        let (up, down) = self.ratio();
+        SAMPLE_RATE * up / down
+    }
+
+    /// Retrieve the ratio (L, M) of this rate to the native rate, in lowest terms.
+    pub fn ratio(&self) -> (usize, usize) {
+        match *self {
+            SampleRate::Hz8000 => (1, 1),
+            SampleRate::Hz16000 => (2, 1),
+            SampleRate::Hz44100 => (441, 80),
+            SampleRate::Hz48000 => (6, 1),
+        }
+    }
+
+    /// Retrieve the number of samples in each 20ms frame at this rate.
+    pub fn samples_per_frame(&self) -> usize {
+        let (up, down) = self.ratio();
+        SAMPLES_PER_FRAME * up / down
+    }
+}
+
+impl Default for SampleRate {
+    fn default() -> SampleRate { SampleRate::Hz8000 }
+}
+
+/// Converts frames of decoded audio to a different sample rate.
+pub struct Resampler {
+    /// Output sample rate.
+    rate: SampleRate,
+    /// Interpolation factor L.
+    up: usize,
+    /// Decimation factor M.
+    down: usize,
+    /// Interpolation kernel h(t) sampled at 0 ≤ t ≤ H.
+    table: [f32; TABLE_LEN],
+    /// Final 2H samples of the previous frame followed by the current frame.
+    hist: [f32; HIST_LEN],
+}
+
+impl Resampler {
+    /// Create a new `Resampler` that converts native frames to the given rate.
+    pub fn new(rate: SampleRate) -> Resampler {
+        let (up, down) = rate.ratio();
+
+        // Compute cutoff relative to the input Nyquist frequency. Since every supported
+        // rate is at least the native rate, this also rejects all spectral images.
+        let cutoff = CUTOFF / (SAMPLE_RATE as f32 / 2.0);
+
+        let mut table = [0.0; TABLE_LEN];
+
+        for (i, coef) in table.iter_mut().enumerate().take(HALF_TAPS * TABLE_RES) {
+            let t = i as f32 / TABLE_RES as f32;
+            *coef = cutoff * sinc(cutoff * t) * kaiser(t);
+        }
+
+        Resampler {
+            rate: rate,
+            up: up,
+            down: down,
+            table: table,
+            hist: [0.0; HIST_LEN],
+        }
+    }
+
+    /// Retrieve the output sample rate.
+    pub fn rate(&self) -> SampleRate { self.rate }
+
+    /// Retrieve the number of output samples produced for each frame.
+    pub fn samples_per_frame(&self) -> usize { self.rate.samples_per_frame() }
+
+    /// Retrieve the delay introduced by filtering, in output samples.
+    pub fn delay(&self) -> f32 { delay(self.rate) }
+
+    /// Clear the filter history, as at the start of a stream.
+    pub fn reset(&mut self) {
+        self.hist = [0.0; HIST_LEN];
+    }
+
+    /// Convert the given native frame into the given output buffer, which must hold
+    /// exactly one frame of samples at the output rate.
+    pub fn process(&mut self, input: &AudioBuf, out: &mut [f32]) {
+        assert_eq!(out.len(), self.samples_per_frame());
+
+        if self.up == self.down {
+            out.copy_from_slice(&input[..]);
+            return;
+        }
+
+        // Shift the previous frame's tail to the front and append the new frame.
+        for i in 0..2 * HALF_TAPS {
+            self.hist[i] = self.hist[i + SAMPLES_PER_FRAME];
+        }
+
+        (&mut self.hist[2 * HALF_TAPS..]).copy_from_slice(&input[..]);
+
+        for (k, y) in out.iter_mut().enumerate() {
+            // Split input time kM/L into whole sample i and phase p/L.
+            let i = k * self.down / self.up;
+            let phase = (k * self.down % self.up) as f32 / self.up as f32;
+
+            // Index of input sample i within the history buffer.
+            let base = i + 2 * HALF_TAPS;
+
+            *y = (0..2 * HALF_TAPS).map(|m| {
+                self.hist[base - m] * self.kernel(m as f32 - HALF_TAPS as f32 + phase)
+            }).fold(0.0, |s, x| s + x);
+        }
+    }
+
+    /// Compute the interpolation kernel h(t) for -H < t < H.
+    fn kernel(&self, t: f32) -> f32 {
+        let pos = t.abs() * TABLE_RES as f32;
+        let idx = pos as usize;
+
+        if idx >= HALF_TAPS * TABLE_RES {
+            return 0.0;
+        }
+
+        let frac = pos - idx as f32;
+
+        self.table[idx] * (1.0 - frac) + self.table[idx + 1] * frac
+    }
+}
+
+/// Compute the delay introduced by resampling to the given rate, in output samples.
+pub fn delay(rate: SampleRate) -> f32 {
+    match rate.ratio() {
+        (1, 1) => 0.0,
+        (up, down) => (HALF_TAPS * up) as f32 / down as f32,
+    }
+}
+
+/// Compute the normalized sinc function, sin(πx) / πx.
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Compute the Kaiser window at the given offset 0 ≤ t ≤ H from its center.
+fn kaiser(t: f32) -> f32 {
+    let r = t / HALF_TAPS as f32;
+    bessel_i0(KAISER_BETA * (1.0 - r * r).max(0.0).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+/// Compute the zeroth-order modified Bessel function of the first kind, I<sub>0</sub>(x).
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+
+    for k in 1..32 {
+        term *= (x / (2.0 * k as f32)).powi(2);
+        sum += term;
+
+        if term < sum * 1e-9 {
+            break;
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::PI;
+    use consts::SAMPLES_PER_FRAME;
+
+    #[test]
+    fn test_rates() {
+        assert_eq!(SampleRate::Hz8000.hz(), 8000);
+        assert_eq!(SampleRate::Hz16000.hz(), 16000);
+        assert_eq!(SampleRate::Hz44100.hz(), 44100);
+        assert_eq!(SampleRate::Hz48000.hz(), 48000);
+
+        assert_eq!(SampleRate::Hz8000.samples_per_frame(), 160);
+        assert_eq!(SampleRate::Hz16000.samples_per_frame(), 320);
+        assert_eq!(SampleRate::Hz44100.samples_per_frame(), 882);
+        assert_eq!(SampleRate::Hz48000.samples_per_frame(), 960);
+
+        for &r in [SampleRate::Hz8000, SampleRate::Hz16000, SampleRate::Hz44100,
+                   SampleRate::Hz48000].iter()
+        {
+            assert!(r.samples_per_frame() <= MAX_SAMPLES_PER_FRAME);
+            // Verify frames map to a whole number of samples.
+            assert_eq!(r.samples_per_frame() * 1000 / r.hz(), 20);
+            assert_eq!(r.samples_per_frame() * 50, r.hz());
+        }
+    }
+
+    #[test]
+    fn test_bessel() {
+        assert!((bessel_i0(0.0) - 1.0).abs() < 1e-6);
+        assert!((bessel_i0(1.0) - 1.2660658).abs() < 1e-5);
+        assert!((bessel_i0(7.0) - 168.59390).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_passthrough() {
+        let mut r = Resampler::new(SampleRate::Hz8000);
+        let mut input = [0.0; SAMPLES_PER_FRAME];
+        let mut out = [0.0; SAMPLES_PER_FRAME];
+
+        for (n, x) in input.iter_mut().enumerate() {
+            *x = n as f32;
+        }
+
+        r.process(&input, &mut out[..]);
+        assert_eq!(&out[..], &input[..]);
+        assert_eq!(r.delay(), 0.0);
+    }
+
+    /// Resample a tone at the given frequency and verify the output matches the same
+    /// tone sampled directly at the output rate.
+    fn check_tone(rate: SampleRate, freq: f32) {
+        let mut r = Resampler::new(rate);
+        let mut input = [0.0; SAMPLES_PER_FRAME];
+        let mut out = [0.0; MAX_SAMPLES_PER_FRAME];
+        let spf = rate.samples_per_frame();
+
+        for f in 0..6 {
+            for (n, x) in input.iter_mut().enumerate() {
+                let t = (f * SAMPLES_PER_FRAME + n) as f32 / SAMPLE_RATE as f32;
+                *x = 1000.0 * (2.0 * PI * freq * t).sin();
+            }
+
+            r.process(&input, &mut out[..spf]);
+
+            // Skip frames affected by the initial filter transient.
+            if f < 2 {
+                continue;
+            }
+
+            for (k, &y) in out[..spf].iter().enumerate() {
+                let t = ((f * spf + k) as f32 - r.delay()) / rate.hz() as f32;
+                let expected = 1000.0 * (2.0 * PI * freq * t).sin();
+
+                assert!((y - expected).abs() < 5.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tones() {
+        for &freq in [200.0, 1000.0, 2500.0, 3300.0].iter() {
+            check_tone(SampleRate::Hz16000, freq);
+            check_tone(SampleRate::Hz44100, freq);
+            check_tone(SampleRate::Hz48000, freq);
+        }
+    }
+
+    #[test]
+    fn test_images() {
+        // Verify spectral images above the input Nyquist frequency are rejected.
+        let mut r = Resampler::new(SampleRate::Hz48000);
+        let mut input = [0.0; SAMPLES_PER_FRAME];
+        let mut out = [0.0; MAX_SAMPLES_PER_FRAME];
+        let spf = SampleRate::Hz48000.samples_per_frame();
+
+        let mut energy = 0.0;
+
+        for f in 0..6 {
+            for (n, x) in input.iter_mut().enumerate() {
+                let t = (f * SAMPLES_PER_FRAME + n) as f32 / SAMPLE_RATE as f32;
+                *x = 1000.0 * (2.0 * PI * 1000.0 * t).sin();
+            }
+
+            r.process(&input, &mut out[..spf]);
+
+            if f < 2 {
+                continue;
+            }
+
+            // Correlate against the first image at 8kHz - 1kHz.
+            energy += out[..spf].iter().enumerate().map(|(k, &y)| {
+                let t = (f * spf + k) as f32 / 48000.0;
+                y * (2.0 * PI * 7000.0 * t).sin()
+            }).fold(0.0, |s, x| s + x).abs();
+        }
+
+        assert!(energy / (4 * spf) as f32 / 1000.0 < 1e-3);
+    }
+}
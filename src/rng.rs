@@ -0,0 +1,161 @@
+//! Random number generation for synthesis.
+//!
+//! Synthesis draws random phases for voiced harmonics [p60] and random spectra for the
+//! unvoiced signal [p58]. These are generated through the `Random` trait, so callers can
+//! supply their own generator, such as a hardware RNG on an embedded target. With the
+//! `std` feature, every `rand::Rng` also implements `Random`.
+
+#[cfg(feature = "std")]
+use rand::{self, Rng};
+#[cfg(feature = "std")]
+use rand::distributions::normal::StandardNormal;
+
+#[cfg(not(feature = "std"))]
+use math::Float;
+
+/// A source of random numbers.
+pub trait Random {
+    /// Generate a 32-bit value with each bit uniformly distributed.
+    fn next_u32(&mut self) -> u32;
+
+    /// Generate a value uniformly distributed over [low, high).
+    fn uniform(&mut self, low: f32, high: f32) -> f32 {
+        // Use the upper 24 bits as the mantissa of a value in [0, 1).
+        let x = (self.next_u32() >> 8) as f32 / (1 << 24) as f32;
+
+        low + (high - low) * x
+    }
+
+    /// Generate a value from the normal distribution with mean 0 and the given standard
+    /// deviation σ.
+    ///
+    /// This uses the Marsaglia polar method by default, and every `rand::Rng` uses the
+    /// ziggurat method of `rand::distributions::normal::StandardNormal` instead.
+    fn normal(&mut self, sigma: f32) -> f32 {
+        // Use the Marsaglia polar method.
+        loop {
+            let x = self.uniform(-1.0, 1.0);
+            let y = self.uniform(-1.0, 1.0);
+            let s = x * x + y * y;
+
+            if s > 0.0 && s < 1.0 {
+                return sigma * x * (-2.0 * s.ln() / s).sqrt();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Rng> Random for R {
+    fn next_u32(&mut self) -> u32 { Rng::next_u32(self) }
+
+    fn uniform(&mut self, low: f32, high: f32) -> f32 { self.gen_range(low, high) }
+
+    fn normal(&mut self, sigma: f32) -> f32 {
+        let StandardNormal(x) = self.gen();
+        (sigma as f64 * x) as f32
+    }
+}
+
+/// Fast, small-state xorshift generator that requires no platform support.
+///
+/// This isn't suitable for cryptography, but its statistics are more than sufficient for
+/// synthesizing noise.
+#[derive(Clone)]
+pub struct XorShift([u32; 4]);
+
+impl XorShift {
+    /// Create a new `XorShift` from the given seed, which must not be all zeros.
+    pub fn new(seed: [u32; 4]) -> XorShift {
+        assert!(seed != [0; 4]);
+        XorShift(seed)
+    }
+
+    /// Create a new `XorShift` seeded from the system.
+    #[cfg(feature = "std")]
+    pub fn seeded() -> XorShift {
+        let mut rng = rand::weak_rng();
+        let mut seed = [0; 4];
+
+        while seed == [0; 4] {
+            seed = rng.gen();
+        }
+
+        XorShift(seed)
+    }
+}
+
+impl Default for XorShift {
+    /// Create a new `XorShift` with a fixed seed.
+    fn default() -> XorShift {
+        XorShift([0x193a6754, 0xa8a7d469, 0x97830e05, 0x113ba7bb])
+    }
+}
+
+impl Random for XorShift {
+    fn next_u32(&mut self) -> u32 {
+        // Compute the xorshift128 recurrence.
+        let t = self.0[0] ^ (self.0[0] << 11);
+
+        self.0[0] = self.0[1];
+        self.0[1] = self.0[2];
+        self.0[2] = self.0[3];
+        self.0[3] = self.0[3] ^ (self.0[3] >> 19) ^ t ^ (t >> 8);
+
+        self.0[3]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_uniform() {
+        let mut r = XorShift::default();
+        let mut sum = 0.0;
+
+        for _ in 0..10000 {
+            let x = r.uniform(-2.0, 3.0);
+            assert!(x >= -2.0 && x < 3.0);
+            sum += x;
+        }
+
+        assert!((sum / 10000.0 - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_normal() {
+        let mut r = XorShift::new([1, 2, 3, 4]);
+        let (mut sum, mut sqr) = (0.0, 0.0);
+
+        for _ in 0..20000 {
+            let x = r.normal(2.0);
+            assert!(x.is_finite());
+
+            sum += x;
+            sqr += x * x;
+        }
+
+        let mean = sum / 20000.0;
+        let var = sqr / 20000.0 - mean * mean;
+
+        assert!(mean.abs() < 0.06);
+        assert!((var - 4.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn test_seeded() {
+        let mut a = XorShift::seeded();
+        let mut b = XorShift::seeded();
+
+        // Verify each generator produces a varying sequence.
+        assert!(a.next_u32() != a.next_u32() || b.next_u32() != b.next_u32());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_seed() {
+        XorShift::new([0; 4]);
+    }
+}
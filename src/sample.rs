@@ -0,0 +1,102 @@
+//! Conversion of decoded audio into output sample formats.
+//!
+//! The decoder synthesizes samples on the same scale as the standard, where full-scale
+//! speech spans roughly the range of a signed 16-bit integer. On a clean channel,
+//! adaptive smoothing limits the sum of enhanced spectral amplitudes to τ<sub>M</sub> =
+//! 20480 [p50], so the voiced signal of Eq 127 can exceed this range by about a factor of
+//! 2 and integer conversions must saturate.
+//!
+//! The unvoiced signal of Eq 126 reaches further. Eq 120 scales the noise so each DFT
+//! bin of an unvoiced band has an average magnitude of about γ<sub>w</sub>
+//! M<sub>l</sub>, so its level grows with the number of bins in each band rather than
+//! with the amplitude sum alone. With the fewest harmonics, a mostly unvoiced frame at
+//! τ<sub>M</sub> has an RMS level above 2τ<sub>M</sub> and peaks of about
+//! 10τ<sub>M</sub>.
+
+use std;
+
+#[cfg(not(feature = "std"))]
+use math::Float;
+
+/// An output audio sample format.
+pub trait Sample: Copy {
+    /// Convert the given decoded sample into this format, saturating if it's out of
+    /// range.
+    fn from_f32(x: f32) -> Self;
+}
+
+impl Sample for f32 {
+    fn from_f32(x: f32) -> f32 { x }
+}
+
+impl Sample for f64 {
+    fn from_f32(x: f32) -> f64 { x as f64 }
+}
+
+impl Sample for i16 {
+    fn from_f32(x: f32) -> i16 {
+        let x = x.round();
+
+        if x >= std::i16::MAX as f32 {
+            std::i16::MAX
+        } else if x <= std::i16::MIN as f32 {
+            std::i16::MIN
+        } else if x.is_nan() {
+            // Map NaN to silence.
+            0
+        } else {
+            x as i16
+        }
+    }
+}
+
+/// Apply the given linear gain to each decoded sample and convert it into the output
+/// format.
+pub fn convert<S: Sample>(src: &[f32], gain: f32, dest: &mut [S]) {
+    assert_eq!(src.len(), dest.len());
+
+    for (d, &s) in dest.iter_mut().zip(src.iter()) {
+        *d = S::from_f32(s * gain);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_i16() {
+        assert_eq!(i16::from_f32(0.0), 0);
+        assert_eq!(i16::from_f32(0.4), 0);
+        assert_eq!(i16::from_f32(0.6), 1);
+        assert_eq!(i16::from_f32(-0.6), -1);
+        assert_eq!(i16::from_f32(1234.5), 1235);
+        assert_eq!(i16::from_f32(32766.7), 32767);
+        assert_eq!(i16::from_f32(32767.0), 32767);
+        assert_eq!(i16::from_f32(40960.0), 32767);
+        assert_eq!(i16::from_f32(-32768.0), -32768);
+        assert_eq!(i16::from_f32(-40960.0), -32768);
+        assert_eq!(i16::from_f32(std::f32::INFINITY), 32767);
+        assert_eq!(i16::from_f32(std::f32::NEG_INFINITY), -32768);
+        assert_eq!(i16::from_f32(std::f32::NAN), 0);
+    }
+
+    #[test]
+    fn test_convert() {
+        let src = [0.0, 100.0, -100.0, 20000.0, -20000.0];
+        let mut dest = [0i16; 5];
+
+        convert(&src[..], 1.0, &mut dest[..]);
+        assert_eq!(dest, [0, 100, -100, 20000, -20000]);
+
+        convert(&src[..], 2.0, &mut dest[..]);
+        assert_eq!(dest, [0, 200, -200, 32767, -32768]);
+
+        convert(&src[..], 0.5, &mut dest[..]);
+        assert_eq!(dest, [0, 50, -50, 10000, -10000]);
+
+        let mut dest = [0.0f32; 5];
+        convert(&src[..], 0.5, &mut dest[..]);
+        assert_eq!(dest, [0.0, 50.0, -50.0, 10000.0, -10000.0]);
+    }
+}
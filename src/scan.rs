@@ -140,7 +140,10 @@ impl<'a> Iterator for ScanBits<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use allocs::allocs;
+    use descramble::random_chunks;
     use params::BaseParams;
+    use rand::XorShiftRng;
 
     #[test]
     fn test_chunks_16() {
@@ -437,4 +440,32 @@ mod tests {
         assert_eq!(c.idx_part, 0b01);
         assert_eq!(c.scanned, 0b1010100001111010);
     }
+
+    #[test]
+    fn test_scan_len() {
+        let mut rng = XorShiftRng::new_unseeded();
+
+        for period in 0...207 {
+            let p = BaseParams::new(period);
+            let (bits, _) = allocs(p.harmonics);
+
+            // Quantized amplitudes b_3, ..., b_L+1 have allocations and the rest don't.
+            let (used, unused) = bits.split_at(p.harmonics as usize - 1);
+            let total = used.iter().fold(0, |s, &b| s + b as usize);
+
+            assert!(unused.iter().all(|&b| b == 0));
+
+            // The scan covers 3 bits of u_0, all of u_1 through u_3, K - 2 bits of u_5,
+            // all of u_6, and 3 bits of u_7.
+            assert_eq!(total, 3 + 36 + (20 - p.bands as usize) + 11 + 3);
+
+            for _ in 0..10 {
+                let chunks = random_chunks(period, &mut rng);
+                let parts = ScanSep::new(&chunks, &p);
+                let scan = ScanBits::new(ScanChunks::new(&chunks, parts.scanned, &p));
+
+                assert_eq!(scan.count(), total);
+            }
+        }
+    }
 }
@@ -0,0 +1,643 @@
+//! Vectorized kernels for the data-parallel loops in synthesis.
+//!
+//! Each kernel has a scalar implementation that's always available. With the `simd`
+//! feature, kernels are also implemented with SSE2 and AVX2 on x86 and NEON on AArch64,
+//! and `Isa::detect` selects the widest instruction set supported by the running CPU, or
+//! in `no_std` builds the widest enabled at compile time. Vector implementations reorder
+//! floating-point sums, so their results agree with the scalar ones to within rounding
+//! error rather than exactly.
+
+#[cfg(not(feature = "std"))]
+use math::Float;
+
+/// Number of elements in the widest vector, which slices passed to `idft` must be a
+/// multiple of.
+pub const LANES: usize = 8;
+
+/// Maximum number of oscillators in an `Oscillators` bank, which covers
+/// `MAX_EXT_HARMONICS` rounded up to a multiple of `LANES`.
+pub const MAX_OSCILLATORS: usize = 128;
+
+/// An instruction set used to evaluate the kernels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Isa {
+    /// Portable scalar code.
+    Scalar,
+    /// 4-wide SSE2 vectors.
+    Sse2,
+    /// 8-wide AVX2 vectors.
+    Avx2,
+    /// 4-wide NEON vectors.
+    Neon,
+}
+
+impl Isa {
+    /// Detect the widest instruction set that can be used on the current CPU.
+    pub fn detect() -> Isa {
+        [Isa::Avx2, Isa::Sse2, Isa::Neon].iter()
+            .cloned()
+            .find(|isa| isa.supported())
+            .unwrap_or(Isa::Scalar)
+    }
+
+    /// Check if the instruction set can be used on the current CPU.
+    pub fn supported(self) -> bool {
+        match self {
+            Isa::Scalar => true,
+            Isa::Sse2 => has_sse2(),
+            Isa::Avx2 => has_avx2(),
+            Isa::Neon => cfg!(all(feature = "simd", target_arch = "aarch64",
+                                  target_feature = "neon")),
+        }
+    }
+}
+
+#[cfg(all(feature = "simd", feature = "std",
+          any(target_arch = "x86", target_arch = "x86_64")))]
+fn has_sse2() -> bool { is_x86_feature_detected!("sse2") }
+
+#[cfg(all(feature = "simd", feature = "std",
+          any(target_arch = "x86", target_arch = "x86_64")))]
+fn has_avx2() -> bool { is_x86_feature_detected!("avx2") }
+
+#[cfg(all(feature = "simd", not(feature = "std"),
+          any(target_arch = "x86", target_arch = "x86_64")))]
+fn has_sse2() -> bool { cfg!(target_feature = "sse2") }
+
+#[cfg(all(feature = "simd", not(feature = "std"),
+          any(target_arch = "x86", target_arch = "x86_64")))]
+fn has_avx2() -> bool { cfg!(target_feature = "avx2") }
+
+#[cfg(not(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64"))))]
+fn has_sse2() -> bool { false }
+
+#[cfg(not(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64"))))]
+fn has_avx2() -> bool { false }
+
+/// A bank of recursive oscillators, each generating an amplitude-weighted cosine that
+/// advances by a fixed phase step every sample.
+pub struct Oscillators {
+    /// Instruction set used to advance the bank.
+    isa: Isa,
+    /// Number of oscillators in the bank.
+    len: usize,
+    /// Amplitude of each oscillator, with unused entries set to zero.
+    amp: [f32; MAX_OSCILLATORS],
+    /// Current cosine of each oscillator.
+    re: [f32; MAX_OSCILLATORS],
+    /// Current sine of each oscillator.
+    im: [f32; MAX_OSCILLATORS],
+    /// Cosine of each phase step.
+    step_re: [f32; MAX_OSCILLATORS],
+    /// Sine of each phase step.
+    step_im: [f32; MAX_OSCILLATORS],
+}
+
+impl Oscillators {
+    /// Create a new empty `Oscillators` bank that uses the given instruction set.
+    pub fn new(isa: Isa) -> Oscillators {
+        assert!(isa.supported());
+
+        Oscillators {
+            isa: isa,
+            len: 0,
+            amp: [0.0; MAX_OSCILLATORS],
+            re: [0.0; MAX_OSCILLATORS],
+            im: [0.0; MAX_OSCILLATORS],
+            step_re: [0.0; MAX_OSCILLATORS],
+            step_im: [0.0; MAX_OSCILLATORS],
+        }
+    }
+
+    /// Add an oscillator with the given amplitude, initial phase, and phase step.
+    pub fn push(&mut self, amp: f32, phase: f32, step: f32) {
+        assert!(self.len < MAX_OSCILLATORS);
+
+        let i = self.len;
+
+        self.amp[i] = amp;
+        self.re[i] = phase.cos();
+        self.im[i] = phase.sin();
+        self.step_re[i] = step.cos();
+        self.step_im[i] = step.sin();
+
+        self.len += 1;
+    }
+
+    /// Compute the sum of the oscillators at the current sample and advance each to the
+    /// next sample.
+    pub fn next(&mut self) -> f32 {
+        // Unused entries have zero amplitude and phase vector, so they can be processed
+        // along with the rest.
+        let len = (self.len + LANES - 1) / LANES * LANES;
+
+        let amp = &self.amp[..len];
+        let re = &mut self.re[..len];
+        let im = &mut self.im[..len];
+        let step_re = &self.step_re[..len];
+        let step_im = &self.step_im[..len];
+
+        match self.isa {
+            #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+            Isa::Sse2 => unsafe { x86::oscillate_sse2(amp, re, im, step_re, step_im) },
+            #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+            Isa::Avx2 => unsafe { x86::oscillate_avx2(amp, re, im, step_re, step_im) },
+            #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+            Isa::Neon => unsafe { neon::oscillate(amp, re, im, step_re, step_im) },
+            _ => scalar::oscillate(amp, re, im, step_re, step_im),
+        }
+    }
+}
+
+/// Compute Σ re<sub>k</sub> cos(kθ) - im<sub>k</sub> sin(kθ) over the given real and
+/// imaginary parts for the given phase step θ, using the given instruction set.
+///
+/// Both slices must have the same length, which must be a multiple of `LANES`.
+pub fn idft(isa: Isa, re: &[f32], im: &[f32], step: f32) -> f32 {
+    assert!(isa.supported());
+    assert!(re.len() == im.len() && re.len() % LANES == 0);
+
+    let (sin, cos) = (step.sin(), step.cos());
+
+    match isa {
+        #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+        Isa::Sse2 => unsafe { x86::idft_sse2(re, im, sin, cos) },
+        #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+        Isa::Avx2 => unsafe { x86::idft_avx2(re, im, sin, cos) },
+        #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+        Isa::Neon => unsafe { neon::idft(re, im, sin, cos) },
+        _ => scalar::idft(re, im, sin, cos),
+    }
+}
+
+/// Add the weighted overlap-add (w<sub>p</sub>p + w<sub>c</sub>c) /
+/// (w<sub>p</sub><sup>2</sup> + w<sub>c</sub><sup>2</sup>) of the given previous and
+/// current frame signals and windows to each element of the given buffer, using the
+/// given instruction set.
+///
+/// All slices must have the same length.
+pub fn overlap_add(isa: Isa, prev: &[f32], cur: &[f32], win_prev: &[f32],
+                   win_cur: &[f32], out: &mut [f32])
+{
+    assert!(isa.supported());
+
+    let len = out.len();
+    assert!(prev.len() == len && cur.len() == len);
+    assert!(win_prev.len() == len && win_cur.len() == len);
+
+    // Process whole vectors with the selected instruction set and any remainder with
+    // scalar code.
+    let split = match isa {
+        #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+        Isa::Sse2 => unsafe { x86::overlap_add_sse2(prev, cur, win_prev, win_cur, out) },
+        #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+        Isa::Avx2 => unsafe { x86::overlap_add_avx2(prev, cur, win_prev, win_cur, out) },
+        #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+        Isa::Neon => unsafe { neon::overlap_add(prev, cur, win_prev, win_cur, out) },
+        _ => 0,
+    };
+
+    scalar::overlap_add(&prev[split..], &cur[split..], &win_prev[split..],
+                        &win_cur[split..], &mut out[split..]);
+}
+
+/// Compute the phase vectors e<sup>ijθ</sup> for the first `count` lanes j, given sin(θ)
+/// and cos(θ), along with the vector e<sup>i count θ</sup> that advances every lane by
+/// `count` steps.
+#[cfg(all(feature = "simd",
+          any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+fn lanes(count: usize, sin: f32, cos: f32) -> ([f32; LANES], [f32; LANES], f32, f32) {
+    let mut re = [0.0; LANES];
+    let mut im = [0.0; LANES];
+
+    let (mut r, mut i) = (1.0, 0.0);
+
+    for j in 0..count {
+        re[j] = r;
+        im[j] = i;
+
+        let next = r * cos - i * sin;
+        i = i * cos + r * sin;
+        r = next;
+    }
+
+    (re, im, r, i)
+}
+
+/// Portable implementations of each kernel.
+mod scalar {
+    pub fn oscillate(amp: &[f32], re: &mut [f32], im: &mut [f32], step_re: &[f32],
+                     step_im: &[f32])
+        -> f32
+    {
+        let mut sum = 0.0;
+
+        for k in 0..amp.len() {
+            sum += amp[k] * re[k];
+
+            // Rotate by the phase step.
+            let next = re[k] * step_re[k] - im[k] * step_im[k];
+            im[k] = im[k] * step_re[k] + re[k] * step_im[k];
+            re[k] = next;
+        }
+
+        sum
+    }
+
+    pub fn idft(re: &[f32], im: &[f32], sin: f32, cos: f32) -> f32 {
+        let (mut r, mut i) = (1.0, 0.0);
+
+        re.iter().zip(im.iter()).fold(0.0, |s, (&x, &y)| {
+            let sum = s + x * r - y * i;
+
+            // Rotate by the phase step.
+            let next = r * cos - i * sin;
+            i = i * cos + r * sin;
+            r = next;
+
+            sum
+        })
+    }
+
+    pub fn overlap_add(prev: &[f32], cur: &[f32], win_prev: &[f32], win_cur: &[f32],
+                       out: &mut [f32])
+    {
+        for (k, x) in out.iter_mut().enumerate() {
+            let numer = win_prev[k] * prev[k] + win_cur[k] * cur[k];
+            let denom = win_prev[k] * win_prev[k] + win_cur[k] * win_cur[k];
+
+            *x += numer / denom;
+        }
+    }
+}
+
+/// SSE2 and AVX2 implementations of each kernel.
+///
+/// The overlap-add kernels process only whole vectors and return the number of elements
+/// processed.
+#[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+mod x86 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::lanes;
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn sum_sse2(x: __m128) -> f32 {
+        let mut buf = [0.0; 4];
+        _mm_storeu_ps(buf.as_mut_ptr(), x);
+
+        buf[0] + buf[1] + buf[2] + buf[3]
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn sum_avx2(x: __m256) -> f32 {
+        let mut buf = [0.0; 8];
+        _mm256_storeu_ps(buf.as_mut_ptr(), x);
+
+        buf.iter().fold(0.0, |s, &x| s + x)
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn oscillate_sse2(amp: &[f32], re: &mut [f32], im: &mut [f32],
+                                 step_re: &[f32], step_im: &[f32])
+        -> f32
+    {
+        let mut acc = _mm_setzero_ps();
+
+        for k in (0..amp.len()).filter(|k| k % 4 == 0) {
+            let k = k as isize;
+
+            let a = _mm_loadu_ps(amp.as_ptr().offset(k));
+            let r = _mm_loadu_ps(re.as_ptr().offset(k));
+            let i = _mm_loadu_ps(im.as_ptr().offset(k));
+            let sr = _mm_loadu_ps(step_re.as_ptr().offset(k));
+            let si = _mm_loadu_ps(step_im.as_ptr().offset(k));
+
+            acc = _mm_add_ps(acc, _mm_mul_ps(a, r));
+
+            _mm_storeu_ps(re.as_mut_ptr().offset(k),
+                          _mm_sub_ps(_mm_mul_ps(r, sr), _mm_mul_ps(i, si)));
+            _mm_storeu_ps(im.as_mut_ptr().offset(k),
+                          _mm_add_ps(_mm_mul_ps(i, sr), _mm_mul_ps(r, si)));
+        }
+
+        sum_sse2(acc)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn oscillate_avx2(amp: &[f32], re: &mut [f32], im: &mut [f32],
+                                 step_re: &[f32], step_im: &[f32])
+        -> f32
+    {
+        let mut acc = _mm256_setzero_ps();
+
+        for k in (0..amp.len()).filter(|k| k % 8 == 0) {
+            let k = k as isize;
+
+            let a = _mm256_loadu_ps(amp.as_ptr().offset(k));
+            let r = _mm256_loadu_ps(re.as_ptr().offset(k));
+            let i = _mm256_loadu_ps(im.as_ptr().offset(k));
+            let sr = _mm256_loadu_ps(step_re.as_ptr().offset(k));
+            let si = _mm256_loadu_ps(step_im.as_ptr().offset(k));
+
+            acc = _mm256_add_ps(acc, _mm256_mul_ps(a, r));
+
+            _mm256_storeu_ps(re.as_mut_ptr().offset(k),
+                             _mm256_sub_ps(_mm256_mul_ps(r, sr), _mm256_mul_ps(i, si)));
+            _mm256_storeu_ps(im.as_mut_ptr().offset(k),
+                             _mm256_add_ps(_mm256_mul_ps(i, sr), _mm256_mul_ps(r, si)));
+        }
+
+        sum_avx2(acc)
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn idft_sse2(re: &[f32], im: &[f32], sin: f32, cos: f32) -> f32 {
+        let (start_re, start_im, rot_re, rot_im) = lanes(4, sin, cos);
+
+        let mut r = _mm_loadu_ps(start_re.as_ptr());
+        let mut i = _mm_loadu_ps(start_im.as_ptr());
+        let sr = _mm_set1_ps(rot_re);
+        let si = _mm_set1_ps(rot_im);
+        let mut acc = _mm_setzero_ps();
+
+        for k in (0..re.len()).filter(|k| k % 4 == 0) {
+            let k = k as isize;
+
+            let x = _mm_loadu_ps(re.as_ptr().offset(k));
+            let y = _mm_loadu_ps(im.as_ptr().offset(k));
+
+            acc = _mm_add_ps(acc, _mm_sub_ps(_mm_mul_ps(x, r), _mm_mul_ps(y, i)));
+
+            let next = _mm_sub_ps(_mm_mul_ps(r, sr), _mm_mul_ps(i, si));
+            i = _mm_add_ps(_mm_mul_ps(i, sr), _mm_mul_ps(r, si));
+            r = next;
+        }
+
+        sum_sse2(acc)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn idft_avx2(re: &[f32], im: &[f32], sin: f32, cos: f32) -> f32 {
+        let (start_re, start_im, rot_re, rot_im) = lanes(8, sin, cos);
+
+        let mut r = _mm256_loadu_ps(start_re.as_ptr());
+        let mut i = _mm256_loadu_ps(start_im.as_ptr());
+        let sr = _mm256_set1_ps(rot_re);
+        let si = _mm256_set1_ps(rot_im);
+        let mut acc = _mm256_setzero_ps();
+
+        for k in (0..re.len()).filter(|k| k % 8 == 0) {
+            let k = k as isize;
+
+            let x = _mm256_loadu_ps(re.as_ptr().offset(k));
+            let y = _mm256_loadu_ps(im.as_ptr().offset(k));
+
+            acc = _mm256_add_ps(acc,
+                                _mm256_sub_ps(_mm256_mul_ps(x, r), _mm256_mul_ps(y, i)));
+
+            let next = _mm256_sub_ps(_mm256_mul_ps(r, sr), _mm256_mul_ps(i, si));
+            i = _mm256_add_ps(_mm256_mul_ps(i, sr), _mm256_mul_ps(r, si));
+            r = next;
+        }
+
+        sum_avx2(acc)
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn overlap_add_sse2(prev: &[f32], cur: &[f32], win_prev: &[f32],
+                                   win_cur: &[f32], out: &mut [f32])
+        -> usize
+    {
+        let len = out.len() / 4 * 4;
+
+        for k in (0..len).filter(|k| k % 4 == 0) {
+            let k = k as isize;
+
+            let p = _mm_loadu_ps(prev.as_ptr().offset(k));
+            let c = _mm_loadu_ps(cur.as_ptr().offset(k));
+            let wp = _mm_loadu_ps(win_prev.as_ptr().offset(k));
+            let wc = _mm_loadu_ps(win_cur.as_ptr().offset(k));
+            let o = _mm_loadu_ps(out.as_ptr().offset(k));
+
+            let numer = _mm_add_ps(_mm_mul_ps(wp, p), _mm_mul_ps(wc, c));
+            let denom = _mm_add_ps(_mm_mul_ps(wp, wp), _mm_mul_ps(wc, wc));
+
+            _mm_storeu_ps(out.as_mut_ptr().offset(k),
+                          _mm_add_ps(o, _mm_div_ps(numer, denom)));
+        }
+
+        len
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn overlap_add_avx2(prev: &[f32], cur: &[f32], win_prev: &[f32],
+                                   win_cur: &[f32], out: &mut [f32])
+        -> usize
+    {
+        let len = out.len() / 8 * 8;
+
+        for k in (0..len).filter(|k| k % 8 == 0) {
+            let k = k as isize;
+
+            let p = _mm256_loadu_ps(prev.as_ptr().offset(k));
+            let c = _mm256_loadu_ps(cur.as_ptr().offset(k));
+            let wp = _mm256_loadu_ps(win_prev.as_ptr().offset(k));
+            let wc = _mm256_loadu_ps(win_cur.as_ptr().offset(k));
+            let o = _mm256_loadu_ps(out.as_ptr().offset(k));
+
+            let numer = _mm256_add_ps(_mm256_mul_ps(wp, p), _mm256_mul_ps(wc, c));
+            let denom = _mm256_add_ps(_mm256_mul_ps(wp, wp), _mm256_mul_ps(wc, wc));
+
+            _mm256_storeu_ps(out.as_mut_ptr().offset(k),
+                             _mm256_add_ps(o, _mm256_div_ps(numer, denom)));
+        }
+
+        len
+    }
+}
+
+/// NEON implementations of each kernel.
+///
+/// The overlap-add kernel processes only whole vectors and returns the number of elements
+/// processed.
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::lanes;
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn oscillate(amp: &[f32], re: &mut [f32], im: &mut [f32], step_re: &[f32],
+                            step_im: &[f32])
+        -> f32
+    {
+        let mut acc = vdupq_n_f32(0.0);
+
+        for k in (0..amp.len()).filter(|k| k % 4 == 0) {
+            let k = k as isize;
+
+            let a = vld1q_f32(amp.as_ptr().offset(k));
+            let r = vld1q_f32(re.as_ptr().offset(k));
+            let i = vld1q_f32(im.as_ptr().offset(k));
+            let sr = vld1q_f32(step_re.as_ptr().offset(k));
+            let si = vld1q_f32(step_im.as_ptr().offset(k));
+
+            acc = vaddq_f32(acc, vmulq_f32(a, r));
+
+            vst1q_f32(re.as_mut_ptr().offset(k),
+                      vsubq_f32(vmulq_f32(r, sr), vmulq_f32(i, si)));
+            vst1q_f32(im.as_mut_ptr().offset(k),
+                      vaddq_f32(vmulq_f32(i, sr), vmulq_f32(r, si)));
+        }
+
+        vaddvq_f32(acc)
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn idft(re: &[f32], im: &[f32], sin: f32, cos: f32) -> f32 {
+        let (start_re, start_im, rot_re, rot_im) = lanes(4, sin, cos);
+
+        let mut r = vld1q_f32(start_re.as_ptr());
+        let mut i = vld1q_f32(start_im.as_ptr());
+        let sr = vdupq_n_f32(rot_re);
+        let si = vdupq_n_f32(rot_im);
+        let mut acc = vdupq_n_f32(0.0);
+
+        for k in (0..re.len()).filter(|k| k % 4 == 0) {
+            let k = k as isize;
+
+            let x = vld1q_f32(re.as_ptr().offset(k));
+            let y = vld1q_f32(im.as_ptr().offset(k));
+
+            acc = vaddq_f32(acc, vsubq_f32(vmulq_f32(x, r), vmulq_f32(y, i)));
+
+            let next = vsubq_f32(vmulq_f32(r, sr), vmulq_f32(i, si));
+            i = vaddq_f32(vmulq_f32(i, sr), vmulq_f32(r, si));
+            r = next;
+        }
+
+        vaddvq_f32(acc)
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn overlap_add(prev: &[f32], cur: &[f32], win_prev: &[f32],
+                              win_cur: &[f32], out: &mut [f32])
+        -> usize
+    {
+        let len = out.len() / 4 * 4;
+
+        for k in (0..len).filter(|k| k % 4 == 0) {
+            let k = k as isize;
+
+            let p = vld1q_f32(prev.as_ptr().offset(k));
+            let c = vld1q_f32(cur.as_ptr().offset(k));
+            let wp = vld1q_f32(win_prev.as_ptr().offset(k));
+            let wc = vld1q_f32(win_cur.as_ptr().offset(k));
+            let o = vld1q_f32(out.as_ptr().offset(k));
+
+            let numer = vaddq_f32(vmulq_f32(wp, p), vmulq_f32(wc, c));
+            let denom = vaddq_f32(vmulq_f32(wp, wp), vmulq_f32(wc, wc));
+
+            vst1q_f32(out.as_mut_ptr().offset(k), vaddq_f32(o, vdivq_f32(numer, denom)));
+        }
+
+        len
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use rng::{Random, XorShift};
+
+    /// Retrieve the instruction sets supported on the current CPU.
+    fn supported() -> Vec<Isa> {
+        [Isa::Scalar, Isa::Sse2, Isa::Avx2, Isa::Neon].iter()
+            .cloned()
+            .filter(|isa| isa.supported())
+            .collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(Isa::Scalar.supported());
+        assert!(Isa::detect().supported());
+
+        if cfg!(not(feature = "simd")) {
+            assert_eq!(Isa::detect(), Isa::Scalar);
+        }
+    }
+
+    #[test]
+    fn test_oscillators() {
+        let mut r = XorShift::new([1, 2, 3, 4]);
+
+        let params = (0..123).map(|_| {
+            (r.uniform(0.0, 100.0), r.uniform(-1000.0, 1000.0), r.uniform(0.0, 3.0))
+        }).collect::<Vec<_>>();
+
+        for isa in supported() {
+            for &count in &[0, 1, 7, 8, 9, 56, 123] {
+                let mut osc = Oscillators::new(isa);
+
+                for &(amp, phase, step) in &params[..count] {
+                    osc.push(amp, phase, step);
+                }
+
+                for n in 0..1920 {
+                    let expected = params[..count].iter().fold(0.0, |s, &(a, p, w)| {
+                        s + a as f64 * (p as f64 + w as f64 * n as f64).cos()
+                    });
+
+                    assert!((osc.next() as f64 - expected).abs() < 0.1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_idft() {
+        let mut r = XorShift::new([5, 6, 7, 8]);
+
+        let re = (0..128).map(|_| r.normal(100.0)).collect::<Vec<f32>>();
+        let im = (0..128).map(|_| r.normal(100.0)).collect::<Vec<f32>>();
+
+        for isa in supported() {
+            for &t in &[-128.0, -37.5, -1.0, 0.0, 0.25, 1.0, 64.0, 127.0] {
+                let step = 2.0 * std::f32::consts::PI * t / 256.0;
+
+                let expected = (0..128).fold(0.0, |s, k| {
+                    let phase = step as f64 * k as f64;
+                    s + re[k] as f64 * phase.cos() - im[k] as f64 * phase.sin()
+                });
+
+                assert!((idft(isa, &re, &im, step) as f64 - expected).abs() < 0.1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_overlap_add() {
+        let mut r = XorShift::new([9, 10, 11, 12]);
+
+        let mut gen = |len| (0..len).map(|_| r.uniform(0.1, 1.0)).collect::<Vec<f32>>();
+        let (prev, cur, win_prev, win_cur) = (gen(37), gen(37), gen(37), gen(37));
+
+        for isa in supported() {
+            let mut out = vec![1.0; 37];
+            overlap_add(isa, &prev, &cur, &win_prev, &win_cur, &mut out);
+
+            for k in 0..37 {
+                let expected = 1.0 + (win_prev[k] * prev[k] + win_cur[k] * cur[k]) /
+                    (win_prev[k].powi(2) + win_cur[k].powi(2));
+
+                assert!((out[k] - expected).abs() < 1e-5);
+            }
+        }
+    }
+}
@@ -6,9 +6,31 @@ use arrayvec::ArrayVec;
 
 use coefs::Coefficients;
 use consts::MAX_HARMONICS;
+#[cfg(not(feature = "std"))]
+use math::Float;
 use params::BaseParams;
 use prev::PrevFrame;
 
+/// Smallest spectral amplitude M<sub>l</sub> used in prediction and synthesis.
+///
+/// Decoded amplitudes stay between about 2<sup>-23</sup> and 2<sup>24</sup>, even for
+/// frames with extreme gains, so clamping only affects corrupt state, where it keeps
+/// log<sub>2</sub> M<sub>l</sub> finite and M<sub>l</sub><sup>2</sup> summable without
+/// overflow.
+pub const MIN_AMP: f32 = 1.0 / 4294967296.0;
+
+/// Largest spectral amplitude M<sub>l</sub> used in prediction and synthesis.
+pub const MAX_AMP: f32 = 4294967296.0;
+
+/// Clamp the given amplitude into [`MIN_AMP`, `MAX_AMP`], taking NaN as the minimum.
+fn sanitize(m: f32) -> f32 {
+    if m >= MIN_AMP {
+        m.min(MAX_AMP)
+    } else {
+        MIN_AMP
+    }
+}
+
 /// Spectral amplitudes M<sub>l</sub>, 1 ≤ l ≤ L, measure the spectral envelope of the
 /// voiced/unvoiced signal spectrum.
 #[derive(Clone)]
@@ -19,7 +41,7 @@ impl Spectrals {
     /// current/previous frame parameters.
     pub fn new(coefs: &Coefficients, params: &BaseParams, prev: &PrevFrame) -> Spectrals {
         // Compute L(-1) / L(0).
-        let scale = prev.params.harmonics as f32 / params.harmonics as f32;
+        let scale = prev.model.harmonics as f32 / params.harmonics as f32;
 
         // Compute (k_l,  δ_l) for the given harmonic l [p35].
         let indexes = |l: u32| {
@@ -30,23 +52,24 @@ impl Spectrals {
         // Compute prediction coefficient ρ [p27].
         let pred = (0.03 * params.harmonics as f32 - 0.05).max(0.4).min(0.7);
 
+        // Compute log2 of the previous amplitude for the given harmonic, guarding
+        // against zero or non-finite amplitudes.
+        let prev_log = |k: usize| sanitize(prev.spectrals.get(k)).log2();
+
         // Compute the sum term.
         let sum = (1...params.harmonics).map(|l| indexes(l)).map(|(k, dec)| {
-            (1.0 - dec) * prev.spectrals.get(k).log2() +
-                dec * prev.spectrals.get(k + 1).log2()
+            (1.0 - dec) * prev_log(k) + dec * prev_log(k + 1)
         }).fold(0.0, |s, x| s + x) / params.harmonics as f32;
 
         // Compute M_l for each harmonic l.
         Spectrals((1...params.harmonics).map(|l| {
             let (k, dec) = indexes(l);
 
-            (
+            sanitize((
                 coefs.get(l as usize) + pred * (
-                    (1.0 - dec) * prev.spectrals.get(k).log2() +
-                    dec * prev.spectrals.get(k + 1).log2() -
-                    sum
+                    (1.0 - dec) * prev_log(k) + dec * prev_log(k + 1) - sum
                 )
-            ).exp2()
+            ).exp2())
         }).collect())
     }
 
@@ -56,8 +79,9 @@ impl Spectrals {
             // Handles case of Eq 78.
             1.0
         } else if l > self.0.len() {
-            // Handle case of Eq 79.
-            *self.0.last().unwrap()
+            // Handle case of Eq 79, falling back to the default amplitude if there are
+            // no harmonics.
+            self.0.last().cloned().unwrap_or(1.0)
         } else {
             self.0[l - 1]
         }
@@ -69,6 +93,13 @@ impl std::ops::Deref for Spectrals {
     fn deref(&self) -> &Self::Target { &self.0 }
 }
 
+impl std::iter::FromIterator<f32> for Spectrals {
+    /// Collect amplitudes M<sub>1</sub>, ..., M<sub>L</sub> into a `Spectrals`.
+    fn from_iter<I: IntoIterator<Item = f32>>(iter: I) -> Spectrals {
+        Spectrals(iter.into_iter().collect())
+    }
+}
+
 impl Default for Spectrals {
     /// Construct the default set of spectral amplitudes.
     fn default() -> Spectrals {
@@ -80,11 +111,13 @@ impl Default for Spectrals {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use descramble::{Bootstrap, descramble};
+    use descramble::{Bootstrap, descramble, random_chunks};
+    use frame::TEST_CHUNKS;
     use gain::Gains;
     use coefs::Coefficients;
     use params::BaseParams;
     use prev::{PrevFrame};
+    use rand::{Rng, XorShiftRng};
 
     #[test]
     fn test_spectrals() {
@@ -150,4 +183,64 @@ mod tests {
         assert!((s.get(15) - 17.53165062111628).abs() < 0.0001);
         assert!((s.get(16) - 19.244170201509185).abs() < 0.0001);
     }
+
+    #[test]
+    fn test_all_periods() {
+        let mut rng = XorShiftRng::new_unseeded();
+        let mut prev = PrevFrame::default();
+
+        // Follow each period with a random one so every transition of L is likely.
+        for i in 0..208 * 20 {
+            let period = if i % 2 == 0 { (i / 20) as u8 } else { rng.gen_range(0, 208) };
+            let mut chunks = random_chunks(period, &mut rng);
+
+            // Saturate the amplitude bits at times to reach extreme gains.
+            match rng.gen_range(0, 4) {
+                0 => for c in chunks[1..7].iter_mut() { *c = 0 },
+                1 => for c in chunks[1..7].iter_mut() { *c = 0x7ff },
+                _ => {},
+            }
+
+            let p = BaseParams::new(period);
+            let (amps, _, gain_idx) = descramble(&chunks, &p);
+            let g = Gains::new(gain_idx, &amps, &p);
+            let c = Coefficients::new(&g, &amps, &p);
+            let s = Spectrals::new(&c, &p, &prev);
+
+            assert_eq!(s.len(), p.harmonics as usize);
+
+            // Amplitudes must stay positive, so log2 in the next frame is finite.
+            for &m in s.iter() {
+                assert!(m.is_finite() && m > 0.0);
+            }
+
+            prev.model = p;
+            prev.spectrals = s;
+        }
+    }
+
+    #[test]
+    fn test_corrupt_prev() {
+        let p = BaseParams::new(Bootstrap::new(&TEST_CHUNKS).unwrap_period());
+        let (amps, _, gain_idx) = descramble(&TEST_CHUNKS, &p);
+        let g = Gains::new(gain_idx, &amps, &p);
+        let c = Coefficients::new(&g, &amps, &p);
+
+        let bad = [0.0, -1.0, ::std::f32::NAN, ::std::f32::INFINITY, ::std::f32::MAX];
+        let mut prev = PrevFrame::default();
+
+        for &x in bad.iter() {
+            prev.spectrals = (0..p.harmonics).map(|_| x).collect();
+            let s = Spectrals::new(&c, &p, &prev);
+
+            for &m in s.iter() {
+                assert!(m >= MIN_AMP && m <= MAX_AMP);
+            }
+        }
+
+        // Mixed values are sanitized individually.
+        prev.spectrals = bad.iter().cycle().take(p.harmonics as usize).cloned().collect();
+        let s = Spectrals::new(&c, &p, &prev);
+        assert!(s.iter().all(|&m| m >= MIN_AMP && m <= MAX_AMP));
+    }
 }
@@ -0,0 +1,207 @@
+//! Running decoder statistics and call-quality metrics.
+
+use enhance::MUTE_RATE;
+use frame::Errors;
+
+/// Number of bins in the ϵ<sub>T</sub> histogram.
+///
+/// Each of the 4 Golay-coded chunks can have at most 3 corrected errors and each of the
+/// 3 Hamming-coded chunks can have at most 1, so 0 ≤ ϵ<sub>T</sub> ≤ 15.
+pub const ERROR_BINS: usize = 16;
+
+/// Describes how the decoder handled a received frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameStatus {
+    /// Frame was decoded and synthesized normally.
+    Normal,
+    /// Frame had too many errors, so the previous frame was repeated [p46].
+    Repeat,
+    /// Error rate was too high, so the frame was muted [p47].
+    Mute,
+    /// Frame contained a silence b<sub>0</sub> value.
+    Silence,
+    /// Frame contained an invalid b<sub>0</sub> value, so the previous frame was repeated
+    /// [p46].
+    Invalid,
+}
+
+/// Accumulates statistics over a stream of decoded frames.
+#[derive(Clone)]
+pub struct DecodeStats {
+    /// Number of frames with each status, indexed by `status_idx`.
+    counts: [usize; 5],
+    /// Histogram of ϵ<sub>T</sub>, with larger totals counted in the last bin.
+    errors: [usize; ERROR_BINS],
+    /// Most recent error rate tracking term ϵ<sub>R</sub>.
+    rate: f32,
+    /// Sum of ϵ<sub>R</sub> over all frames.
+    rate_sum: f32,
+    /// Number of normal frames where adaptive smoothing forced voicing.
+    forced: usize,
+}
+
+impl DecodeStats {
+    /// Create a new `DecodeStats` with no frames recorded.
+    pub fn new() -> DecodeStats {
+        DecodeStats {
+            counts: [0; 5],
+            errors: [0; ERROR_BINS],
+            rate: 0.0,
+            rate_sum: 0.0,
+            forced: 0,
+        }
+    }
+
+    /// Record a frame with the given status, corrected error counts ϵ<sub>i</sub>, and
+    /// resulting ϵ<sub>R</sub>, along with whether adaptive smoothing forced any
+    /// harmonics to voiced.
+    pub fn record(&mut self, status: FrameStatus, errors: &Errors, rate: f32,
+                  forced: bool)
+    {
+        let total = errors.iter().fold(0, |s: usize, &e| s.saturating_add(e));
+
+        self.counts[status_idx(status)] += 1;
+        self.errors[if total < ERROR_BINS { total } else { ERROR_BINS - 1 }] += 1;
+        self.rate = rate;
+        self.rate_sum += rate;
+
+        if forced {
+            self.forced += 1;
+        }
+    }
+
+    /// Total number of frames recorded.
+    pub fn frames(&self) -> usize {
+        self.counts.iter().fold(0, |s, &c| s + c)
+    }
+
+    /// Number of frames recorded with the given status.
+    pub fn count(&self, status: FrameStatus) -> usize {
+        self.counts[status_idx(status)]
+    }
+
+    /// Histogram of ϵ<sub>T</sub>, where bin i counts the frames with i corrected errors.
+    pub fn error_hist(&self) -> &[usize; ERROR_BINS] { &self.errors }
+
+    /// Most recent error rate tracking term ϵ<sub>R</sub>.
+    pub fn err_rate(&self) -> f32 { self.rate }
+
+    /// Average ϵ<sub>R</sub> over all recorded frames.
+    pub fn mean_err_rate(&self) -> f32 {
+        match self.frames() {
+            0 => 0.0,
+            n => self.rate_sum / n as f32,
+        }
+    }
+
+    /// Fraction of normal frames where adaptive smoothing forced voicing [p49].
+    pub fn forced_voicing(&self) -> f32 {
+        match self.count(FrameStatus::Normal) {
+            0 => 0.0,
+            n => self.forced as f32 / n as f32,
+        }
+    }
+
+    /// Compute a call-quality score between 0 (unusable) and 1 (error free).
+    ///
+    /// Each non-silence frame contributes 1 if it was decoded normally, ½ if it was
+    /// concealed by repetition, and 0 if it was muted. The average contribution is then
+    /// scaled down linearly as the mean ϵ<sub>R</sub> approaches the muting threshold
+    /// on [p47].
+    pub fn quality(&self) -> f32 {
+        let speech = self.frames() - self.count(FrameStatus::Silence);
+
+        if speech == 0 {
+            return 1.0;
+        }
+
+        let concealed = self.count(FrameStatus::Repeat) + self.count(FrameStatus::Invalid);
+        let frames = (self.count(FrameStatus::Normal) as f32 + 0.5 * concealed as f32) /
+            speech as f32;
+
+        frames * (1.0 - self.mean_err_rate() / MUTE_RATE).max(0.0)
+    }
+}
+
+impl Default for DecodeStats {
+    fn default() -> DecodeStats { DecodeStats::new() }
+}
+
+/// Map the given status to its index in the counts array.
+fn status_idx(status: FrameStatus) -> usize {
+    match status {
+        FrameStatus::Normal => 0,
+        FrameStatus::Repeat => 1,
+        FrameStatus::Mute => 2,
+        FrameStatus::Silence => 3,
+        FrameStatus::Invalid => 4,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use enhance::{EnhanceErrors, should_mute};
+
+    #[test]
+    fn test_counts() {
+        let mut s = DecodeStats::new();
+
+        assert_eq!(s.frames(), 0);
+        assert_eq!(s.quality(), 1.0);
+        assert_eq!(s.forced_voicing(), 0.0);
+
+        s.record(FrameStatus::Normal, &[0, 0, 0, 0, 0, 0, 0], 0.0, true);
+        s.record(FrameStatus::Normal, &[1, 0, 2, 0, 1, 0, 0], 0.0, false);
+        s.record(FrameStatus::Repeat, &[3, 3, 3, 3, 1, 1, 1], 0.0, false);
+        s.record(FrameStatus::Silence, &[0, 0, 0, 0, 0, 0, 0], 0.0, false);
+        s.record(FrameStatus::Invalid, &[9, 9, 0, 0, 0, 0, 0], 0.0, false);
+
+        assert_eq!(s.frames(), 5);
+        assert_eq!(s.count(FrameStatus::Normal), 2);
+        assert_eq!(s.count(FrameStatus::Repeat), 1);
+        assert_eq!(s.count(FrameStatus::Mute), 0);
+        assert_eq!(s.count(FrameStatus::Silence), 1);
+        assert_eq!(s.count(FrameStatus::Invalid), 1);
+
+        assert_eq!(s.error_hist()[0], 2);
+        assert_eq!(s.error_hist()[4], 1);
+        assert_eq!(s.error_hist()[15], 2);
+
+        assert!((s.forced_voicing() - 0.5).abs() < 1e-6);
+        assert!((s.quality() - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rate() {
+        let mut s = DecodeStats::new();
+
+        // Steady errors that never cause repeats raise ϵ_R until the frame is muted, as
+        // the decoder would: ϵ_R passes the threshold on the 50th frame.
+        let errors = [1, 3, 3, 3, 1, 1, 1];
+        let mut rate = 0.0;
+        let mut sum = 0.0;
+
+        for i in 0..50 {
+            let e = EnhanceErrors::new(&errors, rate);
+            let status = if should_mute(&e) {
+                FrameStatus::Mute
+            } else {
+                FrameStatus::Normal
+            };
+
+            assert_eq!(status == FrameStatus::Mute, i == 49);
+            s.record(status, &errors, e.rate, false);
+
+            sum += e.rate;
+
+            if status == FrameStatus::Normal {
+                rate = e.rate;
+            }
+        }
+
+        assert!(s.err_rate() > MUTE_RATE);
+        assert!((s.mean_err_rate() - sum / 50.0).abs() < 1e-6);
+        assert!((s.quality() - 49.0 / 50.0 * (1.0 - sum / 50.0 / MUTE_RATE)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,401 @@
+//! Decode streams of frames through iterator and I/O adapters.
+//!
+//! Any iterator of received frames can be decoded with `frames.decode_with(&mut dec)`,
+//! which yields a native-rate `AudioBuf` for each frame, or individual samples through
+//! `samples()`. Talk spurts can be tracked alongside decoding with
+//! `frames.detect_with(&mut dec, &mut vad)`, which also yields each frame's voice
+//! activity and speech start/end events. With the `std` feature, `pcm_with` produces a
+//! `Read` source of signed 16-bit little-endian samples at the decoder's configured rate
+//! and gain.
+
+#[cfg(feature = "std")]
+use std::cmp;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+use consts::SAMPLES_PER_FRAME;
+use decode::ImbeDecoder;
+use frame::{AudioBuf, ReceivedFrame};
+#[cfg(feature = "std")]
+use resample::MAX_SAMPLES_PER_FRAME;
+use rng::Random;
+use stats::FrameStatus;
+use vad::{SpeechEvent, Vad};
+
+/// Adds decoding adapters to iterators of received frames.
+pub trait DecodeExt: Iterator<Item = ReceivedFrame> + Sized {
+    /// Decode each frame with the given decoder into native-rate audio at the
+    /// standard's scale.
+    fn decode_with<'a, R: Random>(self, dec: &'a mut ImbeDecoder<R>)
+        -> Decoded<'a, Self, R>
+    {
+        Decoded {
+            frames: self,
+            dec: dec,
+        }
+    }
+
+    /// Decode each frame with the given decoder into native-rate audio, detecting talk
+    /// spurts with the given voice activity detector.
+    fn detect_with<'a, R: Random>(self, dec: &'a mut ImbeDecoder<R>, vad: &'a mut Vad)
+        -> Detected<'a, Self, R>
+    {
+        Detected {
+            frames: self,
+            dec: dec,
+            vad: vad,
+        }
+    }
+
+    /// Decode each frame with the given decoder into a byte stream of signed 16-bit
+    /// little-endian samples, produced at the decoder's configured rate and gain.
+    #[cfg(feature = "std")]
+    fn pcm_with<'a, R: Random>(self, dec: &'a mut ImbeDecoder<R>)
+        -> PcmReader<'a, Self, R>
+    {
+        PcmReader {
+            frames: self,
+            dec: dec,
+            buf: [0; 2 * MAX_SAMPLES_PER_FRAME],
+            pos: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = ReceivedFrame>> DecodeExt for I {}
+
+/// Iterator over the decoded audio of each frame in a stream.
+pub struct Decoded<'a, I, R: 'a + Random> {
+    /// Source of received frames.
+    frames: I,
+    /// Decoder used for every frame.
+    dec: &'a mut ImbeDecoder<R>,
+}
+
+impl<'a, I: Iterator<Item = ReceivedFrame>, R: Random> Decoded<'a, I, R> {
+    /// Flatten the decoded audio into individual samples.
+    pub fn samples(self) -> Samples<'a, I, R> {
+        Samples {
+            frames: self,
+            buf: [0.0; SAMPLES_PER_FRAME],
+            pos: SAMPLES_PER_FRAME,
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = ReceivedFrame>, R: Random> Iterator for Decoded<'a, I, R> {
+    type Item = AudioBuf;
+
+    fn next(&mut self) -> Option<AudioBuf> {
+        let frame = match self.frames.next() {
+            Some(f) => f,
+            None => return None,
+        };
+
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+        self.dec.decode(frame, &mut buf);
+
+        Some(buf)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.frames.size_hint() }
+}
+
+/// Iterator over the individual decoded samples of a stream.
+pub struct Samples<'a, I, R: 'a + Random> {
+    /// Source of decoded frames.
+    frames: Decoded<'a, I, R>,
+    /// Audio of the current frame.
+    buf: AudioBuf,
+    /// Index of the next sample in the current frame.
+    pos: usize,
+}
+
+impl<'a, I: Iterator<Item = ReceivedFrame>, R: Random> Iterator for Samples<'a, I, R> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.pos == SAMPLES_PER_FRAME {
+            self.buf = match self.frames.next() {
+                Some(b) => b,
+                None => return None,
+            };
+
+            self.pos = 0;
+        }
+
+        self.pos += 1;
+
+        Some(self.buf[self.pos - 1])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let rem = SAMPLES_PER_FRAME - self.pos;
+        let (lo, hi) = self.frames.size_hint();
+
+        (lo.saturating_mul(SAMPLES_PER_FRAME).saturating_add(rem),
+         hi.and_then(|h| h.checked_mul(SAMPLES_PER_FRAME))
+           .and_then(|h| h.checked_add(rem)))
+    }
+}
+
+/// Decoded audio of a frame along with its voice activity.
+pub struct Activity {
+    /// Native-rate audio at the standard's scale.
+    pub audio: AudioBuf,
+    /// How the decoder handled the frame.
+    pub status: FrameStatus,
+    /// Whether the frame is within a talk spurt, including its hangover.
+    pub active: bool,
+    /// Talk spurt boundary detected at the frame.
+    ///
+    /// A start event refers back to the first frame of the onset, which may precede the
+    /// current frame.
+    pub event: Option<SpeechEvent>,
+}
+
+/// Iterator over the decoded audio and voice activity of each frame in a stream.
+///
+/// A talk spurt still in progress when the stream ends can be closed with
+/// `Vad::finish`.
+pub struct Detected<'a, I, R: 'a + Random> {
+    /// Source of received frames.
+    frames: I,
+    /// Decoder used for every frame.
+    dec: &'a mut ImbeDecoder<R>,
+    /// Detector updated with every frame.
+    vad: &'a mut Vad,
+}
+
+impl<'a, I: Iterator<Item = ReceivedFrame>, R: Random> Iterator for Detected<'a, I, R> {
+    type Item = Activity;
+
+    fn next(&mut self) -> Option<Activity> {
+        let frame = match self.frames.next() {
+            Some(f) => f,
+            None => return None,
+        };
+
+        let mut buf = [0.0; SAMPLES_PER_FRAME];
+        let status = self.dec.decode(frame, &mut buf);
+        let event = self.vad.update(status, self.dec.prev());
+
+        Some(Activity {
+            audio: buf,
+            status: status,
+            active: self.vad.is_active(),
+            event: event,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.frames.size_hint() }
+}
+
+/// Byte stream of signed 16-bit little-endian samples decoded from a stream of frames.
+#[cfg(feature = "std")]
+pub struct PcmReader<'a, I, R: 'a + Random> {
+    /// Source of received frames.
+    frames: I,
+    /// Decoder used for every frame.
+    dec: &'a mut ImbeDecoder<R>,
+    /// Encoded samples of the current frame.
+    buf: [u8; 2 * MAX_SAMPLES_PER_FRAME],
+    /// Index of the next unread byte in the current frame.
+    pos: usize,
+    /// Number of bytes in the current frame.
+    len: usize,
+}
+
+#[cfg(feature = "std")]
+impl<'a, I: Iterator<Item = ReceivedFrame>, R: Random> PcmReader<'a, I, R> {
+    /// Decode the next frame into the byte buffer, returning false if there are no more
+    /// frames.
+    fn fill(&mut self) -> bool {
+        let frame = match self.frames.next() {
+            Some(f) => f,
+            None => return false,
+        };
+
+        let mut samples = [0i16; MAX_SAMPLES_PER_FRAME];
+        let samples = &mut samples[..self.dec.samples_per_frame()];
+
+        self.dec.decode_i16(frame, samples);
+
+        for (bytes, &s) in self.buf.chunks_mut(2).zip(samples.iter()) {
+            bytes[0] = s as u8;
+            bytes[1] = (s >> 8) as u8;
+        }
+
+        self.pos = 0;
+        self.len = 2 * samples.len();
+
+        true
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, I: Iterator<Item = ReceivedFrame>, R: Random> Read for PcmReader<'a, I, R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.len && !self.fill() {
+            return Ok(0);
+        }
+
+        let n = cmp::min(out.len(), self.len - self.pos);
+
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use decode::DecoderConfig;
+    use frame::TEST_CHUNKS;
+    use resample::SampleRate;
+    use rng::XorShift;
+
+    /// Create a stream of the given number of frames.
+    fn frames(count: usize) -> Vec<ReceivedFrame> {
+        (0..count).map(|_| ReceivedFrame::new(TEST_CHUNKS, [0; 7])).collect()
+    }
+
+    /// Create a decoder with a deterministic RNG.
+    fn decoder(config: DecoderConfig) -> ImbeDecoder<XorShift> {
+        ImbeDecoder::with_rng(config, XorShift::default())
+    }
+
+    #[test]
+    fn test_decode_with() {
+        let mut ref_dec = decoder(DecoderConfig::default());
+        let mut dec = decoder(DecoderConfig::default());
+
+        let bufs = frames(5).into_iter().decode_with(&mut dec).collect::<Vec<_>>();
+        assert_eq!(bufs.len(), 5);
+
+        for (frame, buf) in frames(5).into_iter().zip(bufs.iter()) {
+            let mut expected = [0.0; SAMPLES_PER_FRAME];
+            ref_dec.decode(frame, &mut expected);
+
+            assert_eq!(&buf[..], &expected[..]);
+        }
+
+        assert_eq!(dec.stats().frames(), 5);
+    }
+
+    #[test]
+    fn test_samples() {
+        let mut ref_dec = decoder(DecoderConfig::default());
+        let mut dec = decoder(DecoderConfig::default());
+
+        let expected = frames(3).into_iter().decode_with(&mut ref_dec)
+            .fold(vec![], |mut v, b| { v.extend_from_slice(&b); v });
+
+        let samples = frames(3).into_iter().decode_with(&mut dec).samples();
+        let len = 3 * SAMPLES_PER_FRAME;
+
+        assert_eq!(samples.size_hint(), (len, Some(len)));
+        assert_eq!(samples.collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_detect_with() {
+        // Frames with low and high spectral energy, the latter mostly voiced.
+        let quiet = [527, 388, 3339, 3212, 1603, 970, 47, 109];
+        let loud = [46, 3058, 3948, 3274, 1531, 329, 763, 13];
+        let silence = [0b110110000000, 0, 0, 0, 0, 0, 0, 0];
+
+        let stream = [(quiet, 10), (loud, 20), (quiet, 30), (loud, 5), (silence, 5)];
+        let frames = || stream.iter().flat_map(|&(c, n)| {
+            (0..n).map(move |_| ReceivedFrame::new(c, [0; 7]))
+        }).collect::<Vec<_>>();
+
+        let mut ref_dec = decoder(DecoderConfig::default());
+        let mut dec = decoder(DecoderConfig::default());
+        let mut vad = Vad::default();
+
+        let acts = frames().into_iter().detect_with(&mut dec, &mut vad)
+            .collect::<Vec<_>>();
+
+        assert_eq!(acts.len(), 70);
+
+        let events = acts.iter().enumerate()
+            .filter_map(|(i, a)| a.event.map(|e| (i, e)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], (11, SpeechEvent::Start(10)));
+
+        // The first spurt ends within the hangover after the quiet frames resume.
+        match events[1] {
+            (i, SpeechEvent::End(end)) => {
+                assert!(end >= 30 && end < 35);
+                assert_eq!(i, end + vad.config().hangover - 1);
+            },
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        assert_eq!(events[2], (61, SpeechEvent::Start(60)));
+        // Silence ends the second spurt at once.
+        assert_eq!(events[3], (65, SpeechEvent::End(65)));
+
+        assert!(!acts[5].active && acts[20].active);
+        assert!(acts[64].active && !acts[65].active);
+        assert_eq!(acts[65].status, FrameStatus::Silence);
+
+        // Audio matches plain decoding.
+        for (frame, act) in frames().into_iter().zip(acts.iter()) {
+            let mut expected = [0.0; SAMPLES_PER_FRAME];
+            ref_dec.decode(frame, &mut expected);
+
+            assert_eq!(&act.audio[..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_pcm() {
+        let config = DecoderConfig {
+            rate: SampleRate::Hz16000,
+            gain: 2.0,
+            .. DecoderConfig::default()
+        };
+
+        let mut ref_dec = decoder(config);
+        let mut dec = decoder(config);
+
+        let mut expected = vec![];
+
+        for frame in frames(4) {
+            let mut samples = [0; 320];
+            ref_dec.decode_i16(frame, &mut samples);
+
+            for &s in samples.iter() {
+                expected.push(s as u8);
+                expected.push((s >> 8) as u8);
+            }
+        }
+
+        // Read in small pieces that straddle frame boundaries.
+        let mut pcm = frames(4).into_iter().pcm_with(&mut dec);
+        let mut bytes = vec![];
+        let mut piece = [0; 7];
+
+        loop {
+            match pcm.read(&mut piece).unwrap() {
+                0 => break,
+                n => bytes.extend_from_slice(&piece[..n]),
+            }
+        }
+
+        assert_eq!(bytes.len(), 4 * 320 * 2);
+        assert_eq!(bytes, expected);
+        assert!(bytes.iter().any(|&b| b != 0));
+
+        let mut all = vec![];
+        frames(0).into_iter().pcm_with(&mut dec).read_to_end(&mut all).unwrap();
+        assert!(all.is_empty());
+    }
+}
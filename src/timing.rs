@@ -0,0 +1,192 @@
+//! Mapping of synthesized output samples onto the native time base.
+//!
+//! The synthesis equations in the standard are written in terms of native 8kHz samples
+//! n, 0 ≤ n < N. Since the voiced and unvoiced signals are sums of sinusoids defined for
+//! continuous time, they can instead be evaluated at the fractional native time of each
+//! sample in a higher-rate output signal.
+//!
+//! Playback can also be sped up or slowed down by changing the period between the
+//! centers of successive frames. The sinusoids and noise keep running in real time, so
+//! pitch is unchanged, while the synthesis windows that cross-fade between frames are
+//! stretched to span the new period.
+
+use consts::SAMPLES_PER_FRAME;
+#[cfg(not(feature = "std"))]
+use math::Float;
+use resample::SampleRate;
+
+/// Minimum time-stretched frame period in native samples, for 2x playback speed.
+pub const MIN_PERIOD: usize = SAMPLES_PER_FRAME / 2;
+/// Maximum time-stretched frame period in native samples, for 0.5x playback speed.
+pub const MAX_PERIOD: usize = SAMPLES_PER_FRAME * 2;
+
+/// Maximum number of samples in a time-stretched frame at any supported output rate.
+pub const MAX_STRETCH_SAMPLES: usize = MAX_PERIOD * 6;
+
+/// Maps each output sample of a frame to a point in native time.
+#[derive(Copy, Clone)]
+pub struct Timing {
+    /// Number of output samples in the frame.
+    len: usize,
+    /// Number of output samples per native sample.
+    scale: f32,
+    /// Native time between the centers of the previous and current frames.
+    period: f32,
+}
+
+impl Timing {
+    /// Create a new `Timing` for synthesizing frames at the native rate.
+    pub fn native() -> Timing {
+        Timing {
+            len: SAMPLES_PER_FRAME,
+            scale: 1.0,
+            period: SAMPLES_PER_FRAME as f32,
+        }
+    }
+
+    /// Create a new `Timing` for synthesizing frames directly at the given rate.
+    pub fn new(rate: SampleRate) -> Timing {
+        let (up, down) = rate.ratio();
+
+        Timing {
+            len: rate.samples_per_frame(),
+            scale: up as f32 / down as f32,
+            period: SAMPLES_PER_FRAME as f32,
+        }
+    }
+
+    /// Create a new `Timing` for synthesizing time-stretched frames of the given number
+    /// of samples at the given rate.
+    ///
+    /// The resulting frame period must be between `MIN_PERIOD` and `MAX_PERIOD` native
+    /// samples.
+    pub fn stretched(rate: SampleRate, len: usize) -> Timing {
+        let (up, down) = rate.ratio();
+        let scale = up as f32 / down as f32;
+        let period = len as f32 / scale;
+
+        assert!(period >= MIN_PERIOD as f32 && period <= MAX_PERIOD as f32);
+
+        Timing {
+            len: len,
+            scale: scale,
+            period: period,
+        }
+    }
+
+    /// Retrieve the number of output samples in each frame.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Retrieve the number of output samples per native sample.
+    pub fn scale(&self) -> f32 { self.scale }
+
+    /// Retrieve the native time between the centers of the previous and current frames.
+    pub fn period(&self) -> f32 { self.period }
+
+    /// Compute the native time of the given output sample n, 0 ≤ n < `len()`.
+    pub fn time(&self, n: usize) -> f32 { n as f32 / self.scale }
+
+    /// Map the given native time t, relative to a frame center, onto the synthesis
+    /// window, which spans a standard frame period.
+    pub fn window_time(&self, t: f32) -> f32 {
+        t * (SAMPLES_PER_FRAME as f32 / self.period)
+    }
+}
+
+/// Computes frame lengths that play back at a constant speed.
+///
+/// Since frame lengths are whole samples, the fractional part of the ideal length is
+/// carried between frames so the average length is exact.
+pub struct TimeStretch {
+    /// Ideal number of output samples per frame.
+    len: f32,
+    /// Fractional samples carried from previous frames.
+    carry: f32,
+}
+
+impl TimeStretch {
+    /// Create a new `TimeStretch` for playback at the given speed, where 2 is twice as
+    /// fast, and at the given output rate. The speed must be between 0.5 and 2.
+    pub fn new(speed: f32, rate: SampleRate) -> TimeStretch {
+        let min = SAMPLES_PER_FRAME as f32 / MAX_PERIOD as f32;
+        let max = SAMPLES_PER_FRAME as f32 / MIN_PERIOD as f32;
+
+        assert!(speed >= min && speed <= max);
+
+        TimeStretch {
+            len: rate.samples_per_frame() as f32 / speed,
+            carry: 0.0,
+        }
+    }
+
+    /// Compute the number of output samples in the next frame.
+    pub fn next(&mut self) -> usize {
+        let len = self.len + self.carry;
+        let whole = len.round();
+
+        self.carry = len - whole;
+
+        whole as usize
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Timing { Timing::native() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use resample::SampleRate;
+
+    #[test]
+    fn test_timing() {
+        let t = Timing::native();
+        assert_eq!(t.len(), 160);
+        assert_eq!(t.time(0), 0.0);
+        assert_eq!(t.time(159), 159.0);
+
+        let t = Timing::new(SampleRate::Hz48000);
+        assert_eq!(t.len(), 960);
+        assert_eq!(t.time(6), 1.0);
+        assert_eq!(t.time(9), 1.5);
+
+        let t = Timing::new(SampleRate::Hz44100);
+        assert_eq!(t.len(), 882);
+        assert!((t.time(441) - 80.0).abs() < 1e-4);
+        assert_eq!(t.window_time(-37.5), -37.5);
+    }
+
+    #[test]
+    fn test_stretched() {
+        let t = Timing::stretched(SampleRate::Hz8000, 80);
+        assert_eq!(t.len(), 80);
+        assert_eq!(t.period(), 80.0);
+        assert_eq!(t.time(40), 40.0);
+        assert_eq!(t.window_time(40.0), 80.0);
+
+        let t = Timing::stretched(SampleRate::Hz48000, 1920);
+        assert_eq!(t.period(), 320.0);
+        assert_eq!(t.time(6), 1.0);
+        assert_eq!(t.window_time(-320.0), -160.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_stretched_bounds() {
+        Timing::stretched(SampleRate::Hz8000, 79);
+    }
+
+    #[test]
+    fn test_time_stretch() {
+        let mut s = TimeStretch::new(1.5, SampleRate::Hz8000);
+        let lens = (0..30).map(|_| s.next()).collect::<Vec<_>>();
+
+        assert!(lens.iter().all(|&n| n == 106 || n == 107));
+        assert_eq!(lens.iter().fold(0, |s, &n| s + n), 3200);
+
+        let mut s = TimeStretch::new(0.5, SampleRate::Hz44100);
+        assert_eq!(s.next(), 1764);
+        assert_eq!(Timing::stretched(SampleRate::Hz44100, s.next()).period(), 320.0);
+    }
+}
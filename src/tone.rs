@@ -0,0 +1,839 @@
+//! DTMF and tone detection on decoded frames, and generation of tone frames.
+//!
+//! Tones sent through the voice channel are encoded as frames with one or two dominant
+//! voiced harmonics, so they can be recognized from the model parameters directly:
+//!
+//! 1. Every harmonic of the frame must be voiced, and the one or two strongest enhanced
+//!    amplitudes "overbar" M<sub>l</sub> must hold most of the frame energy, with the
+//!    weaker of a pair within the allowed twist of the stronger.
+//! 2. Candidate tones are confirmed on the synthesized PCM with Goertzel filters at the
+//!    harmonic frequencies, which must also hold most of the signal energy.
+//! 3. Dual tones whose frequencies are both within tolerance of a DTMF row and column
+//!    frequency are reported as that digit.
+//!
+//! Harmonics fall at multiples of the fundamental frequency, so a tone is only
+//! represented to within half the fundamental of its true frequency. DTMF frequencies
+//! are matched with that allowance in addition to the configured tolerance.
+//!
+//! `ToneDetector` follows detections from frame to frame and reports each tone with its
+//! duration once it ends.
+//!
+//! In the other direction, `ToneGenerator` produces frames that the decoder synthesizes
+//! as a requested tone, placing each frequency on the nearest harmonic of a suitable
+//! fundamental and reporting the resulting frequency error.
+
+use std::f32::consts::{LN_10, PI};
+
+use consts::SAMPLE_RATE;
+use descramble::VoiceDecisions;
+use frame::Chunks;
+#[cfg(not(feature = "std"))]
+use math::Float;
+use params::BaseParams;
+use prev::PrevFrame;
+use quantize::{dequantize, quantize};
+use spectral::Spectrals;
+use stats::FrameStatus;
+
+/// Low-group (row) DTMF frequencies, in Hz.
+pub const DTMF_ROWS: [f32; 4] = [697.0, 770.0, 852.0, 941.0];
+
+/// High-group (column) DTMF frequencies, in Hz.
+pub const DTMF_COLS: [f32; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
+
+/// DTMF digits indexed by row and column.
+const DTMF_DIGITS: [[char; 4]; 4] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+
+/// A tone recognized in a decoded frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tone {
+    /// DTMF digit.
+    Dtmf(char),
+    /// Single tone with the given frequency, in Hz.
+    Single(f32),
+    /// Pair of tones, not a DTMF digit, with the given lower and higher frequencies, in
+    /// Hz.
+    Dual(f32, f32),
+}
+
+impl Tone {
+    /// Check whether the given tone is a continuation of this one, allowing the given
+    /// relative frequency tolerance.
+    fn matches(&self, other: &Tone, tol: f32) -> bool {
+        let near = |a: f32, b: f32| (a - b).abs() <= tol * a.max(b);
+
+        match (*self, *other) {
+            (Tone::Dtmf(a), Tone::Dtmf(b)) => a == b,
+            (Tone::Single(a), Tone::Single(b)) => near(a, b),
+            (Tone::Dual(a, b), Tone::Dual(c, d)) => near(a, c) && near(b, d),
+            _ => false,
+        }
+    }
+}
+
+/// Options that control tone detection.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToneConfig {
+    /// Minimum fraction of frame energy held by the tones, in both the model amplitudes
+    /// and the PCM.
+    pub purity: f32,
+    /// Maximum power difference between the two tones of a pair, in dB.
+    pub twist: f32,
+    /// Relative tolerance of DTMF frequencies, beyond the harmonic spacing.
+    pub tolerance: f32,
+    /// Minimum number of consecutive frames for a tone to be reported.
+    pub min_frames: usize,
+}
+
+impl Default for ToneConfig {
+    /// Create a new `ToneConfig` with 80% purity, 8dB twist, 2% tolerance, and a 40ms
+    /// minimum duration.
+    fn default() -> ToneConfig {
+        ToneConfig {
+            purity: 0.8,
+            twist: 8.0,
+            tolerance: 0.02,
+            min_frames: 2,
+        }
+    }
+}
+
+/// Compute the frequency, in Hz, of harmonic l of the given fundamental ω<sub>0</sub>.
+fn harmonic_freq(fundamental: f32, l: usize) -> f32 {
+    fundamental * l as f32 * SAMPLE_RATE as f32 / (2.0 * PI)
+}
+
+/// Compute the fraction of the given signal's energy at the given frequency, in Hz,
+/// using the Goertzel algorithm.
+///
+/// For a sinusoid at the frequency over the whole signal, the result is about 1.
+pub fn goertzel(pcm: &[f32], freq: f32) -> f32 {
+    let energy = pcm.iter().fold(0.0, |s, &x| s + x * x);
+
+    if energy <= 0.0 {
+        return 0.0;
+    }
+
+    let coef = 2.0 * (2.0 * PI * freq / SAMPLE_RATE as f32).cos();
+
+    let (s1, s2) = pcm.iter().fold((0.0, 0.0), |(s1, s2), &x| {
+        (x + coef * s1 - s2, s1)
+    });
+
+    // Compute |X(f)|^2 from the final filter states.
+    let power = s1 * s1 + s2 * s2 - coef * s1 * s2;
+
+    2.0 * power / (pcm.len() as f32 * energy)
+}
+
+/// Find the index of the DTMF frequency nearest the given frequency, if it's within the
+/// given relative tolerance plus absolute slack in Hz.
+fn dtmf_index(freqs: &[f32; 4], f: f32, tol: f32, slack: f32) -> Option<usize> {
+    let (idx, &d) = freqs.iter().enumerate()
+        .fold((0, &freqs[0]), |best, (i, d)| {
+            if (d - f).abs() < (best.1 - f).abs() { (i, d) } else { best }
+        });
+
+    if (d - f).abs() <= tol * d + slack {
+        Some(idx)
+    } else {
+        None
+    }
+}
+
+/// Find the DTMF digit for the given lower and higher frequencies, in Hz, allowing the
+/// given relative tolerance plus absolute slack in Hz.
+pub fn dtmf_digit(low: f32, high: f32, tol: f32, slack: f32) -> Option<char> {
+    match (dtmf_index(&DTMF_ROWS, low, tol, slack),
+           dtmf_index(&DTMF_COLS, high, tol, slack))
+    {
+        (Some(r), Some(c)) => Some(DTMF_DIGITS[r][c]),
+        _ => None,
+    }
+}
+
+/// Find the lower and higher frequencies, in Hz, of the given DTMF digit.
+pub fn dtmf_freqs(digit: char) -> Option<(f32, f32)> {
+    DTMF_DIGITS.iter().enumerate().filter_map(|(r, row)| {
+        row.iter().position(|&d| d == digit).map(|c| (DTMF_ROWS[r], DTMF_COLS[c]))
+    }).next()
+}
+
+/// Find the frequencies, in Hz, of the one or two dominant harmonics in the given saved
+/// parameters of a decoded frame, ordered from low to high.
+fn model_tones(prev: &PrevFrame, config: &ToneConfig) -> Option<(f32, Option<f32>)> {
+    if prev.enhanced.is_empty() || prev.voice.unvoiced_count() != 0 {
+        return None;
+    }
+
+    let energy = prev.enhanced.iter().fold(0.0, |s, &m| s + m * m);
+
+    if energy <= 0.0 {
+        return None;
+    }
+
+    // Find the strongest two harmonics, as (index, power).
+    let (first, second) = prev.enhanced.iter().map(|&m| m * m).enumerate()
+        .fold(((0, 0.0), (0, 0.0)), |(a, b), (l, p)| {
+            if p > a.1 {
+                ((l, p), a)
+            } else if p > b.1 {
+                (a, (l, p))
+            } else {
+                (a, b)
+            }
+        });
+
+    let freq = |l: usize| harmonic_freq(prev.params.fundamental, l + 1);
+
+    // Compute the minimum power ratio of the weaker tone from the allowed twist.
+    let twist = (-config.twist / 10.0 * LN_10).exp();
+
+    // Prefer a pair, so a dual tone with some twist isn't taken for a single tone.
+    if first.1 + second.1 >= config.purity * energy && second.1 >= twist * first.1 {
+        let (lo, hi) = if first.0 < second.0 { (first, second) } else { (second, first) };
+        return Some((freq(lo.0), Some(freq(hi.0))));
+    }
+
+    if first.1 >= config.purity * energy {
+        return Some((freq(first.0), None));
+    }
+
+    None
+}
+
+/// Detect a tone in the given saved parameters and synthesized PCM of a normally decoded
+/// frame.
+pub fn detect(prev: &PrevFrame, pcm: &[f32], config: &ToneConfig) -> Option<Tone> {
+    let (lo, hi) = match model_tones(prev, config) {
+        Some(t) => t,
+        None => return None,
+    };
+
+    let fraction = goertzel(pcm, lo) + hi.map(|f| goertzel(pcm, f)).unwrap_or(0.0);
+
+    if fraction < config.purity {
+        return None;
+    }
+
+    let hi = match hi {
+        Some(f) => f,
+        None => return Some(Tone::Single(lo)),
+    };
+
+    // Allow for tones falling between harmonics.
+    let slack = harmonic_freq(prev.params.fundamental, 1) / 2.0;
+
+    Some(match dtmf_digit(lo, hi, config.tolerance, slack) {
+        Some(d) => Tone::Dtmf(d),
+        None => Tone::Dual(lo, hi),
+    })
+}
+
+/// A tone reported by `ToneDetector` once it ends.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToneEvent {
+    /// Tone that was detected.
+    pub tone: Tone,
+    /// Index of the first frame containing the tone.
+    pub start: usize,
+    /// Number of consecutive frames containing the tone.
+    pub frames: usize,
+}
+
+impl ToneEvent {
+    /// Compute the duration of the tone, in milliseconds.
+    pub fn duration_ms(&self) -> usize { self.frames * 20 }
+}
+
+/// Relative frequency allowance, beyond the configured tolerance, for a detection to
+/// continue the tone in progress, absorbing frame-to-frame drift of the fundamental.
+const TRACK_TOLERANCE: f32 = 0.01;
+
+/// Follows tones across a stream of decoded frames.
+pub struct ToneDetector {
+    /// Detection options.
+    config: ToneConfig,
+    /// Index of the next frame.
+    frame: usize,
+    /// Tone in progress.
+    current: Option<ToneEvent>,
+}
+
+impl ToneDetector {
+    /// Create a new `ToneDetector` with the given options.
+    pub fn new(config: ToneConfig) -> ToneDetector {
+        ToneDetector {
+            config: config,
+            frame: 0,
+            current: None,
+        }
+    }
+
+    /// Retrieve the detection options.
+    pub fn config(&self) -> &ToneConfig { &self.config }
+
+    /// Retrieve the tone in progress, if any.
+    pub fn current(&self) -> Option<&ToneEvent> { self.current.as_ref() }
+
+    /// Examine the frame just decoded with the given status, saved parameters, and
+    /// native-rate PCM, returning the previous tone if it ended at this frame.
+    ///
+    /// Repeated and invalid frames continue a tone in progress, and muted and silent
+    /// frames end it.
+    pub fn update(&mut self, status: FrameStatus, prev: &PrevFrame, pcm: &[f32])
+        -> Option<ToneEvent>
+    {
+        let idx = self.frame;
+        self.frame += 1;
+
+        let tone = match status {
+            FrameStatus::Normal => detect(prev, pcm, &self.config),
+            FrameStatus::Repeat | FrameStatus::Invalid => {
+                if let Some(ref mut cur) = self.current {
+                    cur.frames += 1;
+                }
+
+                return None;
+            },
+            FrameStatus::Mute | FrameStatus::Silence => None,
+        };
+
+        let tol = self.config.tolerance + TRACK_TOLERANCE;
+
+        if let (Some(t), Some(cur)) = (tone, self.current.as_mut()) {
+            if cur.tone.matches(&t, tol) {
+                cur.frames += 1;
+                return None;
+            }
+        }
+
+        let ended = self.finish();
+
+        self.current = tone.map(|t| ToneEvent {
+            tone: t,
+            start: idx,
+            frames: 1,
+        });
+
+        ended
+    }
+
+    /// End any tone in progress, such as at the end of a stream, returning it if it
+    /// lasted long enough to report.
+    pub fn finish(&mut self) -> Option<ToneEvent> {
+        match self.current.take() {
+            Some(e) if e.frames >= self.config.min_frames => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ToneDetector {
+    fn default() -> ToneDetector { ToneDetector::new(ToneConfig::default()) }
+}
+
+/// Largest number of harmonics L in a generated tone frame.
+///
+/// The voiced/unvoiced bits of frames with more harmonics don't voice every harmonic
+/// when decoded.
+const MAX_TONE_HARMONICS: u32 = 36;
+
+/// Levels tried for the harmonics that don't carry a tone, in dB relative to the tones,
+/// from lowest to highest.
+const TONE_FLOORS: [f32; 9] = [-60.0, -55.0, -50.0, -45.0, -40.0, -35.0, -30.0, -25.0,
+                               -20.0];
+
+/// Largest difference, in dB, between the settled and target tone amplitudes for a
+/// floor level to be accepted.
+const TONE_TOLERANCE: f32 = 1.0;
+
+/// Number of frames simulated for the decoded amplitudes to settle.
+const SETTLE_FRAMES: usize = 20;
+
+/// Largest relative frequency error for which a period with fewer harmonics is
+/// preferred over a closer fit.
+const FIT_TOLERANCE: f32 = 0.01;
+
+/// Generates frames that decode to a steady single or dual tone.
+///
+/// Each tone is carried by a harmonic of the fundamental frequency, so the period
+/// b<sub>0</sub> is chosen to place harmonics within 1% of the requested frequencies
+/// where possible, with as few harmonics as possible, and every band is voiced. The
+/// remaining harmonics are kept well below the tones.
+///
+/// Frames are quantized against the spectral amplitudes the decoder reconstructs from
+/// the previous generated frame, so a decoder that receives every frame in order
+/// follows the same amplitudes. Spectral amplitudes are quantized too coarsely to form
+/// arbitrarily deep peaks, and too low a floor holds the tones below their level while
+/// too high a floor overloads the gain, so the floor is chosen as the lowest whose tone
+/// amplitudes settle near their target. Tones build up over the first few frames, and a
+/// decoder joining mid-stream, such as after speech, converges similarly.
+pub struct ToneGenerator {
+    /// Requested frequencies, in Hz.
+    requested: (f32, Option<f32>),
+    /// Period b<sub>0</sub> of generated frames.
+    period: u8,
+    /// Harmonics carrying the tones.
+    harmonics: (usize, Option<usize>),
+    /// Target spectral amplitudes M<sub>l</sub>.
+    amps: Spectrals,
+    /// Decoder state the next frame is predicted from.
+    prev: PrevFrame,
+}
+
+impl ToneGenerator {
+    /// Create a new `ToneGenerator` for the given tone, with each of its frequencies at
+    /// the given level, in dB relative to a full-scale sinusoid.
+    ///
+    /// Returns `None` if the tone isn't a DTMF digit or a frequency is outside the range
+    /// of harmonics that can be generated, about 100Hz to 3.7kHz.
+    pub fn new(tone: Tone, level: f32) -> Option<ToneGenerator> {
+        let requested = match tone {
+            Tone::Dtmf(d) => match dtmf_freqs(d) {
+                Some((lo, hi)) => (lo, Some(hi)),
+                None => return None,
+            },
+            Tone::Single(f) => (f, None),
+            Tone::Dual(lo, hi) => (lo, Some(hi)),
+        };
+
+        let (period, harmonics) = match fit(requested) {
+            Some(f) => f,
+            None => return None,
+        };
+
+        let db = |x: f32| (x / 20.0 * LN_10).exp();
+
+        // Each harmonic contributes a sinusoid of amplitude 2 M_l.
+        let amp = 32767.0 * db(level) / 2.0;
+
+        let mut best: Option<(f32, ToneGenerator)> = None;
+
+        for &floor in TONE_FLOORS.iter() {
+            let mut gen = ToneGenerator {
+                requested: requested,
+                period: period,
+                harmonics: harmonics,
+                amps: Spectrals::default(),
+                prev: PrevFrame::default(),
+            };
+
+            gen.amps = (1...gen.params().harmonics as usize).map(|l| {
+                if gen.is_tone(l) { amp } else { amp * db(floor) }
+            }).collect();
+
+            let err = gen.settle();
+
+            match best {
+                Some((e, _)) if e <= err => {},
+                _ => best = Some((err, gen)),
+            }
+
+            if err <= TONE_TOLERANCE {
+                break;
+            }
+        }
+
+        best.map(|(_, gen)| gen)
+    }
+
+    /// Check if the given harmonic carries a tone.
+    fn is_tone(&self, l: usize) -> bool {
+        l == self.harmonics.0 || Some(l) == self.harmonics.1
+    }
+
+    /// Simulate decoding enough frames for the amplitudes to settle, returning the
+    /// largest difference, in dB, between the decoded and target tone amplitudes.
+    fn settle(&mut self) -> f32 {
+        for _ in 0..SETTLE_FRAMES {
+            self.next_chunks();
+        }
+
+        let err = (1...self.amps.len()).filter(|&l| self.is_tone(l)).map(|l| {
+            (20.0 * (self.prev.spectrals.get(l) / self.amps.get(l)).ln() / LN_10).abs()
+        }).fold(0.0, |s: f32, x| s.max(x));
+
+        // Start over from the decoder's initial state.
+        self.prev = PrevFrame::default();
+
+        err
+    }
+
+    /// Retrieve the period b<sub>0</sub> of generated frames.
+    pub fn period(&self) -> u8 { self.period }
+
+    /// Retrieve the parameters of generated frames.
+    pub fn params(&self) -> BaseParams { BaseParams::new(self.period) }
+
+    /// Retrieve the band voiced/unvoiced bitmap b<sub>1</sub> of generated frames.
+    pub fn voiced(&self) -> u32 { (1 << self.params().bands) - 1 }
+
+    /// Retrieve the harmonic voiced/unvoiced decisions of generated frames.
+    pub fn voice(&self) -> VoiceDecisions {
+        VoiceDecisions::new(self.voiced(), &self.params())
+    }
+
+    /// Retrieve the target spectral amplitudes M<sub>l</sub> of generated frames.
+    pub fn spectrals(&self) -> &Spectrals { &self.amps }
+
+    /// Retrieve the harmonics l carrying the lower and any higher tone.
+    pub fn harmonics(&self) -> (usize, Option<usize>) { self.harmonics }
+
+    /// Retrieve the requested lower and any higher frequency, in Hz.
+    pub fn requested(&self) -> (f32, Option<f32>) { self.requested }
+
+    /// Compute the lower and any higher frequency, in Hz, synthesized by the decoder.
+    pub fn frequencies(&self) -> (f32, Option<f32>) {
+        let fundamental = self.params().fundamental;
+
+        (harmonic_freq(fundamental, self.harmonics.0),
+         self.harmonics.1.map(|l| harmonic_freq(fundamental, l)))
+    }
+
+    /// Compute the error of the lower and any higher synthesized frequency relative to
+    /// the requested frequency, in Hz.
+    pub fn error(&self) -> (f32, Option<f32>) {
+        let (lo, hi) = self.frequencies();
+
+        (lo - self.requested.0,
+         hi.and_then(|f| self.requested.1.map(|r| f - r)))
+    }
+
+    /// Generate the chunks of the next frame.
+    pub fn next_chunks(&mut self) -> Chunks {
+        let chunks = quantize(self.period, self.voiced(), &self.amps, &self.prev);
+
+        // Follow the amplitudes the decoder reconstructs, for prediction in the next
+        // frame.
+        self.prev.spectrals = dequantize(&chunks, self.period, &self.prev);
+        self.prev.model = self.params();
+
+        chunks
+    }
+}
+
+/// Generates an endless stream of frames.
+impl Iterator for ToneGenerator {
+    type Item = Chunks;
+
+    fn next(&mut self) -> Option<Chunks> { Some(self.next_chunks()) }
+}
+
+/// Find the period b<sub>0</sub> whose harmonics best fit the given lower and any
+/// higher frequency, in Hz, along with the harmonics carrying each.
+///
+/// Frames with fewer harmonics quantize each amplitude more finely and hold less energy
+/// outside the tones, so the period with the fewest harmonics is chosen among those
+/// within `FIT_TOLERANCE`, and otherwise the period with the smallest error.
+fn fit(freqs: (f32, Option<f32>)) -> Option<(u8, (usize, Option<usize>))> {
+    // Find the harmonic nearest the given frequency, if it's in the frame.
+    let nearest = |params: &BaseParams, f: f32| {
+        let l = (f / harmonic_freq(params.fundamental, 1)).round();
+
+        if l >= 1.0 && l <= params.harmonics as f32 {
+            Some(l as usize)
+        } else {
+            None
+        }
+    };
+
+    let mut best = None;
+
+    for period in 0...207 {
+        let params = BaseParams::new(period);
+
+        if params.harmonics > MAX_TONE_HARMONICS {
+            continue;
+        }
+
+        let lo = match nearest(&params, freqs.0) {
+            Some(l) => l,
+            None => continue,
+        };
+
+        let hi = match freqs.1.map(|f| nearest(&params, f)) {
+            Some(Some(l)) if l != lo => Some(l),
+            Some(_) => continue,
+            None => None,
+        };
+
+        // Compute the largest relative frequency error.
+        let err = |l: usize, f: f32| (harmonic_freq(params.fundamental, l) - f).abs() / f;
+        let err = err(lo, freqs.0).max(match (hi, freqs.1) {
+            (Some(l), Some(f)) => err(l, f),
+            _ => 0.0,
+        });
+
+        // Rank fits within tolerance by harmonics and the rest by error.
+        let rank = if err <= FIT_TOLERANCE {
+            (0, params.harmonics as f32 + err)
+        } else {
+            (1, err)
+        };
+
+        match best {
+            Some((r, _, _)) if r <= rank => {},
+            _ => best = Some((rank, period, (lo, hi))),
+        }
+    }
+
+    best.map(|(_, period, harmonics)| (period, harmonics))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use decode::ImbeDecoder;
+    use frame::ReceivedFrame;
+    use rng::XorShift;
+
+    /// Create saved parameters and PCM for a fully voiced frame with its energy in the
+    /// harmonics nearest the given frequencies, returning the harmonic frequencies.
+    fn frame(freqs: &[f32]) -> (PrevFrame, Vec<f32>, Vec<f32>) {
+        // Find the period whose harmonics best fit the frequencies.
+        let nearest = |params: &BaseParams, f: f32| {
+            let spacing = harmonic_freq(params.fundamental, 1);
+            ((f / spacing).round() as usize).max(1).min(params.harmonics as usize)
+        };
+
+        let err = |b0: u8| {
+            let params = BaseParams::new(b0);
+
+            freqs.iter().fold(0.0, |s: f32, &f| {
+                s.max((harmonic_freq(params.fundamental, nearest(&params, f)) - f).abs())
+            })
+        };
+
+        // Only frames with at most 36 harmonics can be fully voiced.
+        let b0 = (0..208).filter(|&p| BaseParams::new(p).harmonics <= 36)
+            .fold(0, |b, p| if err(p) < err(b) { p } else { b });
+
+        let mut prev = PrevFrame::default();
+        prev.params = BaseParams::new(b0);
+        prev.voice = VoiceDecisions::new((1 << prev.params.bands) - 1, &prev.params);
+
+        let harmonics = freqs.iter().map(|&f| nearest(&prev.params, f))
+            .collect::<Vec<_>>();
+
+        prev.enhanced = (1...prev.params.harmonics as usize).map(|l| {
+            if harmonics.contains(&l) { 100.0 } else { 0.01 }
+        }).collect();
+
+        let actual = harmonics.iter().map(|&l| harmonic_freq(prev.params.fundamental, l))
+            .collect::<Vec<_>>();
+
+        let pcm = (0..160).map(|n| {
+            actual.iter().fold(0.0, |s, &f| {
+                s + 1000.0 * (2.0 * PI * f * n as f32 / SAMPLE_RATE as f32).cos()
+            })
+        }).collect();
+
+        (prev, pcm, actual)
+    }
+
+    #[test]
+    fn test_goertzel() {
+        let (_, pcm, actual) = frame(&[1000.0]);
+
+        assert!((goertzel(&pcm, actual[0]) - 1.0).abs() < 0.05);
+        assert!(goertzel(&pcm, 2000.0) < 0.05);
+        assert_eq!(goertzel(&[0.0; 160], 1000.0), 0.0);
+    }
+
+    #[test]
+    fn test_dtmf_digit() {
+        assert_eq!(dtmf_digit(770.0, 1336.0, 0.02, 0.0), Some('5'));
+        assert_eq!(dtmf_digit(941.0, 1633.0, 0.02, 0.0), Some('D'));
+        assert_eq!(dtmf_digit(700.0, 1200.0, 0.02, 0.0), Some('1'));
+        assert_eq!(dtmf_digit(730.0, 1209.0, 0.02, 0.0), None);
+        assert_eq!(dtmf_digit(730.0, 1209.0, 0.02, 20.0), Some('1'));
+        assert_eq!(dtmf_digit(770.0, 1100.0, 0.02, 0.0), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let config = ToneConfig::default();
+
+        let (prev, pcm, _) = frame(&[770.0, 1336.0]);
+        assert_eq!(detect(&prev, &pcm, &config), Some(Tone::Dtmf('5')));
+
+        let (prev, pcm, _) = frame(&[941.0, 1477.0]);
+        assert_eq!(detect(&prev, &pcm, &config), Some(Tone::Dtmf('#')));
+
+        let (prev, pcm, actual) = frame(&[1000.0]);
+        assert_eq!(detect(&prev, &pcm, &config), Some(Tone::Single(actual[0])));
+
+        let (prev, pcm, actual) = frame(&[500.0, 2500.0]);
+        assert_eq!(detect(&prev, &pcm, &config), Some(Tone::Dual(actual[0], actual[1])));
+    }
+
+    #[test]
+    fn test_reject() {
+        let config = ToneConfig::default();
+        let (prev, pcm, _) = frame(&[770.0, 1336.0]);
+
+        // Unvoiced harmonics.
+        let mut p = frame(&[770.0, 1336.0]).0;
+        p.voice = VoiceDecisions::new(0b1, &p.params);
+        assert_eq!(detect(&p, &pcm, &config), None);
+
+        // Energy spread over three harmonics.
+        let (p, pcm3, _) = frame(&[770.0, 1336.0, 2000.0]);
+        assert_eq!(detect(&p, &pcm3, &config), None);
+
+        // Tones beyond the allowed twist.
+        let mut p = frame(&[770.0, 1336.0]).0;
+        let hi = p.enhanced.iter().rposition(|&m| m > 1.0).unwrap();
+        p.enhanced[hi] = 30.0;
+        assert_eq!(detect(&p, &pcm, &config), None);
+
+        // PCM that doesn't match the model.
+        let noise = (0..160).map(|n| ((n * 7919) % 263) as f32 - 131.0)
+            .collect::<Vec<_>>();
+        assert_eq!(detect(&prev, &noise, &config), None);
+        assert_eq!(detect(&prev, &pcm, &config), Some(Tone::Dtmf('5')));
+    }
+
+    #[test]
+    fn test_detector() {
+        let (five, five_pcm, _) = frame(&[770.0, 1336.0]);
+        let (single, single_pcm, _) = frame(&[1000.0]);
+        let (voice, voice_pcm, _) = frame(&[300.0, 600.0, 900.0]);
+
+        let mut det = ToneDetector::default();
+
+        assert_eq!(det.update(FrameStatus::Normal, &voice, &voice_pcm), None);
+        assert_eq!(det.update(FrameStatus::Normal, &five, &five_pcm), None);
+        assert_eq!(det.update(FrameStatus::Normal, &five, &five_pcm), None);
+        assert_eq!(det.update(FrameStatus::Repeat, &voice, &voice_pcm), None);
+        assert_eq!(det.current().map(|e| e.frames), Some(3));
+
+        let e = det.update(FrameStatus::Normal, &voice, &voice_pcm).unwrap();
+        assert_eq!(e, ToneEvent { tone: Tone::Dtmf('5'), start: 1, frames: 3 });
+        assert_eq!(e.duration_ms(), 60);
+
+        // Blips shorter than the minimum aren't reported.
+        assert_eq!(det.update(FrameStatus::Normal, &single, &single_pcm), None);
+        assert_eq!(det.update(FrameStatus::Mute, &single, &single_pcm), None);
+        assert!(det.current().is_none());
+
+        // A tone changing ends the previous one.
+        assert_eq!(det.update(FrameStatus::Normal, &single, &single_pcm), None);
+        assert_eq!(det.update(FrameStatus::Normal, &single, &single_pcm), None);
+        assert_eq!(det.update(FrameStatus::Normal, &five, &five_pcm).map(|e| e.start),
+                   Some(7));
+        assert_eq!(det.finish(), None);
+        assert_eq!(det.finish(), None);
+
+        assert_eq!(det.update(FrameStatus::Normal, &five, &five_pcm), None);
+        assert_eq!(det.update(FrameStatus::Normal, &five, &five_pcm), None);
+        assert_eq!(det.finish().map(|e| e.frames), Some(2));
+    }
+
+    #[test]
+    fn test_dtmf_freqs() {
+        assert_eq!(dtmf_freqs('5'), Some((770.0, 1336.0)));
+        assert_eq!(dtmf_freqs('*'), Some((941.0, 1209.0)));
+        assert_eq!(dtmf_freqs('A'), Some((697.0, 1633.0)));
+        assert_eq!(dtmf_freqs('x'), None);
+
+        for d in "123A456B789C*0#D".chars() {
+            let (lo, hi) = dtmf_freqs(d).unwrap();
+            assert_eq!(dtmf_digit(lo, hi, 0.0, 0.0), Some(d));
+        }
+    }
+
+    #[test]
+    fn test_fit() {
+        assert!(ToneGenerator::new(Tone::Dtmf('x'), -10.0).is_none());
+        assert!(ToneGenerator::new(Tone::Single(20.0), -10.0).is_none());
+        assert!(ToneGenerator::new(Tone::Single(3900.0), -10.0).is_none());
+
+        for &tone in &[Tone::Single(300.0), Tone::Single(2100.0), Tone::Dtmf('1'),
+                       Tone::Dual(440.0, 480.0)]
+        {
+            let gen = ToneGenerator::new(tone, -10.0).unwrap();
+            let params = gen.params();
+
+            assert!(params.harmonics <= MAX_TONE_HARMONICS);
+            assert_eq!(gen.voice().unvoiced_count(), 0);
+            assert_eq!(gen.spectrals().len(), params.harmonics as usize);
+
+            let (lo, hi) = gen.frequencies();
+            let (lo_err, hi_err) = gen.error();
+            let (lo_req, hi_req) = gen.requested();
+
+            assert!((lo - lo_req - lo_err).abs() < 1e-3);
+            assert_eq!(hi.is_some(), hi_req.is_some());
+
+            if let (Some(f), Some(r), Some(e)) = (hi, hi_req, hi_err) {
+                assert!((f - r - e).abs() < 1e-3);
+            }
+
+            // Close pairs need a fundamental below the lowest available.
+            let tol = if tone == Tone::Dual(440.0, 480.0) { 0.1 } else { FIT_TOLERANCE };
+
+            assert!(lo_err.abs() <= tol * lo_req);
+            assert!(hi_err.unwrap_or(0.0).abs() <= tol * hi_req.unwrap_or(0.0));
+        }
+
+        // Tones one harmonic apart are always exact.
+        let gen = ToneGenerator::new(Tone::Dual(400.0, 800.0), -10.0).unwrap();
+        assert_eq!(gen.harmonics().1, gen.harmonics().0.checked_mul(2));
+    }
+
+    /// Decode the given number of frames of the given generator, returning the tone
+    /// events and the final frame.
+    fn generate(gen: &mut ToneGenerator, frames: usize) -> (Vec<ToneEvent>, [f32; 160]) {
+        let mut dec = ImbeDecoder::with_rng(Default::default(), XorShift::default());
+        let mut det = ToneDetector::default();
+        let mut buf = [0.0; 160];
+        let mut events = vec![];
+
+        for chunks in gen.take(frames) {
+            let status = dec.decode(ReceivedFrame::new(chunks, [0; 7]), &mut buf);
+            assert_eq!(status, FrameStatus::Normal);
+
+            events.extend(det.update(status, dec.prev(), &buf));
+        }
+
+        events.extend(det.finish());
+
+        (events, buf)
+    }
+
+    #[test]
+    fn test_generate() {
+        for d in "159A0#D".chars() {
+            let mut gen = ToneGenerator::new(Tone::Dtmf(d), -10.0).unwrap();
+            let (events, _) = generate(&mut gen, 25);
+
+            // Tones build up within a couple frames and then hold.
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].tone, Tone::Dtmf(d));
+            assert!(events[0].start <= 2);
+        }
+
+        for &level in &[-40.0, -20.0, -6.0] {
+            let mut gen = ToneGenerator::new(Tone::Single(1000.0), level).unwrap();
+            let (events, pcm) = generate(&mut gen, 25);
+            let freq = gen.frequencies().0;
+
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].tone, Tone::Single(freq));
+
+            // Compare the level of the tone to the target.
+            let energy = pcm.iter().fold(0.0, |s, &x| s + x * x);
+            let power = goertzel(&pcm, freq) * energy / pcm.len() as f32;
+            let db = 10.0 * (2.0 * power / (32767.0f32 * 32767.0)).ln() / LN_10;
+
+            assert!((db - level).abs() < 2.0);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Pitch and formant modification of decoded model parameters.
+//!
+//! Since IMBE speech is synthesized from a fundamental frequency ω<sub>0</sub>, spectral
+//! amplitudes, and voiced/unvoiced decisions, voices can be altered by transforming
+//! these parameters before synthesis:
+//!
+//! - Pitch shifting scales ω<sub>0</sub>, recomputes L and K for the new harmonic grid,
+//!   and resamples the spectral envelope onto it, so harmonics move while the formants
+//!   stay in place.
+//! - Formant shifting stretches the spectral envelope along the frequency axis, so
+//!   formants move while the pitch stays in place.
+//!
+//! Voiced/unvoiced decisions describe the excitation rather than the envelope, so each
+//! new harmonic takes the decision of the original harmonic nearest in frequency.
+
+use arrayvec::ArrayVec;
+use map_in_place::MapInPlace;
+
+use consts::MAX_HARMONICS;
+use descramble::VoiceDecisions;
+use enhance::EnhancedSpectrals;
+#[cfg(not(feature = "std"))]
+use math::Float;
+use params::BaseParams;
+use spectral::Spectrals;
+
+/// Transforms the model parameters of each frame to shift pitch and formants.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VoiceTransform {
+    /// Factor applied to the fundamental frequency.
+    pitch: f32,
+    /// Factor applied to formant frequencies.
+    formant: f32,
+}
+
+impl VoiceTransform {
+    /// Create a new `VoiceTransform` with the given pitch and formant factors, which must
+    /// be positive and finite.
+    pub fn new(pitch: f32, formant: f32) -> VoiceTransform {
+        assert!(pitch > 0.0 && formant > 0.0);
+        assert!(pitch.is_finite() && formant.is_finite());
+
+        VoiceTransform {
+            pitch: pitch,
+            formant: formant,
+        }
+    }
+
+    /// Factor applied to the fundamental frequency, where 2 raises pitch an octave.
+    pub fn pitch(&self) -> f32 { self.pitch }
+
+    /// Factor applied to formant frequencies, where values above 1 give a smaller,
+    /// brighter voice and values below 1 a larger, darker one.
+    pub fn formant(&self) -> f32 { self.formant }
+
+    /// Check whether the transform leaves parameters unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.pitch == 1.0 && self.formant == 1.0
+    }
+
+    /// Compute the parameters with shifted fundamental frequency, with ω<sub>0</sub>
+    /// limited so that 9 ≤ L ≤ 56 as for any received frame.
+    pub fn params(&self, params: &BaseParams) -> BaseParams {
+        let min = BaseParams::new(207).fundamental;
+        let max = BaseParams::new(0).fundamental;
+
+        BaseParams::from_fundamental((params.fundamental * self.pitch).max(min).min(max))
+    }
+
+    /// Resample the given spectral amplitudes from the harmonic grid of the original
+    /// parameters onto that of the transformed parameters.
+    pub fn spectrals(&self, spectrals: &Spectrals, from: &BaseParams, to: &BaseParams)
+        -> Spectrals
+    {
+        self.resample(spectrals, from, to).into_iter().collect()
+    }
+
+    /// Resample the given enhanced spectral amplitudes from the harmonic grid of the
+    /// original parameters onto that of the transformed parameters.
+    pub fn enhanced(&self, enhanced: &EnhancedSpectrals, from: &BaseParams,
+                    to: &BaseParams)
+        -> EnhancedSpectrals
+    {
+        self.resample(enhanced, from, to).into_iter().collect()
+    }
+
+    /// Remap the given voiced/unvoiced decisions from the harmonic grid of the original
+    /// parameters onto that of the transformed parameters.
+    pub fn voice(&self, voice: &VoiceDecisions, from: &BaseParams, to: &BaseParams)
+        -> VoiceDecisions
+    {
+        // Start with all harmonics unvoiced.
+        let mut remapped = VoiceDecisions::new(0, to);
+
+        for l in 1...to.harmonics as usize {
+            // Find the original harmonic nearest in frequency.
+            let pos = l as f32 * to.fundamental / from.fundamental;
+            let k = (pos.round() as usize).max(1).min(from.harmonics as usize);
+
+            if voice.is_voiced(k) {
+                remapped.force_voiced(l);
+            }
+        }
+
+        remapped
+    }
+
+    /// Resample the given amplitudes M<sub>1</sub>, ..., M<sub>L</sub> onto the
+    /// transformed harmonic grid, preserving their total energy.
+    fn resample(&self, amps: &[f32], from: &BaseParams, to: &BaseParams)
+        -> ArrayVec<[f32; MAX_HARMONICS]>
+    {
+        if amps.is_empty() {
+            return ArrayVec::new();
+        }
+
+        let mut resampled = (1...to.harmonics).map(|l| {
+            // Compute position of the new harmonic on the original envelope, in units of
+            // the original harmonics.
+            let pos = (l as f32 * to.fundamental / (from.fundamental * self.formant))
+                .max(1.0)
+                .min(amps.len() as f32);
+
+            let k = pos.trunc() as usize;
+            let dec = pos.fract();
+
+            // Amplitudes beyond the last harmonic take its value, as in Eq 79.
+            let lower = amps[k - 1];
+            let upper = if k < amps.len() { amps[k] } else { lower };
+
+            (1.0 - dec) * lower + dec * upper
+        }).collect::<ArrayVec<[f32; MAX_HARMONICS]>>();
+
+        let energy = amps.iter().fold(0.0, |s, &m| s + m * m);
+        let new = resampled.iter().fold(0.0, |s, &m| s + m * m);
+
+        if new > 0.0 {
+            let scale = (energy / new).sqrt();
+            resampled.map_in_place(|&m| m * scale);
+        }
+
+        resampled
+    }
+}
+
+impl Default for VoiceTransform {
+    /// Create a new `VoiceTransform` that leaves parameters unchanged.
+    fn default() -> VoiceTransform { VoiceTransform::new(1.0, 1.0) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use descramble::{Bootstrap, descramble};
+    use enhance::{EnhancedSpectrals, FrameEnergy};
+    use frame::TEST_CHUNKS;
+    use gain::Gains;
+    use coefs::Coefficients;
+    use params::BaseParams;
+    use prev::PrevFrame;
+    use spectral::Spectrals;
+
+    fn energy(amps: &[f32]) -> f32 {
+        amps.iter().fold(0.0, |s, &m| s + m * m)
+    }
+
+    #[test]
+    fn test_params() {
+        let p = BaseParams::new(104);
+
+        let t = VoiceTransform::new(2.0, 1.0);
+        let q = t.params(&p);
+        assert!((q.fundamental - 2.0 * p.fundamental).abs() < 1e-6);
+        assert_eq!(q.harmonics, 16);
+        assert_eq!(q.bands, 6);
+
+        let q = VoiceTransform::new(0.5, 1.0).params(&p);
+        assert_eq!(q.harmonics, 56);
+        assert_eq!(q.bands, 12);
+
+        // Verify ω0 is limited to the received range.
+        let q = VoiceTransform::new(100.0, 1.0).params(&p);
+        assert_eq!(q.harmonics, 9);
+        let q = VoiceTransform::new(0.01, 1.0).params(&p);
+        assert_eq!(q.harmonics, 56);
+
+        let q = VoiceTransform::default().params(&p);
+        assert_eq!(q.fundamental, p.fundamental);
+        assert_eq!(q.harmonics, p.harmonics);
+    }
+
+    #[test]
+    fn test_identity() {
+        let b = Bootstrap::new(&TEST_CHUNKS);
+        let p = BaseParams::new(b.unwrap_period());
+        let (amps, voice, gain_idx) = descramble(&TEST_CHUNKS, &p);
+        let g = Gains::new(gain_idx, &amps, &p);
+        let c = Coefficients::new(&g, &amps, &p);
+        let prev = PrevFrame::default();
+        let s = Spectrals::new(&c, &p, &prev);
+        let fe = FrameEnergy::new(&s, &FrameEnergy::default(), &p);
+        let e = EnhancedSpectrals::new(&s, &fe, &p);
+
+        let t = VoiceTransform::default();
+        assert!(t.is_identity());
+
+        let q = t.params(&p);
+
+        let s2 = t.spectrals(&s, &p, &q);
+        assert_eq!(s2.len(), s.len());
+        for (a, b) in s2.iter().zip(s.iter()) {
+            assert!((a - b).abs() < 1e-4 * b);
+        }
+
+        let e2 = t.enhanced(&e, &p, &q);
+        for (a, b) in e2.iter().zip(e.iter()) {
+            assert!((a - b).abs() < 1e-4 * b.max(1.0));
+        }
+
+        let v = t.voice(&voice, &p, &q);
+        for l in 1...p.harmonics as usize {
+            assert_eq!(v.is_voiced(l), voice.is_voiced(l));
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let t = VoiceTransform::new(1.5, 0.8);
+        assert_eq!(t.pitch(), 1.5);
+        assert_eq!(t.formant(), 0.8);
+        assert!(!t.is_identity());
+
+        assert!(VoiceTransform::default().is_identity());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_zero() {
+        VoiceTransform::new(0.0, 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_nan() {
+        VoiceTransform::new(1.0, ::std::f32::NAN);
+    }
+
+    #[test]
+    fn test_pitch() {
+        let p = BaseParams::new(104);
+        let t = VoiceTransform::new(1.5, 1.0);
+        let q = t.params(&p);
+
+        assert_eq!(q.harmonics, 22);
+
+        // Build a linearly falling envelope.
+        let e: EnhancedSpectrals = (1...p.harmonics).map(|l| 100.0 - l as f32).collect();
+        let e2 = t.enhanced(&e, &p, &q);
+
+        assert_eq!(e2.len(), 22);
+        assert!((energy(&e2) - energy(&e)).abs() < energy(&e) * 1e-4);
+
+        // Verify new harmonic l samples the envelope at original harmonic 1.5l.
+        assert!((e2.get(2) / e2.get(4) - (100.0 - 3.0) / (100.0 - 6.0)).abs() < 1e-4);
+        assert!((e2.get(5) / e2.get(8) - (100.0 - 7.5) / (100.0 - 12.0)).abs() < 1e-4);
+
+        // Low harmonics voiced, high unvoiced.
+        let mut voice = VoiceDecisions::new(0, &p);
+        for l in 1...16 {
+            voice.force_voiced(l);
+        }
+
+        let v = t.voice(&voice, &p, &q);
+
+        for l in 1...10 {
+            assert!(v.is_voiced(l));
+        }
+        for l in 12...22 {
+            assert!(!v.is_voiced(l));
+        }
+    }
+
+    #[test]
+    fn test_formant() {
+        let p = BaseParams::new(104);
+        let t = VoiceTransform::new(1.0, 2.0);
+        let q = t.params(&p);
+
+        assert_eq!(q.harmonics, p.harmonics);
+
+        // Place a formant peak at harmonic 10.
+        let e: EnhancedSpectrals = (1...p.harmonics).map(|l| {
+            if l == 10 { 100.0 } else { 1.0 }
+        }).collect();
+
+        let e2 = t.enhanced(&e, &p, &q);
+        let peak = e2.iter().enumerate()
+            .fold((0, 0.0), |(i, m), (j, &x)| if x > m { (j, x) } else { (i, m) }).0;
+
+        // Verify the peak moved to twice the frequency.
+        assert_eq!(peak + 1, 20);
+    }
+}
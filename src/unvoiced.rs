@@ -38,6 +38,9 @@
 //! with equal real and imaginary sample variances, but empirical evaluations show that
 //! the result is the same with a real signal.
 //!
+//! Faster approximations of this distribution, as well as the standard's example u(n)
+//! itself, are available as alternative sources in the `noise` module.
+//!
 //! [dft-noise]: http://users.ece.gatech.edu/mrichard/DFT%20of%20Noise.pdf
 //!
 //! ## DFT Symmetry
@@ -84,17 +87,23 @@
 use std::f32::consts::PI;
 
 use map_in_place::MapInPlace;
+#[cfg(feature = "std")]
 use num::complex::Complex32;
+#[cfg(feature = "std")]
 use num::traits::Zero;
+#[cfg(feature = "std")]
 use iq_osc::IQOsc;
-use rand::distributions::IndependentSample;
-use rand::distributions::normal::Normal;
-use rand::Rng;
 
 use consts::SAMPLES_PER_FRAME;
 use descramble::VoiceDecisions;
 use enhance::EnhancedSpectrals;
+#[cfg(not(feature = "std"))]
+use math::{Complex32, Float, IQOsc};
+use noise::{Noise, Polar};
 use params::BaseParams;
+use rng::Random;
+use simd::{self, Isa};
+use timing::Timing;
 use window;
 
 /// Unvoiced scaling coefficient γ<sub>w</sub> computed from Eq 121.
@@ -110,20 +119,37 @@ const DFT_HALF: usize = DFT_SIZE / 2;
 /// Number of points in real half of IDFT.
 const IDFT_HALF: usize = IDFT_SIZE / 2;
 
+/// Number of samples synthesized at a time by `Unvoiced::add_to`.
+const BLOCK_SIZE: usize = 64;
+
+/// Width of the crossfade into a fresh noise segment at the edge of the IDFT range, in
+/// native samples.
+const FADE_WIDTH: f32 = 32.0;
+
 /// Constructs unvoiced DFT/IDFT.
 pub struct UnvoicedDft([Complex32; DFT_HALF]);
 
 impl UnvoicedDft {
-    /// Construct a new `UnvoicedDft` from the given frame parameters and noise generator.
-    pub fn new<R: Rng>(params: &BaseParams, voice: &VoiceDecisions,
-                       amps: &EnhancedSpectrals, mut rng: R)
+    /// Construct a new `UnvoicedDft` from the given frame parameters and random number
+    /// generator, drawing Gaussian noise with `Random::normal`.
+    pub fn new<R: Random + ?Sized>(params: &BaseParams, voice: &VoiceDecisions,
+                                   amps: &EnhancedSpectrals, rng: &mut R)
+        -> Self
+    {
+        Self::with_noise(params, voice, amps, &mut Polar, rng)
+    }
+
+    /// Construct a new `UnvoicedDft` from the given frame parameters, drawing its noise
+    /// spectrum from the given source and random number generator.
+    pub fn with_noise<N: Noise + ?Sized, R: Random + ?Sized>(
+        params: &BaseParams, voice: &VoiceDecisions, amps: &EnhancedSpectrals,
+        noise: &mut N, rng: &mut R)
         -> Self
     {
         // DFT values default to 0 according to Eqs 119 and 124.
         let mut dft = [Complex32::default(); DFT_HALF];
 
-        // Create a Gaussian distribution with mean μ = 0 and variance σ^2 = E_w / 2.
-        let gaus = Normal::new(0.0, (window::ENERGY_SYNTHESIS / 2.0).sqrt() as f64);
+        noise.frame();
 
         for (l, &amp) in amps.iter().enumerate() {
             let l = l + 1;
@@ -136,10 +162,7 @@ impl UnvoicedDft {
             let (lower, upper) = edges(l, params);
 
             // Populate the current band with random spectrum.
-            for m in lower..upper {
-                dft[m] = Complex32::new(gaus.ind_sample(&mut rng) as f32,
-                                        gaus.ind_sample(&mut rng) as f32);
-            }
+            noise.fill(rng, lower, &mut dft[lower..upper]);
 
             // Compute energy of current band according to Eq 120.
             let energy = (lower..upper)
@@ -157,14 +180,37 @@ impl UnvoicedDft {
         UnvoicedDft(dft)
     }
 
+    /// Construct a fresh noise segment with the same magnitude spectrum, and so the same
+    /// band powers of Eq 120, by drawing a new random phase for each DFT point.
+    fn segment<R: Random + ?Sized>(&self, rng: &mut R) -> UnvoicedDft {
+        let mut dft = self.0;
+
+        for x in dft.iter_mut() {
+            let phase = rng.uniform(-PI, PI);
+            let (sin, cos) = (phase.sin(), phase.cos());
+
+            *x = Complex32::new(x.re * cos - x.im * sin, x.re * sin + x.im * cos);
+        }
+
+        UnvoicedDft(dft)
+    }
+
     /// Compute the IDFT u<sub>w</sub>(n) at the given point n.
     pub fn idft(&self, n: isize) -> f32 {
+        self.idft_frac(n as f32)
+    }
+
+    /// Compute the IDFT u<sub>w</sub>(t) at the given fractional point t.
+    ///
+    /// Since the IDFT is a sum of sinusoids, this gives the band-limited interpolation of
+    /// the noise signal between samples, equivalent to a zero-padded IDFT of larger size.
+    pub fn idft_frac(&self, t: f32) -> f32 {
         // The IDFT is zero outside the defined range [p59].
-        if n < -(IDFT_HALF as isize) || n >= IDFT_HALF as isize {
+        if t < -(IDFT_HALF as f32) || t >= IDFT_HALF as f32 {
             return 0.0;
         }
 
-        let mut osc = IQOsc::new(0.0, 2.0 / IDFT_SIZE as f32 * PI * n as f32);
+        let mut osc = IQOsc::new(0.0, 2.0 / IDFT_SIZE as f32 * PI * t);
 
         2.0 / IDFT_SIZE as f32 * self.0.iter().map(|x| {
             let (sin, cos) = osc.next();
@@ -182,50 +228,254 @@ impl Default for UnvoicedDft {
     }
 }
 
+/// Real and imaginary parts of an unvoiced DFT, stored separately for vectorized IDFT
+/// evaluation.
+struct SplitDft {
+    re: [f32; DFT_HALF],
+    im: [f32; DFT_HALF],
+}
+
+impl SplitDft {
+    /// Create a new `SplitDft` from the given unvoiced DFT.
+    fn new(dft: &UnvoicedDft) -> SplitDft {
+        let mut split = SplitDft {
+            re: [0.0; DFT_HALF],
+            im: [0.0; DFT_HALF],
+        };
+
+        for (m, x) in dft.0.iter().enumerate() {
+            split.re[m] = x.re;
+            split.im[m] = x.im;
+        }
+
+        split
+    }
+
+    /// Compute the IDFT u<sub>w</sub>(t) at the given fractional point t using the given
+    /// instruction set, equivalent to `UnvoicedDft::idft_frac`.
+    fn idft(&self, isa: Isa, t: f32) -> f32 {
+        // The IDFT is zero outside the defined range [p59].
+        if t < -(IDFT_HALF as f32) || t >= IDFT_HALF as f32 {
+            return 0.0;
+        }
+
+        2.0 / IDFT_SIZE as f32 *
+            simd::idft(isa, &self.re, &self.im, 2.0 / IDFT_SIZE as f32 * PI * t)
+    }
+}
+
+/// Noise signal of a single frame, continued past one edge of the IDFT range with a
+/// fresh segment when a stretched synthesis window reaches beyond it.
+struct Segments<'a> {
+    /// Unvoiced DFT/IDFT of the frame.
+    dft: &'a UnvoicedDft,
+    /// Split copy of the frame DFT.
+    split: SplitDft,
+    /// Fresh noise segment past the edge and its split copy, if needed.
+    ext: Option<(UnvoicedDft, SplitDft)>,
+    /// Edge of the IDFT range at which the fresh segment takes over, ±128.
+    edge: f32,
+}
+
+impl<'a> Segments<'a> {
+    /// Create a new `Segments` for the given unvoiced DFT with the given fresh segment
+    /// past the given edge.
+    fn new(dft: &'a UnvoicedDft, ext: Option<UnvoicedDft>, edge: f32) -> Self {
+        Segments {
+            dft: dft,
+            split: SplitDft::new(dft),
+            ext: ext.map(|ext| {
+                let split = SplitDft::new(&ext);
+                (ext, split)
+            }),
+            edge: edge,
+        }
+    }
+
+    /// Compute the gains of the frame noise and of the fresh segment at the given time t,
+    /// crossfading with constant power across the edge.
+    fn fade(&self, t: f32) -> (f32, f32) {
+        if self.ext.is_none() {
+            return (1.0, 0.0);
+        }
+
+        // Distance past the edge, positive on the side of the fresh segment.
+        let dist = (t - self.edge) * self.edge.signum();
+        let x = (dist / FADE_WIDTH + 0.5).max(0.0).min(1.0) * PI / 2.0;
+
+        (x.cos(), x.sin())
+    }
+
+    /// Compute the noise signal at the given time t.
+    fn get(&self, t: f32) -> f32 {
+        let (base, fresh) = self.fade(t);
+
+        let mut sum = 0.0;
+
+        if base > 0.0 {
+            sum += base * self.dft.idft_frac(wrap(t));
+        }
+
+        if let Some((ref ext, _)) = self.ext {
+            if fresh > 0.0 {
+                sum += fresh * ext.idft_frac(wrap(t));
+            }
+        }
+
+        sum
+    }
+
+    /// Compute the noise signal at the given time t using the given instruction set,
+    /// equivalent to `get`.
+    fn idft(&self, isa: Isa, t: f32) -> f32 {
+        let (base, fresh) = self.fade(t);
+
+        let mut sum = 0.0;
+
+        if base > 0.0 {
+            sum += base * self.split.idft(isa, wrap(t));
+        }
+
+        if let Some((_, ref ext)) = self.ext {
+            if fresh > 0.0 {
+                sum += fresh * ext.idft(isa, wrap(t));
+            }
+        }
+
+        sum
+    }
+}
+
 /// Synthesizes unvoiced spectrum signal s<sub>uv</sub>(n).
 pub struct Unvoiced<'a, 'b> {
-    /// Unvoiced DFT/IDFT for current frame.
-    cur: &'a UnvoicedDft,
-    /// Unvoiced DFT/IDFT for previous frame.
-    prev: &'b UnvoicedDft,
+    /// Unvoiced noise for current frame.
+    cur: Segments<'a>,
+    /// Unvoiced noise for previous frame.
+    prev: Segments<'b>,
     /// Synthesis window w<sub>s</sub>(n) for "weighted overlap add".
     window: window::Window,
+    /// Maps output samples to native time.
+    timing: Timing,
+    /// Instruction set used for IDFT and overlap-add.
+    isa: Isa,
 }
 
 impl<'a, 'b> Unvoiced<'a, 'b> {
     /// Create a new `Unvoiced` from the given unvoiced spectrums of the current and
     /// previous frames.
     pub fn new(cur: &'a UnvoicedDft, prev: &'b UnvoicedDft) -> Self {
+        Self::build(cur, prev, None, None, Timing::native())
+    }
+
+    /// Create a new `Unvoiced` that synthesizes output samples with the given timing.
+    ///
+    /// When a stretched synthesis window reaches beyond the 256-point IDFT, the noise of
+    /// each frame is continued with a fresh segment drawn with the given random number
+    /// generator, rather than repeating the IDFT every 32ms.
+    pub fn with_timing<R: Random + ?Sized>(cur: &'a UnvoicedDft, prev: &'b UnvoicedDft,
+                                           timing: Timing, rng: &mut R)
+        -> Self
+    {
+        // Native time at which the stretched window falls to zero.
+        let reach = window::synthesis().reach() as f32 * timing.period() /
+            SAMPLES_PER_FRAME as f32;
+
+        if reach <= IDFT_HALF as f32 - FADE_WIDTH / 2.0 {
+            return Self::build(cur, prev, None, None, timing);
+        }
+
+        // Since the frame period is at most MAX_PERIOD, the window never reaches past
+        // one further segment.
+        debug_assert!(reach < (IDFT_SIZE + IDFT_HALF) as f32 - FADE_WIDTH / 2.0);
+
+        let prev_ext = prev.segment(rng);
+        let cur_ext = cur.segment(rng);
+
+        Self::build(cur, prev, Some(cur_ext), Some(prev_ext), timing)
+    }
+
+    /// Create a new `Unvoiced` with the given fresh noise segments, which continue the
+    /// current frame before t = -128 and the previous frame after t = 128.
+    fn build(cur: &'a UnvoicedDft, prev: &'b UnvoicedDft, cur_ext: Option<UnvoicedDft>,
+             prev_ext: Option<UnvoicedDft>, timing: Timing)
+        -> Self
+    {
         Unvoiced {
-            cur: cur,
-            prev: prev,
+            cur: Segments::new(cur, cur_ext, -(IDFT_HALF as f32)),
+            prev: Segments::new(prev, prev_ext, IDFT_HALF as f32),
             window: window::synthesis(),
+            timing: timing,
+            isa: Isa::detect(),
         }
     }
 
-    /// Compute the unvoiced signal sample s<sub>uv</sub>(n) for the given n, 0 ≤ n < N.
+    /// Compute the unvoiced signal sample s<sub>uv</sub>(n) for the given output sample
+    /// n, 0 ≤ n < N.
     pub fn get(&self, n: usize) -> f32 {
-        debug_assert!(n < SAMPLES_PER_FRAME);
+        debug_assert!(n < self.timing.len());
 
-        let n = n as isize;
+        let t = self.timing.time(n);
+        let t_cur = t - self.timing.period();
+
+        let w_prev = self.window.get_frac(self.timing.window_time(t));
+        let w_cur = self.window.get_frac(self.timing.window_time(t_cur));
 
         // Compute numerator in Eq 126.
-        let numer = self.window.get(n) * self.prev.idft(n) +
-            self.window.get(n - SAMPLES_PER_FRAME as isize) *
-                self.cur.idft(n - SAMPLES_PER_FRAME as isize);
+        let numer = w_prev * self.prev.get(t) + w_cur * self.cur.get(t_cur);
 
         // Compute denominator in Eq 126.
-        let denom = self.window.get(n).powi(2) +
-            self.window.get(n - SAMPLES_PER_FRAME as isize).powi(2);
+        let denom = w_prev.powi(2) + w_cur.powi(2);
 
         // Compute Eq 126.
         numer / denom
     }
+
+    /// Add the unvoiced signal samples s<sub>uv</sub>(n) for start ≤ n < start + len to
+    /// the given buffer of length len.
+    ///
+    /// This gives the same result as adding `get` for each sample, but evaluates the
+    /// IDFTs and the weighted overlap-add of Eq 126 with vectorized kernels.
+    pub fn add_to(&self, start: usize, buf: &mut [f32]) {
+        debug_assert!(start + buf.len() <= self.timing.len());
+
+        for (i, chunk) in buf.chunks_mut(BLOCK_SIZE).enumerate() {
+            let mut prev = [0.0; BLOCK_SIZE];
+            let mut cur = [0.0; BLOCK_SIZE];
+            let mut win_prev = [0.0; BLOCK_SIZE];
+            let mut win_cur = [0.0; BLOCK_SIZE];
+
+            for j in 0..chunk.len() {
+                let t = self.timing.time(start + i * BLOCK_SIZE + j);
+                let t_cur = t - self.timing.period();
+
+                win_prev[j] = self.window.get_frac(self.timing.window_time(t));
+                win_cur[j] = self.window.get_frac(self.timing.window_time(t_cur));
+
+                prev[j] = self.prev.idft(self.isa, t);
+                cur[j] = self.cur.idft(self.isa, t_cur);
+            }
+
+            let len = chunk.len();
+
+            // Compute Eq 126.
+            simd::overlap_add(self.isa, &prev[..len], &cur[..len], &win_prev[..len],
+                              &win_cur[..len], chunk);
+        }
+    }
+}
+
+/// Wrap the given time t into the range of the IDFT, -128 ≤ t < 128.
+///
+/// The IDFT of a DFT is periodic, so this continues the noise signal smoothly across the
+/// edges of the range while crossfading into a fresh segment. Within the range, and so
+/// wherever the standard synthesis window is nonzero, t is unchanged.
+fn wrap(t: f32) -> f32 {
+    t - IDFT_SIZE as f32 * ((t + IDFT_HALF as f32) / IDFT_SIZE as f32).floor()
 }
 
 /// Determine the lower and upper band edges (a<sub>l</sub>, b<sub>l</sub>) for the given
 /// harmonic of the fundamental frequency.
-fn edges(l: usize, params: &BaseParams) -> (usize, usize) {
+pub fn edges(l: usize, params: &BaseParams) -> (usize, usize) {
     let common = DFT_SIZE as f32 / (2.0 * PI) * params.fundamental;
 
     (
@@ -243,6 +493,7 @@ mod test {
     use params::BaseParams;
     use num::complex::Complex32;
     use rand::XorShiftRng;
+    use resample::SampleRate;
 
     #[test]
     fn test_edges() {
@@ -283,6 +534,35 @@ mod test {
         assert_eq!(upper, 119);
     }
 
+    #[test]
+    fn test_all_edges() {
+        for period in 0...207 {
+            let p = BaseParams::new(period);
+            let mut prev = edges(1, &p).0;
+
+            for l in 1...p.harmonics as usize {
+                let (lower, upper) = edges(l, &p);
+
+                assert!(lower >= 1 && upper <= 127);
+                assert!(lower < upper);
+
+                // Bands are contiguous.
+                assert_eq!(lower, prev);
+                prev = upper;
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap(0.0), 0.0);
+        assert_eq!(wrap(-128.0), -128.0);
+        assert_eq!(wrap(127.5), 127.5);
+        assert_eq!(wrap(128.0), -128.0);
+        assert_eq!(wrap(-200.0), 56.0);
+        assert_eq!(wrap(300.25), 44.25);
+    }
+
     #[test]
     fn test_dft() {
         // Verify results computed with standalone python script.
@@ -335,7 +615,7 @@ mod test {
         amps.push(9.0);
         amps.push(18.0);
 
-        let dft = UnvoicedDft::new(&p, &voice, &amps, XorShiftRng::new_unseeded());
+        let dft = UnvoicedDft::new(&p, &voice, &amps, &mut XorShiftRng::new_unseeded());
 
         assert_eq!(dft.0[0], Complex32::zero());
         assert_eq!(dft.0[1], Complex32::zero());
@@ -792,7 +1072,7 @@ mod test {
         amps.push(9.0);
         amps.push(18.0);
 
-        let dft = UnvoicedDft::new(&p, &voice, &amps, XorShiftRng::new_unseeded());
+        let dft = UnvoicedDft::new(&p, &voice, &amps, &mut XorShiftRng::new_unseeded());
         let uv = Unvoiced::new(&dft, &dft);
 
         assert!((uv.get(0) - 25.30613912637092255408788332715631).abs() < 1e-3);
@@ -956,4 +1236,122 @@ mod test {
         assert!((uv.get(158) - 11.79304219252217400537574576446787).abs() < 1e-3);
         assert!((uv.get(159) - -31.93931117908418571005313424393535).abs() < 1e-3);
     }
+
+    #[test]
+    fn test_add_to() {
+        let p = BaseParams::new(42);
+        let voice = VoiceDecisions::new(0b101001, &p);
+        let amps = (0..p.harmonics).map(|l| 1.0 + l as f32)
+            .collect::<EnhancedSpectrals>();
+
+        let mut rng = XorShiftRng::new_unseeded();
+        let prev = UnvoicedDft::new(&p, &voice, &amps, &mut rng);
+        let cur = UnvoicedDft::new(&p, &voice, &amps, &mut rng);
+
+        let timings = [
+            Timing::native(),
+            Timing::new(SampleRate::Hz44100),
+            Timing::stretched(SampleRate::Hz48000, 1900),
+        ];
+
+        for &timing in timings.iter() {
+            let uv = Unvoiced::with_timing(&cur, &prev, timing, &mut rng);
+
+            let mut buf = vec![1.0; timing.len()];
+            let (a, b) = buf.split_at_mut(timing.len() / 3);
+            uv.add_to(0, a);
+            uv.add_to(timing.len() / 3, b);
+
+            for (n, &x) in buf.iter().enumerate() {
+                assert!((x - 1.0 - uv.get(n)).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_segments() {
+        let p = BaseParams::new(42);
+        let voice = VoiceDecisions::new(0b101001, &p);
+        let amps = (0..p.harmonics).map(|l| 1.0 + l as f32)
+            .collect::<EnhancedSpectrals>();
+
+        let mut rng = XorShiftRng::new_unseeded();
+        let prev = UnvoicedDft::new(&p, &voice, &amps, &mut rng);
+        let cur = UnvoicedDft::new(&p, &voice, &amps, &mut rng);
+
+        // Windows at the native period stay within the IDFT.
+        let uv = Unvoiced::with_timing(&cur, &prev, Timing::new(SampleRate::Hz48000),
+                                       &mut rng);
+        assert!(uv.prev.ext.is_none() && uv.cur.ext.is_none());
+
+        let timing = Timing::stretched(SampleRate::Hz8000, 320);
+        let uv = Unvoiced::with_timing(&cur, &prev, timing, &mut rng);
+
+        let (ref ext, _) = *uv.prev.ext.as_ref().unwrap();
+        assert!(uv.cur.ext.is_some());
+
+        // Fresh segments keep the band powers of the frame.
+        for (a, b) in ext.0.iter().zip(prev.0.iter()) {
+            assert!((a.norm() - b.norm()).abs() < 1e-3 * b.norm().max(1.0));
+        }
+
+        // Outside the crossfade, only one segment is heard.
+        assert_eq!(uv.prev.get(100.0), prev.idft_frac(100.0));
+        assert_eq!(uv.prev.get(150.0), ext.idft_frac(150.0 - 256.0));
+        assert_eq!(uv.cur.get(-100.0), cur.idft_frac(-100.0));
+
+        // The noise past the edge no longer repeats the start of the IDFT.
+        let (xy, xx, yy) = (144..210).map(|t| {
+            let x = uv.prev.get(t as f32);
+            let y = prev.idft_frac(t as f32 - 256.0);
+            (x * y, x * x, y * y)
+        }).fold((0.0, 0.0, 0.0), |(a, b, c), (x, y, z)| (a + x, b + y, c + z));
+
+        assert!(xy.abs() / (xx * yy).sqrt() < 0.5);
+
+        // The crossfade has no jumps larger than those of the noise itself.
+        let step = |f: &Fn(f32) -> f32, start: f32| (0..256).map(|i| {
+            let t = start + i as f32 / 4.0;
+            (f(t + 0.25) - f(t)).abs()
+        }).fold(0.0, f32::max);
+
+        let edge = step(&|t| uv.prev.get(t), 96.0);
+        let inner = step(&|t| prev.idft_frac(t), -64.0);
+        let fresh = step(&|t| ext.idft_frac(t), -64.0);
+        assert!(edge < 1.5 * inner.max(fresh));
+    }
+
+    #[test]
+    fn test_noise() {
+        use noise::{NoiseKind, NoiseSource};
+
+        let p = BaseParams::new(42);
+        let voice = VoiceDecisions::new(0b101001, &p);
+        let amps = (0..p.harmonics).map(|l| 1.0 + l as f32)
+            .collect::<EnhancedSpectrals>();
+
+        for &kind in [NoiseKind::Polar, NoiseKind::Table, NoiseKind::Uniform,
+                      NoiseKind::Standard].iter()
+        {
+            let mut noise = NoiseSource::new(kind);
+            let mut rng = XorShiftRng::new_unseeded();
+            let dft = UnvoicedDft::with_noise(&p, &voice, &amps, &mut noise, &mut rng);
+
+            // Every source produces bands with the power given by Eq 120.
+            for (l, &amp) in amps.iter().enumerate() {
+                let l = l + 1;
+                let (lower, upper) = edges(l, &p);
+
+                let power = (lower..upper).map(|m| dft.0[m].norm_sqr())
+                    .fold(0.0, |s, x| s + x) / (upper - lower) as f32;
+
+                if voice.is_voiced(l) {
+                    assert_eq!(power, 0.0);
+                } else {
+                    let expected = (SCALING_COEF * amp).powi(2);
+                    assert!((power - expected).abs() < expected * 1e-4);
+                }
+            }
+        }
+    }
 }
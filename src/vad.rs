@@ -0,0 +1,271 @@
+//! Voice activity and talk-spurt detection from decoded frame parameters.
+//!
+//! Each decoded frame is classified as speech or non-speech from its model parameters
+//! rather than its audio:
+//!
+//! - A frame may be speech when its spectral energy R<sub>M0</sub> is within a
+//!   threshold of the energy tracker S<sub>E</sub>, which follows the level of recent
+//!   frames, so quiet frames between louder ones are rejected whatever the overall level.
+//! - Frames must also have some voiced harmonics, unless their energy reaches the
+//!   tracker, so steady unvoiced background noise isn't mistaken for speech.
+//! - Frames with the silence b<sub>0</sub> value are never speech.
+//!
+//! A talk spurt starts after a number of consecutive speech frames, and ends once a
+//! hangover period passes without speech, so short pauses between words don't split an
+//! utterance. Silence frames end a talk spurt immediately, since they're sent only when
+//! the transmitter has already detected the end of speech.
+
+use prev::PrevFrame;
+use stats::FrameStatus;
+
+/// Options that control voice activity detection.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VadConfig {
+    /// Minimum ratio of frame energy R<sub>M0</sub> to the tracked energy
+    /// S<sub>E</sub> for a frame to be speech.
+    pub threshold: f32,
+    /// Minimum ratio of frame energy to tracked energy for a frame with no voiced
+    /// harmonics to be speech.
+    pub unvoiced_threshold: f32,
+    /// Number of consecutive speech frames that start a talk spurt.
+    pub onset: usize,
+    /// Number of frames without speech that end a talk spurt.
+    pub hangover: usize,
+}
+
+impl Default for VadConfig {
+    /// Create a new `VadConfig` with a -15dB energy threshold, 40ms onset, and 300ms
+    /// hangover.
+    fn default() -> VadConfig {
+        VadConfig {
+            threshold: 0.03,
+            unvoiced_threshold: 1.0,
+            onset: 2,
+            hangover: 15,
+        }
+    }
+}
+
+/// Boundary of a talk spurt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpeechEvent {
+    /// Speech starts at the frame with the given index.
+    Start(usize),
+    /// Speech ends before the frame with the given index.
+    End(usize),
+}
+
+/// Detects talk spurts over a stream of decoded frames.
+pub struct Vad {
+    /// Detection options.
+    config: VadConfig,
+    /// Index of the next frame.
+    frame: usize,
+    /// Whether a talk spurt is in progress.
+    active: bool,
+    /// Whether the most recent classified frame was speech.
+    speech: bool,
+    /// Number of consecutive speech frames, while inactive.
+    run: usize,
+    /// Index one past the most recent speech frame.
+    last: usize,
+}
+
+impl Vad {
+    /// Create a new `Vad` with the given options, outside of any talk spurt.
+    pub fn new(config: VadConfig) -> Vad {
+        Vad {
+            config: config,
+            frame: 0,
+            active: false,
+            speech: false,
+            run: 0,
+            last: 0,
+        }
+    }
+
+    /// Retrieve the detection options.
+    pub fn config(&self) -> &VadConfig { &self.config }
+
+    /// Check whether a talk spurt is in progress, including its hangover.
+    pub fn is_active(&self) -> bool { self.active }
+
+    /// Retrieve the number of frames processed.
+    pub fn frames(&self) -> usize { self.frame }
+
+    /// Classify the frame just decoded with the given status and saved parameters,
+    /// returning any talk spurt boundary it causes.
+    ///
+    /// Repeated and invalid frames take the classification of the preceding frame, since
+    /// they continue its audio.
+    pub fn update(&mut self, status: FrameStatus, prev: &PrevFrame)
+        -> Option<SpeechEvent>
+    {
+        let idx = self.frame;
+        self.frame += 1;
+
+        let speech = match status {
+            FrameStatus::Normal => self.is_speech(prev),
+            FrameStatus::Repeat | FrameStatus::Invalid => self.speech,
+            FrameStatus::Mute => false,
+            FrameStatus::Silence => {
+                self.speech = false;
+                self.run = 0;
+
+                return self.finish();
+            },
+        };
+
+        self.speech = speech;
+
+        if speech {
+            self.last = idx + 1;
+        }
+
+        if self.active {
+            if idx + 1 - self.last >= self.config.hangover.max(1) {
+                self.active = false;
+                return Some(SpeechEvent::End(self.last));
+            }
+
+            return None;
+        }
+
+        self.run = if speech { self.run + 1 } else { 0 };
+
+        if speech && self.run >= self.config.onset {
+            self.active = true;
+            self.run = 0;
+
+            return Some(SpeechEvent::Start(idx + 1 - self.config.onset.max(1)));
+        }
+
+        None
+    }
+
+    /// End any talk spurt in progress, such as at the end of a stream, returning its
+    /// boundary.
+    pub fn finish(&mut self) -> Option<SpeechEvent> {
+        if self.active {
+            self.active = false;
+            Some(SpeechEvent::End(self.last))
+        } else {
+            None
+        }
+    }
+
+    /// Check whether the given saved parameters of a normally decoded frame are speech.
+    fn is_speech(&self, prev: &PrevFrame) -> bool {
+        let energy = prev.energy.energy;
+        let tracking = prev.energy.tracking;
+        let voiced = prev.voice.unvoiced_count() < prev.params.harmonics;
+
+        energy >= self.config.threshold * tracking &&
+            (voiced || energy >= self.config.unvoiced_threshold * tracking)
+    }
+}
+
+impl Default for Vad {
+    fn default() -> Vad { Vad::new(VadConfig::default()) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use descramble::VoiceDecisions;
+    use params::BaseParams;
+
+    /// Create saved parameters with the given energy and tracked energy, voiced or not.
+    fn prev(energy: f32, tracking: f32, voiced: bool) -> PrevFrame {
+        let mut p = PrevFrame::default();
+
+        p.params = BaseParams::new(50);
+        p.voice = VoiceDecisions::new(if voiced { 0b1 } else { 0 }, &p.params);
+        p.energy.energy = energy;
+        p.energy.tracking = tracking;
+
+        p
+    }
+
+    /// Run the given sequence of speech (true) and non-speech frames through a detector
+    /// with the given options, returning the events and the frames they occurred at.
+    fn run(config: VadConfig, frames: &[bool]) -> Vec<(usize, SpeechEvent)> {
+        let mut vad = Vad::new(config);
+        let loud = prev(1e6, 1e6, true);
+        let quiet = prev(10.0, 1e6, true);
+
+        frames.iter().enumerate().filter_map(|(i, &s)| {
+            let prev = if s { &loud } else { &quiet };
+            vad.update(FrameStatus::Normal, prev).map(|e| (i, e))
+        }).collect()
+    }
+
+    #[test]
+    fn test_classify() {
+        let vad = Vad::default();
+
+        assert!(vad.is_speech(&prev(1e5, 1e6, true)));
+        assert!(!vad.is_speech(&prev(1e4, 1e6, true)));
+
+        // Unvoiced frames need more energy.
+        assert!(!vad.is_speech(&prev(1e5, 1e6, false)));
+        assert!(vad.is_speech(&prev(1e6, 1e6, false)));
+    }
+
+    #[test]
+    fn test_spurts() {
+        let config = VadConfig {
+            onset: 2,
+            hangover: 3,
+            .. VadConfig::default()
+        };
+
+        let (t, f) = (true, false);
+
+        // A single speech frame doesn't start a spurt.
+        assert_eq!(run(config, &[f, t, f, f]), vec![]);
+
+        assert_eq!(run(config, &[f, t, t, t, f, f, f, f]), vec![
+            (2, SpeechEvent::Start(1)),
+            (6, SpeechEvent::End(4)),
+        ]);
+
+        // Pauses shorter than the hangover don't split the spurt.
+        assert_eq!(run(config, &[t, t, f, f, t, f, f, f, t, t]), vec![
+            (1, SpeechEvent::Start(0)),
+            (7, SpeechEvent::End(5)),
+            (9, SpeechEvent::Start(8)),
+        ]);
+    }
+
+    #[test]
+    fn test_status() {
+        let mut vad = Vad::new(VadConfig {
+            onset: 1,
+            hangover: 2,
+            .. VadConfig::default()
+        });
+
+        let loud = prev(1e6, 1e6, true);
+        let quiet = prev(10.0, 1e6, true);
+
+        assert_eq!(vad.update(FrameStatus::Normal, &loud), Some(SpeechEvent::Start(0)));
+
+        // Repeats continue speech and muted frames end it.
+        assert_eq!(vad.update(FrameStatus::Repeat, &quiet), None);
+        assert_eq!(vad.update(FrameStatus::Invalid, &quiet), None);
+        assert_eq!(vad.update(FrameStatus::Mute, &loud), None);
+        assert_eq!(vad.update(FrameStatus::Mute, &loud), Some(SpeechEvent::End(3)));
+        assert!(!vad.is_active());
+
+        // Silence frames skip the hangover.
+        assert_eq!(vad.update(FrameStatus::Normal, &loud), Some(SpeechEvent::Start(5)));
+        assert_eq!(vad.update(FrameStatus::Silence, &loud), Some(SpeechEvent::End(6)));
+        assert_eq!(vad.update(FrameStatus::Silence, &loud), None);
+
+        assert_eq!(vad.update(FrameStatus::Normal, &loud), Some(SpeechEvent::Start(8)));
+        assert_eq!(vad.finish(), Some(SpeechEvent::End(9)));
+        assert_eq!(vad.finish(), None);
+        assert_eq!(vad.frames(), 9);
+    }
+}
@@ -1,40 +1,52 @@
 //! Voiced spectrum synthesis.
 
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::f32::consts::PI;
 
 use collect_slice::CollectSlice;
 use map_in_place::MapInPlace;
-use rand::Rng;
 
-use consts::{SAMPLES_PER_FRAME, MAX_HARMONICS};
+use consts::{SAMPLES_PER_FRAME, MAX_EXT_HARMONICS};
 use descramble::VoiceDecisions;
 use enhance::EnhancedSpectrals;
+#[cfg(not(feature = "std"))]
+use math::Float;
 use params::BaseParams;
 use prev::PrevFrame;
+use rng::Random;
+use simd::{Isa, Oscillators};
+use timing::Timing;
 use window;
 
 /// Computes the base phase offsets Ψ<sub>l</sub>.
-pub struct PhaseBase([f32; MAX_HARMONICS]);
+///
+/// Phases are tracked for harmonics beyond L so that bandwidth-extended synthesis stays
+/// continuous across frames.
+pub struct PhaseBase([f32; MAX_EXT_HARMONICS]);
 
 impl PhaseBase {
     /// Create a new `PhaseBase` from the given current and previous frame parameters.
     pub fn new(params: &BaseParams, prev: &PrevFrame) -> Self {
-        let mut base = [0.0; MAX_HARMONICS];
+        Self::with_period(params, prev, SAMPLES_PER_FRAME as f32)
+    }
+
+    /// Create a new `PhaseBase` for a current frame centered the given native time after
+    /// the previous frame.
+    pub fn with_period(params: &BaseParams, prev: &PrevFrame, period: f32) -> Self {
+        let mut base = [0.0; MAX_EXT_HARMONICS];
 
-        // Compute common scaling factor in Eq 139.
-        let scale = (prev.params.fundamental + params.fundamental) *
-            SAMPLES_PER_FRAME as f32 / 2.0;
+        // Compute common scaling factor in Eq 139, with N replaced by the frame period.
+        let scale = (prev.params.fundamental + params.fundamental) * period / 2.0;
 
         // Compute Eq 139.
-        (1...MAX_HARMONICS).map(|l| {
+        (1...MAX_EXT_HARMONICS).map(|l| {
             prev.phase_base.get(l) + scale * l as f32
         }).collect_slice_checked(&mut base[..]);
 
         PhaseBase(base)
     }
 
-    /// Retrieve the phase term Ψ<sub>l</sub>, 1 ≤ l ≤ 56.
+    /// Retrieve the phase term Ψ<sub>l</sub>, 1 ≤ l ≤ 123.
     pub fn get(&self, l: usize) -> f32 { self.0[l - 1] }
 }
 
@@ -42,20 +54,20 @@ impl Default for PhaseBase {
     /// Create a new `PhaseBase` in the default state.
     fn default() -> Self {
         // By default all phase terms are 0 [p64].
-        PhaseBase([0.0; MAX_HARMONICS])
+        PhaseBase([0.0; MAX_EXT_HARMONICS])
     }
 }
 
 /// Computes the random phase terms Φ<sub>l</sub>.
-pub struct Phase([f32; MAX_HARMONICS]);
+pub struct Phase([f32; MAX_EXT_HARMONICS]);
 
 impl Phase {
     /// Create a new `Phase` building on the given base phase terms.
-    pub fn new<R: Rng>(base: &PhaseBase, params: &BaseParams, prev: &PrevFrame,
-                       voice: &VoiceDecisions, mut noise: R)
+    pub fn new<R: Random + ?Sized>(base: &PhaseBase, params: &BaseParams,
+                                   prev: &PrevFrame, voice: &VoiceDecisions, noise: &mut R)
         -> Self
     {
-        let mut phase = [0.0; MAX_HARMONICS];
+        let mut phase = [0.0; MAX_EXT_HARMONICS];
 
         // Derive phase terms from base phase offsets according to Eq 140.
         (&mut phase[..]).copy_from_slice(&base.0[..]);
@@ -70,13 +82,13 @@ impl Phase {
         // Modify Ψ_l from start + 1 ≤ l ≤ stop. Since i = l - 1, start ≤ i ≤ stop - 1.
         (&mut phase[start..stop]).map_in_place(|&x| {
             // Compute Eq 140.
-            x + scale * noise.gen_range(-PI, PI)
+            x + scale * noise.uniform(-PI, PI)
         });
 
         Phase(phase)
     }
 
-    /// Retrieve the phase term Φ<sub>l</sub>, 1 ≤ l ≤ 56.
+    /// Retrieve the phase term Φ<sub>l</sub>, 1 ≤ l ≤ 123.
     pub fn get(&self, l: usize) -> f32 { self.0[l - 1] }
 }
 
@@ -84,8 +96,66 @@ impl Default for Phase {
     /// Create a new `Phase` in the default state.
     fn default() -> Self {
         // By default all phase terms are 0 [p64].
-        Phase([0.0; MAX_HARMONICS])
+        Phase([0.0; MAX_EXT_HARMONICS])
+    }
+}
+
+/// Harmonics above L synthesized to extend the bandwidth of a frame.
+///
+/// The extended harmonics continue the voicing of harmonic L with amplitudes that roll
+/// off from the average of the highest 3 enhanced spectral amplitudes.
+#[derive(Copy, Clone)]
+struct Extension {
+    /// First harmonic synthesized from the extension rather than the frame.
+    start: usize,
+    /// Final synthesized harmonic.
+    end: usize,
+    /// Amplitude scale of the extended harmonics.
+    amp: f32,
+    /// Whether the extended harmonics are voiced.
+    voiced: bool,
+}
+
+impl Extension {
+    /// Create a new `Extension` that adds no harmonics to a frame with the given
+    /// parameters.
+    fn none(params: &BaseParams) -> Extension {
+        Extension {
+            // This is past any harmonic that will be synthesized.
+            start: MAX_EXT_HARMONICS + 1,
+            end: params.harmonics as usize,
+            amp: 0.0,
+            voiced: false,
+        }
+    }
+
+    /// Create a new `Extension` that adds harmonics below the given frequency limit, in
+    /// radians per native sample, to the frame with the given parameters.
+    fn new(params: &BaseParams, amps: &EnhancedSpectrals, voice: &VoiceDecisions,
+           limit: f32)
+        -> Extension
+    {
+        let harmonics = params.harmonics as usize;
+        let count = min(amps.len(), 3);
+
+        Extension {
+            start: harmonics + 1,
+            end: ((limit / params.fundamental).ceil() as usize - 1)
+                .max(harmonics)
+                .min(MAX_EXT_HARMONICS),
+            amp: if count == 0 {
+                0.0
+            } else {
+                amps.iter().rev().take(count).fold(0.0, |s, &m| s + m) / count as f32 *
+                    harmonics as f32
+            },
+            voiced: voice.is_voiced(harmonics),
+        }
     }
+
+    /// Compute the amplitude of the given extended harmonic, which rolls off at 6dB
+    /// per octave with an additional 6dB of attenuation.
+    fn get(&self, l: usize) -> f32 { 0.5 * self.amp / l as f32 }
 }
 
 /// Synthesizes voiced spectrum signal s<sub>v</sub>(n).
@@ -100,6 +170,14 @@ pub struct Voiced<'a, 'b, 'c, 'd> {
     fundamental: f32,
     /// Number of harmonics that make up each signal sample.
     end: usize,
+    /// Maps output samples to native time.
+    timing: Timing,
+    /// Extended harmonics of the current frame.
+    ext_cur: Extension,
+    /// Extended harmonics of the previous frame.
+    ext_prev: Extension,
+    /// Instruction set used for oscillator banks.
+    isa: Isa,
 }
 
 impl<'a, 'b, 'c, 'd> Voiced<'a, 'b, 'c, 'd> {
@@ -107,6 +185,28 @@ impl<'a, 'b, 'c, 'd> Voiced<'a, 'b, 'c, 'd> {
                amps: &'c EnhancedSpectrals, voice: &'d VoiceDecisions)
         -> Self
     {
+        Self::with_timing(params, prev, phase, amps, voice, Timing::native(), false)
+    }
+
+    /// Create a new `Voiced` that synthesizes output samples with the given timing,
+    /// optionally extending each frame with harmonics up to the lesser of 8kHz and the
+    /// output Nyquist frequency.
+    pub fn with_timing(params: &BaseParams, prev: &'a PrevFrame, phase: &'b Phase,
+                       amps: &'c EnhancedSpectrals, voice: &'d VoiceDecisions,
+                       timing: Timing, extend: bool)
+        -> Self
+    {
+        let (ext_cur, ext_prev) = if extend {
+            let limit = PI * timing.scale().min(2.0);
+
+            (
+                Extension::new(params, amps, voice, limit),
+                Extension::new(&prev.params, &prev.enhanced, &prev.voice, limit),
+            )
+        } else {
+            (Extension::none(params), Extension::none(&prev.params))
+        };
+
         Voiced {
             prev: prev,
             phase: phase,
@@ -115,54 +215,138 @@ impl<'a, 'b, 'c, 'd> Voiced<'a, 'b, 'c, 'd> {
             window: window::synthesis(),
             fundamental: params.fundamental,
             // Compute the sum bound in Eq 127.
-            end: max(params.harmonics, prev.params.harmonics) as usize,
+            end: max(ext_cur.end, ext_prev.end),
+            timing: timing,
+            ext_cur: ext_cur,
+            ext_prev: ext_prev,
+            isa: Isa::detect(),
         }
     }
 
-    /// Compute s<sub>v,l</sub>(n), the signal level at sample n for the l'th spectral
-    /// amplitude.
-    fn get_pair(&self, l: usize, n: isize) -> f32 {
-        match (self.voice.is_voiced(l), self.prev.voice.is_voiced(l)) {
+    /// Check if the l'th harmonic is voiced in the current frame.
+    fn voiced_cur(&self, l: usize) -> bool {
+        if l < self.ext_cur.start {
+            self.voice.is_voiced(l)
+        } else {
+            l <= self.ext_cur.end && self.ext_cur.voiced
+        }
+    }
+
+    /// Check if the l'th harmonic is voiced in the previous frame.
+    fn voiced_prev(&self, l: usize) -> bool {
+        if l < self.ext_prev.start {
+            self.prev.voice.is_voiced(l)
+        } else {
+            l <= self.ext_prev.end && self.ext_prev.voiced
+        }
+    }
+
+    /// Compute s<sub>v,l</sub>(t), the signal level at native time t for the l'th
+    /// spectral amplitude.
+    fn get_pair(&self, l: usize, t: f32) -> f32 {
+        match (self.voiced_cur(l), self.voiced_prev(l)) {
             // Use Eq 130.
             (false, false) => 0.0,
             // Use Eq 131.
-            (false, true) => self.sig_prev(l, n),
+            (false, true) => self.sig_prev(l, t),
             // Use Eq 132.
-            (true, false) => self.sig_cur(l, n),
+            (true, false) => self.sig_cur(l, t),
             // Use Eq 133. The Eq 134 form for voiced/voiced frames isn't used due to its
             // complexity and lack of rationale.
-            (true, true) => self.sig_prev(l, n) + self.sig_cur(l, n)
+            (true, true) => self.sig_prev(l, t) + self.sig_cur(l, t)
+        }
+    }
+
+    /// Retrieve the amplitude of the l'th harmonic in the current frame.
+    fn amp_cur(&self, l: usize) -> f32 {
+        if l < self.ext_cur.start {
+            self.amps.get(l)
+        } else {
+            self.ext_cur.get(l)
+        }
+    }
+
+    /// Retrieve the amplitude of the l'th harmonic in the previous frame.
+    fn amp_prev(&self, l: usize) -> f32 {
+        if l < self.ext_prev.start {
+            self.prev.enhanced.get(l)
+        } else {
+            self.ext_prev.get(l)
         }
     }
 
-    /// Compute s<sub>v,l</sub>(n) for a voiced current frame and unvoiced previous frame.
-    fn sig_cur(&self, l: usize, n: isize) -> f32 {
+    /// Compute s<sub>v,l</sub>(t) for a voiced current frame and unvoiced previous frame.
+    fn sig_cur(&self, l: usize, t: f32) -> f32 {
+        let t = t - self.timing.period();
+
         // Compute Eq 132.
-        self.window.get(n - SAMPLES_PER_FRAME as isize) * self.amps.get(l) * (
-            self.fundamental * (n - SAMPLES_PER_FRAME as isize) as f32 * l as f32 +
-                self.phase.get(l)
+        self.window.get_frac(self.timing.window_time(t)) * self.amp_cur(l) * (
+            self.fundamental * t * l as f32 + self.phase.get(l)
         ).cos()
     }
 
-    /// Compute s<sub>v,l</sub>(n) for an unvoiced current frame and voiced previous frame.
-    fn sig_prev(&self, l: usize, n: isize) -> f32 {
+    /// Compute s<sub>v,l</sub>(t) for an unvoiced current frame and voiced previous frame.
+    fn sig_prev(&self, l: usize, t: f32) -> f32 {
         // Compute Eq 131.
-        self.window.get(n) * self.prev.enhanced.get(l) * (
-            self.prev.params.fundamental * n as f32 * l as f32 +
-                self.prev.phase.get(l)
+        self.window.get_frac(self.timing.window_time(t)) * self.amp_prev(l) * (
+            self.prev.params.fundamental * t * l as f32 + self.prev.phase.get(l)
         ).cos()
     }
 
-    /// Compute the voiced signal sample s<sub>v</sub>(n) for the given sample n, 0 ≤ n <
-    /// 160.
+    /// Compute the voiced signal sample s<sub>v</sub>(n) for the given output sample n, 0
+    /// ≤ n < N.
     pub fn get(&self, n: usize) -> f32 {
-        debug_assert!(n < SAMPLES_PER_FRAME);
+        debug_assert!(n < self.timing.len());
+
+        let t = self.timing.time(n);
 
         // Compute Eq 127
         2.0 * (1...self.end)
-            .map(|l| self.get_pair(l, n as isize))
+            .map(|l| self.get_pair(l, t))
             .fold(0.0, |s, x| s + x)
     }
+
+    /// Compute the voiced signal samples s<sub>v</sub>(n) for start ≤ n < start + len
+    /// into the given buffer of length len.
+    ///
+    /// This gives the same result as `get` for each sample, but rather than evaluating
+    /// cos() for every harmonic at every sample, each voiced harmonic of the previous and
+    /// current frame is generated by a recursive oscillator that advances its phase by a
+    /// fixed step, with the oscillators of each frame evaluated together by vectorized
+    /// kernels.
+    pub fn fill(&self, start: usize, buf: &mut [f32]) {
+        debug_assert!(start + buf.len() <= self.timing.len());
+
+        let t = self.timing.time(start);
+        let step = self.timing.time(1);
+
+        let mut prev = Oscillators::new(self.isa);
+        let mut cur = Oscillators::new(self.isa);
+
+        // Start each oscillator at the phase of Eq 131 or 132 for the first sample.
+        for l in (1...self.end).filter(|&l| self.voiced_prev(l)) {
+            let freq = self.prev.params.fundamental * l as f32;
+            prev.push(self.amp_prev(l), freq * t + self.prev.phase.get(l), freq * step);
+        }
+
+        for l in (1...self.end).filter(|&l| self.voiced_cur(l)) {
+            let freq = self.fundamental * l as f32;
+            let t = t - self.timing.period();
+
+            cur.push(self.amp_cur(l), freq * t + self.phase.get(l), freq * step);
+        }
+
+        for (n, x) in buf.iter_mut().enumerate() {
+            let t = self.timing.time(start + n);
+
+            let win_prev = self.window.get_frac(self.timing.window_time(t));
+            let win_cur = self.window.get_frac(
+                self.timing.window_time(t - self.timing.period()));
+
+            // Compute Eq 127 using Eqs 131 through 133.
+            *x = 2.0 * (win_prev * prev.next() + win_cur * cur.next());
+        }
+    }
 }
 
 #[cfg(test)]
@@ -172,6 +356,36 @@ mod test {
     use prev::PrevFrame;
     use descramble::{Bootstrap, descramble};
     use rand::XorShiftRng;
+    use coefs::Coefficients;
+    use enhance::FrameEnergy;
+    use gain::Gains;
+    use resample::SampleRate;
+    use spectral::Spectrals;
+
+    /// Decode the given chunks into a frame following the given previous frame.
+    fn decode(chunks: &[u32; 8], prev: &PrevFrame, rng: &mut XorShiftRng) -> PrevFrame {
+        let params = BaseParams::new(Bootstrap::new(chunks).unwrap_period());
+        let (amps, voice, gain_idx) = descramble(chunks, &params);
+        let gains = Gains::new(gain_idx, &amps, &params);
+        let coefs = Coefficients::new(&gains, &amps, &params);
+        let spectrals = Spectrals::new(&coefs, &params, prev);
+        let energy = FrameEnergy::new(&spectrals, &prev.energy, &params);
+        let enhanced = EnhancedSpectrals::new(&spectrals, &energy, &params);
+        let base = PhaseBase::new(&params, prev);
+        let phase = Phase::new(&base, &params, prev, &voice, rng);
+
+        PrevFrame {
+            params: params.clone(),
+            model: params,
+            spectrals: spectrals,
+            enhanced: enhanced,
+            voice: voice,
+            energy: energy,
+            phase_base: base,
+            phase: phase,
+            .. PrevFrame::default()
+        }
+    }
 
     #[test]
     fn test_phase_base() {
@@ -289,7 +503,7 @@ mod test {
         assert_eq!(voice.unvoiced_count(), 6);
 
         let pb = PhaseBase::new(&p, &prev);
-        let p = Phase::new(&pb, &p, &prev, &voice, XorShiftRng::new_unseeded());
+        let p = Phase::new(&pb, &p, &prev, &voice, &mut XorShiftRng::new_unseeded());
 
         assert!((p.get(1) - 22.56239845600000037961763155180961).abs() < 1e-3);
         assert!((p.get(2) - 45.12479691200000075923526310361922).abs() < 1e-3);
@@ -348,4 +562,65 @@ mod test {
         assert!((p.get(55) - 1240.93191507999995337740983814001083).abs() < 1e-3);
         assert!((p.get(56) - 1263.49431353599993599345907568931580).abs() < 1e-3);
     }
+
+    #[test]
+    fn test_fill() {
+        let chunks = [
+            [
+                0b001000010010,
+                0b110011001100,
+                0b111000111000,
+                0b111111111111,
+                0b10101110101,
+                0b00101111010,
+                0b01110111011,
+                0b00001000,
+            ],
+            [
+                0b000111010101,
+                0b101010010110,
+                0b010111001011,
+                0b110100101100,
+                0b11011010110,
+                0b10110100101,
+                0b01001011010,
+                0b01101100,
+            ],
+        ];
+
+        let mut rng = XorShiftRng::new_unseeded();
+        let prev = decode(&chunks[0], &PrevFrame::default(), &mut rng);
+        let cur = decode(&chunks[1], &prev, &mut rng);
+
+        let timings = [
+            (Timing::native(), false),
+            (Timing::new(SampleRate::Hz48000), false),
+            (Timing::new(SampleRate::Hz44100), true),
+            (Timing::stretched(SampleRate::Hz16000, 600), true),
+            (Timing::stretched(SampleRate::Hz8000, 80), false),
+        ];
+
+        for &(timing, extend) in timings.iter() {
+            let v = Voiced::with_timing(&cur.params, &prev, &cur.phase, &cur.enhanced,
+                                        &cur.voice, timing, extend);
+
+            let expected = (0..timing.len()).map(|n| v.get(n)).collect::<Vec<f32>>();
+            let peak = expected.iter().fold(0.0f32, |m, &x| m.max(x.abs()));
+            assert!(peak > 10.0);
+
+            // Fill the frame in one piece and in uneven pieces.
+            let mut whole = vec![0.0; timing.len()];
+            v.fill(0, &mut whole[..]);
+
+            let mut split = vec![0.0; timing.len()];
+            let (a, b) = split.split_at_mut(timing.len() / 3);
+            v.fill(0, a);
+            v.fill(timing.len() / 3, b);
+
+            for (n, &x) in expected.iter().enumerate() {
+                assert!((whole[n] - x).abs() < peak * 1e-4);
+                assert!((split[n] - x).abs() < peak * 1e-4);
+            }
+        }
+    }
 }
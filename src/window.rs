@@ -1,5 +1,8 @@
 //! Signal windowing.
 
+#[cfg(not(feature = "std"))]
+use math::Float;
+
 /// Retrieve the speech synthesis window w<sub>s</sub>.
 pub fn synthesis() -> Window {
     Window::new(&WINDOW_SYNTHESIS[..])
@@ -22,6 +25,9 @@ impl Window {
         }
     }
 
+    /// Retrieve the largest n for which the coefficient w(n) is defined.
+    pub fn reach(&self) -> isize { self.coefs.len() as isize - 1 - self.offset }
+
     /// Retrieve the coefficient w(n) for the given n.
     pub fn get(&self, n: isize) -> f32 {
         match self.coefs.get((n + self.offset) as usize) {
@@ -29,6 +35,19 @@ impl Window {
             None => 0.0,
         }
     }
+
+    /// Retrieve the coefficient w(t) for the given fractional t by linearly interpolating
+    /// between the nearest coefficients.
+    pub fn get_frac(&self, t: f32) -> f32 {
+        let n = t.floor();
+        let frac = t - n;
+
+        if frac == 0.0 {
+            self.get(n as isize)
+        } else {
+            self.get(n as isize) * (1.0 - frac) + self.get(n as isize + 1) * frac
+        }
+    }
 }
 
 /// Coefficients of the speech synthesis window [p95].
@@ -270,6 +289,19 @@ mod test {
         assert_eq!(w.get(200), 0.0);
     }
 
+    #[test]
+    fn test_get_frac() {
+        let w = synthesis();
+
+        assert_eq!(w.get_frac(-105.0), 0.0);
+        assert_eq!(w.get_frac(-68.0), 0.74);
+        assert_eq!(w.get_frac(0.5), 1.0);
+        assert!((w.get_frac(-104.5) - 0.01).abs() < 1e-6);
+        assert!((w.get_frac(76.25) - 0.575).abs() < 1e-6);
+        assert!((w.get_frac(104.5) - 0.01).abs() < 1e-6);
+        assert_eq!(w.get_frac(105.5), 0.0);
+    }
+
     #[test]
     fn verify_window_energy() {
         let e = WINDOW_SYNTHESIS.iter().fold(0.0, |sum, &x| sum + x.powi(2));
@@ -0,0 +1,25 @@
+//! Check the decoder against the bundled conformance vectors.
+
+#![cfg(feature = "std")]
+
+extern crate imbe;
+
+use std::path::Path;
+
+use imbe::conformance::{self, Tolerance};
+
+#[test]
+fn test_vectors() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("vectors");
+    let reports = conformance::check_dir(&dir, &Tolerance::default()).unwrap();
+
+    assert!(!reports.is_empty());
+
+    for r in reports {
+        assert!(r.frames > 0);
+
+        if let Some(d) = r.divergence {
+            panic!("{}: {}", r.path.display(), d);
+        }
+    }
+}